@@ -0,0 +1,321 @@
+//! Functional versions of autodiff: [vjp()], [jvp()], [jacobian()] and [hvp()].
+//!
+//! These are all built on [Trace] & [OwnedTape], and take closures from an input
+//! tensor to an output tensor:
+//!
+//! ```rust
+//! # use dfdx_core::prelude::*;
+//! # use dfdx_core::autograd::*;
+//! # let dev: Cpu = Default::default();
+//! let x: Tensor<Rank1<3>, f32, _> = dev.tensor([1.0, 2.0, 3.0]);
+//! let j = jacobian(|x| x.square(), &x);
+//! assert_eq!(j.shape(), &(3, 3));
+//! assert_eq!(j.as_vec(), [2.0, 0.0, 0.0, 0.0, 4.0, 0.0, 0.0, 0.0, 6.0]);
+//! ```
+//!
+//! [jvp()] and [hvp()] differentiate through the backward pass, so they only support
+//! operations with higher order gradients (see [crate::tensor_ops::BackwardTraced]), and
+//! return [Error::HigherOrderUnsupported] otherwise.
+
+#![allow(clippy::type_complexity)]
+
+use crate::prelude::*;
+use std::vec::Vec;
+
+type Traced<S, E, D> = Tensor<S, E, D, OwnedTape<E, D>>;
+
+/// Vector-Jacobian product. Computes `y = f(x)` and `v^T J`, where `J` is the
+/// jacobian of `f` at `x`. `v` must have the same shape as `y`.
+///
+/// Returns `(y, v^T J)`.
+///
+/// ```rust
+/// # use dfdx_core::prelude::*;
+/// # use dfdx_core::autograd::*;
+/// # let dev: Cpu = Default::default();
+/// let x = dev.tensor([1.0f32, 2.0]);
+/// let v = dev.tensor([1.0f32, -1.0]);
+/// let (y, vjp) = vjp(|x| x.square() * 3.0, &x, &v);
+/// assert_eq!(y.array(), [3.0, 12.0]);
+/// assert_eq!(vjp.array(), [6.0, -12.0]);
+/// ```
+pub fn vjp<In: Shape, Out: Shape, E: Dtype, D: Device<E>, F>(
+    f: F,
+    x: &Tensor<In, E, D>,
+    v: &Tensor<Out, E, D>,
+) -> (Tensor<Out, E, D>, Tensor<In, E, D>)
+where
+    F: FnOnce(Traced<In, E, D>) -> Traced<Out, E, D>,
+{
+    try_vjp(f, x, v).unwrap()
+}
+
+/// Fallible version of [vjp()].
+pub fn try_vjp<In: Shape, Out: Shape, E: Dtype, D: Device<E>, F>(
+    f: F,
+    x: &Tensor<In, E, D>,
+    v: &Tensor<Out, E, D>,
+) -> Result<(Tensor<Out, E, D>, Tensor<In, E, D>), Error>
+where
+    F: FnOnce(Traced<In, E, D>) -> Traced<Out, E, D>,
+{
+    let (y, tape) = f(x.leaky_trace()).split_tape();
+    let grads = y
+        .clone()
+        .put_tape(tape)
+        .try_mul(v.clone())?
+        .try_sum()?
+        .try_backward()?;
+    let grad = match grads.get_ref_checked(x) {
+        Some(_) => grads.get(x),
+        None => x.device.try_zeros_like(&x.shape)?,
+    };
+    Ok((y, grad))
+}
+
+/// Jacobian-vector product. Computes `y = f(x)` and `J v`, where `J` is the
+/// jacobian of `f` at `x`. `v` must have the same shape as `x`.
+///
+/// This is computed with two backward passes, by differentiating the linear function
+/// `u -> u^T J` with respect to `u`, so all operations in `f` must support
+/// higher order gradients.
+///
+/// Returns `(y, J v)`.
+///
+/// ```rust
+/// # use dfdx_core::prelude::*;
+/// # use dfdx_core::autograd::*;
+/// # let dev: Cpu = Default::default();
+/// let x = dev.tensor([1.0f32, 2.0]);
+/// let v = dev.tensor([1.0f32, -1.0]);
+/// let (y, jvp) = jvp(|x| x.square() * 3.0, &x, &v);
+/// assert_eq!(y.array(), [3.0, 12.0]);
+/// assert_eq!(jvp.array(), [6.0, -12.0]);
+/// ```
+pub fn jvp<In: Shape, Out: Shape, E: Dtype, D: Device<E>, F>(
+    f: F,
+    x: &Tensor<In, E, D>,
+    v: &Tensor<In, E, D>,
+) -> (Tensor<Out, E, D>, Tensor<Out, E, D>)
+where
+    F: FnOnce(Traced<In, E, D>) -> Traced<Out, E, D>,
+{
+    try_jvp(f, x, v).unwrap()
+}
+
+/// Fallible version of [jvp()].
+pub fn try_jvp<In: Shape, Out: Shape, E: Dtype, D: Device<E>, F>(
+    f: F,
+    x: &Tensor<In, E, D>,
+    v: &Tensor<In, E, D>,
+) -> Result<(Tensor<Out, E, D>, Tensor<Out, E, D>), Error>
+where
+    F: FnOnce(Traced<In, E, D>) -> Traced<Out, E, D>,
+{
+    let (y, tape) = f(x.trace_higher_order(Gradients::leaky())).split_tape();
+    // `u^T J` is linear in `u`, so the value of `u` doesn't matter.
+    let u: Tensor<Out, E, D> = y.device.try_zeros_like(&y.shape)?;
+    let grads = y
+        .clone()
+        .put_tape(tape)
+        .try_mul(u.clone())?
+        .try_sum()?
+        .try_backward_traced()?;
+    let Some(u_t_j) = grads.get_checked(x) else {
+        return Ok((y, u.clone()));
+    };
+    let grads = grads.try_backward(u_t_j.try_mul(v.clone())?.try_sum()?)?;
+    let jvp = match grads.get_ref_checked(&u) {
+        Some(_) => grads.get(&u),
+        None => u.clone(),
+    };
+    Ok((y, jvp))
+}
+
+/// Computes the full jacobian of `f` at `x`, with one backward pass per element of `f(x)`.
+///
+/// The result has shape `(y.len(), x.len())`, where each row is the gradient of one
+/// element of `y` with respect to all elements of `x`. Elements are in row major order
+/// of their shapes.
+pub fn jacobian<In: Shape, Out: Shape, E: Dtype, D: Device<E>, F>(
+    f: F,
+    x: &Tensor<In, E, D>,
+) -> Tensor<(usize, usize), E, D>
+where
+    F: FnMut(Traced<In, E, D>) -> Traced<Out, E, D>,
+{
+    try_jacobian(f, x).unwrap()
+}
+
+/// Fallible version of [jacobian()].
+pub fn try_jacobian<In: Shape, Out: Shape, E: Dtype, D: Device<E>, F>(
+    mut f: F,
+    x: &Tensor<In, E, D>,
+) -> Result<Tensor<(usize, usize), E, D>, Error>
+where
+    F: FnMut(Traced<In, E, D>) -> Traced<Out, E, D>,
+{
+    let dev = x.device.clone();
+    let out_shape = *f(x.leaky_trace()).shape();
+    let (m, n) = (out_shape.num_elements(), x.shape.num_elements());
+    let mut data = Vec::with_capacity(m * n);
+    let mut one_hot = std::vec![E::default(); m];
+    for i in 0..m {
+        one_hot[i] = E::ONE;
+        let mut v = dev.try_zeros_like(&out_shape)?;
+        v.copy_from(&one_hot);
+        one_hot[i] = E::default();
+        let (_, row) = try_vjp(&mut f, x, &v)?;
+        data.extend(row.as_vec());
+    }
+    dev.try_tensor_from_vec(data, (m, n))
+}
+
+/// Hessian-vector product of the scalar function `f`. Computes `y = f(x)` and `H v`,
+/// where `H` is the hessian of `f` at `x`. `v` must have the same shape as `x`.
+///
+/// All operations in `f` must support higher order gradients.
+///
+/// Returns `(y, H v)`.
+///
+/// ```rust
+/// # use dfdx_core::prelude::*;
+/// # use dfdx_core::autograd::*;
+/// # let dev: Cpu = Default::default();
+/// let x = dev.tensor([1.0f32, 2.0]);
+/// let v = dev.tensor([1.0f32, 0.0]);
+/// // the hessian of sum(x^3) is diag(6x)
+/// let (y, hvp) = hvp(|x| (x.retaped() * x.square()).sum::<Rank0, _>(), &x, &v);
+/// assert_eq!(y.array(), 9.0);
+/// assert_eq!(hvp.array(), [6.0, 0.0]);
+/// ```
+pub fn hvp<In: Shape, E: Dtype, D: Device<E>, F>(
+    f: F,
+    x: &Tensor<In, E, D>,
+    v: &Tensor<In, E, D>,
+) -> (Tensor<Rank0, E, D>, Tensor<In, E, D>)
+where
+    F: FnOnce(Traced<In, E, D>) -> Traced<Rank0, E, D>,
+{
+    try_hvp(f, x, v).unwrap()
+}
+
+/// Fallible version of [hvp()].
+pub fn try_hvp<In: Shape, E: Dtype, D: Device<E>, F>(
+    f: F,
+    x: &Tensor<In, E, D>,
+    v: &Tensor<In, E, D>,
+) -> Result<(Tensor<Rank0, E, D>, Tensor<In, E, D>), Error>
+where
+    F: FnOnce(Traced<In, E, D>) -> Traced<Rank0, E, D>,
+{
+    let (y, tape) = f(x.trace_higher_order(Gradients::leaky())).split_tape();
+    let grads = y.clone().put_tape(tape).try_backward_traced()?;
+    let zeros = || x.device.try_zeros_like(&x.shape);
+    let Some(grad) = grads.get_checked(x) else {
+        return Ok((y, zeros()?));
+    };
+    let grads = grads.try_backward(grad.try_mul(v.clone())?.try_sum()?)?;
+    let hvp = match grads.get_ref_checked(x) {
+        Some(_) => grads.get(x),
+        None => zeros()?,
+    };
+    Ok((y, hvp))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::*;
+
+    #[test]
+    fn test_vjp_matmul() {
+        let dev: TestDevice = Default::default();
+        let w = dev
+            .tensor([[1.0, -1.0, 0.5], [2.0, 0.0, 1.0]])
+            .to_dtype::<TestDtype>();
+        let x = dev.tensor([0.5, -2.0]).to_dtype::<TestDtype>();
+        let v = dev.tensor([1.0, 2.0, 3.0]).to_dtype::<TestDtype>();
+        let (y, g) = vjp(|x| x.matmul(w.clone()), &x, &v);
+        assert_close_to_literal!(y, [-3.5, -0.5, -1.75]);
+        // J = w^T, so v^T J = w v
+        assert_close_to_literal!(g, [0.5, 5.0]);
+    }
+
+    #[test]
+    fn test_vjp_unused_input() {
+        let dev: TestDevice = Default::default();
+        let c = dev.tensor([1.0, 2.0]).to_dtype::<TestDtype>();
+        let x = dev.tensor([3.0, 4.0]).to_dtype::<TestDtype>();
+        let (_, g) = vjp(|x| c.clone().put_tape(x.split_tape().1) * 2.0, &x, &c);
+        assert_close_to_literal!(g, [0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_jacobian_matches_vjp() {
+        let dev: TestDevice = Default::default();
+        let x: Tensor<Rank1<3>, TestDtype, _> = dev.sample_normal();
+        let w: Tensor<Rank2<3, 2>, TestDtype, _> = dev.sample_normal();
+        let j = jacobian(|x| x.matmul(w.clone()).tanh(), &x);
+        assert_eq!(j.shape, (2, 3));
+        let j = j.as_vec();
+        for i in 0..2 {
+            let mut e = [0.0; 2];
+            e[i] = 1.0;
+            let v = dev.tensor(e).to_dtype::<TestDtype>();
+            let (_, row) = vjp(|x| x.matmul(w.clone()).tanh(), &x, &v);
+            assert_eq!(row.as_vec(), &j[i * 3..(i + 1) * 3]);
+        }
+    }
+
+    #[test]
+    fn test_jacobian_2d() {
+        let dev: TestDevice = Default::default();
+        let x = dev.tensor([[1.0, 2.0], [3.0, 4.0]]).to_dtype::<TestDtype>();
+        let j = jacobian(|x| x.sum::<Rank1<2>, Axis<1>>() * 2.0, &x);
+        assert_eq!(j.shape, (2, 4));
+        let j = dev.tensor_from_vec(j.as_vec(), (Const::<2>, Const::<4>));
+        assert_close_to_literal!(j, [[2.0, 2.0, 0.0, 0.0], [0.0, 0.0, 2.0, 2.0]]);
+    }
+
+    #[test]
+    fn test_jvp_matches_jacobian() {
+        let dev: TestDevice = Default::default();
+        let x: Tensor<Rank1<3>, TestDtype, _> = dev.sample_normal();
+        let v: Tensor<Rank1<3>, TestDtype, _> = dev.sample_normal();
+        let w: Tensor<Rank2<3, 4>, TestDtype, _> = dev.sample_normal();
+        let (y, jv) = jvp(|x| x.matmul(w.clone()).sin(), &x, &v);
+        assert_close_to_tensor!(y, x.clone().matmul(w.clone()).sin());
+        let j = jacobian(|x| x.matmul(w.clone()).sin(), &x);
+        let j = dev.tensor_from_vec(j.as_vec(), (Const::<4>, Const::<3>));
+        assert_close_to_tensor!(jv, j.matmul(v));
+    }
+
+    #[test]
+    fn test_jvp_unsupported() {
+        let dev: TestDevice = Default::default();
+        let x = dev.tensor([1.0, 2.0]).to_dtype::<TestDtype>();
+        let r = try_jvp(|x| x.powi(3), &x, &x);
+        assert!(matches!(r, Err(Error::HigherOrderUnsupported)));
+    }
+
+    #[test]
+    fn test_hvp_quadratic() {
+        let dev: TestDevice = Default::default();
+        // f(x) = 0.5 * x^T A x with symmetric A has hessian A
+        let a = dev.tensor([[2.0, 1.0], [1.0, 3.0]]).to_dtype::<TestDtype>();
+        let x = dev.tensor([[1.0, -1.0]]).to_dtype::<TestDtype>();
+        let v = dev.tensor([[1.0, 2.0]]).to_dtype::<TestDtype>();
+        let (y, hv) = hvp(
+            |x| {
+                let (x, tape) = x.split_tape();
+                let ax = x.clone().put_tape(tape).matmul(a.clone());
+                (ax * x).sum() * 0.5
+            },
+            &x,
+            &v,
+        );
+        assert_close_to_literal!(y, 1.5);
+        assert_close_to_literal!(hv, [[4.0, 7.0]]);
+    }
+}
@@ -6,6 +6,9 @@
 //!
 //! [AMP](https://pytorch.org/docs/stable/amp.html) is a technique for mixed precision training.
 //! This is a data type in dfdx, you can use it like any normal dtype like [`AMP<f16>`] or [`AMP<bf16>`].
+//!
+//! Small gradients can underflow to zero in f16, use `dfdx::nn::optim::GradScaler` to scale the loss
+//! (and so the gradients) up during backward.
 
 mod amp;
 mod from_le_bytes;
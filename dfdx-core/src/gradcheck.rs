@@ -0,0 +1,543 @@
+//! Numerical validation of gradients with central differences.
+//!
+//! [gradcheck()] and [gradcheck_module()] perturb every element of an input (and of every
+//! parameter of a module) by `±eps`, estimate the derivative of a scalar loss from the
+//! two perturbed losses, and compare the estimate against what `loss.backward()` computed.
+//!
+//! ```rust
+//! # use dfdx_core::prelude::*;
+//! # use dfdx_core::gradcheck::*;
+//! # let dev: Cpu = Default::default();
+//! let x: Tensor<Rank1<5>, f64, _> = dev.sample_normal();
+//! let report = gradcheck(|x| x.square().sin().sum(), &x, Default::default());
+//! assert!(report.passed(), "{report}");
+//! ```
+//!
+//! The finite differences are computed in the dtype of the tensors, so checking
+//! with `f64` allows much tighter tolerances than `f32`.
+//!
+//! Gradients are compared per *physical* element, so if the input is a broadcasted
+//! tensor, the gradient of each underlying element is the sum over all the places it
+//! was broadcasted to.
+
+use crate::{
+    nn_traits::{Optimizer, UpdateParams},
+    prelude::*,
+};
+use std::vec::Vec;
+
+/// Configuration for [gradcheck()] and [gradcheck_module()].
+///
+/// An element mismatches if `|analytical - numerical| > atol + rtol * |numerical|`.
+#[derive(Debug, Clone, Copy)]
+pub struct GradCheckConfig {
+    /// The amount each element is perturbed by in each direction.
+    pub eps: f64,
+    /// Absolute tolerance.
+    pub atol: f64,
+    /// Relative tolerance, scaled by the magnitude of the numerical gradient.
+    pub rtol: f64,
+}
+
+impl Default for GradCheckConfig {
+    fn default() -> Self {
+        Self {
+            eps: 1e-3,
+            atol: 1e-3,
+            rtol: 1e-2,
+        }
+    }
+}
+
+/// Which tensor a [GradCheckMismatch] was found in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GradCheckTensor {
+    /// The input tensor.
+    Input,
+    /// The `i`th parameter of the module, in the order they are visited by [UpdateParams].
+    Param(usize),
+}
+
+/// A single element whose analytical & numerical gradients were compared.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GradCheckMismatch {
+    /// The tensor this element belongs to.
+    pub tensor: GradCheckTensor,
+    /// The index of the element in the tensor.
+    pub index: Vec<usize>,
+    /// The gradient computed by `backward()`.
+    pub analytical: f64,
+    /// The gradient estimated by central differences.
+    pub numerical: f64,
+}
+
+impl GradCheckMismatch {
+    /// `|analytical - numerical|`
+    pub fn abs_error(&self) -> f64 {
+        (self.analytical - self.numerical).abs()
+    }
+}
+
+/// The result of [gradcheck()] or [gradcheck_module()].
+#[derive(Debug, Clone, PartialEq)]
+pub struct GradCheckReport {
+    /// How many elements were checked.
+    pub num_checked: usize,
+    /// How many elements were outside of the tolerance.
+    pub num_mismatched: usize,
+    /// The element with the largest error relative to its tolerance. This is
+    /// set even if all elements are within tolerance, and is `None` only if there
+    /// were no elements to check.
+    pub worst: Option<GradCheckMismatch>,
+}
+
+impl GradCheckReport {
+    /// Whether all the checked elements were within tolerance.
+    pub fn passed(&self) -> bool {
+        self.num_mismatched == 0
+    }
+}
+
+impl std::fmt::Display for GradCheckReport {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{} of {} gradient elements mismatched",
+            self.num_mismatched, self.num_checked
+        )?;
+        if let Some(worst) = &self.worst {
+            write!(
+                f,
+                ", worst at {:?}{:?}: analytical={}, numerical={}",
+                worst.tensor, worst.index, worst.analytical, worst.numerical
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks the gradients of the scalar loss `f` with respect to `x` against
+/// central difference estimates. See [GradCheckConfig] for the tolerances used.
+///
+/// `f` is called once to compute the analytical gradients, and twice per element of `x`.
+pub fn gradcheck<S: Shape, E: Dtype, D: Device<E>, F>(
+    f: F,
+    x: &Tensor<S, E, D>,
+    cfg: GradCheckConfig,
+) -> GradCheckReport
+where
+    F: FnMut(Tensor<S, E, D, OwnedTape<E, D>>) -> Tensor<Rank0, E, D, OwnedTape<E, D>>,
+{
+    try_gradcheck(f, x, cfg).unwrap()
+}
+
+/// Fallible version of [gradcheck()].
+pub fn try_gradcheck<S: Shape, E: Dtype, D: Device<E>, F>(
+    mut f: F,
+    x: &Tensor<S, E, D>,
+    cfg: GradCheckConfig,
+) -> Result<GradCheckReport, Error>
+where
+    F: FnMut(Tensor<S, E, D, OwnedTape<E, D>>) -> Tensor<Rank0, E, D, OwnedTape<E, D>>,
+{
+    try_gradcheck_module(&mut NoParams, x, |_, x| f(x), cfg)
+}
+
+/// A module without any parameters, used by [try_gradcheck()].
+struct NoParams;
+
+impl<E: Dtype, D: Device<E>> UpdateParams<E, D> for NoParams {
+    fn try_update_params<M, Optim: Optimizer<M, E, D>>(
+        &mut self,
+        _optimizer: &mut Optim,
+        _gradients: &Gradients<E, D>,
+        _missing_tensors: &mut Vec<UniqueId>,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Checks the gradients of the scalar loss `f` with respect to both `x` and every
+/// parameter of `module` against central difference estimates. See [GradCheckConfig]
+/// for the tolerances used.
+///
+/// `module` is perturbed in place, and restored to its original values before returning.
+///
+/// ```rust
+/// # use dfdx_core::prelude::*;
+/// # use dfdx_core::gradcheck::*;
+/// # let dev: Cpu = Default::default();
+/// let mut model: (Tensor<Rank2<3, 2>, f64, _>, Tensor<Rank1<2>, f64, _>) =
+///     (dev.sample_normal(), dev.sample_normal());
+/// let x: Tensor<Rank2<4, 3>, f64, _> = dev.sample_normal();
+/// let report = gradcheck_module(
+///     &mut model,
+///     &x,
+///     |(w, b), x| (x.matmul(w.clone()) + b.clone().broadcast()).tanh().mean(),
+///     Default::default(),
+/// );
+/// assert!(report.passed(), "{report}");
+/// ```
+pub fn gradcheck_module<M, S: Shape, E: Dtype, D: Device<E>, F>(
+    module: &mut M,
+    x: &Tensor<S, E, D>,
+    f: F,
+    cfg: GradCheckConfig,
+) -> GradCheckReport
+where
+    M: UpdateParams<E, D>,
+    F: FnMut(&M, Tensor<S, E, D, OwnedTape<E, D>>) -> Tensor<Rank0, E, D, OwnedTape<E, D>>,
+{
+    try_gradcheck_module(module, x, f, cfg).unwrap()
+}
+
+/// Fallible version of [gradcheck_module()].
+pub fn try_gradcheck_module<M, S: Shape, E: Dtype, D: Device<E>, F>(
+    module: &mut M,
+    x: &Tensor<S, E, D>,
+    mut f: F,
+    cfg: GradCheckConfig,
+) -> Result<GradCheckReport, Error>
+where
+    M: UpdateParams<E, D>,
+    F: FnMut(&M, Tensor<S, E, D, OwnedTape<E, D>>) -> Tensor<Rank0, E, D, OwnedTape<E, D>>,
+{
+    let grads = f(module, x.leaky_trace()).try_backward()?;
+
+    let mut params = Vec::new();
+    module.try_update_params::<M, _>(
+        &mut ParamVisitor {
+            visit: Visit::Collect(&mut params),
+            seen: 0,
+        },
+        &grads,
+        &mut Vec::new(),
+    )?;
+
+    let mut checker = Checker::new(cfg);
+
+    // parameters
+    for (i, param) in params.iter().enumerate() {
+        let mut data = param.data.clone();
+        for j in 0..data.len() {
+            let numerical = central_difference(&mut data, j, cfg.eps, |data| {
+                write_param(module, i, data)?;
+                Ok(scalar(&f(module, x.leaky_trace())))
+            })?;
+            checker.check(GradCheckTensor::Param(i), param, j, numerical);
+        }
+        write_param(module, i, &param.data)?;
+    }
+
+    // input
+    let input = Param::new(x, grads.get_ref_checked(x).map(|_| grads.get(x)));
+    let mut x = x.clone();
+    let mut data = input.data.clone();
+    for j in 0..data.len() {
+        let numerical = central_difference(&mut data, j, cfg.eps, |data| {
+            x.copy_from(data);
+            Ok(scalar(&f(module, x.leaky_trace())))
+        })?;
+        checker.check(GradCheckTensor::Input, &input, j, numerical);
+    }
+
+    Ok(checker.report)
+}
+
+/// Estimates the derivative of `loss` with respect to `data[j]`, and leaves `data` unchanged.
+fn central_difference<E: Dtype>(
+    data: &mut [E],
+    j: usize,
+    eps: f64,
+    mut loss: impl FnMut(&[E]) -> Result<f64, Error>,
+) -> Result<f64, Error> {
+    let orig = data[j];
+    let v = orig.to_f64().unwrap();
+    let hi = E::from_f64(v + eps).unwrap();
+    let lo = E::from_f64(v - eps).unwrap();
+    data[j] = hi;
+    let loss_hi = loss(data)?;
+    data[j] = lo;
+    let loss_lo = loss(data)?;
+    data[j] = orig;
+    // use the actual step after rounding to `E`
+    let step = hi.to_f64().unwrap() - lo.to_f64().unwrap();
+    Ok((loss_hi - loss_lo) / step)
+}
+
+/// Physical data & gradient of a tensor, along with what is needed to
+/// turn a physical index back into a logical one.
+struct Param<E> {
+    data: Vec<E>,
+    grad: Option<Vec<E>>,
+    dims: Vec<usize>,
+    strides: Vec<usize>,
+}
+
+impl<E: Dtype> Param<E> {
+    fn new<S: Shape, D: Device<E>, T>(
+        t: &Tensor<S, E, D, T>,
+        grad: Option<Tensor<S, E, D>>,
+    ) -> Self {
+        Self {
+            data: physical(t),
+            grad: grad.as_ref().map(physical),
+            dims: t.shape.concrete().into_iter().collect(),
+            strides: t.strides.into_iter().collect(),
+        }
+    }
+
+    fn analytical(&self, j: usize) -> f64 {
+        self.grad.as_ref().map_or(0.0, |g| g[j].to_f64().unwrap())
+    }
+
+    /// The first logical index that refers to physical index `j`.
+    fn logical_index(&self, j: usize) -> Vec<usize> {
+        let numel: usize = self.dims.iter().product();
+        let mut index = std::vec![0; self.dims.len()];
+        for i in 0..numel {
+            let mut rem = i;
+            for (idx, dim) in index.iter_mut().zip(self.dims.iter()).rev() {
+                *idx = rem % dim;
+                rem /= dim;
+            }
+            let offset: usize = index
+                .iter()
+                .zip(self.strides.iter())
+                .map(|(a, b)| a * b)
+                .sum();
+            if offset == j {
+                break;
+            }
+        }
+        index
+    }
+}
+
+fn physical<S: Shape, E: Dtype, D: Device<E>, T>(t: &Tensor<S, E, D, T>) -> Vec<E> {
+    let mut buf = std::vec![E::default(); <D as Storage<E>>::len(&t.device, &t.data)];
+    t.copy_into(&mut buf);
+    buf
+}
+
+fn scalar<E: Dtype, D: Device<E>, T>(t: &Tensor<Rank0, E, D, T>) -> f64 {
+    physical(t)[0].to_f64().unwrap()
+}
+
+struct Checker {
+    cfg: GradCheckConfig,
+    worst_ratio: f64,
+    report: GradCheckReport,
+}
+
+impl Checker {
+    fn new(cfg: GradCheckConfig) -> Self {
+        Self {
+            cfg,
+            worst_ratio: -1.0,
+            report: GradCheckReport {
+                num_checked: 0,
+                num_mismatched: 0,
+                worst: None,
+            },
+        }
+    }
+
+    fn check<E: Dtype>(&mut self, tensor: GradCheckTensor, p: &Param<E>, j: usize, numerical: f64) {
+        let analytical = p.analytical(j);
+        let err = (analytical - numerical).abs();
+        let tol = self.cfg.atol + self.cfg.rtol * numerical.abs();
+        let ratio = if err == 0.0 { 0.0 } else { err / tol };
+
+        self.report.num_checked += 1;
+        if err > tol || err.is_nan() {
+            self.report.num_mismatched += 1;
+        }
+        if ratio.is_nan() || ratio > self.worst_ratio {
+            self.worst_ratio = ratio;
+            self.report.worst = Some(GradCheckMismatch {
+                tensor,
+                index: p.logical_index(j),
+                analytical,
+                numerical,
+            });
+        }
+    }
+}
+
+fn write_param<M: UpdateParams<E, D>, E: Dtype, D: Device<E>>(
+    module: &mut M,
+    index: usize,
+    data: &[E],
+) -> Result<(), Error> {
+    module.try_update_params::<M, _>(
+        &mut ParamVisitor {
+            visit: Visit::Write { index, data },
+            seen: 0,
+        },
+        &Gradients::leaky(),
+        &mut Vec::new(),
+    )
+}
+
+enum Visit<'a, E> {
+    Collect(&'a mut Vec<Param<E>>),
+    Write { index: usize, data: &'a [E] },
+}
+
+/// Visits every parameter of a module through [UpdateParams], either
+/// reading them out or overwriting a single one.
+struct ParamVisitor<'a, E> {
+    visit: Visit<'a, E>,
+    seen: usize,
+}
+
+impl<M, E: Dtype, D: Device<E>> Optimizer<M, E, D> for ParamVisitor<'_, E> {
+    fn update_tensor<S: Shape>(
+        &mut self,
+        t: &mut Tensor<S, E, D>,
+        gradients: &Gradients<E, D>,
+        _missing_tensors: &mut Vec<UniqueId>,
+    ) -> Result<(), Error> {
+        match &mut self.visit {
+            Visit::Collect(params) => {
+                let grad = gradients.get_ref_checked(t).map(|_| gradients.get(t));
+                params.push(Param::new(t, grad));
+            }
+            Visit::Write { index, data } => {
+                if self.seen == *index {
+                    t.copy_from(data);
+                }
+            }
+        }
+        self.seen += 1;
+        Ok(())
+    }
+
+    fn update_frozen_tensor<S: Shape>(
+        &mut self,
+        t: &mut Tensor<S, E, D>,
+        gradients: &Gradients<E, D>,
+        missing_tensors: &mut Vec<UniqueId>,
+    ) -> Result<(), Error> {
+        Optimizer::<M, E, D>::update_tensor(self, t, gradients, missing_tensors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::*;
+
+    #[test]
+    fn test_gradcheck_passes() {
+        let dev: TestDevice = Default::default();
+        let x: Tensor<Rank2<2, 3>, TestDtype, _> = dev.sample_normal();
+        let report = gradcheck(
+            |x| x.square().sin().sum::<Rank1<3>, _>().tanh().mean(),
+            &x,
+            Default::default(),
+        );
+        assert!(report.passed(), "{report}");
+        assert_eq!(report.num_checked, 6);
+        assert!(report.worst.is_some());
+    }
+
+    #[test]
+    fn test_gradcheck_broadcasted_input() {
+        let dev: TestDevice = Default::default();
+        let x: Tensor<Rank1<3>, TestDtype, _> = dev.sample_normal();
+        let x: Tensor<Rank2<2, 3>, TestDtype, _> = x.broadcast();
+        let report = gradcheck(|x| x.tanh().square().sum(), &x, Default::default());
+        assert!(report.passed(), "{report}");
+        assert_eq!(report.num_checked, 3);
+    }
+
+    #[derive(Debug, Clone)]
+    struct WrongSquare;
+
+    impl<S: Shape> CustomOp<S, TestDtype, TestDevice> for WrongSquare {
+        type Output = S;
+        fn forward(
+            &self,
+            x: &Tensor<S, TestDtype, TestDevice>,
+        ) -> Result<Tensor<S, TestDtype, TestDevice>, Error> {
+            x.clone().try_square()
+        }
+        fn backward(
+            &self,
+            x: &Tensor<S, TestDtype, TestDevice>,
+            _y: &Tensor<S, TestDtype, TestDevice>,
+            grad_y: &Tensor<S, TestDtype, TestDevice>,
+        ) -> Result<Tensor<S, TestDtype, TestDevice>, Error> {
+            // missing the factor of 2
+            x.clone().try_mul(grad_y.clone())
+        }
+    }
+
+    #[test]
+    fn test_gradcheck_reports_worst_element() {
+        let dev: TestDevice = Default::default();
+        let x: Tensor<Rank2<2, 2>, TestDtype, _> = dev
+            .tensor([[0.1, -0.2], [2.0, 0.5]])
+            .to_dtype::<TestDtype>();
+        let report = gradcheck(|x| x.custom_op(WrongSquare).sum(), &x, Default::default());
+        assert!(!report.passed());
+        assert_eq!(report.num_checked, 4);
+        assert_eq!(report.num_mismatched, 4);
+        let worst = report.worst.unwrap();
+        assert_eq!(worst.tensor, GradCheckTensor::Input);
+        assert_eq!(worst.index, std::vec![1, 0]);
+        assert!((worst.analytical - 2.0).abs() < 1e-3);
+        assert!((worst.numerical - 4.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn test_gradcheck_module() {
+        let dev: TestDevice = Default::default();
+        let mut model: (
+            Tensor<Rank2<3, 2>, TestDtype, _>,
+            Tensor<Rank1<2>, TestDtype, _>,
+        ) = (dev.sample_normal(), dev.sample_normal());
+        let model_before = (model.0.array(), model.1.array());
+        let x: Tensor<Rank2<4, 3>, TestDtype, _> = dev.sample_normal();
+
+        let report = gradcheck_module(
+            &mut model,
+            &x,
+            |(w, b), x| {
+                (x.matmul(w.clone()) + b.clone().broadcast())
+                    .sigmoid()
+                    .mean()
+            },
+            Default::default(),
+        );
+        assert!(report.passed(), "{report}");
+        assert_eq!(report.num_checked, 6 + 2 + 12);
+
+        // parameters are restored
+        assert_eq!(model.0.array(), model_before.0);
+        assert_eq!(model.1.array(), model_before.1);
+    }
+
+    #[test]
+    fn test_gradcheck_module_wrong_param_grad() {
+        let dev: TestDevice = Default::default();
+        let mut model: (Tensor<Rank1<3>, TestDtype, _>,) =
+            (dev.tensor([0.5, -1.0, 1.5]).to_dtype::<TestDtype>(),);
+        let x: Tensor<Rank1<3>, TestDtype, _> = dev.ones();
+        let report = gradcheck_module(
+            &mut model,
+            &x,
+            |(w,), x| {
+                let (x, tape) = x.split_tape();
+                (w.clone().put_tape(tape).custom_op(WrongSquare) * x).sum()
+            },
+            Default::default(),
+        );
+        assert!(!report.passed());
+        let worst = report.worst.unwrap();
+        assert_eq!(worst.tensor, GradCheckTensor::Param(0));
+        assert_eq!(worst.index, std::vec![2]);
+    }
+}
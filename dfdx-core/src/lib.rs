@@ -112,8 +112,10 @@ extern crate alloc;
 extern crate no_std_compat as std;
 extern crate self as dfdx_core;
 
+pub mod autograd;
 pub mod data;
 pub mod dtypes;
+pub mod gradcheck;
 pub mod losses;
 pub mod nn_traits;
 pub mod shapes;
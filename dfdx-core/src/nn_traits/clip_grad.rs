@@ -0,0 +1,175 @@
+use crate::{
+    nn_traits::{ParamVisitor, UpdateParams},
+    prelude::*,
+};
+
+/// Rescales the gradients of all parameters of `module` so that their global L2 norm
+/// is at most `max_norm`. Returns the global norm from *before* clipping.
+///
+/// The parameters are visited with [UpdateParams::try_visit_params()], and `module` itself
+/// is not modified. Parameters without a gradient in `grads` are skipped.
+///
+/// ```rust
+/// # use dfdx_core::prelude::*;
+/// # use dfdx_core::nn_traits::*;
+/// # let dev: Cpu = Default::default();
+/// let model: (Tensor<Rank1<2>, f32, _>, Tensor<Rank1<2>, f32, _>) =
+///     (dev.tensor([3.0, 0.0]), dev.tensor([0.0, 4.0]));
+/// // the gradient of `0.5 * x^2` is `x`
+/// let loss = model.0.leaky_trace().square().sum() + model.1.leaky_trace().square().sum();
+/// let mut grads = (loss * 0.5).backward();
+/// let norm = clip_grad_norm(&model, &mut grads, 1.0);
+/// assert_eq!(norm, 5.0);
+/// // the gradients are now roughly [0.6, 0.0] and [0.0, 0.8]
+/// ```
+pub fn clip_grad_norm<M: UpdateParams<E, D>, E: Dtype, D: Device<E>>(
+    module: &M,
+    grads: &mut Gradients<E, D>,
+    max_norm: impl Into<f64>,
+) -> f64 {
+    try_clip_grad_norm(module, grads, max_norm).unwrap()
+}
+
+/// Fallible version of [clip_grad_norm()].
+pub fn try_clip_grad_norm<M: UpdateParams<E, D>, E: Dtype, D: Device<E>>(
+    module: &M,
+    grads: &mut Gradients<E, D>,
+    max_norm: impl Into<f64>,
+) -> Result<f64, Error> {
+    let mut visitor = GradVisitor {
+        grads,
+        op: GradOp::SumSquares(0.0),
+    };
+    visitor.visit(module)?;
+    let GradOp::SumSquares(sum_squares) = visitor.op else {
+        unreachable!()
+    };
+    let norm = sum_squares.sqrt();
+
+    let max_norm = max_norm.into();
+    let scale = max_norm / (norm + 1e-6);
+    if scale < 1.0 {
+        visitor.op = GradOp::Scale(scale);
+        visitor.visit(module)?;
+    }
+    Ok(norm)
+}
+
+/// Clamps every gradient element of all parameters of `module` to `[-value, value]`.
+///
+/// The parameters are visited with [UpdateParams::try_visit_params()], and `module` itself
+/// is not modified. Parameters without a gradient in `grads` are skipped.
+pub fn clip_grad_value<M: UpdateParams<E, D>, E: Dtype, D: Device<E>>(
+    module: &M,
+    grads: &mut Gradients<E, D>,
+    value: impl Into<f64>,
+) {
+    try_clip_grad_value(module, grads, value).unwrap()
+}
+
+/// Fallible version of [clip_grad_value()].
+pub fn try_clip_grad_value<M: UpdateParams<E, D>, E: Dtype, D: Device<E>>(
+    module: &M,
+    grads: &mut Gradients<E, D>,
+    value: impl Into<f64>,
+) -> Result<(), Error> {
+    GradVisitor {
+        grads,
+        op: GradOp::Clamp(value.into()),
+    }
+    .visit(module)
+}
+
+enum GradOp {
+    SumSquares(f64),
+    Scale(f64),
+    Clamp(f64),
+}
+
+/// Applies [GradOp] to the gradient of each parameter visited through [UpdateParams].
+struct GradVisitor<'a, E, D: Storage<E>> {
+    grads: &'a mut Gradients<E, D>,
+    op: GradOp,
+}
+
+impl<E: Dtype, D: Device<E>> GradVisitor<'_, E, D> {
+    fn visit<M: UpdateParams<E, D>>(&mut self, module: &M) -> Result<(), Error> {
+        module.try_visit_params(self)
+    }
+}
+
+impl<E: Dtype, D: Device<E>> ParamVisitor<E, D> for GradVisitor<'_, E, D> {
+    fn visit_tensor<S: Shape>(&mut self, t: &Tensor<S, E, D>) -> Result<(), Error> {
+        if self.grads.get_ref_checked(t).is_none() {
+            return Ok(());
+        }
+        let g = self.grads.get(t);
+        let g = match &mut self.op {
+            GradOp::SumSquares(sum_squares) => {
+                let g = g.try_square()?.try_sum::<Rank0, _>()?.as_vec()[0];
+                *sum_squares += g.to_f64().unwrap();
+                return Ok(());
+            }
+            GradOp::Scale(scale) => g.try_mul(*scale)?,
+            GradOp::Clamp(value) => g.try_clamp(-*value, *value)?,
+        };
+        self.grads.insert(t, g);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::*;
+
+    #[test]
+    fn test_clip_grad_norm() {
+        let dev: TestDevice = Default::default();
+        let model: (
+            Tensor<Rank1<2>, TestDtype, _>,
+            Tensor<Rank2<2, 2>, TestDtype, _>,
+        ) = (
+            dev.tensor([1.0, 2.0]).to_dtype(),
+            dev.tensor([[0.0, 1.0], [-1.0, 3.0]]).to_dtype(),
+        );
+        // gradient of 0.5 * x^2 is x, so the gradients are equal to the parameters
+        let loss = model.0.leaky_trace().square().sum() * 0.5;
+        let loss = loss + model.1.leaky_trace().square().sum() * 0.5;
+        let mut grads = loss.backward();
+
+        let norm = clip_grad_norm(&model, &mut grads, 1.5);
+        assert!((norm - 4.0).abs() < 1e-4);
+        assert_close_to_literal!(grads.get(&model.0), [0.375, 0.75]);
+        assert_close_to_literal!(grads.get(&model.1), [[0.0, 0.375], [-0.375, 1.125]]);
+
+        // already within the max norm
+        let norm = clip_grad_norm(&model, &mut grads, 10.0);
+        assert!((norm - 1.5).abs() < 1e-4);
+        assert_close_to_literal!(grads.get(&model.0), [0.375, 0.75]);
+    }
+
+    #[test]
+    fn test_clip_grad_norm_skips_missing_grads() {
+        let dev: TestDevice = Default::default();
+        let model: (
+            Tensor<Rank1<2>, TestDtype, _>,
+            Tensor<Rank1<3>, TestDtype, _>,
+        ) = (dev.tensor([3.0, 4.0]).to_dtype(), dev.sample_normal());
+        let mut grads = (model.0.leaky_trace().square().sum() * 0.5).backward();
+        let norm = clip_grad_norm(&model, &mut grads, 1.0);
+        assert!((norm - 5.0).abs() < 1e-4);
+        assert_close_to_literal!(grads.get(&model.0), [0.6, 0.8]);
+        assert!(grads.get_ref_checked(&model.1).is_none());
+    }
+
+    #[test]
+    fn test_clip_grad_value() {
+        let dev: TestDevice = Default::default();
+        let model: (Tensor<Rank1<4>, TestDtype, _>,) =
+            (dev.tensor([-3.0, -0.5, 0.25, 2.0]).to_dtype(),);
+        let mut grads = (model.0.leaky_trace().square().sum() * 0.5).backward();
+        clip_grad_value(&model, &mut grads, 1.0);
+        assert_close_to_literal!(grads.get(&model.0), [-1.0, -0.5, 0.25, 1.0]);
+    }
+}
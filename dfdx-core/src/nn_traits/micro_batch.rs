@@ -0,0 +1,236 @@
+use std::{ops::Range, vec::Vec};
+
+use crate::{
+    nn_traits::{Optimizer, UpdateParams},
+    prelude::*,
+    tensor_ops::SliceKernel,
+};
+
+/// Something that can be split into micro-batches along its first dimension, like
+/// the inputs (and targets) of a batch. Implemented for tensors without a tape, and
+/// tuples of them.
+pub trait MicroBatch {
+    type Micro;
+
+    /// The size of the first dimension.
+    fn batch_size(&self) -> usize {
+        self.try_batch_size().unwrap()
+    }
+
+    /// Fallible version of [MicroBatch::batch_size()]. Returns [Error::InvalidArgument] if
+    /// the parts of a batch have different sizes.
+    fn try_batch_size(&self) -> Result<usize, Error>;
+
+    /// Slices `range` out of the first dimension.
+    fn try_micro_batch(&self, range: Range<usize>) -> Result<Self::Micro, Error>;
+}
+
+macro_rules! tensor_micro_batch {
+    ([$($Dim:ident),*] [$($full:tt),*]) => {
+        impl<B: Dim, $($Dim: Dim,)* E: Unit, D: SliceKernel<E>> MicroBatch
+            for Tensor<(B, $($Dim,)*), E, D>
+        {
+            type Micro = Tensor<(usize, $($Dim,)*), E, D>;
+
+            fn try_batch_size(&self) -> Result<usize, Error> {
+                Ok(self.shape.0.size())
+            }
+
+            fn try_micro_batch(&self, range: Range<usize>) -> Result<Self::Micro, Error> {
+                self.clone().try_slice((range, $($full,)*))
+            }
+        }
+    };
+}
+
+tensor_micro_batch!([] []);
+tensor_micro_batch!([D1][..]);
+tensor_micro_batch!([D1, D2] [.., ..]);
+tensor_micro_batch!([D1, D2, D3] [.., .., ..]);
+tensor_micro_batch!([D1, D2, D3, D4] [.., .., .., ..]);
+tensor_micro_batch!([D1, D2, D3, D4, D5] [.., .., .., .., ..]);
+
+macro_rules! tuple_micro_batch {
+    ([$($name:ident),+] [$($idx:tt),+]) => {
+        impl<$($name: MicroBatch),+> MicroBatch for ($($name,)+) {
+            type Micro = ($($name::Micro,)+);
+
+            fn try_batch_size(&self) -> Result<usize, Error> {
+                let sizes = [$(self.$idx.try_batch_size()?),+];
+                if sizes.iter().any(|&s| s != sizes[0]) {
+                    return Err(Error::InvalidArgument(std::format!(
+                        "All parts of a batch must have the same batch size, found {sizes:?}"
+                    )));
+                }
+                Ok(sizes[0])
+            }
+
+            fn try_micro_batch(&self, range: Range<usize>) -> Result<Self::Micro, Error> {
+                Ok(($(self.$idx.try_micro_batch(range.clone())?,)+))
+            }
+        }
+    };
+}
+
+tuple_micro_batch!([A, B] [0, 1]);
+tuple_micro_batch!([A, B, C] [0, 1, 2]);
+tuple_micro_batch!([A, B, C, D] [0, 1, 2, 3]);
+
+/// Splits `batch` into micro-batches of at most `micro_batch_size` along its first dimension,
+/// and accumulates the gradients of the loss of each micro-batch into a single [Gradients],
+/// ready for [Optimizer::update()]. This trades speed for memory: only one micro-batch is
+/// in memory at a time, so the effective batch size can be much larger.
+///
+/// `loss_fn` is called with the module, a micro-batch, and the gradients to trace the
+/// micro-batch with (e.g. `x.traced(grads)`). It should return the **mean** loss over the micro-batch.
+/// Each loss is weighted by the fraction of the batch in its micro-batch, so the result is the
+/// gradient of the mean loss over the whole batch, even if the last micro-batch is smaller.
+///
+/// ```rust
+/// # use dfdx_core::prelude::*;
+/// # use dfdx_core::nn_traits::*;
+/// # let dev: Cpu = Default::default();
+/// let mut model: Tensor<Rank2<3, 2>, f32, _> = dev.sample_normal();
+/// let x: Tensor<Rank2<10, 3>, f32, _> = dev.sample_normal();
+/// let y: Tensor<Rank2<10, 2>, f32, _> = dev.sample_normal();
+/// // 4 micro-batches of sizes 3, 3, 3 & 1
+/// let grads = accumulate_grads(&mut model, &(x, y), 3, |m, (x, y), grads| {
+///     (x.traced(grads).matmul(m.clone()) - y).square().mean()
+/// });
+/// ```
+pub fn accumulate_grads<M, B, E, D, F>(
+    module: &mut M,
+    batch: &B,
+    micro_batch_size: usize,
+    loss_fn: F,
+) -> Gradients<E, D>
+where
+    M: UpdateParams<E, D>,
+    B: MicroBatch,
+    E: Dtype,
+    D: Device<E>,
+    F: FnMut(&mut M, B::Micro, Gradients<E, D>) -> Tensor<Rank0, E, D, OwnedTape<E, D>>,
+{
+    try_accumulate_grads(module, batch, micro_batch_size, loss_fn).unwrap()
+}
+
+/// Fallible version of [accumulate_grads()].
+pub fn try_accumulate_grads<M, B, E, D, F>(
+    module: &mut M,
+    batch: &B,
+    micro_batch_size: usize,
+    mut loss_fn: F,
+) -> Result<Gradients<E, D>, Error>
+where
+    M: UpdateParams<E, D>,
+    B: MicroBatch,
+    E: Dtype,
+    D: Device<E>,
+    F: FnMut(&mut M, B::Micro, Gradients<E, D>) -> Tensor<Rank0, E, D, OwnedTape<E, D>>,
+{
+    if micro_batch_size == 0 {
+        return Err(Error::InvalidArgument(
+            "micro_batch_size must be at least 1".into(),
+        ));
+    }
+
+    // pre-allocate the gradients of the parameters, so the gradients of
+    // temporary tensors are dropped after each backward
+    let mut grads = Gradients::leaky();
+    module.try_update_params::<M, _>(
+        &mut AllocGrads(&mut grads),
+        &Gradients::leaky(),
+        &mut Vec::new(),
+    )?;
+    grads.retain_current_grads_as_leafs();
+
+    let batch_size = batch.try_batch_size()?;
+    let mut start = 0;
+    while start < batch_size {
+        let end = batch_size.min(start + micro_batch_size);
+        let micro = batch.try_micro_batch(start..end)?;
+        let loss = loss_fn(module, micro, grads);
+        let weight = (end - start) as f64 / batch_size as f64;
+        grads = loss.try_mul(weight)?.try_backward()?;
+        start = end;
+    }
+    Ok(grads)
+}
+
+/// Allocates the gradient of each parameter.
+struct AllocGrads<'a, E, D: Storage<E>>(&'a mut Gradients<E, D>);
+
+impl<M, E: Dtype, D: Device<E>> Optimizer<M, E, D> for AllocGrads<'_, E, D> {
+    fn update_tensor<S: Shape>(
+        &mut self,
+        t: &mut Tensor<S, E, D>,
+        _gradients: &Gradients<E, D>,
+        _missing_tensors: &mut Vec<UniqueId>,
+    ) -> Result<(), Error> {
+        self.0.try_alloc_for(t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::*;
+
+    #[test]
+    fn test_micro_batch_tensors() {
+        let dev: TestDevice = Default::default();
+        let x: Tensor<Rank2<3, 2>, TestDtype, _> =
+            dev.tensor([[1.0, 2.0], [3.0, 4.0], [5.0, 6.0]]).to_dtype();
+        let y: Tensor<Rank1<3>, usize, _> = dev.tensor([7, 8, 9]);
+        let batch = (x, y);
+        assert_eq!(batch.batch_size(), 3);
+        let (x, y) = batch.try_micro_batch(1..3).unwrap();
+        assert_eq!(x.shape, (2, Const::<2>));
+        assert_close_to_literal!(x.realize::<Rank2<2, 2>>(), [[3.0, 4.0], [5.0, 6.0]]);
+        assert_eq!(y.as_vec(), [8, 9]);
+    }
+
+    type Model = (
+        Tensor<Rank2<3, 2>, TestDtype, TestDevice>,
+        Tensor<Rank1<2>, TestDtype, TestDevice>,
+    );
+    type Micro<const N: usize> = Tensor<(usize, Const<N>), TestDtype, TestDevice>;
+
+    fn loss_fn(
+        m: &mut Model,
+        (x, y): (Micro<3>, Micro<2>),
+        grads: Gradients<TestDtype, TestDevice>,
+    ) -> Tensor<Rank0, TestDtype, TestDevice, OwnedTape<TestDtype, TestDevice>> {
+        let b = x.shape.0;
+        let out = x.traced(grads).matmul(m.0.clone()) + m.1.clone().broadcast_like(&(b, Const));
+        (out - y).square().mean()
+    }
+
+    #[test]
+    fn test_accumulate_grads_matches_full_batch() {
+        let dev: TestDevice = Default::default();
+        let mut model: Model = (dev.sample_normal(), dev.sample_normal());
+        let x: Tensor<Rank2<5, 3>, TestDtype, _> = dev.sample_normal();
+        let y: Tensor<Rank2<5, 2>, TestDtype, _> = dev.sample_normal();
+
+        let full = accumulate_grads(&mut model, &(x.clone(), y.clone()), 5, loss_fn);
+        // micro-batches of 2, 2 & 1
+        let micro = accumulate_grads(&mut model, &(x, y), 2, loss_fn);
+        assert_close_to_tensor!(micro.get(&model.0), full.get(&model.0));
+        assert_close_to_tensor!(micro.get(&model.1), full.get(&model.1));
+    }
+
+    #[test]
+    fn test_accumulate_grads_invalid_arguments() {
+        let dev: TestDevice = Default::default();
+        let mut model: Model = (dev.sample_normal(), dev.sample_normal());
+        let x: Tensor<Rank2<5, 3>, TestDtype, _> = dev.sample_normal();
+        let y: Tensor<Rank2<5, 2>, TestDtype, _> = dev.sample_normal();
+        let result = try_accumulate_grads(&mut model, &(x.clone(), y), 0, loss_fn);
+        assert!(matches!(result, Err(Error::InvalidArgument(_))));
+
+        let y: Tensor<Rank2<4, 2>, TestDtype, _> = dev.sample_normal();
+        let result = try_accumulate_grads(&mut model, &(x, y), 2, loss_fn);
+        assert!(matches!(result, Err(Error::InvalidArgument(_))));
+    }
+}
@@ -1,7 +1,16 @@
+mod clip_grad;
+mod micro_batch;
+#[cfg(feature = "safetensors")]
+mod optimizer_state;
 mod tuples;
 mod vecs;
 
-use std::vec::Vec;
+pub use clip_grad::{clip_grad_norm, clip_grad_value, try_clip_grad_norm, try_clip_grad_value};
+pub use micro_batch::{accumulate_grads, try_accumulate_grads, MicroBatch};
+#[cfg(feature = "safetensors")]
+pub use optimizer_state::OptimizerState;
+
+use std::{string::String, vec::Vec};
 
 use crate::prelude::{Device, Dtype, Error, Gradients, Shape, Tensor, UniqueId};
 
@@ -34,6 +43,20 @@ pub trait Optimizer<M, E: Dtype, D: Device<E>>: Sized {
         missing_tensors: &mut Vec<UniqueId>,
     ) -> Result<(), Error>;
 
+    /// Called instead of [Optimizer::update_tensor()] for the parameters of fields marked
+    /// with `#[frozen]`. Does nothing by default, so optimizers neither update them nor
+    /// require gradients for them. Visitors that aren't optimizers forward this to
+    /// [Optimizer::update_tensor()].
+    fn update_frozen_tensor<S: Shape>(
+        &mut self,
+        t: &mut Tensor<S, E, D>,
+        gradients: &Gradients<E, D>,
+        missing_tensors: &mut Vec<UniqueId>,
+    ) -> Result<(), Error> {
+        let _ = (t, gradients, missing_tensors);
+        Ok(())
+    }
+
     fn update(&mut self, module: &mut M, gradients: &Gradients<E, D>) -> Result<(), Error>
     where
         M: UpdateParams<E, D>,
@@ -48,6 +71,30 @@ pub trait Optimizer<M, E: Dtype, D: Device<E>>: Sized {
     }
 }
 
+/// Passes every parameter to [Optimizer::update_frozen_tensor()] of the wrapped optimizer.
+/// `#[derive(UpdateParams)]` uses this for sub modules marked with `#[frozen]`.
+pub struct Frozen<'a, O>(pub &'a mut O);
+
+impl<M, E: Dtype, D: Device<E>, O: Optimizer<M, E, D>> Optimizer<M, E, D> for Frozen<'_, O> {
+    fn update_tensor<S: Shape>(
+        &mut self,
+        t: &mut Tensor<S, E, D>,
+        gradients: &Gradients<E, D>,
+        missing_tensors: &mut Vec<UniqueId>,
+    ) -> Result<(), Error> {
+        self.0.update_frozen_tensor(t, gradients, missing_tensors)
+    }
+
+    fn update_frozen_tensor<S: Shape>(
+        &mut self,
+        t: &mut Tensor<S, E, D>,
+        gradients: &Gradients<E, D>,
+        missing_tensors: &mut Vec<UniqueId>,
+    ) -> Result<(), Error> {
+        self.0.update_frozen_tensor(t, gradients, missing_tensors)
+    }
+}
+
 /// Something that can be constructed on a device as a certain dtype.
 pub trait BuildOnDevice<E: Dtype, D: Device<E>>: Clone {
     type Built: Clone + std::fmt::Debug;
@@ -82,6 +129,26 @@ pub trait UpdateParams<E: Dtype, D: Device<E>> {
         gradients: &Gradients<E, D>,
         missing_tensors: &mut Vec<UniqueId>,
     ) -> Result<(), crate::tensor::Error>;
+
+    /// Collects the path & [UniqueId] of every parameter that [UpdateParams::try_update_params()]
+    /// updates, e.g. `"0.weight"` for the weight of the first layer of a tuple. A path is the key the
+    /// parameter is saved under with `SaveSafeTensors`. Optimizers use this to select parameters by
+    /// name, and to save their state. Does nothing by default, since only tensors have ids.
+    fn param_paths(&self, location: &str, paths: &mut Vec<(String, UniqueId)>) {
+        let _ = (location, paths);
+    }
+
+    /// Passes every parameter that [UpdateParams::param_paths()] collects to `visitor`, in the
+    /// same order, without modifying them. Does nothing by default.
+    fn try_visit_params<V: ParamVisitor<E, D>>(&self, visitor: &mut V) -> Result<(), Error> {
+        let _ = visitor;
+        Ok(())
+    }
+}
+
+/// Something that can read the parameters of a module, see [UpdateParams::try_visit_params()].
+pub trait ParamVisitor<E: Dtype, D: Device<E>> {
+    fn visit_tensor<S: Shape>(&mut self, t: &Tensor<S, E, D>) -> Result<(), Error>;
 }
 
 impl<S: Shape, E: Dtype, D: Device<E>> UpdateParams<E, D> for Tensor<S, E, D> {
@@ -93,6 +160,14 @@ impl<S: Shape, E: Dtype, D: Device<E>> UpdateParams<E, D> for Tensor<S, E, D> {
     ) -> Result<(), crate::tensor::Error> {
         optimizer.update_tensor(self, gradients, missing_tensors)
     }
+
+    fn param_paths(&self, location: &str, paths: &mut Vec<(String, UniqueId)>) {
+        paths.push((String::from(location), self.id));
+    }
+
+    fn try_visit_params<V: ParamVisitor<E, D>>(&self, visitor: &mut V) -> Result<(), Error> {
+        visitor.visit_tensor(self)
+    }
 }
 
 /// Something that can allocate a [Gradients] object or zero out the [Gradients] object.
@@ -0,0 +1,174 @@
+use crate::{
+    nn_traits::{Optimizer, SaveSafeTensors, UpdateParams},
+    shapes::{Dtype, Shape},
+    tensor::{Error, Gradients, Storage, Tensor, UniqueId},
+    tensor_ops::Device,
+};
+
+use ::safetensors::{SafeTensorError, SafeTensors};
+use std::{collections::BTreeMap, string::String, vec::Vec};
+
+/// An optimizer whose state can be saved to & loaded from a .safetensors file, so
+/// resuming a training run continues with the same moments/velocities instead of
+/// starting them from zero.
+///
+/// Each per-parameter buffer is stored under `"{buffer}.{key}"`, where `key` is the path of
+/// that parameter from [UpdateParams::param_paths()], e.g. `"moment1.0.weight"`. This is the
+/// same key that [SaveSafeTensors] uses for the parameter. Parameters are matched by key
+/// when loading, so the optimizer state can be loaded into a freshly built model (that has
+/// different [UniqueId]s) after loading its parameters.
+///
+/// `model` is only used to find the parameters, and is never modified.
+pub trait OptimizerState<E: Dtype, D: Device<E>> {
+    /// The per-parameter state of this optimizer, along with the name of each buffer.
+    fn state_buffers(&mut self) -> Vec<(&'static str, &mut Gradients<E, D>)>;
+
+    /// Writes state that isn't per-parameter, like the number of steps taken.
+    fn write_state_scalars(
+        &self,
+        tensors: &mut Vec<(String, ::safetensors::Dtype, Vec<usize>, Vec<u8>)>,
+    ) {
+        let _ = tensors;
+    }
+
+    /// Reads the state written by [OptimizerState::write_state_scalars()].
+    fn read_state_scalars(&mut self, tensors: &SafeTensors) -> Result<(), SafeTensorError> {
+        let _ = tensors;
+        Ok(())
+    }
+
+    /// Saves the state of this optimizer for the parameters of `model` to `path`.
+    fn save_state<M, P>(&mut self, model: &mut M, path: P) -> Result<(), SafeTensorError>
+    where
+        M: UpdateParams<E, D>,
+        P: AsRef<std::path::Path>,
+    {
+        let mut tensors = Vec::new();
+        self.write_state(model, &mut tensors)?;
+        let data = tensors.iter().map(|(k, dtype, shape, data)| {
+            (
+                k.clone(),
+                ::safetensors::tensor::TensorView::new(*dtype, shape.clone(), data).unwrap(),
+            )
+        });
+        ::safetensors::serialize_to_file(data, &None, path.as_ref())
+    }
+
+    /// Loads state saved with [OptimizerState::save_state()] for the parameters of `model`.
+    fn load_state<M, P>(&mut self, model: &mut M, path: P) -> Result<(), SafeTensorError>
+    where
+        M: UpdateParams<E, D>,
+        P: AsRef<std::path::Path>,
+    {
+        let f = std::fs::File::open(path)?;
+        let buffer = unsafe { memmap2::MmapOptions::new().map(&f)? };
+        let tensors = SafeTensors::deserialize(&buffer)?;
+        self.read_state(model, &tensors)
+    }
+
+    fn write_state<M>(
+        &mut self,
+        model: &mut M,
+        tensors: &mut Vec<(String, ::safetensors::Dtype, Vec<usize>, Vec<u8>)>,
+    ) -> Result<(), SafeTensorError>
+    where
+        M: UpdateParams<E, D>,
+    {
+        self.write_state_scalars(tensors);
+        StateVisitor::new(model, self.state_buffers(), StateOp::Write(tensors)).visit(model)
+    }
+
+    fn read_state<M>(&mut self, model: &mut M, tensors: &SafeTensors) -> Result<(), SafeTensorError>
+    where
+        M: UpdateParams<E, D>,
+    {
+        self.read_state_scalars(tensors)?;
+        StateVisitor::new(model, self.state_buffers(), StateOp::Read(tensors)).visit(model)
+    }
+}
+
+enum StateOp<'a, 'b> {
+    Write(&'a mut Vec<(String, ::safetensors::Dtype, Vec<usize>, Vec<u8>)>),
+    Read(&'a SafeTensors<'b>),
+}
+
+/// Visits each parameter through [UpdateParams], and writes or reads all of its buffers.
+struct StateVisitor<'a, 'b, E, D: Storage<E>> {
+    keys: BTreeMap<UniqueId, String>,
+    buffers: Vec<(&'static str, &'a mut Gradients<E, D>)>,
+    op: StateOp<'a, 'b>,
+    error: Option<SafeTensorError>,
+}
+
+impl<'a, 'b, E: Dtype, D: Device<E>> StateVisitor<'a, 'b, E, D> {
+    fn new<M: UpdateParams<E, D>>(
+        model: &M,
+        buffers: Vec<(&'static str, &'a mut Gradients<E, D>)>,
+        op: StateOp<'a, 'b>,
+    ) -> Self {
+        let mut paths = Vec::new();
+        model.param_paths("", &mut paths);
+        Self {
+            keys: paths.into_iter().map(|(key, id)| (id, key)).collect(),
+            buffers,
+            op,
+            error: None,
+        }
+    }
+
+    fn visit<M: UpdateParams<E, D>>(mut self, model: &mut M) -> Result<(), SafeTensorError> {
+        let mut missing = Vec::new();
+        let result = model.try_update_params::<M, _>(&mut self, &Gradients::leaky(), &mut missing);
+        if let Some(err) = self.error {
+            return Err(err);
+        }
+        result.map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e).into())
+    }
+}
+
+impl<M, E: Dtype, D: Device<E>> Optimizer<M, E, D> for StateVisitor<'_, '_, E, D> {
+    fn update_tensor<S: Shape>(
+        &mut self,
+        t: &mut Tensor<S, E, D>,
+        _gradients: &Gradients<E, D>,
+        _missing_tensors: &mut Vec<UniqueId>,
+    ) -> Result<(), Error> {
+        // parameters without a path (see [UpdateParams::param_paths()]) can't be matched up
+        // when loading
+        let Some(key) = self.keys.get(&t.id) else {
+            return Ok(());
+        };
+        if self.error.is_some() {
+            return Ok(());
+        }
+        for (name, buffer) in self.buffers.iter_mut() {
+            let key = if key.is_empty() {
+                String::from(*name)
+            } else {
+                std::format!("{name}.{key}")
+            };
+            match &mut self.op {
+                StateOp::Write(tensors) => {
+                    if buffer.get_ref_checked(t).is_some() {
+                        buffer.get(t).write_safetensors(&key, tensors);
+                    }
+                }
+                StateOp::Read(tensors) => {
+                    let mut state: Tensor<S, E, D> = t.device.try_zeros_like(&t.shape)?;
+                    match state.load_safetensor(tensors, &key) {
+                        Ok(()) => {
+                            buffer.insert(t, state);
+                        }
+                        // the optimizer didn't have any state for this parameter yet
+                        Err(SafeTensorError::TensorNotFound(_)) => (),
+                        Err(err) => {
+                            self.error = Some(err);
+                            return Ok(());
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -4,7 +4,7 @@ use crate::{
     tensor_ops::Device,
 };
 
-use std::vec::Vec;
+use std::{string::String, vec::Vec};
 
 macro_rules! tuple_impls {
     ([$($name:ident),+] [$($idx:tt),+], $last:ident, [$($rev_tail:ident),*]) => {
@@ -58,6 +58,15 @@ macro_rules! tuple_impls {
                 $(self.$idx.try_update_params(optimizer, gradients, missing_tensors)?;)+
                 Ok(())
             }
+
+            fn param_paths(&self, location: &str, paths: &mut Vec<(String, UniqueId)>) {
+                $(self.$idx.param_paths(&format!("{location}{}.", $idx), paths);)+
+            }
+
+            fn try_visit_params<V: crate::nn_traits::ParamVisitor<Elem, Dev>>(&self, visitor: &mut V) -> Result<(), Error> {
+                $(self.$idx.try_visit_params(visitor)?;)+
+                Ok(())
+            }
         }
 
         impl<Dev: Device<Elem>, Elem: Dtype, $($name: crate::nn_traits::ZeroGrads<Elem, Dev>),+> crate::nn_traits::ZeroGrads<Elem, Dev> for ($($name,)+) {
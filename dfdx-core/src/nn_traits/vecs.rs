@@ -4,7 +4,7 @@ use crate::{
     tensor_ops::Device,
 };
 
-use std::vec::Vec;
+use std::{string::String, vec::Vec};
 
 impl<E: Dtype, D: Device<E>, T: crate::nn_traits::BuildOnDevice<E, D>>
     crate::nn_traits::BuildOnDevice<E, D> for Vec<T>
@@ -42,6 +42,22 @@ impl<E: Dtype, D: Device<E>, T: crate::nn_traits::UpdateParams<E, D>>
         }
         Ok(())
     }
+
+    fn param_paths(&self, location: &str, paths: &mut Vec<(String, UniqueId)>) {
+        for (i, m_i) in self.iter().enumerate() {
+            m_i.param_paths(&format!("{location}{i}."), paths);
+        }
+    }
+
+    fn try_visit_params<V: crate::nn_traits::ParamVisitor<E, D>>(
+        &self,
+        visitor: &mut V,
+    ) -> Result<(), crate::tensor::Error> {
+        for m_i in self.iter() {
+            m_i.try_visit_params(visitor)?;
+        }
+        Ok(())
+    }
 }
 
 impl<E: Dtype, D: Device<E>, T: crate::nn_traits::ZeroGrads<E, D>> crate::nn_traits::ZeroGrads<E, D>
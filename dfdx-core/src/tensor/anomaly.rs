@@ -0,0 +1,105 @@
+use std::sync::Arc;
+
+use super::{unique_id, NoneTape, OwnedTape, Storage, Tensor};
+use crate::shapes::{Dtype, Shape};
+
+impl<S: Shape, E: Dtype, D: Storage<E>> Tensor<S, E, D, OwnedTape<E, D>> {
+    /// Enables anomaly detection on this tensor's tape. Operations return
+    /// [crate::tensor::Error::NonFiniteValue] as soon as their output contains NaN or Inf,
+    /// and so does backward as soon as an operation writes a NaN or Inf gradient.
+    /// Merging tapes keeps anomaly detection enabled.
+    ///
+    /// This copies every output & gradient to check it, so it is only meant for
+    /// debugging. Only operations that show up in [OwnedTape::graph()] are checked
+    /// in the forward pass.
+    ///
+    /// ```rust
+    /// # use dfdx_core::prelude::*;
+    /// # let dev: Cpu = Default::default();
+    /// let x = dev.tensor([1.0f32, 0.0, 2.0]);
+    /// let y = x.leaky_trace().detect_anomaly().sqrt();
+    /// // the derivative of sqrt is infinite at 0
+    /// let err = y.sum().try_backward().unwrap_err();
+    /// assert!(matches!(err, Error::NonFiniteValue { backward: true, .. }));
+    /// ```
+    pub fn detect_anomaly(mut self) -> Self {
+        let device = self.device.clone();
+        self.tape.anomaly_check = Some(Arc::new(move |data: &D::Vec| {
+            let t: Tensor<(usize,), E, D> = Tensor {
+                id: unique_id(),
+                data: Arc::new(data.clone()),
+                shape: (device.len(data),),
+                strides: [1],
+                device: device.clone(),
+                tape: NoneTape,
+            };
+            device
+                .tensor_to_vec(&t)
+                .into_iter()
+                .any(|x| !x.to_f64().map_or(false, f64::is_finite))
+        }));
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{shapes::*, tensor::*, tensor_ops::*, tests::*};
+
+    #[test]
+    fn test_no_anomaly() {
+        let dev: TestDevice = Default::default();
+        let x: Tensor<Rank2<2, 3>, TestDtype, _> = dev.sample_normal();
+        let y = x.leaky_trace().detect_anomaly().exp().sum::<Rank1<2>, _>();
+        let g = y.ln().sum().try_backward().unwrap();
+        assert_close_to_tensor!(g.get(&x), x.softmax::<Axis<1>>());
+    }
+
+    #[test]
+    fn test_forward_anomaly() {
+        let dev: TestDevice = Default::default();
+        let x: Tensor<Rank1<3>, TestDtype, _> = dev.tensor([1.0, -1.0, 2.0]).to_dtype();
+        let w: Tensor<Rank2<3, 2>, TestDtype, _> = dev.sample_normal();
+        let y = x.leaky_trace().detect_anomaly().exp().try_ln().unwrap();
+        let y = y.try_sqrt().unwrap_err();
+        assert!(matches!(
+            y,
+            Error::NonFiniteValue { ref op, ref shape, backward: false } if op == "sqrt" && shape == &[3]
+        ));
+
+        // anomaly detection carries over to tapes that were merged in
+        let y = x.leaky_trace().try_sqrt().unwrap();
+        let err = y.try_matmul(w.leaky_trace().detect_anomaly()).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::NonFiniteValue { ref op, backward: false, .. } if op == "matmul"
+        ));
+    }
+
+    #[test]
+    fn test_backward_anomaly() {
+        let dev: TestDevice = Default::default();
+        let x: Tensor<Rank2<2, 2>, TestDtype, _> = dev.tensor([[1.0, 0.0], [4.0, 1.0]]).to_dtype();
+        let y = x.leaky_trace().detect_anomaly().sqrt() * 2.0;
+        let err = y.sum().try_backward().unwrap_err();
+        let Error::NonFiniteValue {
+            op,
+            shape,
+            backward,
+        } = err
+        else {
+            panic!("{err:?}");
+        };
+        assert_eq!(op, "sqrt");
+        assert_eq!(shape, [2, 2]);
+        assert!(backward);
+
+        // without anomaly detection the inf is silently propagated
+        let g = (x.leaky_trace().sqrt() * 2.0).sum().backward();
+        assert!(g
+            .get(&x)
+            .as_vec()
+            .iter()
+            .any(|x| num_traits::Float::is_infinite(*x)));
+    }
+}
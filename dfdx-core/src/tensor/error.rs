@@ -9,6 +9,29 @@ pub enum Error {
     WrongNumElements,
     /// Some tensors were unused by an optimizer in a graph.
     UnusedTensors(std::vec::Vec<crate::tensor::UniqueId>),
+    /// A higher order backward pass went through an operation that only supports
+    /// first order gradients.
+    HigherOrderUnsupported,
+    /// A forward mode pass with [crate::tensor::DualTape] went through an operation that
+    /// doesn't compute tangents.
+    ForwardModeUnsupported,
+    /// A tensor didn't have the shape that an operation expected, like a gradient
+    /// returned by a [crate::tensor_ops::CustomOp].
+    ShapeMismatch {
+        expected: std::vec::Vec<usize>,
+        found: std::vec::Vec<usize>,
+    },
+    /// An argument was invalid for the operation or constructor it was given to,
+    /// e.g. a batch size of 0. The message describes what was wrong.
+    InvalidArgument(std::string::String),
+    /// Anomaly detection found a NaN or Inf, see [crate::tensor::Tensor::detect_anomaly].
+    /// `op` is the operation that produced it, and `shape` is the shape of its output,
+    /// or of its input if this happened in the backward pass.
+    NonFiniteValue {
+        op: std::string::String,
+        shape: std::vec::Vec<usize>,
+        backward: bool,
+    },
     #[cfg(feature = "cuda")]
     CublasError(cudarc::cublas::result::CublasError),
     #[cfg(feature = "cuda")]
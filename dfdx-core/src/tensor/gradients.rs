@@ -1,12 +1,15 @@
 //! Implementations of [OwnedTape], [NoneTape], and generic Nd array containers via [Gradients].
 #![allow(clippy::type_complexity)]
 
+use std::any::Any;
 use std::collections::{BTreeMap, BTreeSet};
 use std::{boxed::Box, vec::Vec};
 
+use super::graph::GraphNode;
 use super::tensorlike::Tensorlike;
-use super::{storage_traits::Storage, unique_id, Error, Tensor, UniqueId};
-use crate::shapes::Shape;
+use super::{storage_traits::Storage, unique_id, Error, Tensor, UniqueId, ZeroFillStorage};
+use crate::shapes::{Dtype, Shape};
+use crate::tensor_ops::{axpy::AxpyKernel, sparse_rows::SparseRowsKernel, HigherOrderOps};
 
 /// A generic container for keeping gradients of tensors keyed by the
 /// tensor's [UniqueId].
@@ -16,12 +19,27 @@ use crate::shapes::Shape;
 /// 2. Remove entries
 /// 3. Access references to arrays
 /// 4. Access mutable references to arrays
+///
+/// It can also record which rows of a gradient can be non-zero (see [Gradients::add_sparse_rows()]),
+/// so that optimizers only need to update those rows.
 #[derive(Clone, Debug)]
 pub struct Gradients<E, D: Storage<E>> {
     /// Using BTreeMap for no-std support
     gradient_by_id: BTreeMap<UniqueId, D::Vec>,
     /// Using BTreeSet for no-std support
     leaf_ids: Option<BTreeSet<UniqueId>>,
+    /// The length of each row, and the rows (indices into the first dimension) that were
+    /// written to by sparse operations.
+    sparse_rows: BTreeMap<UniqueId, (usize, BTreeSet<usize>)>,
+    /// Only recorded while [OwnedTape::execute] runs with anomaly detection.
+    accesses: Option<GradientAccesses>,
+}
+
+/// The ids of the gradients that were read & written by a backward operation.
+#[derive(Clone, Debug, Default)]
+struct GradientAccesses {
+    reads: Vec<UniqueId>,
+    writes: Vec<UniqueId>,
 }
 
 impl<E, D: Storage<E>> Gradients<E, D> {
@@ -35,6 +53,8 @@ impl<E, D: Storage<E>> Gradients<E, D> {
         Self {
             gradient_by_id: Default::default(),
             leaf_ids: None,
+            sparse_rows: Default::default(),
+            accesses: None,
         }
     }
 }
@@ -75,6 +95,51 @@ impl<E, D: Storage<E>> Gradients<E, D> {
     pub fn drop_non_leafs(&mut self) {
         if let Some(leafs) = &self.leaf_ids {
             self.gradient_by_id.retain(|k, _| leafs.contains(k));
+            self.sparse_rows.retain(|k, _| leafs.contains(k));
+        }
+    }
+
+    /// Records that only `rows` of the gradient of `t` were written to, like the rows of an
+    /// embedding table that were looked up. Rows recorded for the same tensor are combined.
+    ///
+    /// This is only valid if every operation that writes to the gradient of `t` records its rows.
+    pub fn add_sparse_rows<S: Shape>(
+        &mut self,
+        t: &impl Tensorlike<S, E, D>,
+        rows: impl IntoIterator<Item = usize>,
+    ) {
+        let shape = t.shape();
+        let num_rows = shape.concrete().into_iter().next().unwrap_or(1);
+        let row_len = shape.num_elements() / num_rows.max(1);
+        self.sparse_rows
+            .entry(t.id())
+            .or_insert_with(|| (row_len, BTreeSet::new()))
+            .1
+            .extend(rows);
+    }
+
+    /// Returns the rows recorded by [Gradients::add_sparse_rows()] for `t`, or `None`
+    /// if the whole gradient may be non-zero.
+    pub fn sparse_rows<S: Shape, T>(&self, t: &Tensor<S, E, D, T>) -> Option<&BTreeSet<usize>> {
+        self.sparse_rows.get(&t.id).map(|(_, rows)| rows)
+    }
+
+    /// Forgets the rows recorded for `t`, e.g. after its gradient is zeroed.
+    pub fn clear_sparse_rows<S: Shape>(&mut self, t: &impl Tensorlike<S, E, D>) {
+        self.sparse_rows.remove(&t.id());
+    }
+
+    /// Adds the rows of `other` to the rows recorded for the same tensors in `self`.
+    fn union_sparse_rows(&mut self, other: BTreeMap<UniqueId, (usize, BTreeSet<usize>)>) {
+        for (id, (row_len, mut rows)) in other {
+            match self.sparse_rows.entry(id) {
+                std::collections::btree_map::Entry::Vacant(e) => {
+                    e.insert((row_len, rows));
+                }
+                std::collections::btree_map::Entry::Occupied(mut e) => {
+                    e.get_mut().1.append(&mut rows)
+                }
+            }
         }
     }
 
@@ -87,6 +152,9 @@ impl<E, D: Storage<E>> Gradients<E, D> {
     ///
     /// **Panics** if data associated with `t` is not found. This indicates an unrecoverable bug.
     pub(crate) fn get_mut<S: Shape>(&mut self, t: &impl Tensorlike<S, E, D>) -> &mut D::Vec {
+        if let Some(accesses) = &mut self.accesses {
+            accesses.writes.push(t.id());
+        }
         self.gradient_by_id.get_mut(&t.id()).unwrap()
     }
 
@@ -94,6 +162,9 @@ impl<E, D: Storage<E>> Gradients<E, D> {
     ///
     /// **Panics** if data associated with `t` is not found. This indicates an unrecoverable bug.
     pub(crate) fn get_ref<S: Shape>(&mut self, t: &impl Tensorlike<S, E, D>) -> &D::Vec {
+        if let Some(accesses) = &mut self.accesses {
+            accesses.reads.push(t.id());
+        }
         self.gradient_by_id.get(&t.id()).unwrap()
     }
 
@@ -114,6 +185,24 @@ impl<E, D: Storage<E>> Gradients<E, D> {
         }
     }
 
+    /// Same as [Gradients::get], but returns `None` if there is no data associated with `t`.
+    pub(crate) fn get_checked<S: Shape>(
+        &self,
+        t: &impl Tensorlike<S, E, D>,
+    ) -> Option<Tensor<S, E, D>> {
+        self.gradient_by_id.get(&t.id())?;
+        Some(self.get(t))
+    }
+
+    /// Replaces the data associated with `t` by the data of `grad`.
+    ///
+    /// **Panics** if `grad` doesn't have the same strides as `t`.
+    pub fn insert<S: Shape>(&mut self, t: &impl Tensorlike<S, E, D>, grad: Tensor<S, E, D>) {
+        assert_eq!(grad.strides, t.strides());
+        let data = std::sync::Arc::try_unwrap(grad.data).unwrap_or_else(|data| (*data).clone());
+        self.gradient_by_id.insert(t.id(), data);
+    }
+
     /// Borrows a pair of a gradients `(&mut L, &R)`.
     /// `l` is the gradient to update, and `r` is the gradient to backprop.
     ///
@@ -175,18 +264,145 @@ impl<E, D: Storage<E>> Gradients<E, D> {
     }
 }
 
+impl<E: Dtype, D: ZeroFillStorage<E> + SparseRowsKernel<E>> Gradients<E, D> {
+    /// Sets the gradient of `t` to zero, allocating it if it isn't present. If rows were recorded
+    /// for `t` (see [Gradients::add_sparse_rows()]), only those rows are zeroed, and then forgotten.
+    pub fn try_zero_for<S: Shape>(&mut self, t: &impl Tensorlike<S, E, D>) -> Result<(), Error> {
+        // a newly allocated gradient isn't necessarily zeroed
+        let rows = self
+            .sparse_rows
+            .remove(&t.id())
+            .filter(|_| self.gradient_by_id.contains_key(&t.id()));
+        let grad = self.get_or_alloc_mut(t)?;
+        match rows {
+            Some((row_len, rows)) => {
+                let rows: Vec<usize> = rows.into_iter().collect();
+                t.dev().zero_rows(grad, &rows, row_len)
+            }
+            None => t.dev().try_fill_with_zeros(grad),
+        }
+    }
+}
+
+impl<E: Dtype, D: AxpyKernel<E> + SparseRowsKernel<E>> Gradients<E, D> {
+    /// Adds each gradient in `other` to the matching gradient in `self`.
+    /// Gradients that are not present in `self` are moved over as is. If rows were recorded
+    /// for both gradients, only the rows of `other` are added.
+    pub fn try_accumulate(&mut self, device: &D, mut other: Self) -> Result<(), Error> {
+        for (id, grad) in other.gradient_by_id {
+            match self.gradient_by_id.entry(id) {
+                std::collections::btree_map::Entry::Vacant(e) => {
+                    e.insert(grad);
+                    if let Some(rows) = other.sparse_rows.remove(&id) {
+                        self.sparse_rows.insert(id, rows);
+                    }
+                }
+                std::collections::btree_map::Entry::Occupied(mut e) => {
+                    // the sum is only sparse if both gradients were
+                    match (self.sparse_rows.get_mut(&id), other.sparse_rows.remove(&id)) {
+                        (Some((_, rows)), Some((row_len, mut other_rows))) => {
+                            let added: Vec<usize> = other_rows.iter().copied().collect();
+                            device.add_rows(e.get_mut(), &grad, &added, row_len)?;
+                            rows.append(&mut other_rows);
+                        }
+                        _ => {
+                            let one = E::from_f64(1.0).unwrap();
+                            device.forward(e.get_mut(), one, &grad, one)?;
+                            self.sparse_rows.remove(&id);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Gradients computed by a higher order backward pass. See
+/// [crate::tensor_ops::BackwardTraced].
+///
+/// Unlike [Gradients], every gradient in here is the output of recorded tensor
+/// operations, so it can be differentiated again. Use [TracedGradients::get] to
+/// retrieve gradients, combine them into a scalar, and then call
+/// [TracedGradients::backward] with it.
+pub struct TracedGradients<E, D: Storage<E>> {
+    /// Each gradient is a `Tensor<(usize,), E, D>` laid out exactly like the
+    /// gradient buffer in [Gradients] would be.
+    pub(crate) gradient_by_id: BTreeMap<UniqueId, Box<dyn Any>>,
+    /// Contains both the operations that computed the gradients, and the
+    /// original forward operations.
+    pub(crate) tape: OwnedTape<E, D>,
+    pub(crate) ops: HigherOrderOps<E, D>,
+}
+
+impl<E: std::fmt::Debug, D: Storage<E>> std::fmt::Debug for TracedGradients<E, D> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TracedGradients")
+            .field("num_gradients", &self.gradient_by_id.len())
+            .field("tape", &self.tape)
+            .finish()
+    }
+}
+
+impl<E: 'static, D: Storage<E>> TracedGradients<E, D> {
+    /// Returns the gradient of `t`. The returned tensor has an empty tape, operations
+    /// on it are still recorded and are linked back to this gradient's computation
+    /// by [TracedGradients::backward].
+    ///
+    /// # Panics
+    /// If no gradient was computed for `t`.
+    pub fn get<S: Shape>(&self, t: &impl Tensorlike<S, E, D>) -> Tensor<S, E, D, OwnedTape<E, D>> {
+        self.get_checked(t).unwrap()
+    }
+
+    /// Returns the gradient of `t` if it was computed. See [TracedGradients::get].
+    pub fn get_checked<S: Shape>(
+        &self,
+        t: &impl Tensorlike<S, E, D>,
+    ) -> Option<Tensor<S, E, D, OwnedTape<E, D>>> {
+        let grad: &Tensor<(usize,), E, D> = self.gradient_by_id.get(&t.id())?.downcast_ref()?;
+        Some(Tensor {
+            id: grad.id,
+            data: grad.data.clone(),
+            shape: *t.shape(),
+            strides: t.strides(),
+            device: grad.device.clone(),
+            tape: Default::default(),
+        })
+    }
+}
+
 /// Contains a [Gradients] and list of backward operations.
 pub struct OwnedTape<E, D: Storage<E>> {
     /// A list of (Time, BackwardOp) pairs. The Time is used to ensure operations
     /// from merged tapes are executed in the correct order.
     pub(crate) operations: Vec<(UniqueId, BackwardOp<E, D>)>,
+    /// Higher order versions of `operations`, keyed by the same Time. These are only
+    /// recorded when `higher_order` is set.
+    pub(crate) traced_operations: BTreeMap<UniqueId, TracedBackwardOp<E, D>>,
+    /// Descriptions of the operations, keyed by the Time they were recorded at. These are
+    /// only recorded with `record_graph` or `anomaly_check`.
+    pub(crate) graph_nodes: BTreeMap<UniqueId, GraphNode>,
+    /// Set by [Tensor::record_graph].
+    pub(crate) record_graph: bool,
+    /// Set by [crate::tensor::Trace::traced_higher_order].
+    pub(crate) higher_order: Option<HigherOrderOps<E, D>>,
+    /// Returns whether a buffer contains NaN or Inf, set by [Tensor::detect_anomaly].
+    pub(crate) anomaly_check: Option<AnomalyCheck<D::Vec>>,
     pub(crate) gradients: Gradients<E, D>,
 }
 
+pub(crate) type AnomalyCheck<V> = std::sync::Arc<dyn Fn(&V) -> bool>;
+
 impl<E, D: Storage<E>> Default for OwnedTape<E, D> {
     fn default() -> Self {
         Self {
             operations: Default::default(),
+            traced_operations: Default::default(),
+            graph_nodes: Default::default(),
+            record_graph: false,
+            higher_order: None,
+            anomaly_check: None,
             gradients: Gradients::leaky(),
         }
     }
@@ -196,6 +412,8 @@ impl<E: std::fmt::Debug, D: Storage<E>> std::fmt::Debug for OwnedTape<E, D> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("OwnedTape")
             .field("num_operations", &self.operations.len())
+            .field("higher_order", &self.higher_order.is_some())
+            .field("detect_anomaly", &self.anomaly_check.is_some())
             .field("gradients", &self.gradients)
             .finish()
     }
@@ -205,6 +423,11 @@ impl<E, D: Storage<E>> From<Gradients<E, D>> for OwnedTape<E, D> {
     fn from(gradients: Gradients<E, D>) -> Self {
         Self {
             operations: Default::default(),
+            traced_operations: Default::default(),
+            graph_nodes: Default::default(),
+            record_graph: false,
+            higher_order: None,
+            anomaly_check: None,
             gradients,
         }
     }
@@ -221,19 +444,125 @@ impl<E, D: Storage<E>> OwnedTape<E, D> {
         self.operations.sort_by_key(|(k, _)| *k);
         // In case the same operation is present multiple times, we dedup it.
         self.operations.dedup_by_key(|(k, _)| *k);
-        for (_, operation) in self.operations.drain(..).rev() {
+        self.traced_operations.clear();
+        for (_, operation) in std::mem::take(&mut self.operations).into_iter().rev() {
+            if self.anomaly_check.is_some() {
+                self.gradients.accesses = Some(Default::default());
+            }
             (operation)(&mut self.gradients)?;
+            if let Some(accesses) = self.gradients.accesses.take() {
+                self.check_gradients(accesses)?;
+            }
         }
         Ok(std::mem::replace(&mut self.gradients, Gradients::leaky()))
     }
+
+    /// Returns [Error::NonFiniteValue] if any gradient written by a backward operation
+    /// contains NaN or Inf. The operation is found from the gradient it read, which is the
+    /// gradient of its output.
+    fn check_gradients(&self, accesses: GradientAccesses) -> Result<(), Error> {
+        let is_anomaly = self.anomaly_check.as_ref().unwrap();
+        for id in accesses.writes.iter() {
+            let Some(grad) = self.gradients.gradient_by_id.get(id) else {
+                continue;
+            };
+            if !is_anomaly(grad) {
+                continue;
+            }
+            let node = accesses
+                .reads
+                .iter()
+                .find_map(|read| self.graph_nodes.values().rfind(|n| n.output.id == *read));
+            let shape = node
+                .and_then(|n| n.inputs.iter().find(|t| t.id == *id))
+                .map(|t| t.shape.clone());
+            return Err(Error::NonFiniteValue {
+                op: node.map_or_else(|| "unknown".into(), |n| n.op.clone()),
+                shape: shape.unwrap_or_default(),
+                backward: true,
+            });
+        }
+        Ok(())
+    }
+
+    /// Runs the higher order operations, accumulating into `grads`. The first order
+    /// operations are not executed, and instead moved onto the tape of `grads` along with
+    /// this tape's [Gradients], so the result can be differentiated through the forward pass.
+    ///
+    /// Returns [Error::HigherOrderUnsupported] if any operation doesn't have a higher
+    /// order version.
+    pub(crate) fn execute_traced(
+        &mut self,
+        mut grads: TracedGradients<E, D>,
+    ) -> Result<TracedGradients<E, D>, Error> {
+        self.operations.sort_by_key(|(k, _)| *k);
+        self.operations.dedup_by_key(|(k, _)| *k);
+        if self
+            .operations
+            .iter()
+            .any(|(k, _)| !self.traced_operations.contains_key(k))
+        {
+            return Err(Error::HigherOrderUnsupported);
+        }
+        for (k, _) in self.operations.iter().rev() {
+            let operation = self.traced_operations.remove(k).unwrap();
+            (operation)(&mut grads)?;
+        }
+        grads.tape.operations.append(&mut self.operations);
+        grads.tape.graph_nodes.append(&mut self.graph_nodes);
+        grads.tape.record_graph = self.record_graph;
+        grads.tape.anomaly_check = self.anomaly_check.take();
+        grads.tape.gradients = std::mem::replace(&mut self.gradients, Gradients::leaky());
+        Ok(grads)
+    }
 }
 
 type BackwardOp<E, D> = Box<dyn FnOnce(&mut Gradients<E, D>) -> Result<(), Error>>;
+type TracedBackwardOp<E, D> = Box<dyn FnOnce(&mut TracedGradients<E, D>) -> Result<(), Error>>;
 
 /// Contains nothing. When [Tape::add_backward_op] is called, this struct does nothing.
 #[derive(Default, Debug, Clone, Copy)]
 pub struct NoneTape;
 
+/// Computes tangents during the forward pass, for forward mode autodiff. Start with
+/// [Tensor::dual()], and get the directional derivative of any output with
+/// [Tensor::tangent()].
+///
+/// Unlike [OwnedTape], this doesn't record backward operations. Instead, each operation
+/// computes the tangent of its output as soon as it runs. Operations that don't support
+/// forward mode return [Error::ForwardModeUnsupported]. Operations with a custom backward
+/// pass can't fail right away, so the error comes from the next operation or from
+/// [Tensor::try_tangent()] instead.
+pub struct DualTape<E, D: Storage<E>> {
+    /// Tangents laid out exactly like the data of their tensor. Tensors without a tangent
+    /// have a tangent of zeros.
+    pub(crate) tangents: Gradients<E, D>,
+    /// Set when an operation that doesn't compute tangents was recorded while there
+    /// were tangents to compute.
+    pub(crate) unsupported: bool,
+    /// Set by [Tensor::dual()]. Without it there are no tangents to compute.
+    pub(crate) ops: Option<HigherOrderOps<E, D>>,
+}
+
+impl<E, D: Storage<E>> Default for DualTape<E, D> {
+    fn default() -> Self {
+        Self {
+            tangents: Gradients::leaky(),
+            unsupported: false,
+            ops: None,
+        }
+    }
+}
+
+impl<E: std::fmt::Debug, D: Storage<E>> std::fmt::Debug for DualTape<E, D> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DualTape")
+            .field("tangents", &self.tangents)
+            .field("unsupported", &self.unsupported)
+            .finish()
+    }
+}
+
 /// Something that can track backward operations.
 pub trait Tape<E, D: Storage<E>>: Default + Merge<Self> + Merge<NoneTape> {
     /// Whether this object is currently tracking gradients. This is known at compile time.
@@ -241,6 +570,46 @@ pub trait Tape<E, D: Storage<E>>: Default + Merge<Self> + Merge<NoneTape> {
     fn add_backward_op<F>(&mut self, operation: F)
     where
         F: 'static + FnOnce(&mut Gradients<E, D>) -> Result<(), Error>;
+
+    /// Whether this tape is recording higher order operations with
+    /// [Tape::add_traced_backward_op]. This is only known at runtime.
+    fn is_higher_order(&self) -> bool {
+        false
+    }
+
+    /// Adds the higher order version of the most recently added backward operation.
+    /// Instead of operating on gradient buffers, this records tensor operations
+    /// on [TracedGradients].
+    fn add_traced_backward_op<F>(&mut self, _: F)
+    where
+        F: 'static + FnOnce(&mut TracedGradients<E, D>) -> Result<(), Error>,
+    {
+    }
+
+    /// Whether this tape computes tangents with [Tape::add_forward_op], instead of
+    /// recording backward operations. This is only known at runtime.
+    fn is_forward_mode(&self) -> bool {
+        false
+    }
+
+    /// Computes the tangent of an operation's output for forward mode autodiff, see
+    /// [DualTape]. `operation` is only called by tapes that compute tangents, and is
+    /// given the tangents of all tensors, keyed by their id.
+    fn add_forward_op<F>(&mut self, _: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut Gradients<E, D>, &HigherOrderOps<E, D>) -> Result<(), Error>,
+    {
+        Ok(())
+    }
+
+    /// Records a description of an operation, which shows up in [OwnedTape::graph()].
+    /// `node` is only called by tapes that record operations, see [Tensor::record_graph].
+    ///
+    /// `output` is the data of the operation's output. With [Tensor::detect_anomaly], this
+    /// returns [Error::NonFiniteValue] if it contains NaN or Inf.
+    fn add_graph_node<F: FnOnce() -> GraphNode>(&mut self, _: &D::Vec, _: F) -> Result<(), Error> {
+        Ok(())
+    }
 }
 
 impl<E, D: Storage<E>> Tape<E, D> for OwnedTape<E, D> {
@@ -251,6 +620,41 @@ impl<E, D: Storage<E>> Tape<E, D> for OwnedTape<E, D> {
     {
         self.operations.push((unique_id(), Box::new(operation)));
     }
+
+    fn is_higher_order(&self) -> bool {
+        self.higher_order.is_some()
+    }
+
+    fn add_traced_backward_op<F>(&mut self, operation: F)
+    where
+        F: 'static + FnOnce(&mut TracedGradients<E, D>) -> Result<(), Error>,
+    {
+        // without a first order operation, there is nothing to differentiate
+        if let Some((time, _)) = self.operations.last() {
+            self.traced_operations.insert(*time, Box::new(operation));
+        }
+    }
+
+    fn add_graph_node<F: FnOnce() -> GraphNode>(
+        &mut self,
+        output: &D::Vec,
+        node: F,
+    ) -> Result<(), Error> {
+        if !self.record_graph && self.anomaly_check.is_none() {
+            return Ok(());
+        }
+        let node = node();
+        if self.anomaly_check.as_ref().map_or(false, |f| f(output)) {
+            return Err(Error::NonFiniteValue {
+                op: node.op,
+                shape: node.output.shape,
+                backward: false,
+            });
+        }
+        // ops like log_softmax reuse ids, so nodes can't be keyed by their output
+        self.graph_nodes.insert(unique_id(), node);
+        Ok(())
+    }
 }
 
 impl<E, D: Storage<E>> Tape<E, D> for NoneTape {
@@ -260,6 +664,37 @@ impl<E, D: Storage<E>> Tape<E, D> for NoneTape {
         F: 'static + FnOnce(&mut Gradients<E, D>) -> Result<(), Error>,
     {
     }
+
+    fn add_graph_node<F: FnOnce() -> GraphNode>(&mut self, _: &D::Vec, _: F) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<E, D: Storage<E>> Tape<E, D> for DualTape<E, D> {
+    const OWNS_TAPE: bool = false;
+    fn add_backward_op<F>(&mut self, _: F)
+    where
+        F: 'static + FnOnce(&mut Gradients<E, D>) -> Result<(), Error>,
+    {
+        self.unsupported |= self.ops.is_some();
+    }
+
+    fn is_forward_mode(&self) -> bool {
+        self.ops.is_some()
+    }
+
+    fn add_forward_op<F>(&mut self, operation: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut Gradients<E, D>, &HigherOrderOps<E, D>) -> Result<(), Error>,
+    {
+        if self.unsupported {
+            return Err(Error::ForwardModeUnsupported);
+        }
+        match self.ops {
+            Some(ops) => operation(&mut self.tangents, &ops),
+            None => Ok(()),
+        }
+    }
 }
 
 /// Combine two things
@@ -280,11 +715,30 @@ impl<E, D: Storage<E>> Merge<NoneTape> for OwnedTape<E, D> {
     }
 }
 
+impl<E, D: Storage<E>> Merge<NoneTape> for DualTape<E, D> {
+    fn merge(self, _: NoneTape) -> Self {
+        self
+    }
+}
+
+impl<E, D: Storage<E>> Merge<DualTape<E, D>> for DualTape<E, D> {
+    fn merge(mut self, other: Self) -> Self {
+        self.tangents
+            .gradient_by_id
+            .extend(other.tangents.gradient_by_id);
+        self.unsupported |= other.unsupported;
+        self.ops = self.ops.or(other.ops);
+        self
+    }
+}
+
 impl<E, D: Storage<E>> Merge<OwnedTape<E, D>> for OwnedTape<E, D> {
     fn merge(mut self, mut other: Self) -> Self {
         self.gradients
             .gradient_by_id
             .extend(other.gradients.gradient_by_id);
+        self.gradients
+            .union_sparse_rows(other.gradients.sparse_rows);
         if let Some(leafs) = other.gradients.leaf_ids {
             self.gradients
                 .leaf_ids
@@ -292,6 +746,11 @@ impl<E, D: Storage<E>> Merge<OwnedTape<E, D>> for OwnedTape<E, D> {
                 .extend(leafs);
         }
         self.operations.append(&mut other.operations);
+        self.traced_operations.append(&mut other.traced_operations);
+        self.graph_nodes.append(&mut other.graph_nodes);
+        self.record_graph |= other.record_graph;
+        self.higher_order = self.higher_order.or(other.higher_order);
+        self.anomaly_check = self.anomaly_check.or(other.anomaly_check);
         self
     }
 }
@@ -312,6 +771,8 @@ impl<E, D: Storage<E>> Merge<Self> for std::sync::Arc<std::sync::Mutex<OwnedTape
             lhs.gradients
                 .gradient_by_id
                 .append(&mut rhs.gradients.gradient_by_id);
+            let rows = std::mem::take(&mut rhs.gradients.sparse_rows);
+            lhs.gradients.union_sparse_rows(rows);
             if let Some(leafs) = &mut rhs.gradients.leaf_ids {
                 lhs.gradients
                     .leaf_ids
@@ -319,6 +780,13 @@ impl<E, D: Storage<E>> Merge<Self> for std::sync::Arc<std::sync::Mutex<OwnedTape
                     .append(leafs);
             }
             lhs.operations.append(&mut rhs.operations);
+            lhs.traced_operations.append(&mut rhs.traced_operations);
+            lhs.graph_nodes.append(&mut rhs.graph_nodes);
+            lhs.record_graph |= rhs.record_graph;
+            lhs.higher_order = lhs.higher_order.or(rhs.higher_order);
+            if lhs.anomaly_check.is_none() {
+                lhs.anomaly_check = rhs.anomaly_check.take();
+            }
         }
         self
     }
@@ -334,4 +802,24 @@ impl<E, D: Storage<E>> Tape<E, D> for std::sync::Arc<std::sync::Mutex<OwnedTape<
         let mut tape = self.lock().unwrap();
         tape.add_backward_op(operation);
     }
+
+    fn is_higher_order(&self) -> bool {
+        self.lock().unwrap().is_higher_order()
+    }
+
+    fn add_traced_backward_op<F>(&mut self, operation: F)
+    where
+        F: 'static + FnOnce(&mut TracedGradients<E, D>) -> Result<(), Error>,
+    {
+        let mut tape = self.lock().unwrap();
+        tape.add_traced_backward_op(operation);
+    }
+
+    fn add_graph_node<F: FnOnce() -> GraphNode>(
+        &mut self,
+        output: &D::Vec,
+        node: F,
+    ) -> Result<(), Error> {
+        self.lock().unwrap().add_graph_node(output, node)
+    }
 }
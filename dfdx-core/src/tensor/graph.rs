@@ -0,0 +1,307 @@
+//! A description of the operations recorded by an [OwnedTape], which can be exported
+//! to [Graphviz DOT](https://graphviz.org/doc/info/lang.html) or JSON.
+
+use std::{collections::BTreeSet, string::String, vec::Vec};
+
+use super::{OwnedTape, Storage, Tensor, Tensorlike, UniqueId};
+use crate::shapes::Shape;
+
+/// A tensor that is an input or output of a [GraphNode].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GraphTensor {
+    /// The [UniqueId] of the tensor.
+    pub id: UniqueId,
+    /// The concrete dimensions of the tensor.
+    pub shape: Vec<usize>,
+}
+
+impl GraphTensor {
+    /// Describes a tensor with `id` and `shape`, for tensors that aren't available
+    /// when the node is recorded (e.g. only their id was kept).
+    pub fn new<S: Shape>(id: UniqueId, shape: &S) -> Self {
+        Self {
+            id,
+            shape: shape.concrete().into_iter().collect(),
+        }
+    }
+
+    /// Describes `t`.
+    pub fn of<S: Shape, E, D: Storage<E>>(t: &impl Tensorlike<S, E, D>) -> Self {
+        Self::new(t.id(), t.shape())
+    }
+}
+
+/// A single operation recorded onto a tape, see [crate::tensor::Tape::add_graph_node].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GraphNode {
+    /// The name of the operation, like `"matmul"` or `"exp"`.
+    pub op: String,
+    /// The tensors the operation read, in the order of its arguments.
+    pub inputs: Vec<GraphTensor>,
+    /// The tensor the operation created.
+    pub output: GraphTensor,
+}
+
+impl GraphNode {
+    /// Describes the operation `op` that computed `output` from `inputs`.
+    pub fn new(op: impl Into<String>, inputs: Vec<GraphTensor>, output: GraphTensor) -> Self {
+        Self {
+            op: op.into(),
+            inputs,
+            output,
+        }
+    }
+}
+
+/// The operations recorded by an [OwnedTape], in the order their outputs were created
+/// during the forward pass. Start recording with [Tensor::record_graph()], and get
+/// this with [Tensor::graph()].
+///
+/// Only operations that record a [GraphNode] show up here; backward operations added
+/// with [crate::tensor::Tape::add_backward_op] alone are not included.
+///
+/// ```rust
+/// # use dfdx_core::prelude::*;
+/// # let dev: Cpu = Default::default();
+/// let w: Tensor<Rank2<3, 2>, f32, _> = dev.sample_normal();
+/// let x: Tensor<Rank1<3>, f32, _> = dev.sample_normal();
+/// let y = x.leaky_trace().record_graph().matmul(w.clone()).exp().sum();
+/// let graph = y.graph();
+/// let ops: Vec<&str> = graph.nodes.iter().map(|n| n.op.as_str()).collect();
+/// assert_eq!(ops, ["matmul", "exp", "sum_to"]);
+/// let dot = graph.to_dot();
+/// let json = graph.to_json();
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Graph {
+    /// The recorded operations, in the order they ran.
+    pub nodes: Vec<GraphNode>,
+}
+
+impl Graph {
+    /// Whether the tensor with `id` is an input or output of any operation. Parameters
+    /// listed in [crate::tensor::Error::UnusedTensors] won't be in the graph.
+    pub fn contains(&self, id: UniqueId) -> bool {
+        self.nodes
+            .iter()
+            .any(|n| n.output.id == id || n.inputs.iter().any(|t| t.id == id))
+    }
+
+    /// Renders the graph in the Graphviz DOT language. Tensors are drawn as boxes
+    /// labeled with their id & shape, and operations as ellipses.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph {\n");
+        let mut tensors = BTreeSet::new();
+        for node in self.nodes.iter() {
+            for t in node.inputs.iter().chain(std::iter::once(&node.output)) {
+                if tensors.insert(t.id) {
+                    dot += &std::format!(
+                        "    t{} [shape=box, label=\"t{}\\n{:?}\"];\n",
+                        t.id.0,
+                        t.id.0,
+                        t.shape
+                    );
+                }
+            }
+        }
+        for (i, node) in self.nodes.iter().enumerate() {
+            dot += &std::format!("    op{i} [label=\"{}\"];\n", escape(&node.op));
+            for inp in node.inputs.iter() {
+                dot += &std::format!("    t{} -> op{i};\n", inp.id.0);
+            }
+            dot += &std::format!("    op{i} -> t{};\n", node.output.id.0);
+        }
+        dot += "}\n";
+        dot
+    }
+
+    /// Serializes the graph as JSON of the form
+    /// `{"nodes":[{"op":"exp","inputs":[{"id":0,"shape":[3]}],"output":{"id":1,"shape":[3]}}]}`.
+    pub fn to_json(&self) -> String {
+        let tensor = |t: &GraphTensor| {
+            let shape: Vec<String> = t.shape.iter().map(|d| std::format!("{d}")).collect();
+            std::format!("{{\"id\":{},\"shape\":[{}]}}", t.id.0, shape.join(","))
+        };
+        let nodes: Vec<String> = self
+            .nodes
+            .iter()
+            .map(|node| {
+                let inputs: Vec<String> = node.inputs.iter().map(tensor).collect();
+                std::format!(
+                    "{{\"op\":\"{}\",\"inputs\":[{}],\"output\":{}}}",
+                    escape(&node.op),
+                    inputs.join(","),
+                    tensor(&node.output)
+                )
+            })
+            .collect();
+        std::format!("{{\"nodes\":[{}]}}", nodes.join(","))
+    }
+}
+
+/// Escapes quotes & backslashes, which is enough for both DOT & JSON strings.
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl<E, D: Storage<E>> OwnedTape<E, D> {
+    /// The [Graph] of all operations recorded so far.
+    pub fn graph(&self) -> Graph {
+        Graph {
+            nodes: self.graph_nodes.values().cloned().collect(),
+        }
+    }
+}
+
+impl<S: Shape, E, D: Storage<E>> Tensor<S, E, D, OwnedTape<E, D>> {
+    /// Enables recording a [GraphNode] for every operation on this tensor's tape,
+    /// which is off by default. Merging tapes keeps recording enabled.
+    pub fn record_graph(mut self) -> Self {
+        self.tape.record_graph = true;
+        self
+    }
+
+    /// The [Graph] of all operations recorded on this tensor's tape. This is empty
+    /// unless [Tensor::record_graph()] was called.
+    pub fn graph(&self) -> Graph {
+        self.tape.graph()
+    }
+}
+
+/// Turns an op struct like `BinaryAddKernelOp<f32>` into a name like `"binary_add"`.
+pub(crate) fn op_name<Op>() -> String {
+    let name = std::any::type_name::<Op>();
+    let name = name.split('<').next().unwrap();
+    let name = name.rsplit("::").next().unwrap();
+    let name = name.strip_suffix("Op").unwrap_or(name);
+    let name = name.strip_suffix("Kernel").unwrap_or(name);
+    // only start a new word at the beginning of a capitalized word, so `ReLU` is `relu`
+    let chars: Vec<char> = name.chars().collect();
+    let mut snake = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        let prev_lower = i > 0 && chars[i - 1].is_lowercase();
+        let next_lower = chars.get(i + 1).map_or(false, |n| n.is_lowercase());
+        if c.is_uppercase() && prev_lower && next_lower {
+            snake.push('_');
+        }
+        snake.extend(c.to_lowercase());
+    }
+    snake
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{prelude::*, tests::*};
+
+    #[test]
+    fn test_op_name() {
+        struct BinaryAddKernelOp;
+        struct ReLUKernelOp;
+        struct ScalarMulKernelOp<E>(E);
+        struct FastGeLUKernelOp;
+        struct RollOp;
+        assert_eq!(op_name::<BinaryAddKernelOp>(), "binary_add");
+        assert_eq!(op_name::<ReLUKernelOp>(), "relu");
+        assert_eq!(op_name::<FastGeLUKernelOp>(), "fast_gelu");
+        assert_eq!(op_name::<ScalarMulKernelOp<f32>>(), "scalar_mul");
+        assert_eq!(op_name::<RollOp>(), "roll");
+    }
+
+    #[test]
+    fn test_graph_records_ops() {
+        let dev: TestDevice = Default::default();
+        let a: Tensor<Rank2<2, 3>, TestDtype, _> = dev.sample_normal();
+        let b: Tensor<Rank2<3, 4>, TestDtype, _> = dev.sample_normal();
+        let c = a.leaky_trace().record_graph().matmul(b.clone());
+        let d = c.relu() * 2.0;
+        let e = d.sum::<Rank1<4>, _>();
+
+        let graph = e.graph();
+        let ops: Vec<&str> = graph.nodes.iter().map(|n| n.op.as_str()).collect();
+        assert_eq!(ops, ["matmul", "relu", "scalar_mul", "sum_to"]);
+
+        let matmul = &graph.nodes[0];
+        assert_eq!(matmul.inputs.len(), 2);
+        assert_eq!(matmul.inputs[0].id, a.id);
+        assert_eq!(matmul.inputs[0].shape, [2, 3]);
+        assert_eq!(matmul.inputs[1].id, b.id);
+        assert_eq!(matmul.inputs[1].shape, [3, 4]);
+        assert_eq!(matmul.output.shape, [2, 4]);
+
+        // each op consumes the output of the previous one
+        for i in 1..graph.nodes.len() {
+            assert_eq!(graph.nodes[i].inputs[0].id, graph.nodes[i - 1].output.id);
+        }
+        assert_eq!(graph.nodes[3].output.id, e.id);
+        assert_eq!(graph.nodes[3].output.shape, [4]);
+
+        assert!(graph.contains(a.id));
+        assert!(graph.contains(b.id));
+        let unused: Tensor<Rank1<4>, TestDtype, _> = dev.zeros();
+        assert!(!graph.contains(unused.id));
+    }
+
+    #[test]
+    fn test_graph_is_opt_in() {
+        let dev: TestDevice = Default::default();
+        let a: Tensor<Rank1<3>, TestDtype, _> = dev.sample_normal();
+        let b = a.leaky_trace().exp().sum();
+        assert!(b.graph().nodes.is_empty());
+    }
+
+    #[test]
+    fn test_graph_keeps_nodes_with_reused_ids() {
+        let dev: TestDevice = Default::default();
+        let a: Tensor<Rank1<3>, TestDtype, _> = dev.sample_normal();
+        let (b, mut tape) = a.leaky_trace().record_graph().exp().split_tape();
+        // an op whose output keeps the id of its input
+        tape.add_graph_node(&b.data, || {
+            GraphNode::new(
+                "in_place",
+                std::vec![GraphTensor::of(&b)],
+                GraphTensor::of(&b),
+            )
+        })
+        .unwrap();
+        let ops: Vec<String> = tape.graph().nodes.into_iter().map(|n| n.op).collect();
+        assert_eq!(ops, ["exp", "in_place"]);
+    }
+
+    #[test]
+    fn test_graph_merges_tapes() {
+        let dev: TestDevice = Default::default();
+        let a: Tensor<Rank1<3>, TestDtype, _> = dev.sample_normal();
+        let b: Tensor<Rank1<3>, TestDtype, _> = dev.sample_normal();
+        let a = a.leaky_trace().record_graph().exp();
+        let b = b.leaky_trace().record_graph().sin();
+        let c = a + b;
+        let ops: Vec<String> = c.graph().nodes.into_iter().map(|n| n.op).collect();
+        assert_eq!(ops, ["exp", "sin", "binary_add"]);
+    }
+
+    #[test]
+    fn test_graph_export() {
+        let graph = Graph {
+            nodes: std::vec![GraphNode::new(
+                "exp",
+                std::vec![GraphTensor {
+                    id: UniqueId(0),
+                    shape: std::vec![2, 3],
+                }],
+                GraphTensor {
+                    id: UniqueId(1),
+                    shape: std::vec![2, 3],
+                },
+            )],
+        };
+        assert_eq!(
+            graph.to_dot(),
+            "digraph {\n    t0 [shape=box, label=\"t0\\n[2, 3]\"];\n    t1 [shape=box, label=\"t1\\n[2, 3]\"];\n    op0 [label=\"exp\"];\n    t0 -> op0;\n    op0 -> t1;\n}\n"
+        );
+        assert_eq!(
+            graph.to_json(),
+            r#"{"nodes":[{"op":"exp","inputs":[{"id":0,"shape":[2,3]}],"output":{"id":1,"shape":[2,3]}}]}"#
+        );
+    }
+}
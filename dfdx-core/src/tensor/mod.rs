@@ -136,12 +136,14 @@
 //! 2. Disable the cache entirely by calling [Cache::disable_cache()]. This will
 //! empty out any existing allocations and prevent any new ones from being cached.
 
+mod anomaly;
 pub(crate) mod cache;
 pub(crate) mod cpu;
 #[cfg(feature = "cuda")]
 pub(crate) mod cuda;
 mod ghost;
 mod gradients;
+mod graph;
 mod masks;
 #[cfg(feature = "numpy")]
 pub(crate) mod numpy;
@@ -189,7 +191,9 @@ pub use tensor_impls::{Tensor0D, Tensor1D, Tensor2D, Tensor3D, Tensor4D, Tensor5
 pub(crate) use unique_id::unique_id;
 pub use unique_id::UniqueId;
 
-pub use gradients::{Gradients, Merge, NoneTape, OwnedTape, Tape};
+pub use gradients::{DualTape, Gradients, Merge, NoneTape, OwnedTape, Tape, TracedGradients};
+pub(crate) use graph::op_name;
+pub use graph::{Graph, GraphNode, GraphTensor};
 
 #[cfg(test)]
 mod tests {
@@ -72,6 +72,24 @@ pub trait Trace<E, D: Storage<E>>: Clone {
     }
     /// Accumulates gradients into `gradients`.
     fn traced(self, gradients: Gradients<E, D>) -> Self::Traced;
+
+    /// Accumulates gradients into `gradients`, and also records the operations
+    /// needed to compute higher order gradients, clones self.
+    /// See [crate::tensor_ops::BackwardTraced].
+    fn trace_higher_order(&self, gradients: Gradients<E, D>) -> Self::Traced
+    where
+        E: Dtype,
+        D: crate::tensor_ops::Device<E>,
+    {
+        self.clone().traced_higher_order(gradients)
+    }
+    /// Accumulates gradients into `gradients`, and also records the operations
+    /// needed to compute higher order gradients.
+    /// See [crate::tensor_ops::BackwardTraced].
+    fn traced_higher_order(self, gradients: Gradients<E, D>) -> Self::Traced
+    where
+        E: Dtype,
+        D: crate::tensor_ops::Device<E>;
 }
 
 impl<S: Shape, E: Unit, F: Unit, D: Storage<F> + Storage<E>> Trace<E, D>
@@ -82,10 +100,16 @@ impl<S: Shape, E: Unit, F: Unit, D: Storage<F> + Storage<E>> Trace<E, D>
         self.put_tape(Default::default())
     }
     fn traced(self, gradients: Gradients<E, D>) -> Self::Traced {
-        self.put_tape(OwnedTape {
-            gradients,
-            operations: std::vec::Vec::new(),
-        })
+        self.put_tape(OwnedTape::from(gradients))
+    }
+    fn traced_higher_order(self, gradients: Gradients<E, D>) -> Self::Traced
+    where
+        E: Dtype,
+        D: crate::tensor_ops::Device<E>,
+    {
+        let mut tape = OwnedTape::from(gradients);
+        tape.higher_order = Some(crate::tensor_ops::HigherOrderOps::new());
+        self.put_tape(tape)
     }
 }
 
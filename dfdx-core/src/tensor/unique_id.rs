@@ -1,6 +1,6 @@
 /// An id used in to associate gradients with Tensors.
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, PartialOrd, Ord)]
-pub struct UniqueId(usize);
+pub struct UniqueId(pub(crate) usize);
 
 /// Generate a [UniqueId].
 pub(crate) fn unique_id() -> UniqueId {
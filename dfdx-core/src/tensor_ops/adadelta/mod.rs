@@ -0,0 +1,77 @@
+mod cpu_kernel;
+
+use crate::{
+    shapes::{Dtype, Shape},
+    tensor::{Error, Storage, Tensor},
+};
+
+use super::WeightDecay;
+
+/// Configuration of hyperparameters for Adadelta.
+///
+/// Changing all default parameters:
+/// ```rust
+/// # use dfdx_core::prelude::*;
+/// AdadeltaConfig {
+///     lr: 0.5,
+///     rho: 0.95,
+///     eps: 1e-8,
+///     weight_decay: Some(WeightDecay::L2(1e-2)),
+/// };
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct AdadeltaConfig {
+    /// Coefficient that scales the delta before it is applied. Defaults to `1.0`.
+    pub lr: f64,
+
+    /// Coefficient of the running averages of squared gradients & squared deltas. Defaults to `0.9`.
+    pub rho: f64,
+
+    /// Epsilon for numerical stability. Defaults to `1e-6`.
+    pub eps: f64,
+
+    /// Optional weight decay. Defaults to `None`.
+    pub weight_decay: Option<WeightDecay>,
+}
+
+impl Default for AdadeltaConfig {
+    fn default() -> Self {
+        Self {
+            lr: 1.0,
+            rho: 0.9,
+            eps: 1e-6,
+            weight_decay: None,
+        }
+    }
+}
+
+/// Only implemented for [crate::tensor::Cpu].
+pub trait AdadeltaKernel<E: Dtype>: Storage<E> {
+    fn adadelta_kernel(
+        &self,
+        cfg: &AdadeltaConfig,
+        param: &mut Self::Vec,
+        square_avg: &mut Self::Vec,
+        acc_delta: &mut Self::Vec,
+        grad: &Self::Vec,
+    ) -> Result<(), Error>;
+}
+
+impl AdadeltaConfig {
+    /// Update a single tensor using Adadelta.
+    pub fn try_update<S: Shape, E: Dtype, D: AdadeltaKernel<E>>(
+        &self,
+        param: &mut Tensor<S, E, D>,
+        square_avg: &mut D::Vec,
+        acc_delta: &mut D::Vec,
+        grad: &D::Vec,
+    ) -> Result<(), crate::tensor::Error> {
+        param.device.adadelta_kernel(
+            self,
+            std::sync::Arc::make_mut(&mut param.data),
+            square_avg,
+            acc_delta,
+            grad,
+        )
+    }
+}
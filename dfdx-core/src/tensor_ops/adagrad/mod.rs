@@ -0,0 +1,77 @@
+mod cpu_kernel;
+
+use crate::{
+    shapes::{Dtype, Shape},
+    tensor::{Error, Storage, Tensor},
+};
+
+use super::WeightDecay;
+
+/// Configuration of hyperparameters for Adagrad.
+///
+/// Changing all default parameters:
+/// ```rust
+/// # use dfdx_core::prelude::*;
+/// AdagradConfig {
+///     lr: 1e-1,
+///     lr_decay: 1e-3,
+///     eps: 1e-8,
+///     weight_decay: Some(WeightDecay::L2(1e-2)),
+/// };
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct AdagradConfig {
+    /// Learning rate. Defaults to `1e-2`.
+    pub lr: f64,
+
+    /// The learning rate at step `t` is `lr / (1 + (t - 1) * lr_decay)`. Defaults to `0.0`.
+    pub lr_decay: f64,
+
+    /// Epsilon for numerical stability. Defaults to `1e-10`.
+    pub eps: f64,
+
+    /// Optional weight decay. Defaults to `None`.
+    pub weight_decay: Option<WeightDecay>,
+}
+
+impl Default for AdagradConfig {
+    fn default() -> Self {
+        Self {
+            lr: 1e-2,
+            lr_decay: 0.0,
+            eps: 1e-10,
+            weight_decay: None,
+        }
+    }
+}
+
+/// Only implemented for [crate::tensor::Cpu].
+pub trait AdagradKernel<E: Dtype>: Storage<E> {
+    fn adagrad_kernel(
+        &self,
+        t: i32,
+        cfg: &AdagradConfig,
+        param: &mut Self::Vec,
+        sum_squares: &mut Self::Vec,
+        grad: &Self::Vec,
+    ) -> Result<(), Error>;
+}
+
+impl AdagradConfig {
+    /// Update a single tensor using Adagrad.
+    pub fn try_update<S: Shape, E: Dtype, D: AdagradKernel<E>>(
+        &self,
+        t: i32,
+        param: &mut Tensor<S, E, D>,
+        sum_squares: &mut D::Vec,
+        grad: &D::Vec,
+    ) -> Result<(), crate::tensor::Error> {
+        param.device.adagrad_kernel(
+            t,
+            self,
+            std::sync::Arc::make_mut(&mut param.data),
+            sum_squares,
+            grad,
+        )
+    }
+}
@@ -66,7 +66,10 @@ where
     type Output = Self;
     /// See [add]
     fn try_add(self, rhs: Tensor<S, E, D, R>) -> Result<Self, Error> {
-        try_binary_op(BinaryAddKernelOp, self, rhs)
+        try_binary_op_higher_order(BinaryAddKernelOp, self, rhs, |ops, g, _, _| {
+            // the gradients of lhs & rhs need different ids
+            Ok((ops.try_scale(g.retaped(), 1.0)?, g))
+        })
     }
 }
 
@@ -79,7 +82,7 @@ where
     fn try_add(self, rhs: Rhs) -> Result<Self, Error> {
         let rhs: f64 = rhs.into();
         let scalar = E::from_f64(rhs).unwrap();
-        try_unary_op(ScalarAddKernelOp { scalar }, self)
+        try_unary_op_higher_order(ScalarAddKernelOp { scalar }, self, |_, g, _, _| Ok(g))
     }
 }
 
@@ -188,6 +191,17 @@ mod tests {
         assert_close_to_literal!(g.get(&b2), [[[1.0 / 6.0; 3]; 2]; 4]);
     }
 
+    #[test]
+    fn test_add_runtime_shape_mismatch() {
+        let dev: TestDevice = Default::default();
+        let a: Tensor<(usize,), TestDtype, _> = dev.zeros_like(&(2,));
+        let b: Tensor<(usize,), TestDtype, _> = dev.zeros_like(&(3,));
+        assert!(matches!(
+            a.leaky_trace().try_add(b),
+            Err(Error::ShapeMismatch { expected, found }) if expected == [2] && found == [3]
+        ));
+    }
+
     #[test]
     fn test_scalar_add_0d() {
         let dev: TestDevice = Default::default();
@@ -8,7 +8,7 @@ mod webgpu_kernel;
 
 use crate::{
     shapes::{Dtype, HasShape, Shape},
-    tensor::{Error, Merge, PutTape, SplitTape, Storage, Tape, Tensor},
+    tensor::{Error, GraphNode, GraphTensor, Merge, PutTape, SplitTape, Storage, Tape, Tensor},
 };
 
 pub trait ChooseKernel<E: Dtype>: Storage<E> + Storage<bool> {
@@ -81,6 +81,17 @@ impl<
         let rhs_ghost = rhs.ghost();
         let out_ghost = out.ghost();
         let mut tape = tape.merge(rhs_tape);
+        tape.add_graph_node(&out.data, || {
+            GraphNode::new(
+                "choose",
+                std::vec![
+                    GraphTensor::of(&self),
+                    GraphTensor::of(&lhs),
+                    GraphTensor::of(&rhs)
+                ],
+                GraphTensor::of(&out),
+            )
+        })?;
         tape.add_backward_op(move |grads| {
             grads.try_alloc_for(&lhs_ghost)?;
             grads.try_alloc_for(&rhs_ghost)?;
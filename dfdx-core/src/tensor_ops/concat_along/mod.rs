@@ -85,6 +85,13 @@ where
         let lhs_ghost = lhs.ghost();
         let rhs_ghost = rhs.ghost();
         let out_ghost = out.ghost();
+        tape.add_graph_node(&out.data, || {
+            GraphNode::new(
+                "concat_along",
+                std::vec![GraphTensor::of(&lhs), GraphTensor::of(&rhs)],
+                GraphTensor::of(&out),
+            )
+        })?;
         tape.add_backward_op(move |grads| {
             grads.try_alloc_for(&lhs_ghost)?;
             grads.try_alloc_for(&rhs_ghost)?;
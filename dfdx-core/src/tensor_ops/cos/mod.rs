@@ -6,7 +6,7 @@ mod cuda_kernel;
 #[cfg(feature = "webgpu")]
 mod webgpu_kernel;
 
-use super::ops::{try_unary_op, UnaryKernel};
+use super::ops::{try_unary_op_higher_order, UnaryKernel};
 use crate::{shapes::*, tensor::*};
 
 #[repr(C)]
@@ -37,7 +37,10 @@ impl<S: Shape, E: Dtype, D: UnaryKernel<CosKernelOp, E>, T: Tape<E, D>> Tensor<S
     }
     /// See [cos]
     pub fn try_cos(self) -> Result<Self, crate::tensor::Error> {
-        try_unary_op(CosKernelOp, self)
+        try_unary_op_higher_order(CosKernelOp, self, |ops, g, x, _| {
+            let sin = ops.try_sin(x)?;
+            ops.try_scale(ops.try_mul(g, sin)?, -1.0)
+        })
     }
 }
 
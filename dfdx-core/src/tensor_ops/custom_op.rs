@@ -0,0 +1,347 @@
+#![allow(clippy::type_complexity)]
+
+use crate::{
+    shapes::{Dtype, HasShape, Shape},
+    tensor::{
+        op_name, Error, Gradients, GraphNode, GraphTensor, Merge, PutTape, SplitTape, Tape, Tensor,
+    },
+};
+
+use super::{axpy::AxpyKernel, reshape_to::ReshapeKernel, Device, ReshapeTo};
+
+/// A user defined differentiable operation with a single input.
+///
+/// [CustomOp::forward] & [CustomOp::backward] operate on tensors without tapes,
+/// and the tape is handled by [Tensor::custom_op]: the input's tape is moved
+/// to the output, and a backward operation is recorded that calls [CustomOp::backward]
+/// and adds the result to the input's gradient.
+///
+/// Example implementing `x^3`:
+/// ```rust
+/// # use dfdx_core::prelude::*;
+/// #[derive(Clone)]
+/// struct Cube;
+///
+/// impl<S: Shape> CustomOp<S, f32, Cpu> for Cube {
+///     type Output = S;
+///     fn forward(&self, inp: &Tensor<S, f32, Cpu>) -> Result<Tensor<S, f32, Cpu>, Error> {
+///         inp.clone().try_powi(3)
+///     }
+///     fn backward(
+///         &self,
+///         inp: &Tensor<S, f32, Cpu>,
+///         _out: &Tensor<S, f32, Cpu>,
+///         grad_out: &Tensor<S, f32, Cpu>,
+///     ) -> Result<Tensor<S, f32, Cpu>, Error> {
+///         inp.clone().try_square()?.try_mul(3.0)?.try_mul(grad_out.clone())
+///     }
+/// }
+///
+/// # let dev: Cpu = Default::default();
+/// let x = dev.tensor([1.0, 2.0, 3.0]);
+/// let y = x.leaky_trace().custom_op(Cube);
+/// assert_eq!(y.array(), [1.0, 8.0, 27.0]);
+/// let g = y.sum().backward();
+/// assert_eq!(g.get(&x).array(), [3.0, 12.0, 27.0]);
+/// ```
+pub trait CustomOp<S: Shape, E: Dtype, D: Device<E>>: 'static + Clone {
+    /// The shape of the output of [CustomOp::forward].
+    type Output: Shape;
+
+    /// Computes the output from the input.
+    fn forward(&self, inp: &Tensor<S, E, D>) -> Result<Tensor<Self::Output, E, D>, Error>;
+
+    /// Computes the gradient of the input from the gradient of the output.
+    /// The returned tensor must have the same shape as `inp`, otherwise the backward pass
+    /// returns [Error::ShapeMismatch].
+    fn backward(
+        &self,
+        inp: &Tensor<S, E, D>,
+        out: &Tensor<Self::Output, E, D>,
+        grad_out: &Tensor<Self::Output, E, D>,
+    ) -> Result<Tensor<S, E, D>, Error>;
+}
+
+/// A user defined differentiable operation with two inputs. See [CustomOp].
+pub trait CustomBinaryOp<L: Shape, R: Shape, E: Dtype, D: Device<E>>: 'static + Clone {
+    /// The shape of the output of [CustomBinaryOp::forward].
+    type Output: Shape;
+
+    /// Computes the output from both inputs.
+    fn forward(
+        &self,
+        lhs: &Tensor<L, E, D>,
+        rhs: &Tensor<R, E, D>,
+    ) -> Result<Tensor<Self::Output, E, D>, Error>;
+
+    /// Computes the gradients of `lhs` & `rhs` from the gradient of the output.
+    /// The returned tensors must have the same shapes as `lhs` & `rhs`.
+    fn backward(
+        &self,
+        lhs: &Tensor<L, E, D>,
+        rhs: &Tensor<R, E, D>,
+        out: &Tensor<Self::Output, E, D>,
+        grad_out: &Tensor<Self::Output, E, D>,
+    ) -> Result<(Tensor<L, E, D>, Tensor<R, E, D>), Error>;
+}
+
+/// Applies a user defined [CustomOp] to `t`. See [CustomOp] for an example.
+pub fn custom_op<S: Shape, E: Dtype, D: Device<E>, T: Tape<E, D>, Op: CustomOp<S, E, D>>(
+    t: Tensor<S, E, D, T>,
+    op: Op,
+) -> Tensor<Op::Output, E, D, T> {
+    t.custom_op(op)
+}
+
+/// Applies a user defined [CustomBinaryOp] to `lhs` & `rhs`.
+pub fn custom_binary_op<L: Shape, R: Shape, E: Dtype, D: Device<E>, LTape, RTape, Op>(
+    op: Op,
+    lhs: Tensor<L, E, D, LTape>,
+    rhs: Tensor<R, E, D, RTape>,
+) -> Tensor<Op::Output, E, D, LTape>
+where
+    LTape: Tape<E, D> + Merge<RTape>,
+    Op: CustomBinaryOp<L, R, E, D>,
+{
+    lhs.custom_binary_op(op, rhs)
+}
+
+impl<S: Shape, E: Dtype, D: Device<E>, T: Tape<E, D>> Tensor<S, E, D, T> {
+    /// See [custom_op]
+    pub fn custom_op<Op: CustomOp<S, E, D>>(self, op: Op) -> Tensor<Op::Output, E, D, T> {
+        self.try_custom_op(op).unwrap()
+    }
+
+    /// See [custom_op]
+    pub fn try_custom_op<Op: CustomOp<S, E, D>>(
+        self,
+        op: Op,
+    ) -> Result<Tensor<Op::Output, E, D, T>, Error> {
+        let (inp, mut tape) = self.split_tape();
+        let out = op.forward(&inp)?.try_contiguous()?;
+        let out_clone = out.clone();
+        tape.add_graph_node(&out.data, || {
+            GraphNode::new(
+                op_name::<Op>(),
+                std::vec![GraphTensor::of(&inp)],
+                GraphTensor::of(&out),
+            )
+        })?;
+        tape.add_backward_op(move |grads| {
+            grads.try_alloc_for(&out_clone)?;
+            let grad_out = grads.get(&out_clone);
+            let grad_inp = op.backward(&inp, &out_clone, &grad_out)?;
+            try_accumulate_grad(grads, &inp, grad_inp)
+        });
+        Ok(out.put_tape(tape))
+    }
+
+    /// See [custom_binary_op]
+    pub fn custom_binary_op<R: Shape, RTape, Op: CustomBinaryOp<S, R, E, D>>(
+        self,
+        op: Op,
+        rhs: Tensor<R, E, D, RTape>,
+    ) -> Tensor<Op::Output, E, D, T>
+    where
+        T: Merge<RTape>,
+    {
+        self.try_custom_binary_op(op, rhs).unwrap()
+    }
+
+    /// See [custom_binary_op]
+    pub fn try_custom_binary_op<R: Shape, RTape, Op: CustomBinaryOp<S, R, E, D>>(
+        self,
+        op: Op,
+        rhs: Tensor<R, E, D, RTape>,
+    ) -> Result<Tensor<Op::Output, E, D, T>, Error>
+    where
+        T: Merge<RTape>,
+    {
+        let (lhs, ltape) = self.split_tape();
+        let (rhs, rtape) = rhs.split_tape();
+        let mut tape = ltape.merge(rtape);
+        let out = op.forward(&lhs, &rhs)?.try_contiguous()?;
+        let out_clone = out.clone();
+        tape.add_graph_node(&out.data, || {
+            GraphNode::new(
+                op_name::<Op>(),
+                std::vec![GraphTensor::of(&lhs), GraphTensor::of(&rhs)],
+                GraphTensor::of(&out),
+            )
+        })?;
+        tape.add_backward_op(move |grads| {
+            grads.try_alloc_for(&out_clone)?;
+            let grad_out = grads.get(&out_clone);
+            let (grad_lhs, grad_rhs) = op.backward(&lhs, &rhs, &out_clone, &grad_out)?;
+            try_accumulate_grad(grads, &lhs, grad_lhs)?;
+            try_accumulate_grad(grads, &rhs, grad_rhs)
+        });
+        Ok(out.put_tape(tape))
+    }
+}
+
+/// Adds `grad` to the gradient of `t`, summing over any broadcasted axes of `t`.
+///
+/// Returns [Error::ShapeMismatch] if the custom op returned a gradient with the wrong shape.
+fn try_accumulate_grad<S: Shape, E: Dtype, D: Device<E>>(
+    grads: &mut Gradients<E, D>,
+    t: &Tensor<S, E, D>,
+    grad: Tensor<S, E, D>,
+) -> Result<(), Error> {
+    if grad.shape().concrete() != t.shape().concrete() {
+        return Err(Error::ShapeMismatch {
+            expected: t.shape().concrete().into_iter().collect(),
+            found: grad.shape().concrete().into_iter().collect(),
+        });
+    }
+    let grad = grad.try_contiguous()?;
+    let grad_t = grads.get_or_alloc_mut(t)?;
+    if t.strides == t.shape.strides() {
+        let one = E::from_f64(1.0).unwrap();
+        AxpyKernel::forward(&t.device, grad_t, one, grad.data.as_ref(), one)
+    } else {
+        ReshapeKernel::backward(&t.device, &t.shape, t, grad_t, grad.data.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{shapes::*, tensor::*, tensor_ops::*, tests::*};
+
+    #[derive(Clone)]
+    struct Cube;
+
+    impl<S: Shape> CustomOp<S, TestDtype, TestDevice> for Cube {
+        type Output = S;
+        fn forward(
+            &self,
+            inp: &Tensor<S, TestDtype, TestDevice>,
+        ) -> Result<Tensor<S, TestDtype, TestDevice>, Error> {
+            inp.clone().try_powi(3)
+        }
+        fn backward(
+            &self,
+            inp: &Tensor<S, TestDtype, TestDevice>,
+            _out: &Tensor<S, TestDtype, TestDevice>,
+            grad_out: &Tensor<S, TestDtype, TestDevice>,
+        ) -> Result<Tensor<S, TestDtype, TestDevice>, Error> {
+            inp.clone()
+                .try_square()?
+                .try_mul(3.0)?
+                .try_mul(grad_out.clone())
+        }
+    }
+
+    #[derive(Clone)]
+    struct RowDot;
+
+    impl<M: Dim, N: Dim> CustomBinaryOp<(M, N), (N,), TestDtype, TestDevice> for RowDot {
+        type Output = (M,);
+        fn forward(
+            &self,
+            lhs: &Tensor<(M, N), TestDtype, TestDevice>,
+            rhs: &Tensor<(N,), TestDtype, TestDevice>,
+        ) -> Result<Tensor<(M,), TestDtype, TestDevice>, Error> {
+            lhs.clone().try_matmul(rhs.clone())
+        }
+        fn backward(
+            &self,
+            lhs: &Tensor<(M, N), TestDtype, TestDevice>,
+            rhs: &Tensor<(N,), TestDtype, TestDevice>,
+            _out: &Tensor<(M,), TestDtype, TestDevice>,
+            grad_out: &Tensor<(M,), TestDtype, TestDevice>,
+        ) -> Result<
+            (
+                Tensor<(M, N), TestDtype, TestDevice>,
+                Tensor<(N,), TestDtype, TestDevice>,
+            ),
+            Error,
+        > {
+            let shape = *lhs.shape();
+            let grad_lhs = grad_out
+                .clone()
+                .try_broadcast_like::<_, Axis<1>>(&shape)?
+                .try_mul(rhs.clone().try_broadcast_like::<_, Axis<0>>(&shape)?)?;
+            let grad_rhs = grad_out
+                .clone()
+                .try_broadcast_like::<_, Axis<1>>(&shape)?
+                .try_mul(lhs.clone())?
+                .try_sum::<_, Axis<0>>()?;
+            Ok((grad_lhs, grad_rhs))
+        }
+    }
+
+    #[test]
+    fn test_custom_op_matches_builtin() {
+        let dev: TestDevice = Default::default();
+        let x = dev
+            .tensor([[-1.0, 0.5, 2.0], [1.5, -0.5, 3.0]])
+            .to_dtype::<TestDtype>();
+        let y = x.leaky_trace().custom_op(Cube);
+        let y_expected = x.leaky_trace().powi(3);
+        assert_close_to_tensor!(y, y_expected);
+        let g = y.exp().mean().backward();
+        let g_expected = y_expected.exp().mean().backward();
+        assert_close_to_tensor!(g.get(&x), g_expected.get(&x));
+    }
+
+    #[test]
+    fn test_custom_op_broadcasted_input() {
+        let dev: TestDevice = Default::default();
+        let x = dev.tensor([1.0, -2.0, 0.5]).to_dtype::<TestDtype>();
+        let y = x
+            .leaky_trace()
+            .broadcast::<Rank2<2, 3>, _>()
+            .custom_op(Cube);
+        let g = y.sum().backward();
+        assert_close_to_literal!(g.get(&x), [6.0, 24.0, 1.5]);
+    }
+
+    #[derive(Clone)]
+    struct WrongGradShape;
+
+    impl CustomOp<(usize,), TestDtype, TestDevice> for WrongGradShape {
+        type Output = (usize,);
+        fn forward(
+            &self,
+            inp: &Tensor<(usize,), TestDtype, TestDevice>,
+        ) -> Result<Tensor<(usize,), TestDtype, TestDevice>, Error> {
+            Ok(inp.clone())
+        }
+        fn backward(
+            &self,
+            inp: &Tensor<(usize,), TestDtype, TestDevice>,
+            _out: &Tensor<(usize,), TestDtype, TestDevice>,
+            _grad_out: &Tensor<(usize,), TestDtype, TestDevice>,
+        ) -> Result<Tensor<(usize,), TestDtype, TestDevice>, Error> {
+            inp.device.try_zeros_like(&(inp.shape.0 + 1,))
+        }
+    }
+
+    #[test]
+    fn test_custom_op_wrong_grad_shape() {
+        let dev: TestDevice = Default::default();
+        let x: Tensor<(usize,), TestDtype, _> = dev.zeros_like(&(3,));
+        let y = x.leaky_trace().custom_op(WrongGradShape);
+        assert!(matches!(
+            y.sum().try_backward(),
+            Err(Error::ShapeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_custom_binary_op_matches_builtin() {
+        let dev: TestDevice = Default::default();
+        let a = dev
+            .tensor([[1.0, 2.0, 3.0], [-1.0, 0.5, 2.0]])
+            .to_dtype::<TestDtype>();
+        let b = dev.tensor([0.5, -1.0, 2.0]).to_dtype::<TestDtype>();
+        let y = a.leaky_trace().custom_binary_op(RowDot, b.clone());
+        assert_close_to_literal!(y, [4.5, 3.0]);
+        let g = y.square().sum().backward();
+        let y_expected = a.leaky_trace().matmul(b.clone());
+        let g_expected = y_expected.square().sum().backward();
+        assert_close_to_tensor!(g.get(&a), g_expected.get(&a));
+        assert_close_to_tensor!(g.get(&b), g_expected.get(&b));
+    }
+}
@@ -64,7 +64,11 @@ where
     type Output = Self;
     /// See [div]
     fn try_div(self, rhs: Tensor<S, E, D, R>) -> Result<Self, Error> {
-        try_binary_op(BinaryDivKernelOp, self, rhs)
+        try_binary_op_higher_order(BinaryDivKernelOp, self, rhs, |ops, g, l, r| {
+            let grad_lhs = ops.try_div(g, r.retaped())?;
+            let grad_rhs = ops.try_div(ops.try_mul(grad_lhs.retaped(), l)?, r)?;
+            Ok((grad_lhs, ops.try_scale(grad_rhs, -1.0)?))
+        })
     }
 }
 
@@ -77,7 +81,9 @@ where
     fn try_div(self, rhs: Rhs) -> Result<Self, Error> {
         let rhs: f64 = rhs.into();
         let scalar = E::from_f64(rhs).unwrap();
-        try_unary_op(ScalarDivKernelOp { scalar }, self)
+        try_unary_op_higher_order(ScalarDivKernelOp { scalar }, self, move |ops, g, _, _| {
+            ops.try_scale(g, 1.0 / rhs)
+        })
     }
 }
 
@@ -0,0 +1,162 @@
+use crate::{shapes::*, tensor::*};
+
+use super::{Device, HigherOrderOps, ReshapeTo};
+
+impl<S: Shape, E: Dtype, D: Device<E>> Tensor<S, E, D> {
+    /// Starts forward mode autodiff with [DualTape], where `tangent` is the direction
+    /// to differentiate in. The tangent of any tensor computed from the result is then
+    /// the directional derivative along `tangent`, see [Tensor::tangent()].
+    ///
+    /// A single forward pass gives the derivatives of all outputs, which is cheaper than
+    /// reverse mode when there are few inputs and many outputs.
+    ///
+    /// ```rust
+    /// # use dfdx_core::prelude::*;
+    /// # let dev: Cpu = Default::default();
+    /// let x = dev.tensor([1.0f32, 2.0, 3.0]);
+    /// let v = dev.tensor([1.0f32, 0.0, -1.0]);
+    /// let y = x.dual(v).square() * 0.5;
+    /// assert_eq!(y.tangent().array(), [1.0, 0.0, -3.0]);
+    /// ```
+    ///
+    /// Elementwise unary operations, add/sub/mul/div, matmul of 1d & 2d tensors, sums
+    /// (and so means), softmax, and operations that only change the view of a tensor
+    /// (like broadcast, permute & reshape) support forward mode. Other operations return
+    /// [Error::ForwardModeUnsupported], see [DualTape].
+    pub fn dual(&self, tangent: Tensor<S, E, D>) -> Tensor<S, E, D, DualTape<E, D>> {
+        self.try_dual(tangent).unwrap()
+    }
+
+    /// Fallible version of [Tensor::dual()].
+    pub fn try_dual(
+        &self,
+        tangent: Tensor<S, E, D>,
+    ) -> Result<Tensor<S, E, D, DualTape<E, D>>, Error> {
+        assert_eq!(self.shape, tangent.shape);
+        // tangents need to have the same strides as the data of their tensor
+        let primal = self.clone().try_contiguous()?;
+        let tangent = tangent.try_contiguous()?;
+        let mut tape: DualTape<E, D> = Default::default();
+        tape.tangents.insert(&primal, tangent);
+        tape.ops = Some(HigherOrderOps::new());
+        Ok(primal.put_tape(tape))
+    }
+}
+
+impl<S: Shape, E: Dtype, D: Device<E>> Tensor<S, E, D, DualTape<E, D>> {
+    /// The tangent of this tensor, which is the directional derivative from
+    /// [Tensor::dual()]. Tensors that don't depend on the dual tensor have a tangent of zeros.
+    pub fn tangent(&self) -> Tensor<S, E, D> {
+        self.try_tangent().unwrap()
+    }
+
+    /// Fallible version of [Tensor::tangent()]. Returns [Error::ForwardModeUnsupported]
+    /// if this was computed with an operation that doesn't support forward mode.
+    pub fn try_tangent(&self) -> Result<Tensor<S, E, D>, Error> {
+        if self.tape.unsupported {
+            return Err(Error::ForwardModeUnsupported);
+        }
+        match self.tape.tangents.get_checked(self) {
+            Some(tangent) => Ok(tangent),
+            None => self.device.try_zeros_like(&self.shape),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{autograd::jacobian, shapes::*, tensor::*, tensor_ops::*, tests::*};
+
+    #[test]
+    fn test_dual_unary() {
+        let dev: TestDevice = Default::default();
+        let x: Tensor<Rank1<4>, TestDtype, _> = dev.tensor([-1.0, 0.5, 1.0, 2.0]).to_dtype();
+        let v: Tensor<Rank1<4>, TestDtype, _> = dev.tensor([1.0, 2.0, -1.0, 0.5]).to_dtype();
+        let y = x.dual(v.clone()).sin().exp();
+        assert_close_to_tensor!(y.retaped::<NoneTape>(), x.clone().sin().exp());
+        let expected = x.clone().cos() * x.clone().sin().exp() * v.clone();
+        assert_close_to_tensor!(y.tangent(), expected);
+
+        // kernels without higher order gradients are supported as well
+        let y = x.dual(v.clone()).powi(3).fast_gelu();
+        let j = jacobian(|x| x.powi(3).fast_gelu(), &x);
+        let j = dev.tensor_from_vec(j.as_vec(), (Const::<4>, Const::<4>));
+        assert_close_to_tensor!(y.tangent(), j.matmul(v));
+    }
+
+    #[test]
+    fn test_dual_binary() {
+        let dev: TestDevice = Default::default();
+        let x: Tensor<Rank2<2, 3>, TestDtype, _> = dev.sample_normal();
+        let b: Tensor<Rank1<3>, TestDtype, _> = dev.sample_normal();
+        let v: Tensor<Rank2<2, 3>, TestDtype, _> = dev.sample_normal();
+        let y = x.dual(v.clone());
+        let y = y.retaped::<DualTape<_, _>>() * y / b.clone().broadcast();
+        // d(x^2 / b) = 2 x v / b
+        let expected = (x * v * 2.0) / b.broadcast();
+        assert_close_to_tensor!(y.tangent(), expected);
+    }
+
+    #[test]
+    fn test_dual_mlp_matches_jacobian() {
+        let dev: TestDevice = Default::default();
+        let x: Tensor<Rank1<3>, TestDtype, _> = dev.sample_normal();
+        let v: Tensor<Rank1<3>, TestDtype, _> = dev.sample_normal();
+        let w1: Tensor<Rank2<3, 4>, TestDtype, _> = dev.sample_normal();
+        let b1: Tensor<Rank1<4>, TestDtype, _> = dev.sample_normal();
+        let w2: Tensor<Rank2<5, 4>, TestDtype, _> = dev.sample_normal();
+
+        let y = x.dual(v.clone()).matmul(w1.clone()) + b1.clone();
+        let y = y.tanh().matmul(w2.clone().permute()).softmax::<Axis<0>>();
+        let j = jacobian(
+            |x| {
+                let y = x.matmul(w1.clone()) + b1.clone();
+                y.tanh().matmul(w2.clone().permute()).softmax::<Axis<0>>()
+            },
+            &x,
+        );
+        let j = dev.tensor_from_vec(j.as_vec(), (Const::<5>, Const::<3>));
+        assert_close_to_tensor!(y.tangent(), j.matmul(v));
+    }
+
+    #[test]
+    fn test_dual_reductions_and_reshapes() {
+        let dev: TestDevice = Default::default();
+        let x: Tensor<Rank2<2, 3>, TestDtype, _> = dev.sample_normal();
+        let v: Tensor<Rank2<2, 3>, TestDtype, _> = dev.sample_normal();
+        let y = x.dual(v.clone()).permute::<Rank2<3, 2>, _>();
+        let y = y.reshape::<Rank1<6>>().mean::<Rank0, _>();
+        assert_close_to_tensor!(y.tangent(), v.mean::<Rank0, _>());
+    }
+
+    #[test]
+    fn test_dual_constant_has_zero_tangent() {
+        let dev: TestDevice = Default::default();
+        let x: Tensor<Rank1<3>, TestDtype, _> = dev.sample_normal();
+        let y = x.clone().put_tape(DualTape::default()).exp();
+        assert_close_to_literal!(y.tangent(), [0.0; 3]);
+    }
+
+    #[test]
+    fn test_dual_unsupported() {
+        let dev: TestDevice = Default::default();
+        let x: Tensor<Rank1<3>, TestDtype, _> = dev.sample_normal();
+        assert!(matches!(
+            x.dual(x.clone()).try_maximum(x.clone()),
+            Err(Error::ForwardModeUnsupported)
+        ));
+
+        // ops with a custom backward pass fail on the next op instead
+        let y = x.dual(x.clone()).max::<Rank0, _>();
+        assert!(matches!(
+            y.try_tangent(),
+            Err(Error::ForwardModeUnsupported)
+        ));
+        assert!(matches!(y.try_exp(), Err(Error::ForwardModeUnsupported)));
+
+        // without tangents to compute, every op works
+        let y = x.clone().put_tape(DualTape::default()).max::<Rank0, _>();
+        let y = y.maximum(dev.zeros()).exp();
+        assert_close_to_literal!(y.tangent(), 0.0);
+    }
+}
@@ -0,0 +1,157 @@
+use crate::{shapes::*, tensor::*};
+
+use super::{Device, ReshapeTo};
+
+/// Registers `hook` to be called with the gradient of `t` during backward, once all
+/// operations recorded after `t` have accumulated into it, and before it is propagated
+/// to the operations that produced `t`. The hook can modify the gradient in place,
+/// which changes the gradients of everything before `t`.
+///
+/// This does nothing if `t` does not have an [OwnedTape].
+///
+/// Scaling the gradient that flows through an intermediate tensor:
+/// ```rust
+/// # use dfdx_core::prelude::*;
+/// # let dev: Cpu = Default::default();
+/// let x = dev.tensor([1.0f32, 2.0, 3.0]);
+/// let y = x.leaky_trace().square().register_hook(|g| {
+///     *g = g.clone() * 0.5;
+///     Ok(())
+/// });
+/// let grads = y.sum().backward();
+/// assert_eq!(grads.get(&x).array(), [1.0, 2.0, 3.0]);
+/// ```
+///
+/// Hooks are not supported for higher order gradients. The hook must keep the shape of the
+/// gradient it was given, or backward returns [Error::ShapeMismatch]. If the gradient of `t`
+/// does not have contiguous strides (e.g. when `t` is broadcasted), the hook must keep its
+/// strides too, or backward returns [Error::InvalidArgument].
+pub fn register_hook<S: Shape, E: Dtype, D: Device<E>, T: Tape<E, D>, F>(
+    t: Tensor<S, E, D, T>,
+    hook: F,
+) -> Tensor<S, E, D, T>
+where
+    F: 'static + FnOnce(&mut Tensor<S, E, D>) -> Result<(), Error>,
+{
+    t.register_hook(hook)
+}
+
+impl<S: Shape, E: Dtype, D: Device<E>, T: Tape<E, D>> Tensor<S, E, D, T> {
+    /// See [register_hook]
+    pub fn register_hook<F>(self, hook: F) -> Self
+    where
+        F: 'static + FnOnce(&mut Tensor<S, E, D>) -> Result<(), Error>,
+    {
+        let (t, mut tape) = self.split_tape();
+        let t_ghost = t.ghost();
+        tape.add_backward_op(move |grads| {
+            grads.try_alloc_for(&t_ghost)?;
+            let mut grad = grads.get(&t_ghost);
+            hook(&mut grad)?;
+            if grad.shape.concrete() != t_ghost.shape.concrete() {
+                return Err(Error::ShapeMismatch {
+                    expected: t_ghost.shape.concrete().into_iter().collect(),
+                    found: grad.shape.concrete().into_iter().collect(),
+                });
+            }
+            if grad.strides != t_ghost.strides {
+                if t_ghost.strides != t_ghost.shape.strides() {
+                    return Err(Error::InvalidArgument(
+                        "Gradient hooks must keep the strides of non-contiguous gradients".into(),
+                    ));
+                }
+                grad = grad.try_contiguous()?;
+            }
+            grads.insert(&t_ghost, grad);
+            Ok(())
+        });
+        t.put_tape(tape)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{shapes::*, tensor::*, tensor_ops::*, tests::*};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_hook_on_leaf_scales_grad() {
+        let dev: TestDevice = Default::default();
+        let x: Tensor<Rank1<3>, TestDtype, _> = dev.tensor([1.0, -2.0, 3.0]).to_dtype();
+        let y = x.leaky_trace().register_hook(|g| {
+            *g = g.clone() * 2.0;
+            Ok(())
+        });
+        let g = y.square().sum().backward();
+        assert_close_to_literal!(g.get(&x), [4.0, -8.0, 12.0]);
+    }
+
+    #[test]
+    fn test_hook_on_intermediate_clamps_grad() {
+        let dev: TestDevice = Default::default();
+        let x: Tensor<Rank1<3>, TestDtype, _> = dev.tensor([0.0, 1.0, -1.0]).to_dtype();
+        let y = x.leaky_trace().exp().register_hook(|g| {
+            *g = g.clone().try_clamp(-1.0, 1.0)?;
+            Ok(())
+        });
+        let g = (y * 3.0).sum().backward();
+        assert_close_to_tensor!(g.get(&x), x.exp());
+    }
+
+    #[test]
+    fn test_hook_sees_accumulated_grad() {
+        let dev: TestDevice = Default::default();
+        let x: Tensor<Rank1<2>, TestDtype, _> = dev.tensor([1.0, 2.0]).to_dtype();
+        let seen = Arc::new(Mutex::new(None));
+        let seen_in_hook = seen.clone();
+        let y = x
+            .leaky_trace()
+            .sin()
+            .register_hook(move |g| {
+                *seen_in_hook.lock().unwrap() = Some(g.array());
+                Ok(())
+            })
+            .broadcast::<Rank2<3, 2>, _>();
+        let g = y.sum().backward();
+
+        // the gradient from all 3 broadcasted rows is summed before the hook runs
+        let seen = seen.lock().unwrap().unwrap();
+        assert_close_to_literal!(dev.tensor(seen), [3.0, 3.0]);
+        assert_close_to_tensor!(g.get(&x), x.cos() * 3.0);
+    }
+
+    #[test]
+    fn test_gradient_reversal() {
+        let dev: TestDevice = Default::default();
+        let x: Tensor<Rank1<3>, TestDtype, _> = dev.sample_normal();
+        let y = x.leaky_trace().tanh().register_hook(|g| {
+            *g = g.clone().try_negate()?;
+            Ok(())
+        });
+        let g = y.sum().backward();
+        let expected = x.leaky_trace().tanh().sum().backward();
+        assert_close_to_tensor!(g.get(&x), expected.get(&x).negate());
+    }
+
+    #[test]
+    fn test_hook_changing_grad_shape_errors() {
+        let dev: TestDevice = Default::default();
+        let x: Tensor<(usize,), TestDtype, _> = dev.sample_normal_like(&(3,));
+        let y = x.leaky_trace().exp().register_hook(|g| {
+            *g = g.device.zeros_like(&(2,));
+            Ok(())
+        });
+        assert!(matches!(
+            y.sum().try_backward(),
+            Err(Error::ShapeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_hook_without_tape_does_nothing() {
+        let dev: TestDevice = Default::default();
+        let x: Tensor<Rank1<3>, TestDtype, _> = dev.sample_normal();
+        let y = x.clone().register_hook(|_| panic!("hook should not run"));
+        assert_eq!(y.array(), x.array());
+    }
+}
@@ -0,0 +1,83 @@
+use super::{LionConfig, LionKernel, WeightDecay};
+use crate::{
+    dtypes::{Dtype, NotMixedPrecision},
+    tensor::{Cpu, Error},
+};
+
+#[cfg(feature = "f16")]
+impl LionKernel<crate::dtypes::AMP<crate::dtypes::f16>> for Cpu {
+    fn lion_kernel(
+        &self,
+        cfg: &LionConfig,
+        param: &mut Self::Vec,
+        moment: &mut Self::Vec,
+        grad: &Self::Vec,
+    ) -> Result<(), Error> {
+        let betas = cfg.betas.map(|x| x as f32);
+        let lr = cfg.lr as f32;
+
+        for ((p, g), m) in param
+            .iter_mut()
+            .zip(grad.iter().cloned())
+            .zip(moment.iter_mut())
+        {
+            let p_f32 = p.0.to_f32();
+            let mut g_f32 = g.0.to_f32();
+            let m_f32 = m.0.to_f32();
+
+            if let Some(WeightDecay::L2(wd)) = cfg.weight_decay {
+                g_f32 += (wd as f32) * p_f32;
+            }
+
+            let c = m_f32 * betas[0] + g_f32 * (1.0 - betas[0]);
+            let mut u = if c == 0.0 { 0.0 } else { lr * c.signum() };
+
+            if let Some(WeightDecay::Decoupled(wd)) = cfg.weight_decay {
+                u += (wd * cfg.lr) as f32 * p_f32;
+            }
+
+            p.0 = crate::dtypes::f16::from_f32(p_f32 - u);
+            m.0 = crate::dtypes::f16::from_f32(m_f32 * betas[1] + g_f32 * (1.0 - betas[1]));
+        }
+        Ok(())
+    }
+}
+
+impl<E: num_traits::Float + Dtype + NotMixedPrecision> LionKernel<E> for Cpu {
+    fn lion_kernel(
+        &self,
+        cfg: &LionConfig,
+        param: &mut Self::Vec,
+        moment: &mut Self::Vec,
+        grad: &Self::Vec,
+    ) -> Result<(), Error> {
+        let betas = cfg.betas.map(E::from_f64).map(Option::unwrap);
+        let lr = E::from_f64(cfg.lr).unwrap();
+
+        for ((p, mut g), m) in param
+            .iter_mut()
+            .zip(grad.iter().cloned())
+            .zip(moment.iter_mut())
+        {
+            if let Some(WeightDecay::L2(wd)) = cfg.weight_decay {
+                g += E::from_f64(wd).unwrap() * *p;
+            }
+
+            // only the sign of the interpolated momentum is used
+            let c = *m * betas[0] + g * (E::one() - betas[0]);
+            let mut u = if c.is_zero() {
+                E::zero()
+            } else {
+                lr * c.signum()
+            };
+
+            if let Some(WeightDecay::Decoupled(wd)) = cfg.weight_decay {
+                u += E::from_f64(wd * cfg.lr).unwrap() * *p;
+            }
+
+            *p -= u;
+            *m = *m * betas[1] + g * (E::one() - betas[1]);
+        }
+        Ok(())
+    }
+}
@@ -6,7 +6,7 @@ mod cuda_kernel;
 #[cfg(feature = "webgpu")]
 mod webgpu_kernel;
 
-use super::ops::{try_unary_op, UnaryKernel};
+use super::ops::{try_unary_op_higher_order, UnaryKernel};
 use crate::{shapes::*, tensor::*};
 
 #[repr(C)]
@@ -37,7 +37,7 @@ impl<S: Shape, E: Dtype, D: UnaryKernel<LnKernelOp, E>, T: Tape<E, D>> Tensor<S,
     }
     /// See [ln]
     pub fn try_ln(self) -> Result<Self, crate::tensor::Error> {
-        try_unary_op(LnKernelOp, self)
+        try_unary_op_higher_order(LnKernelOp, self, |ops, g, x, _| ops.try_div(g, x))
     }
 }
 
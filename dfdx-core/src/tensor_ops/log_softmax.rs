@@ -67,10 +67,17 @@ impl<S: Shape, E: Dtype, D: Device<E>, T: Tape<E, D>> Tensor<S, E, D, T> {
             let keep_id = t.id;
             let mut t = t.try_sub(max.try_broadcast_like::<_, Ax>(&shape)?)?;
             t.id = keep_id;
-            t.put_tape(tape)
+            t
         };
-        let logsumexp = tm.retaped::<T>().try_exp()?.try_sum::<_, Ax>()?.try_ln()?;
-        tm.try_sub(logsumexp.try_broadcast_like(&shape)?)
+        let (logsumexp, tape) = tm
+            .clone()
+            .put_tape(tape)
+            .try_exp()?
+            .try_sum::<_, Ax>()?
+            .try_ln()?
+            .split_tape();
+        tm.put_tape(tape)
+            .try_sub(logsumexp.try_broadcast_like(&shape)?)
     }
 }
 
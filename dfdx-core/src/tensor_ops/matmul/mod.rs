@@ -10,10 +10,14 @@ pub(super) mod webgpu_kernel;
 
 use crate::{
     shapes::{Const, Dim, Dtype, Shape},
-    tensor::{Error, Merge, PutTape, SplitTape, Storage, Tape, Tensor},
+    tensor::{
+        Error, GraphNode, GraphTensor, Merge, NoneTape, OwnedTape, PutTape, SplitTape, Storage,
+        Tape, Tensor,
+    },
 };
 
 use super::reshape_to::{ReshapeKernel, ReshapeTo};
+use super::{HigherOrderOps, PermuteTo};
 
 /// Matrix * Matrix, Vector * Matrix, Vector * Vector, and broadcasted/batched versions.
 ///
@@ -103,6 +107,13 @@ fn try_binary_op<
     let rhs_ghost = rhs.ghost();
     let out = fwd(&lhs.device, &lhs, &rhs)?;
     let out_ghost = out.ghost();
+    tape.add_graph_node(&out.data, || {
+        GraphNode::new(
+            "matmul",
+            std::vec![GraphTensor::of(&lhs), GraphTensor::of(&rhs)],
+            GraphTensor::of(&out),
+        )
+    })?;
     tape.add_backward_op(move |grads| {
         grads.try_alloc_for(&lhs_ghost)?;
         grads.try_alloc_for(&rhs_ghost)?;
@@ -190,10 +201,73 @@ where
     /// ```
     fn try_matmul(self, rhs: Tensor<(K, N), E, D, R>) -> Result<Self::Output, Error> {
         assert_eq!(self.shape.1, rhs.shape.0);
-        try_binary_op(self, rhs, D::forward, D::backward)
+        if self.tape.is_forward_mode() {
+            return try_matmul_forward_mode(self, rhs);
+        }
+        let lhs_ghost = self.ghost();
+        let rhs_ghost = rhs.ghost();
+        let (lhs, rhs_clone) = (self.retaped::<NoneTape>(), rhs.retaped::<NoneTape>());
+        let (out, mut tape) = try_binary_op(self, rhs, D::forward, D::backward)?.split_tape();
+        let out_ghost = out.ghost();
+        tape.add_traced_backward_op(move |grads| {
+            if let Some(grad_out) = grads.get_checked(&out_ghost) {
+                let rhs_t = rhs_clone.retaped::<OwnedTape<E, D>>().try_permute()?;
+                let lhs_t = lhs.retaped::<OwnedTape<E, D>>().try_permute()?;
+                let grad_lhs = grad_out.retaped::<OwnedTape<E, D>>().try_matmul(rhs_t)?;
+                let grad_rhs = lhs_t.try_matmul(grad_out)?;
+                grads.accumulate(&lhs_ghost, grad_lhs)?;
+                grads.accumulate(&rhs_ghost, grad_rhs)?;
+            }
+            Ok(())
+        });
+        Ok(out.put_tape(tape))
     }
 }
 
+/// Matmul is bilinear, so the tangent of the output is `tangent_lhs * rhs + lhs * tangent_rhs`.
+fn try_matmul_forward_mode<M: Dim, K: Dim, N: Dim, E: Dtype, D, T, R>(
+    lhs: Tensor<(M, K), E, D, T>,
+    rhs: Tensor<(K, N), E, D, R>,
+) -> Result<Tensor<(M, N), E, D, T>, Error>
+where
+    D: MatMatKernel<E>,
+    T: Tape<E, D> + Merge<R>,
+{
+    let (lhs, ltape) = lhs.split_tape();
+    let (rhs, rtape) = rhs.split_tape();
+    let mut tape = ltape.merge(rtape);
+    let out = lhs.device.forward(&lhs, &rhs)?;
+    tape.add_forward_op(|tangents, ops| {
+        let tangent_lhs = match tangents.get_checked(&lhs) {
+            Some(t) => Some(t.try_matmul(rhs.clone())?),
+            None => None,
+        };
+        let tangent_rhs = match tangents.get_checked(&rhs) {
+            Some(t) => Some(lhs.clone().try_matmul(t)?),
+            None => None,
+        };
+        let tangent_out = match (tangent_lhs, tangent_rhs) {
+            (Some(l), Some(r)) => {
+                let (l, r) = (HigherOrderOps::buffer(&l), HigherOrderOps::buffer(&r));
+                let sum = ops.try_add(l, r)?;
+                Tensor {
+                    id: sum.id,
+                    data: sum.data,
+                    shape: out.shape,
+                    strides: out.strides,
+                    device: sum.device,
+                    tape: NoneTape,
+                }
+            }
+            (Some(t), None) | (None, Some(t)) => t,
+            (None, None) => return Ok(()),
+        };
+        tangents.insert(&out, tangent_out);
+        Ok(())
+    })?;
+    Ok(out.put_tape(tape))
+}
+
 pub trait MatMatBrKernel<E: Dtype>: Storage<E> {
     fn forward<B: Dim, M: Dim, K: Dim, N: Dim>(
         &self,
@@ -151,6 +151,8 @@ pub use utilities::*;
 
 mod abs;
 mod accurate_gelu;
+mod adadelta;
+mod adagrad;
 mod adam;
 mod add;
 mod attention_reshape;
@@ -166,11 +168,16 @@ mod concat_along;
 mod concat_shape_along;
 mod concat_tensor_along;
 mod cos;
+mod custom_op;
 mod div;
 mod dropout;
+mod dual;
 mod exp;
 mod fast_gelu;
+mod hook;
 mod huber_error;
+mod lamb;
+mod lion;
 mod ln;
 mod log_softmax;
 mod logsumexp_to;
@@ -200,6 +207,8 @@ mod sigmoid;
 mod sin;
 mod slice;
 mod softmax;
+mod sparse_adam;
+pub(crate) mod sparse_rows;
 mod sqrt;
 mod square;
 mod stack;
@@ -214,6 +223,8 @@ mod var_to;
 
 pub use abs::abs;
 pub use accurate_gelu::accurate_gelu;
+pub use adadelta::{AdadeltaConfig, AdadeltaKernel};
+pub use adagrad::{AdagradConfig, AdagradKernel};
 pub use adam::AdamConfig;
 pub use add::{add, TryAdd};
 pub use attention_reshape::TryAttentionReshape;
@@ -231,13 +242,17 @@ pub use concat_along::TryConcatAlong;
 pub use concat_shape_along::TryConcatShapeAlong;
 pub use concat_tensor_along::TryConcatTensorAlong;
 pub use cos::cos;
+pub use custom_op::{custom_binary_op, custom_op, CustomBinaryOp, CustomOp};
 pub use div::{div, TryDiv};
 pub use dropout::dropout;
 pub use exp::exp;
 pub use fast_gelu::fast_gelu;
 #[allow(deprecated)]
 pub use fast_gelu::gelu;
+pub use hook::register_hook;
 pub use huber_error::huber_error;
+pub use lamb::{LambConfig, LambKernel};
+pub use lion::{LionConfig, LionKernel};
 pub use ln::ln;
 pub use log_softmax::log_softmax;
 pub use logsumexp_to::LogSumExpTo;
@@ -265,8 +280,9 @@ pub use select_and_gather::{GatherTo, SelectTo};
 pub use sgd::SgdConfig;
 pub use sigmoid::sigmoid;
 pub use sin::sin;
-pub use slice::slice;
+pub use slice::{slice, SliceKernel};
 pub use softmax::softmax;
+pub use sparse_adam::{SparseAdamConfig, SparseAdamKernel};
 pub use sqrt::sqrt;
 pub use square::square;
 pub use stack::{AddDim, TryStack};
@@ -61,7 +61,9 @@ where
 {
     type Output = Self;
     fn try_mul(self, rhs: Tensor<S, E, D, R>) -> Result<Self, Error> {
-        try_binary_op(BinaryMulKernelOp, self, rhs)
+        try_binary_op_higher_order(BinaryMulKernelOp, self, rhs, |ops, g, l, r| {
+            Ok((ops.try_mul(g.retaped(), r)?, ops.try_mul(g, l)?))
+        })
     }
 }
 
@@ -73,7 +75,9 @@ where
     fn try_mul(self, rhs: Rhs) -> Result<Self, Error> {
         let rhs: f64 = rhs.into();
         let scalar: E = E::from_f64(rhs).unwrap();
-        try_unary_op(ScalarMulKernelOp { scalar }, self)
+        try_unary_op_higher_order(ScalarMulKernelOp { scalar }, self, move |ops, g, _, _| {
+            ops.try_scale(g, rhs)
+        })
     }
 }
 
@@ -6,7 +6,7 @@ mod cuda_kernel;
 #[cfg(feature = "webgpu")]
 mod webgpu_kernel;
 
-use super::ops::{try_unary_op, UnaryKernel};
+use super::ops::{try_unary_op_higher_order, UnaryKernel};
 use crate::{shapes::*, tensor::*};
 
 #[repr(C)]
@@ -34,7 +34,7 @@ impl<S: Shape, E: Dtype, D: UnaryKernel<NegateKernelOp, E>, T: Tape<E, D>> Tenso
         self.try_negate().unwrap()
     }
     pub fn try_negate(self) -> Result<Self, crate::tensor::Error> {
-        try_unary_op(NegateKernelOp, self)
+        try_unary_op_higher_order(NegateKernelOp, self, |ops, g, _, _| ops.try_scale(g, -1.0))
     }
 }
 
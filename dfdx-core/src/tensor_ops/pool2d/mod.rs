@@ -251,6 +251,13 @@ where
         let img_ghost = img.ghost();
         let out_ghost = out.ghost();
         let out_clone = out.clone();
+        tape.add_graph_node(&out.data, || {
+            GraphNode::new(
+                "pool2d",
+                std::vec![GraphTensor::of(&img)],
+                GraphTensor::of(&out),
+            )
+        })?;
         tape.add_backward_op(move |grads| {
             grads.try_alloc_for(&img_ghost)?;
             grads.try_alloc_for(&out_ghost)?;
@@ -6,7 +6,7 @@ mod cuda_kernel;
 #[cfg(feature = "webgpu")]
 mod webgpu_kernel;
 
-use super::ops::{try_unary_op, UnaryKernel};
+use super::ops::{try_unary_derivative, try_unary_op_higher_order, UnaryKernel};
 use crate::{shapes::*, tensor::*};
 
 #[repr(C)]
@@ -38,7 +38,10 @@ impl<S: Shape, E: Dtype, D: UnaryKernel<ReLUKernelOp, E>, T: Tape<E, D>> Tensor<
     }
     /// See [relu]
     pub fn try_relu(self) -> Result<Self, crate::tensor::Error> {
-        try_unary_op(ReLUKernelOp, self)
+        try_unary_op_higher_order(ReLUKernelOp, self, |ops, g, x, _| {
+            let dx = try_unary_derivative(ops, ReLUKernelOp, &x)?;
+            ops.try_mul(g, dx)
+        })
     }
 }
 
@@ -101,9 +101,25 @@ impl<S: Shape, E: Dtype, D: ReshapeKernel<E>, T: Tape<E, D>> ReshapeTo for Tenso
         } else {
             let (inp, mut tape) = self.split_tape();
             let out = inp.device.forward(dst, &inp)?;
+            if tape.is_forward_mode() {
+                tape.add_forward_op(|tangents, _| {
+                    if let Some(tangent_inp) = tangents.get_checked(&inp) {
+                        tangents.insert(&out, inp.device.forward(dst, &tangent_inp)?);
+                    }
+                    Ok(())
+                })?;
+                return Ok(out.put_tape(tape));
+            }
             let inp_ghost = inp.ghost();
             let out_ghost = out.ghost();
             let dst = *dst;
+            tape.add_graph_node(&out.data, || {
+                GraphNode::new(
+                    "reshape_to",
+                    std::vec![GraphTensor::of(&inp)],
+                    GraphTensor::of(&out),
+                )
+            })?;
             tape.add_backward_op(move |grads| {
                 grads.try_alloc_for(&inp_ghost)?;
                 grads.try_alloc_for(&out_ghost)?;
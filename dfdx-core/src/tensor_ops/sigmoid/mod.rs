@@ -6,7 +6,7 @@ mod cuda_kernel;
 #[cfg(feature = "webgpu")]
 mod webgpu_kernel;
 
-use super::ops::{try_unary_op, UnaryKernel};
+use super::ops::{try_unary_op_higher_order, UnaryKernel};
 use crate::{shapes::*, tensor::*};
 
 #[repr(C)]
@@ -37,7 +37,12 @@ impl<S: Shape, E: Dtype, D: UnaryKernel<SigmoidKernelOp, E>, T: Tape<E, D>> Tens
     }
     /// See [sigmoid]
     pub fn try_sigmoid(self) -> Result<Self, crate::tensor::Error> {
-        try_unary_op(SigmoidKernelOp, self)
+        try_unary_op_higher_order(SigmoidKernelOp, self, |ops, g, _, y| {
+            // g * y * (1 - y)
+            let g = ops.try_mul(g, y.retaped())?;
+            let one_minus_y = ops.try_shift(ops.try_scale(y, -1.0)?, 1.0)?;
+            ops.try_mul(g, one_minus_y)
+        })
     }
 }
 
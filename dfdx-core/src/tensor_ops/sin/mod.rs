@@ -6,7 +6,7 @@ mod cuda_kernel;
 #[cfg(feature = "webgpu")]
 mod webgpu_kernel;
 
-use super::ops::{try_unary_op, UnaryKernel};
+use super::ops::{try_unary_op_higher_order, UnaryKernel};
 use crate::{shapes::*, tensor::*};
 
 #[repr(C)]
@@ -37,7 +37,10 @@ impl<S: Shape, E: Dtype, D: UnaryKernel<SinKernelOp, E>, T: Tape<E, D>> Tensor<S
     }
     /// See [sin]
     pub fn try_sin(self) -> Result<Self, crate::tensor::Error> {
-        try_unary_op(SinKernelOp, self)
+        try_unary_op_higher_order(SinKernelOp, self, |ops, g, x, _| {
+            let cos = ops.try_cos(x)?;
+            ops.try_mul(g, cos)
+        })
     }
 }
 
@@ -2,49 +2,88 @@ use crate::prelude::cpu::{LendingIterator, NdIndex};
 
 use super::*;
 
-impl<E: Unit> SliceKernel<E> for Cpu {
+impl<E: Dtype> SliceKernel<E> for Cpu {
     fn forward<Src: Shape + SliceShape<Slice>, Slice>(
         &self,
         inp: &Tensor<Src, E, Self>,
         slice: &Slice,
     ) -> Result<Tensor<Src::Sliced, E, Self>, Error> {
-        let dst = inp.shape.slice(slice).unwrap();
-        let mut out = self.try_zeros_like(&dst)?;
-
-        let mut inp_idx = NdIndex::new(dst, inp.strides);
-        let mut out_iter = out.iter_mut();
-
-        let start_idx = NdIndex::new(inp.shape, inp.strides)
-            .get_strided_index(inp.shape.first_idx_in_slice(slice));
-        let view = &inp.data[start_idx..];
+        slice_fwd(self, inp, slice)
+    }
 
-        while let Some((inp_i, o)) = inp_idx.next().zip(out_iter.next()) {
-            *o = view[inp_i];
-        }
+    fn backward<Src: Shape + SliceShape<Slice>, Slice>(
+        &self,
+        inp: &Tensor<Src, E, Self>,
+        grad_inp: &mut Self::Vec,
+        grad_out: &Self::Vec,
+        slice: &Slice,
+    ) -> Result<(), Error> {
+        // the same elements can be sliced more than once (or be broadcasted), so accumulate
+        slice_bwd(inp, grad_inp, grad_out, slice, |g, o| *g += o)
+    }
+}
 
-        Ok(out)
+impl SliceKernel<bool> for Cpu {
+    fn forward<Src: Shape + SliceShape<Slice>, Slice>(
+        &self,
+        inp: &Tensor<Src, bool, Self>,
+        slice: &Slice,
+    ) -> Result<Tensor<Src::Sliced, bool, Self>, Error> {
+        slice_fwd(self, inp, slice)
     }
 
     fn backward<Src: Shape + SliceShape<Slice>, Slice>(
         &self,
-        inp: &Tensor<Src, E, Self>,
+        inp: &Tensor<Src, bool, Self>,
         grad_inp: &mut Self::Vec,
         grad_out: &Self::Vec,
         slice: &Slice,
     ) -> Result<(), Error> {
-        let dst = inp.shape.slice(slice).unwrap();
+        slice_bwd(inp, grad_inp, grad_out, slice, |g, o| *g |= o)
+    }
+}
 
-        let mut inp_idx = NdIndex::new(dst, inp.strides);
-        let mut out_iter = grad_out.iter();
+fn slice_fwd<Src: Shape + SliceShape<Slice>, Slice, E: Unit>(
+    dev: &Cpu,
+    inp: &Tensor<Src, E, Cpu>,
+    slice: &Slice,
+) -> Result<Tensor<Src::Sliced, E, Cpu>, Error> {
+    let dst = inp.shape.slice(slice).unwrap();
+    let mut out = dev.try_zeros_like(&dst)?;
 
-        let start_idx = NdIndex::new(inp.shape, inp.strides)
-            .get_strided_index(inp.shape.first_idx_in_slice(slice));
-        let view = &mut grad_inp[start_idx..];
+    let mut inp_idx = NdIndex::new(dst, inp.strides);
+    let mut out_iter = out.iter_mut();
 
-        while let Some((inp_i, o)) = inp_idx.next().zip(out_iter.next()) {
-            view[inp_i] = *o;
-        }
+    let start_idx =
+        NdIndex::new(inp.shape, inp.strides).get_strided_index(inp.shape.first_idx_in_slice(slice));
+    let view = &inp.data[start_idx..];
 
-        Ok(())
+    while let Some((inp_i, o)) = inp_idx.next().zip(out_iter.next()) {
+        *o = view[inp_i];
     }
+
+    Ok(out)
+}
+
+fn slice_bwd<Src: Shape + SliceShape<Slice>, Slice, E: Unit>(
+    inp: &Tensor<Src, E, Cpu>,
+    grad_inp: &mut [E],
+    grad_out: &[E],
+    slice: &Slice,
+    accumulate: impl Fn(&mut E, E),
+) -> Result<(), Error> {
+    let dst = inp.shape.slice(slice).unwrap();
+
+    let mut inp_idx = NdIndex::new(dst, inp.strides);
+    let mut out_iter = grad_out.iter();
+
+    let start_idx =
+        NdIndex::new(inp.shape, inp.strides).get_strided_index(inp.shape.first_idx_in_slice(slice));
+    let view = &mut grad_inp[start_idx..];
+
+    while let Some((inp_i, o)) = inp_idx.next().zip(out_iter.next()) {
+        accumulate(&mut view[inp_i], *o);
+    }
+
+    Ok(())
 }
@@ -68,6 +68,13 @@ impl<S: Shape, E: Unit, D: SliceKernel<E>, T: Tape<E, D>> Tensor<S, E, D, T> {
         let out = inp.device.forward(&inp, &slice)?;
         let inp_ghost = inp.ghost();
         let out_ghost = out.ghost();
+        tape.add_graph_node(&out.data, || {
+            GraphNode::new(
+                "slice",
+                std::vec![GraphTensor::of(&inp)],
+                GraphTensor::of(&out),
+            )
+        })?;
         tape.add_backward_op(move |grads| {
             grads.try_alloc_for(&inp_ghost)?;
             grads.try_alloc_for(&out_ghost)?;
@@ -191,4 +198,14 @@ mod tests {
             [[0.; 4], [0.; 4], [0., 0., 22., 24.], [0., 0., 30., 32.]]
         );
     }
+
+    #[test]
+    fn test_slice_backward_accumulates() {
+        let dev = TestDevice::default();
+        let a = dev.tensor([1., 2., 3., 4.]).to_dtype::<TestDtype>();
+        let b = a.leaky_trace().slice((..3,)).sum();
+        let c = a.leaky_trace().slice((1..,)).square().sum();
+        let g = (b + c).backward();
+        assert_close_to_literal!(g.get(&a), [1., 5., 7., 8.]);
+    }
 }
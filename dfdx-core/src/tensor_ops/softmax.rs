@@ -78,9 +78,16 @@ impl<S: Shape, E: Dtype, D: Device<E>, T: Tape<E, D>> Tensor<S, E, D, T> {
             t.id = keep_id;
             t
         };
-        let t_exp = t.put_tape(tape).try_exp()?;
-        let t_expsum = t_exp.retaped::<T>().try_sum::<_, Ax>()?;
-        t_exp.try_div(t_expsum.try_broadcast_like(&shape)?)
+        let (t_exp, tape) = t.put_tape(tape).try_exp()?.split_tape();
+        // keep the tape flowing through the sum so forward mode tapes see `t_exp`
+        let (t_expsum, tape) = t_exp
+            .clone()
+            .put_tape(tape)
+            .try_sum::<_, Ax>()?
+            .split_tape();
+        t_exp
+            .put_tape(tape)
+            .try_div(t_expsum.try_broadcast_like(&shape)?)
     }
 }
 
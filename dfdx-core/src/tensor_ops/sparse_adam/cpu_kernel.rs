@@ -0,0 +1,85 @@
+use std::boxed::Box;
+
+use super::{SparseAdamConfig, SparseAdamKernel};
+use crate::{
+    dtypes::{Dtype, NotMixedPrecision},
+    tensor::{Cpu, Error},
+};
+
+/// The indices of the elements to update.
+fn indices(
+    rows: Option<&[usize]>,
+    row_len: usize,
+    numel: usize,
+) -> Box<dyn Iterator<Item = usize> + '_> {
+    match rows {
+        Some(rows) => Box::new(
+            rows.iter()
+                .flat_map(move |&r| r * row_len..(r + 1) * row_len),
+        ),
+        None => Box::new(0..numel),
+    }
+}
+
+#[cfg(feature = "f16")]
+impl SparseAdamKernel<crate::dtypes::AMP<crate::dtypes::f16>> for Cpu {
+    fn sparse_adam_kernel(
+        &self,
+        t: i32,
+        cfg: &SparseAdamConfig,
+        param: &mut Self::Vec,
+        moment1: &mut Self::Vec,
+        moment2: &mut Self::Vec,
+        grad: &Self::Vec,
+        rows: Option<&[usize]>,
+        row_len: usize,
+    ) -> Result<(), Error> {
+        let betas = cfg.betas.map(|x| x as f32);
+        let eps = cfg.eps as f32;
+        let lr = cfg.lr as f32;
+
+        for i in indices(rows, row_len, param.len()) {
+            let g = grad[i].0.to_f32();
+            let m = moment1[i].0.to_f32() * betas[0] + g * (1.0 - betas[0]);
+            let v = moment2[i].0.to_f32() * betas[1] + g.powi(2) * (1.0 - betas[1]);
+            let m_hat = m * (1.0 - betas[0].powi(t)).recip();
+            let v_hat = v * (1.0 - betas[1].powi(t)).recip();
+            let p = param[i].0.to_f32() - lr * m_hat / (v_hat.sqrt() + eps);
+
+            param[i].0 = crate::dtypes::f16::from_f32(p);
+            moment1[i].0 = crate::dtypes::f16::from_f32(m);
+            moment2[i].0 = crate::dtypes::f16::from_f32(v);
+        }
+        Ok(())
+    }
+}
+
+impl<E: num_traits::Float + Dtype + NotMixedPrecision> SparseAdamKernel<E> for Cpu {
+    fn sparse_adam_kernel(
+        &self,
+        t: i32,
+        cfg: &SparseAdamConfig,
+        param: &mut Self::Vec,
+        moment1: &mut Self::Vec,
+        moment2: &mut Self::Vec,
+        grad: &Self::Vec,
+        rows: Option<&[usize]>,
+        row_len: usize,
+    ) -> Result<(), Error> {
+        let betas = cfg.betas.map(E::from_f64).map(Option::unwrap);
+        let eps = E::from_f64(cfg.eps).unwrap();
+        let lr = E::from_f64(cfg.lr).unwrap();
+
+        for i in indices(rows, row_len, param.len()) {
+            let g = grad[i];
+            let m = &mut moment1[i];
+            let v = &mut moment2[i];
+            *m = *m * betas[0] + g * (E::one() - betas[0]);
+            *v = *v * betas[1] + g.powi(2) * (E::one() - betas[1]);
+            let m_hat = *m * (E::one() - betas[0].powi(t)).recip();
+            let v_hat = *v * (E::one() - betas[1].powi(t)).recip();
+            param[i] -= lr * m_hat / (v_hat.sqrt() + eps);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,84 @@
+mod cpu_kernel;
+
+use crate::{
+    shapes::{Dtype, Shape},
+    tensor::{Error, Storage, Tensor},
+};
+
+/// Configuration of hyperparameters for SparseAdam.
+///
+/// Changing all default parameters:
+/// ```rust
+/// # use dfdx_core::prelude::*;
+/// SparseAdamConfig {
+///     lr: 1e-2,
+///     betas: [0.1, 0.2],
+///     eps: 1e-6,
+/// };
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct SparseAdamConfig {
+    /// Learning rate. Defaults to `1e-3`.
+    pub lr: f64,
+
+    /// Betas from Adam paper. Defaults to `[0.9, 0.999]`.
+    pub betas: [f64; 2],
+
+    /// Epsilon for numerical stability. Defaults to `1e-8`.
+    pub eps: f64,
+}
+
+impl Default for SparseAdamConfig {
+    fn default() -> Self {
+        Self {
+            lr: 1e-3,
+            betas: [0.9, 0.999],
+            eps: 1e-8,
+        }
+    }
+}
+
+/// Only implemented for [crate::tensor::Cpu].
+pub trait SparseAdamKernel<E: Dtype>: Storage<E> {
+    /// Updates `rows` (each of `row_len` elements), or every element if `rows` is `None`.
+    #[allow(clippy::too_many_arguments)]
+    fn sparse_adam_kernel(
+        &self,
+        t: i32,
+        cfg: &SparseAdamConfig,
+        param: &mut Self::Vec,
+        moment1: &mut Self::Vec,
+        moment2: &mut Self::Vec,
+        grad: &Self::Vec,
+        rows: Option<&[usize]>,
+        row_len: usize,
+    ) -> Result<(), Error>;
+}
+
+impl SparseAdamConfig {
+    /// Update a single tensor using Adam, but only the `rows` (indices into the first dimension
+    /// of `param`) that have a gradient. The moments of the other rows are left as is.
+    /// If `rows` is `None`, the whole tensor is updated.
+    pub fn try_update<S: Shape, E: Dtype, D: SparseAdamKernel<E>>(
+        &self,
+        t: i32,
+        param: &mut Tensor<S, E, D>,
+        moment1: &mut D::Vec,
+        moment2: &mut D::Vec,
+        grad: &D::Vec,
+        rows: Option<&[usize]>,
+    ) -> Result<(), crate::tensor::Error> {
+        let num_rows = param.shape.concrete().into_iter().next().unwrap_or(1);
+        let row_len = param.shape.num_elements() / num_rows.max(1);
+        param.device.sparse_adam_kernel(
+            t,
+            self,
+            std::sync::Arc::make_mut(&mut param.data),
+            moment1,
+            moment2,
+            grad,
+            rows,
+            row_len,
+        )
+    }
+}
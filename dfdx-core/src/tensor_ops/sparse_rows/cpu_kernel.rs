@@ -0,0 +1,29 @@
+use super::SparseRowsKernel;
+use crate::{
+    dtypes::Dtype,
+    tensor::{Cpu, Error},
+};
+
+impl<E: Dtype> SparseRowsKernel<E> for Cpu {
+    fn zero_rows(&self, dst: &mut Self::Vec, rows: &[usize], row_len: usize) -> Result<(), Error> {
+        for &r in rows {
+            dst[r * row_len..(r + 1) * row_len].fill(E::default());
+        }
+        Ok(())
+    }
+
+    fn add_rows(
+        &self,
+        dst: &mut Self::Vec,
+        src: &Self::Vec,
+        rows: &[usize],
+        row_len: usize,
+    ) -> Result<(), Error> {
+        for &r in rows {
+            for i in r * row_len..(r + 1) * row_len {
+                dst[i] += src[i];
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,87 @@
+use super::SparseRowsKernel;
+use crate::{
+    dtypes::*,
+    tensor::{launch_cfg, Cuda, Error},
+};
+
+use cudarc::driver::{CudaSlice, LaunchAsync};
+
+const PTX_SRC: &str = include_str!(concat!(env!("OUT_DIR"), "/sparse_rows.ptx"));
+
+trait HasCudaKernel<E> {
+    const MOD: &'static str;
+    const FNS: &'static [&'static str];
+}
+#[cfg(feature = "f16")]
+impl HasCudaKernel<AMP<f16>> for Cuda {
+    const MOD: &'static str = "sparse_rows_f16";
+    const FNS: &'static [&'static str] = &["zero_rows_f16", "add_rows_f16"];
+}
+#[cfg(feature = "f16")]
+impl HasCudaKernel<f16> for Cuda {
+    const MOD: &'static str = "sparse_rows_f16";
+    const FNS: &'static [&'static str] = &["zero_rows_f16", "add_rows_f16"];
+}
+impl HasCudaKernel<f32> for Cuda {
+    const MOD: &'static str = "sparse_rows_f32";
+    const FNS: &'static [&'static str] = &["zero_rows_f32", "add_rows_f32"];
+}
+impl HasCudaKernel<f64> for Cuda {
+    const MOD: &'static str = "sparse_rows_f64";
+    const FNS: &'static [&'static str] = &["zero_rows_f64", "add_rows_f64"];
+}
+
+impl Cuda {
+    /// Loads the kernels & copies `rows` to the device. Returns `None` if there is nothing to do.
+    fn sparse_rows_setup<E>(
+        &self,
+        rows: &[usize],
+        row_len: usize,
+    ) -> Result<Option<CudaSlice<usize>>, Error>
+    where
+        Self: HasCudaKernel<E>,
+    {
+        if rows.is_empty() || row_len == 0 {
+            return Ok(None);
+        }
+        if !self.dev.has_func(Self::MOD, Self::FNS[0]) {
+            self.dev.load_ptx(PTX_SRC.into(), Self::MOD, Self::FNS)?;
+        }
+        Ok(Some(self.dev.htod_copy(rows.to_vec())?))
+    }
+}
+
+impl<E: Dtype> SparseRowsKernel<E> for Cuda
+where
+    Self: HasCudaKernel<E>,
+{
+    fn zero_rows(&self, dst: &mut Self::Vec, rows: &[usize], row_len: usize) -> Result<(), Error> {
+        let Some(dev_rows) = self.sparse_rows_setup::<E>(rows, row_len)? else {
+            return Ok(());
+        };
+        let numel = rows.len() * row_len;
+        let fwd_fn = self.dev.get_func(Self::MOD, Self::FNS[0]).unwrap();
+        let cfg = launch_cfg::<128>(numel as u32);
+        let params = (numel, dst, &dev_rows, row_len, E::default());
+        unsafe { fwd_fn.launch(cfg, params) }?;
+        Ok(())
+    }
+
+    fn add_rows(
+        &self,
+        dst: &mut Self::Vec,
+        src: &Self::Vec,
+        rows: &[usize],
+        row_len: usize,
+    ) -> Result<(), Error> {
+        let Some(dev_rows) = self.sparse_rows_setup::<E>(rows, row_len)? else {
+            return Ok(());
+        };
+        let numel = rows.len() * row_len;
+        let fwd_fn = self.dev.get_func(Self::MOD, Self::FNS[1]).unwrap();
+        let cfg = launch_cfg::<128>(numel as u32);
+        let params = (numel, dst, src, &dev_rows, row_len);
+        unsafe { fwd_fn.launch(cfg, params) }?;
+        Ok(())
+    }
+}
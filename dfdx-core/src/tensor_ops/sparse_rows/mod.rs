@@ -0,0 +1,89 @@
+mod cpu_kernel;
+#[cfg(feature = "cuda")]
+mod cuda_kernel;
+#[cfg(feature = "webgpu")]
+mod webgpu_kernel;
+
+use crate::{dtypes::Dtype, tensor::Error, tensor::Storage};
+
+/// Operations on some of the rows (indices into the first dimension) of a gradient, so that
+/// gradients with sparse rows (see [crate::tensor::Gradients::add_sparse_rows()]) don't need
+/// to touch the whole buffer. Each row is `row_len` contiguous elements.
+pub trait SparseRowsKernel<E: Dtype>: Storage<E> {
+    /// Sets `rows` of `dst` to zero.
+    fn zero_rows(&self, dst: &mut Self::Vec, rows: &[usize], row_len: usize) -> Result<(), Error>;
+
+    /// Adds `rows` of `src` to the same rows of `dst`.
+    fn add_rows(
+        &self,
+        dst: &mut Self::Vec,
+        src: &Self::Vec,
+        rows: &[usize],
+        row_len: usize,
+    ) -> Result<(), Error>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{shapes::*, tensor::*, tensor_ops::*, tests::*};
+
+    #[test]
+    fn test_zero_and_add_rows() {
+        let dev: TestDevice = Default::default();
+        let mut a = dev
+            .tensor([[1.0, 2.0], [3.0, 4.0], [5.0, 6.0]])
+            .to_dtype::<TestDtype>();
+        let b = dev.tensor([[1.0; 2]; 3]).to_dtype::<TestDtype>();
+        let data = std::sync::Arc::make_mut(&mut a.data);
+        dev.zero_rows(data, &[0, 2], 2).unwrap();
+        dev.add_rows(data, b.data.as_ref(), &[1, 2], 2).unwrap();
+        assert_close_to_literal!(a, [[0.0, 0.0], [4.0, 5.0], [1.0, 1.0]]);
+    }
+
+    fn rows(
+        grads: &Gradients<TestDtype, TestDevice>,
+        t: &Tensor<Rank2<3, 2>, TestDtype, TestDevice>,
+    ) -> Vec<usize> {
+        grads.sparse_rows(t).unwrap().iter().copied().collect()
+    }
+
+    #[test]
+    fn test_accumulate_sparse_rows() {
+        let dev: TestDevice = Default::default();
+        let t: Tensor<Rank2<3, 2>, TestDtype, _> = dev.zeros();
+        let a_data = dev
+            .tensor([[1.0; 2], [0.0; 2], [2.0; 2]])
+            .to_dtype::<TestDtype>();
+        let mut a = Gradients::leaky();
+        *a.get_or_alloc_mut(&t).unwrap() = a_data.data.as_ref().clone();
+        a.add_sparse_rows(&t, [0, 2]);
+        let b_data = dev
+            .tensor([[0.0; 2], [0.0; 2], [1.0; 2]])
+            .to_dtype::<TestDtype>();
+        let mut b = Gradients::leaky();
+        *b.get_or_alloc_mut(&t).unwrap() = b_data.data.as_ref().clone();
+        b.add_sparse_rows(&t, [2]);
+
+        a.try_accumulate(&dev, b).unwrap();
+        assert_eq!(rows(&a, &t), [0, 2]);
+        assert_close_to_literal!(a.get(&t), [[1.0; 2], [0.0; 2], [3.0; 2]]);
+
+        a.try_zero_for(&t).unwrap();
+        assert!(a.sparse_rows(&t).is_none());
+        assert_close_to_literal!(a.get(&t), [[0.0; 2]; 3]);
+    }
+
+    #[test]
+    fn test_merge_unions_sparse_rows() {
+        let dev: TestDevice = Default::default();
+        let t: Tensor<Rank2<3, 2>, TestDtype, _> = dev.zeros();
+        let mut a = Gradients::leaky();
+        a.add_sparse_rows(&t, [0, 1]);
+        let mut b = Gradients::leaky();
+        b.add_sparse_rows(&t, [2]);
+        let u: Tensor<Rank2<3, 2>, TestDtype, _> = dev.zeros();
+        let grads = (t.clone().traced(a).sum() + u.traced(b).sum()).backward();
+        assert_eq!(rows(&grads, &t), [0, 1, 2]);
+    }
+}
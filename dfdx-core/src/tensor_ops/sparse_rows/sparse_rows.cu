@@ -0,0 +1,28 @@
+#include "cuda_fp16.h"
+
+template<typename T>
+__device__ void zero_rows(const size_t n, T* dst, const size_t* rows, const size_t row_len, const T zero) {
+    for (unsigned int i = blockIdx.x * blockDim.x + threadIdx.x; i < n; i += blockDim.x * gridDim.x) {
+        dst[rows[i / row_len] * row_len + i % row_len] = zero;
+    }
+}
+
+template<typename T>
+__device__ void add_rows(const size_t n, T* dst, const T* src, const size_t* rows, const size_t row_len) {
+    for (unsigned int i = blockIdx.x * blockDim.x + threadIdx.x; i < n; i += blockDim.x * gridDim.x) {
+        const size_t j = rows[i / row_len] * row_len + i % row_len;
+        dst[j] = dst[j] + src[j];
+    }
+}
+
+#define SPARSE_ROWS(TYPENAME, ZERO_FN, ADD_FN) \
+extern "C" __global__ void ZERO_FN(const size_t n, TYPENAME* dst, const size_t* rows, const size_t row_len, const TYPENAME zero) { \
+    zero_rows(n, dst, rows, row_len, zero); \
+} \
+extern "C" __global__ void ADD_FN(const size_t n, TYPENAME* dst, const TYPENAME* src, const size_t* rows, const size_t row_len) { \
+    add_rows(n, dst, src, rows, row_len); \
+}
+
+SPARSE_ROWS(__half, zero_rows_f16, add_rows_f16);
+SPARSE_ROWS(float, zero_rows_f32, add_rows_f32);
+SPARSE_ROWS(double, zero_rows_f64, add_rows_f64);
@@ -0,0 +1,44 @@
+use super::SparseRowsKernel;
+use crate::{
+    dtypes::Dtype,
+    tensor::{Error, Webgpu},
+};
+
+// There are no compute kernels for this yet, so the rows are updated on the host.
+impl<E: Dtype> SparseRowsKernel<E> for Webgpu {
+    fn zero_rows(&self, dst: &mut Self::Vec, rows: &[usize], row_len: usize) -> Result<(), Error> {
+        if rows.is_empty() {
+            return Ok(());
+        }
+        let mut buf = vec![E::default(); dst.data.len::<E>()];
+        dst.data.copy_to_host(&self.dev, &self.queue, &mut buf);
+        for &r in rows {
+            buf[r * row_len..(r + 1) * row_len].fill(E::default());
+        }
+        dst.data.copy_to_device(&self.dev, &self.queue, &buf);
+        Ok(())
+    }
+
+    fn add_rows(
+        &self,
+        dst: &mut Self::Vec,
+        src: &Self::Vec,
+        rows: &[usize],
+        row_len: usize,
+    ) -> Result<(), Error> {
+        if rows.is_empty() {
+            return Ok(());
+        }
+        let mut dst_buf = vec![E::default(); dst.data.len::<E>()];
+        dst.data.copy_to_host(&self.dev, &self.queue, &mut dst_buf);
+        let mut src_buf = vec![E::default(); src.data.len::<E>()];
+        src.data.copy_to_host(&self.dev, &self.queue, &mut src_buf);
+        for &r in rows {
+            for i in r * row_len..(r + 1) * row_len {
+                dst_buf[i] += src_buf[i];
+            }
+        }
+        dst.data.copy_to_device(&self.dev, &self.queue, &dst_buf);
+        Ok(())
+    }
+}
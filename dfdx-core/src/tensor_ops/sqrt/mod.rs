@@ -6,7 +6,7 @@ mod cuda_kernel;
 #[cfg(feature = "webgpu")]
 mod webgpu_kernel;
 
-use super::ops::{try_unary_op, UnaryKernel};
+use super::ops::{try_unary_op_higher_order, UnaryKernel};
 use crate::{shapes::*, tensor::*};
 
 #[repr(C)]
@@ -37,7 +37,9 @@ impl<S: Shape, E: Dtype, D: UnaryKernel<SqrtKernelOp, E>, T: Tape<E, D>> Tensor<
     }
     /// See [sqrt]
     pub fn try_sqrt(self) -> Result<Self, crate::tensor::Error> {
-        try_unary_op(SqrtKernelOp, self)
+        try_unary_op_higher_order(SqrtKernelOp, self, |ops, g, _, y| {
+            ops.try_scale(ops.try_div(g, y)?, 0.5)
+        })
     }
 }
 
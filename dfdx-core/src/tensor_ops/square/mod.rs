@@ -6,7 +6,7 @@ mod cuda_kernel;
 #[cfg(feature = "webgpu")]
 mod webgpu_kernel;
 
-use super::ops::{try_unary_op, UnaryKernel};
+use super::ops::{try_unary_op_higher_order, UnaryKernel};
 use crate::{shapes::*, tensor::*};
 
 #[repr(C)]
@@ -37,7 +37,9 @@ impl<S: Shape, E: Dtype, D: UnaryKernel<SquareKernelOp, E>, T: Tape<E, D>> Tenso
     }
     /// See [square]
     pub fn try_square(self) -> Result<Self, crate::tensor::Error> {
-        try_unary_op(SquareKernelOp, self)
+        try_unary_op_higher_order(SquareKernelOp, self, |ops, g, x, _| {
+            ops.try_scale(ops.try_mul(g, x)?, 2.0)
+        })
     }
 }
 
@@ -152,6 +152,13 @@ where
 
     let inp_ghosts: Vec<_> = tensors.iter().map(|t| t.ghost()).collect();
     let out_ghost = out.ghost();
+    tape.add_graph_node(&out.data, || {
+        GraphNode::new(
+            "stack",
+            tensors.iter().map(GraphTensor::of).collect(),
+            GraphTensor::of(&out),
+        )
+    })?;
     tape.add_backward_op(move |grads| {
         for t in inp_ghosts.iter() {
             grads.try_alloc_for(t)?;
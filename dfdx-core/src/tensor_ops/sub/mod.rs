@@ -62,7 +62,9 @@ where
 {
     type Output = Self;
     fn try_sub(self, rhs: Tensor<S, E, D, R>) -> Result<Self, Error> {
-        try_binary_op(BinarySubKernelOp, self, rhs)
+        try_binary_op_higher_order(BinarySubKernelOp, self, rhs, |ops, g, _, _| {
+            Ok((g.retaped(), ops.try_scale(g, -1.0)?))
+        })
     }
 }
 
@@ -74,7 +76,7 @@ where
     fn try_sub(self, rhs: Rhs) -> Result<Self, Error> {
         let rhs: f64 = rhs.into();
         let scalar = E::from_f64(rhs).unwrap();
-        try_unary_op(ScalarSubKernelOp { scalar }, self)
+        try_unary_op_higher_order(ScalarSubKernelOp { scalar }, self, |_, g, _, _| Ok(g))
     }
 }
 
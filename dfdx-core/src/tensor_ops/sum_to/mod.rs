@@ -68,14 +68,51 @@ impl<S: Shape, E: Dtype, D: SumKernel<E>, T: Tape<E, D>> SumTo for Tensor<S, E,
         let dst: Dst = self.shape().reduced();
         let (inp, mut tape) = self.split_tape();
         let out = inp.device.forward(dst, &inp)?;
+        if tape.is_forward_mode() {
+            tape.add_forward_op(|tangents, _| {
+                if let Some(tangent_inp) = tangents.get_checked(&inp) {
+                    let tangent_out = inp.device.forward(dst, &tangent_inp)?;
+                    tangents.insert(&out, tangent_out);
+                }
+                Ok(())
+            })?;
+            return Ok(out.put_tape(tape));
+        }
         let inp_ghost = inp.ghost();
         let out_ghost = out.ghost();
+        let traced_inp_ghost = inp_ghost.clone();
+        let traced_out_ghost = out_ghost.clone();
+        tape.add_graph_node(&out.data, || {
+            GraphNode::new(
+                "sum_to",
+                std::vec![GraphTensor::of(&inp)],
+                GraphTensor::of(&out),
+            )
+        })?;
         tape.add_backward_op(move |grads| {
             grads.try_alloc_for(&inp_ghost)?;
             grads.try_alloc_for(&out_ghost)?;
             let (grad_inp, grad_out) = grads.mut_and_ref(&inp_ghost, &out_ghost);
             inp.device.backward(dst, &inp_ghost, grad_inp, grad_out)
         });
+        tape.add_traced_backward_op(move |grads| {
+            if let Some(grad_out) = grads.get_buffer(&traced_out_ghost) {
+                // broadcast the gradient back along the reduced axes
+                let mut strides: S::Concrete = Default::default();
+                let mut j = 0;
+                for i in 0..S::NUM_DIMS {
+                    if !Ax::as_array().into_iter().any(|x| x == i as isize) {
+                        strides[i] = traced_out_ghost.strides[j];
+                        j += 1;
+                    }
+                }
+                let ops = grads.ops;
+                let grad_inp = ops.try_broadcast(grad_out, &traced_inp_ghost.shape, strides)?;
+                let grad_inp = ops.try_unflatten(grad_inp, &traced_inp_ghost)?;
+                grads.accumulate_buffer(&traced_inp_ghost, grad_inp)?;
+            }
+            Ok(())
+        });
         Ok(out.put_tape(tape))
     }
 }
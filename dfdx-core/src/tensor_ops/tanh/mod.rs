@@ -6,7 +6,7 @@ mod cuda_kernel;
 #[cfg(feature = "webgpu")]
 mod webgpu_kernel;
 
-use super::ops::{try_unary_op, UnaryKernel};
+use super::ops::{try_unary_op_higher_order, UnaryKernel};
 use crate::{shapes::*, tensor::*};
 
 #[repr(C)]
@@ -37,7 +37,12 @@ impl<S: Shape, E: Dtype, D: UnaryKernel<TanhKernelOp, E>, T: Tape<E, D>> Tensor<
     }
     /// See [tanh]
     pub fn try_tanh(self) -> Result<Self, crate::tensor::Error> {
-        try_unary_op(TanhKernelOp, self)
+        try_unary_op_higher_order(TanhKernelOp, self, |ops, g, _, y| {
+            // g * (1 - y) * (1 + y)
+            let one_minus_y = ops.try_shift(ops.try_scale(y.retaped(), -1.0)?, 1.0)?;
+            let g = ops.try_mul(g, one_minus_y)?;
+            ops.try_mul(g, ops.try_shift(y, 1.0)?)
+        })
     }
 }
 
@@ -5,7 +5,9 @@ mod cuda_kernel;
 
 use crate::{
     shapes::*,
-    tensor::{Error, PutTape, SplitTape, Storage, Tape, Tensor, ZerosTensor},
+    tensor::{
+        Error, GraphNode, GraphTensor, PutTape, SplitTape, Storage, Tape, Tensor, ZerosTensor,
+    },
 };
 
 #[repr(C)]
@@ -187,6 +189,13 @@ impl<
         let inp_ghost = inp.ghost();
         let out_ghost = out.ghost();
         let out_clone = out.clone();
+        tape.add_graph_node(&out.data, || {
+            GraphNode::new(
+                "upscale2d",
+                std::vec![GraphTensor::of(&inp)],
+                GraphTensor::of(&out),
+            )
+        })?;
         tape.add_backward_op(move |grads| {
             grads.try_alloc_for(&inp_ghost)?;
             grads.try_alloc_for(&out_ghost)?;
@@ -233,6 +242,13 @@ impl<
         let inp_ghost = inp.ghost();
         let out_ghost = out.ghost();
         let out_clone = out.clone();
+        tape.add_graph_node(&out.data, || {
+            GraphNode::new(
+                "upscale2d",
+                std::vec![GraphTensor::of(&inp)],
+                GraphTensor::of(&out),
+            )
+        })?;
         tape.add_backward_op(move |grads| {
             grads.try_alloc_for(&inp_ghost)?;
             grads.try_alloc_for(&out_ghost)?;
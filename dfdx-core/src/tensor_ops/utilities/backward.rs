@@ -1,5 +1,8 @@
-use crate::shapes::Rank0;
+use super::device::Device;
+use crate::shapes::{Dtype, Rank0, Shape};
 use crate::tensor::*;
+use crate::tensor_ops::{axpy::AxpyKernel, reshape_to::ReshapeKernel, TryAdd, TryDiv, TryMul};
+use std::{boxed::Box, sync::Arc};
 
 /// Runs backprop algorithm with all operations contained in the tape that `t` has.
 ///
@@ -46,3 +49,468 @@ impl<E: 'static + Clone, D: OneFillStorage<E>> Backward<E, D>
         Ok(grads)
     }
 }
+
+/// Runs a higher order backprop algorithm with all operations contained in the tape that `t` has.
+/// The tape must have been started with [Trace::traced_higher_order].
+///
+/// This differs from [Backward] in that the gradients themselves are computed with recorded tensor
+/// operations, which means that they can be differentiated again. This enables things
+/// like gradient penalties and hessian vector products:
+///
+/// ```rust
+/// # use dfdx_core::prelude::*;
+/// # let dev: Cpu = Default::default();
+/// let x = dev.tensor([1.0f32, 2.0, 3.0]);
+/// let y = x.trace_higher_order(Gradients::leaky()).exp().sum();
+/// let grads = y.backward_traced();
+/// // dy/dx = exp(x)
+/// let dx = grads.get(&x);
+/// assert_eq!(dx.array(), x.clone().exp().array());
+/// // d(sum(dy/dx))/dx = exp(x)
+/// let dx2 = grads.backward(dx.sum());
+/// assert_eq!(dx2.get(&x).array(), x.exp().array());
+/// ```
+///
+/// Only some operations support higher order gradients, if one that doesn't
+/// was recorded, [Error::HigherOrderUnsupported] is returned.
+pub trait BackwardTraced<E, D: Storage<E>>: Sized {
+    /// Runs higher order backprop
+    fn backward_traced(self) -> TracedGradients<E, D> {
+        self.try_backward_traced().unwrap()
+    }
+    /// Fallible version of [BackwardTraced::backward_traced]
+    fn try_backward_traced(self) -> Result<TracedGradients<E, D>, Error>;
+}
+
+impl<E: Dtype, D: OneFillStorage<E>> BackwardTraced<E, D> for Tensor<Rank0, E, D, OwnedTape<E, D>> {
+    fn try_backward_traced(self) -> Result<TracedGradients<E, D>, Error> {
+        let (t, mut tape) = self.split_tape();
+        let Some(ops) = tape.higher_order else {
+            return Err(Error::HigherOrderUnsupported);
+        };
+        let mut seed = t.device.try_alloc_len(1)?;
+        t.device.try_fill_with_ones(&mut seed)?;
+        let seed: Tensor<(usize,), E, D> = Tensor {
+            id: unique_id(),
+            data: Arc::new(seed),
+            shape: (1,),
+            strides: [1],
+            device: t.device.clone(),
+            tape: NoneTape,
+        };
+        let mut grads = TracedGradients {
+            gradient_by_id: Default::default(),
+            tape: Default::default(),
+            ops,
+        };
+        grads.gradient_by_id.insert(t.id, Box::new(seed));
+        tape.execute_traced(grads)
+    }
+}
+
+/// A contiguous tensor, or the whole data buffer of a strided tensor, that records
+/// first order operations.
+pub(crate) type Flat<E, D> = Tensor<(usize,), E, D, OwnedTape<E, D>>;
+
+/// A strided view into a data buffer, with its shape padded to 6 dimensions.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct View {
+    shape: (usize, usize, usize, usize, usize, usize),
+    strides: [usize; 6],
+}
+
+impl View {
+    fn of<S: Shape>(shape: &S, strides: S::Concrete) -> Self {
+        let mut dims = [1; 6];
+        let mut view_strides = [0; 6];
+        let offset = 6 - S::NUM_DIMS;
+        for (i, (dim, stride)) in shape.concrete().into_iter().zip(strides).enumerate() {
+            dims[offset + i] = dim;
+            view_strides[offset + i] = stride;
+        }
+        Self {
+            shape: (dims[0], dims[1], dims[2], dims[3], dims[4], dims[5]),
+            strides: view_strides,
+        }
+    }
+
+    fn tensor<E, D: Storage<E>>(&self, data: Arc<D::Vec>, device: D) -> Tensor<Rank6Dyn, E, D> {
+        Tensor {
+            id: unique_id(),
+            data,
+            shape: self.shape,
+            strides: self.strides,
+            device,
+            tape: NoneTape,
+        }
+    }
+}
+
+type Rank6Dyn = (usize, usize, usize, usize, usize, usize);
+
+/// The tensor operations that higher order gradients (and forward mode tangents) are
+/// computed with.
+///
+/// These are captured when a tape is created with [Trace::traced_higher_order] or
+/// [Tensor::dual()], where the device is known to support all of them, so that the
+/// bounds of first order operations don't need to include them.
+pub struct HigherOrderOps<E, D: Storage<E>> {
+    add: BinaryFn<E, D>,
+    mul: BinaryFn<E, D>,
+    div: BinaryFn<E, D>,
+    scale: ScalarFn<E, D>,
+    shift: ScalarFn<E, D>,
+    sin: UnaryFn<E, D>,
+    cos: UnaryFn<E, D>,
+    ones: fn(&D, usize) -> Result<D::Vec, Error>,
+    gather: fn(Flat<E, D>, View) -> FlatResult<E, D>,
+    scatter: fn(Flat<E, D>, View, usize) -> FlatResult<E, D>,
+}
+
+type FlatResult<E, D> = Result<Flat<E, D>, Error>;
+type UnaryFn<E, D> = fn(Flat<E, D>) -> FlatResult<E, D>;
+type BinaryFn<E, D> = fn(Flat<E, D>, Flat<E, D>) -> FlatResult<E, D>;
+type ScalarFn<E, D> = fn(Flat<E, D>, f64) -> FlatResult<E, D>;
+
+impl<E, D: Storage<E>> Clone for HigherOrderOps<E, D> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<E, D: Storage<E>> Copy for HigherOrderOps<E, D> {}
+
+impl<E, D: Storage<E>> std::fmt::Debug for HigherOrderOps<E, D> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HigherOrderOps").finish_non_exhaustive()
+    }
+}
+
+impl<E: Dtype, D: Storage<E>> HigherOrderOps<E, D> {
+    pub(crate) fn new() -> Self
+    where
+        D: Device<E>,
+    {
+        Self {
+            add: |lhs, rhs| lhs.try_add(rhs),
+            mul: |lhs, rhs| lhs.try_mul(rhs),
+            div: |lhs, rhs| lhs.try_div(rhs),
+            scale: |t, scalar| t.try_mul(scalar),
+            shift: |t, scalar| t.try_add(scalar),
+            sin: |t| t.try_sin(),
+            cos: |t| t.try_cos(),
+            ones: |dev, len| {
+                let mut buf = Storage::<E>::try_alloc_len(dev, len)?;
+                dev.try_fill_with_ones(&mut buf)?;
+                Ok(buf)
+            },
+            gather: try_gather,
+            scatter: try_scatter,
+        }
+    }
+
+    pub(crate) fn try_add(&self, lhs: Flat<E, D>, rhs: Flat<E, D>) -> Result<Flat<E, D>, Error> {
+        (self.add)(lhs, rhs)
+    }
+
+    pub(crate) fn try_mul(&self, lhs: Flat<E, D>, rhs: Flat<E, D>) -> Result<Flat<E, D>, Error> {
+        (self.mul)(lhs, rhs)
+    }
+
+    pub(crate) fn try_div(&self, lhs: Flat<E, D>, rhs: Flat<E, D>) -> Result<Flat<E, D>, Error> {
+        (self.div)(lhs, rhs)
+    }
+
+    /// Multiplies by a scalar
+    pub(crate) fn try_scale(&self, t: Flat<E, D>, scalar: f64) -> Result<Flat<E, D>, Error> {
+        (self.scale)(t, scalar)
+    }
+
+    /// Adds a scalar
+    pub(crate) fn try_shift(&self, t: Flat<E, D>, scalar: f64) -> Result<Flat<E, D>, Error> {
+        (self.shift)(t, scalar)
+    }
+
+    pub(crate) fn try_sin(&self, t: Flat<E, D>) -> Result<Flat<E, D>, Error> {
+        (self.sin)(t)
+    }
+
+    pub(crate) fn try_cos(&self, t: Flat<E, D>) -> Result<Flat<E, D>, Error> {
+        (self.cos)(t)
+    }
+
+    /// Allocates a buffer of `len` ones.
+    pub(crate) fn try_ones(&self, device: &D, len: usize) -> Result<D::Vec, Error> {
+        (self.ones)(device, len)
+    }
+
+    /// The whole data buffer of `t` as a [Flat] tensor with the same id, so that
+    /// operations on it are linked to `t`.
+    pub(crate) fn buffer<S: Shape, T>(t: &Tensor<S, E, D, T>) -> Flat<E, D> {
+        Tensor {
+            id: t.id,
+            data: t.data.clone(),
+            shape: (t.len(),),
+            strides: [1],
+            device: t.device.clone(),
+            tape: Default::default(),
+        }
+    }
+
+    /// Copies the elements of `t` into a contiguous tensor in row major order,
+    /// which is a no-op if `t` is already contiguous.
+    pub(crate) fn try_flatten<S: Shape>(
+        &self,
+        t: Tensor<S, E, D, OwnedTape<E, D>>,
+    ) -> Result<Flat<E, D>, Error> {
+        let (t, tape) = t.split_tape();
+        let mut buf = Self::buffer(&t);
+        buf.tape = tape;
+        if t.strides == t.shape.strides() && t.len() == t.shape.num_elements() {
+            Ok(buf)
+        } else {
+            (self.gather)(buf, View::of(&t.shape, t.strides))
+        }
+    }
+
+    /// Copies the elements of the buffer `t` through a strided view with `shape` & `strides`
+    /// into a contiguous tensor. The result always has a new id.
+    pub(crate) fn try_broadcast<S: Shape>(
+        &self,
+        t: Flat<E, D>,
+        shape: &S,
+        strides: S::Concrete,
+    ) -> Result<Flat<E, D>, Error> {
+        (self.gather)(t, View::of(shape, strides))
+    }
+
+    /// The inverse of [HigherOrderOps::try_flatten]: sums the contiguous `t` into a buffer
+    /// laid out like the data of `like`, which may have broadcasted or permuted axes.
+    pub(crate) fn try_unflatten<S: Shape>(
+        &self,
+        t: Flat<E, D>,
+        like: &impl Tensorlike<S, E, D>,
+    ) -> Result<Flat<E, D>, Error> {
+        let (shape, strides, len) = (*like.shape(), like.strides(), like.len());
+        if strides == shape.strides() && len == shape.num_elements() {
+            Ok(t)
+        } else {
+            (self.scatter)(t, View::of(&shape, strides), len)
+        }
+    }
+}
+
+/// Copies `view` of the data of `t` into a contiguous tensor.
+fn try_gather<E: Dtype, D: Device<E>>(t: Flat<E, D>, view: View) -> Result<Flat<E, D>, Error> {
+    let (t, mut tape) = t.split_tape();
+    let mut strided = view.tensor(t.data.clone(), t.device.clone());
+    strided.id = t.id;
+    let numel = strided.shape.num_elements();
+    let out = ReshapeKernel::forward(&t.device, &(numel,), &strided)?;
+    let out_ghost = out.ghost();
+    tape.add_graph_node(&out.data, || {
+        GraphNode::new(
+            "gather",
+            std::vec![GraphTensor::of(&t)],
+            GraphTensor::of(&out),
+        )
+    })?;
+    tape.add_backward_op(move |grads| {
+        grads.try_alloc_for(&strided)?;
+        grads.try_alloc_for(&out_ghost)?;
+        let (grad_inp, grad_out) = grads.mut_and_ref(&strided, &out_ghost);
+        ReshapeKernel::backward(&strided.device, &(numel,), &strided, grad_inp, grad_out)
+    });
+    Ok(out.put_tape(tape))
+}
+
+/// Sums the contiguous `t` into a buffer of `len` elements through `view`.
+fn try_scatter<E: Dtype, D: Device<E>>(
+    t: Flat<E, D>,
+    view: View,
+    len: usize,
+) -> Result<Flat<E, D>, Error> {
+    let (t, mut tape) = t.split_tape();
+    let dev = t.device.clone();
+    let numel = t.shape.0;
+    let mut buf = Storage::<E>::try_alloc_len(&dev, len)?;
+    let strided = view.tensor(t.data.clone(), dev.clone());
+    ReshapeKernel::backward(&dev, &(numel,), &strided, &mut buf, &t.data)?;
+    let out: Tensor<(usize,), E, D> = Tensor {
+        id: unique_id(),
+        data: Arc::new(buf),
+        shape: (len,),
+        strides: [1],
+        device: dev.clone(),
+        tape: NoneTape,
+    };
+    let t_ghost = t.ghost();
+    let out_ghost = out.ghost();
+    tape.add_graph_node(&out.data, || {
+        GraphNode::new(
+            "scatter",
+            std::vec![GraphTensor::of(&t)],
+            GraphTensor::of(&out),
+        )
+    })?;
+    tape.add_backward_op(move |grads| {
+        grads.try_alloc_for(&t_ghost)?;
+        grads.try_alloc_for(&out_ghost)?;
+        let (grad_inp, grad_out) = grads.mut_and_ref(&t_ghost, &out_ghost);
+        let grad_out = view.tensor(Arc::new(grad_out.clone()), dev.clone());
+        let gathered = ReshapeKernel::forward(&dev, &(numel,), &grad_out)?;
+        let one = E::from_f64(1.0).unwrap();
+        AxpyKernel::forward(&dev, grad_inp, one, gathered.data.as_ref(), one)
+    });
+    Ok(out.put_tape(tape))
+}
+
+impl<E: Dtype, D: Storage<E>> TracedGradients<E, D> {
+    /// The gradient of `t`, laid out like its data buffer.
+    pub(crate) fn get_buffer<S: Shape>(&self, t: &impl Tensorlike<S, E, D>) -> Option<Flat<E, D>> {
+        let grad: &Tensor<(usize,), E, D> = self.gradient_by_id.get(&t.id())?.downcast_ref()?;
+        Some(grad.clone().put_tape(Default::default()))
+    }
+
+    /// Adds `grad`, which is laid out like the data buffer of `t`, into the gradient
+    /// of `t`, recording the addition.
+    pub(crate) fn accumulate_buffer<S: Shape>(
+        &mut self,
+        t: &impl Tensorlike<S, E, D>,
+        grad: Flat<E, D>,
+    ) -> Result<(), Error> {
+        let grad = match self.gradient_by_id.remove(&t.id()) {
+            Some(prev) => {
+                let prev: Box<Tensor<(usize,), E, D>> = prev.downcast().unwrap();
+                self.ops.try_add(prev.put_tape(Default::default()), grad)?
+            }
+            None => grad,
+        };
+        let (grad, tape) = grad.split_tape();
+        self.tape = std::mem::take(&mut self.tape).merge(tape);
+        self.gradient_by_id.insert(t.id(), Box::new(grad));
+        Ok(())
+    }
+
+    /// Adds `grad` into the gradient of `t`, recording the addition.
+    pub(crate) fn accumulate<S: Shape>(
+        &mut self,
+        t: &impl Tensorlike<S, E, D>,
+        grad: Tensor<S, E, D, OwnedTape<E, D>>,
+    ) -> Result<(), Error> {
+        let grad = self.ops.try_flatten(grad)?;
+        let grad = self.ops.try_unflatten(grad, t)?;
+        self.accumulate_buffer(t, grad)
+    }
+}
+
+impl<E: Dtype, D: OneFillStorage<E>> TracedGradients<E, D> {
+    /// Runs backprop from `t` through both the operations that computed these gradients,
+    /// and the original forward pass. `t` should be computed from tensors returned
+    /// by [TracedGradients::get].
+    pub fn backward(self, t: Tensor<Rank0, E, D, OwnedTape<E, D>>) -> Gradients<E, D> {
+        self.try_backward(t).unwrap()
+    }
+
+    /// Fallible version of [TracedGradients::backward]
+    pub fn try_backward(
+        self,
+        t: Tensor<Rank0, E, D, OwnedTape<E, D>>,
+    ) -> Result<Gradients<E, D>, Error> {
+        let (t, tape) = t.split_tape();
+        t.put_tape(self.tape.merge(tape)).try_backward()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{shapes::*, tensor::*, tensor_ops::*, tests::*};
+
+    #[test]
+    fn test_second_order_unary() {
+        let dev: TestDevice = Default::default();
+        let x = dev.tensor([-1.0, 0.5, 2.0]).to_dtype::<TestDtype>();
+        let y = x.trace_higher_order(Gradients::leaky()).sin().sum();
+        let grads = y.backward_traced();
+        let dx = grads.get(&x);
+        assert_close_to_literal!(dx, [0.5403023, 0.87758255, -0.41614684]);
+        let g = grads.backward(dx.sum());
+        assert_close_to_literal!(g.get(&x), [0.84147096, -0.47942555, -0.9092974]);
+    }
+
+    #[test]
+    fn test_second_order_binary() {
+        let dev: TestDevice = Default::default();
+        let x = dev.tensor([1.0, 2.0, 3.0]).to_dtype::<TestDtype>();
+        let w = dev.tensor([0.5, -1.0, 2.0]).to_dtype::<TestDtype>();
+        let y = x
+            .trace_higher_order(Gradients::leaky())
+            .square()
+            .try_mul(w.clone())
+            .unwrap()
+            .sum();
+        let grads = y.backward_traced();
+        // dy/dx = 2 * x * w
+        let dx = grads.get(&x);
+        assert_close_to_literal!(dx, [1.0, -4.0, 12.0]);
+        let g = grads.backward(dx.sum());
+        assert_close_to_literal!(g.get(&x), [1.0, -2.0, 4.0]);
+        assert_close_to_literal!(g.get(&w), [2.0, 4.0, 6.0]);
+    }
+
+    #[test]
+    fn test_second_order_broadcast() {
+        let dev: TestDevice = Default::default();
+        let x = dev.tensor([0.0, 1.0, -1.0]).to_dtype::<TestDtype>();
+        let y = x
+            .trace_higher_order(Gradients::leaky())
+            .broadcast::<Rank2<2, 3>, _>()
+            .exp()
+            .sum();
+        let grads = y.backward_traced();
+        let e = [1.0f64, 1.0f64.exp(), (-1.0f64).exp()];
+        let dx = grads.get(&x);
+        assert_close_to_literal!(dx, e.map(|v| 2.0 * v));
+        let g = grads.backward(dx.sum());
+        assert_close_to_literal!(g.get(&x), e.map(|v| 2.0 * v));
+    }
+
+    #[test]
+    fn test_hessian_vector_product_matmul() {
+        let dev: TestDevice = Default::default();
+        let x = dev.tensor([[1.0, 2.0], [3.0, 4.0]]).to_dtype::<TestDtype>();
+        let w = dev
+            .tensor([[0.1, -0.2], [0.3, 0.4]])
+            .to_dtype::<TestDtype>();
+        let y = x
+            .trace_higher_order(Gradients::leaky())
+            .matmul(w.clone())
+            .square()
+            .sum();
+        let grads = y.backward_traced();
+        let dx = grads.get(&x);
+        assert_close_to_literal!(dx, [[-0.1, 0.9], [-0.1, 1.7]]);
+        let g = grads.backward(dx.sum());
+        assert_close_to_literal!(g.get(&x), [[0.0, 0.4], [0.0, 0.4]]);
+        assert_close_to_literal!(g.get(&w), [[7.6, 4.8], [9.2, 5.6]]);
+    }
+
+    #[test]
+    fn test_higher_order_unsupported_op() {
+        let dev: TestDevice = Default::default();
+        let x = dev.tensor([1.0, 2.0, 3.0]).to_dtype::<TestDtype>();
+        let y = x.trace_higher_order(Gradients::leaky()).powi(2).sum();
+        assert!(matches!(
+            y.try_backward_traced(),
+            Err(Error::HigherOrderUnsupported)
+        ));
+    }
+
+    #[test]
+    fn test_first_order_unchanged_by_traced_ops() {
+        let dev: TestDevice = Default::default();
+        let x = dev.tensor([1.0, 2.0, 3.0]).to_dtype::<TestDtype>();
+        let g = x.leaky_trace().powi(2).sum().backward();
+        assert_close_to_literal!(g.get(&x), [2.0, 4.0, 6.0]);
+    }
+}
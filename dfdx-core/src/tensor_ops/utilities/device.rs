@@ -26,6 +26,7 @@ pub trait Device<E: Dtype>:
     + super::super::adam::AdamKernel<E>
     + super::super::sgd::SgdKernel<E>
     + super::super::rmsprop::RMSpropKernel<E>
+    + super::super::sparse_rows::SparseRowsKernel<E>
 
     // allocation
     + crate::tensor::ZerosTensor<E>
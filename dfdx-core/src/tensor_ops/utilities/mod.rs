@@ -8,5 +8,5 @@ pub(crate) mod reduction_utils;
 #[cfg(feature = "webgpu")]
 pub(crate) mod webgpu_kernels;
 
-pub use backward::Backward;
+pub use backward::{Backward, BackwardTraced, HigherOrderOps};
 pub use device::Device;
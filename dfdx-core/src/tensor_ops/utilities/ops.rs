@@ -1,3 +1,4 @@
+use super::backward::{Flat, HigherOrderOps};
 use crate::{
     shapes::{Dtype, HasShape, Shape},
     tensor::*,
@@ -52,9 +53,13 @@ pub(crate) fn try_unary_op<
     inp: Tensor<S, E, D, T>,
 ) -> Result<Tensor<S, E, D, T>, crate::tensor::Error> {
     let (inp, mut tape) = inp.split_tape();
+    if tape.is_forward_mode() {
+        return try_unary_op_forward_mode(op, inp, tape);
+    }
     let inp_ghost = inp.ghost();
+    let inp_id = inp.id;
     let dev = inp.device.clone();
-    if !T::OWNS_TAPE || D::BACKWARD_WITHOUT_DATA {
+    let out = if !T::OWNS_TAPE || D::BACKWARD_WITHOUT_DATA {
         let out = inp_ghost.dev.forward(op.clone(), Cow::Owned(inp))?;
         let out_ghost = out.ghost();
         tape.add_backward_op(move |grads| {
@@ -63,7 +68,7 @@ pub(crate) fn try_unary_op<
             let (grad_inp, grad_out) = grads.mut_and_ref(&inp_ghost, &out_ghost);
             dev.backward(op, &inp_ghost, grad_inp, &out_ghost, grad_out)
         });
-        Ok(out.put_tape(tape))
+        out
     } else if D::BACKWARD_WITHOUT_INP {
         let out = inp_ghost.dev.forward(op.clone(), Cow::Owned(inp))?;
         let out_ghost = out.ghost();
@@ -74,7 +79,7 @@ pub(crate) fn try_unary_op<
             let (grad_inp, grad_out) = grads.mut_and_ref(&inp_ghost, &out_ghost);
             dev.backward(op, &inp_ghost, grad_inp, &out_clone, grad_out)
         });
-        Ok(out.put_tape(tape))
+        out
     } else {
         let out = inp.device.forward(op.clone(), Cow::Borrowed(&inp))?;
         let out_ghost = out.ghost();
@@ -84,8 +89,61 @@ pub(crate) fn try_unary_op<
             let (grad_inp, grad_out) = grads.mut_and_ref(&inp_ghost, &out_ghost);
             dev.backward(op, &inp, grad_inp, &out_ghost, grad_out)
         });
-        Ok(out.put_tape(tape))
-    }
+        out
+    };
+    tape.add_graph_node(&out.data, || {
+        elementwise_node::<Op, _, _, _>(&[inp_id], &out)
+    })?;
+    Ok(out.put_tape(tape))
+}
+
+/// Computes the tangent of the output of an elementwise op with its backward kernel.
+/// Since the jacobian is diagonal, backpropagating the tangent of the input gives
+/// the tangent of the output.
+fn try_unary_op_forward_mode<
+    Op: Clone,
+    S: Shape,
+    E: Dtype,
+    D: UnaryKernel<Op, E>,
+    T: Tape<E, D>,
+>(
+    op: Op,
+    inp: Tensor<S, E, D>,
+    mut tape: T,
+) -> Result<Tensor<S, E, D, T>, Error> {
+    let out = inp.device.forward(op.clone(), Cow::Borrowed(&inp))?;
+    tape.add_forward_op(|tangents, _| {
+        let Some(tangent_inp) = tangents.get_ref_checked(&inp) else {
+            return Ok(());
+        };
+        // inp, out, and their tangents all have the same strides
+        let dev = inp.device.clone();
+        let mut tangent_out = dev.try_alloc_grad(tangent_inp)?;
+        if D::BACKWARD_WITHOUT_DATA {
+            dev.backward(
+                op,
+                &inp.ghost(),
+                &mut tangent_out,
+                &out.ghost(),
+                tangent_inp,
+            )?;
+        } else if D::BACKWARD_WITHOUT_INP {
+            dev.backward(op, &inp.ghost(), &mut tangent_out, &out, tangent_inp)?;
+        } else {
+            dev.backward(op, &inp, &mut tangent_out, &out.ghost(), tangent_inp)?;
+        }
+        let tangent_out = Tensor {
+            id: unique_id(),
+            data: std::sync::Arc::new(tangent_out),
+            shape: out.shape,
+            strides: out.strides,
+            device: dev,
+            tape: NoneTape,
+        };
+        tangents.insert(&out, tangent_out);
+        Ok(())
+    })?;
+    Ok(out.put_tape(tape))
 }
 
 pub(crate) fn try_binary_op<
@@ -105,8 +163,13 @@ pub(crate) fn try_binary_op<
     let (rhs, rtape) = rhs.split_tape();
     let lhs_ghost = lhs.ghost();
     let rhs_ghost = rhs.ghost();
+    let inp_ids = [lhs.id, rhs.id];
     let mut tape = ltape.merge(rtape);
-    if !LhsTape::OWNS_TAPE || D::BACKWARD_WITHOUT_DATA {
+    // see [try_binary_op_higher_order] for ops that support forward mode
+    if tape.is_forward_mode() {
+        return Err(Error::ForwardModeUnsupported);
+    }
+    let out = if !LhsTape::OWNS_TAPE || D::BACKWARD_WITHOUT_DATA {
         let out = lhs_ghost
             .dev
             .forward(op, Cow::Owned(lhs), Cow::Owned(rhs))?;
@@ -121,7 +184,7 @@ pub(crate) fn try_binary_op<
                 .dev
                 .backward(op, &lhs_ghost, grad_lhs, &rhs_ghost, grad_rhs, grad_out)
         });
-        Ok(out.put_tape(tape))
+        out
     } else {
         let out = lhs
             .device
@@ -136,6 +199,244 @@ pub(crate) fn try_binary_op<
             lhs.device
                 .backward(op, &lhs, grad_lhs, &rhs, grad_rhs, grad_out)
         });
-        Ok(out.put_tape(tape))
+        out
+    };
+    tape.add_graph_node(&out.data, || {
+        elementwise_node::<Op, _, _, _>(&inp_ids, &out)
+    })?;
+    Ok(out.put_tape(tape))
+}
+
+/// Describes an elementwise op, where all inputs have the same shape as the output.
+fn elementwise_node<Op, S: Shape, E, D: Storage<E>>(
+    inputs: &[UniqueId],
+    out: &Tensor<S, E, D>,
+) -> GraphNode {
+    let inputs = inputs
+        .iter()
+        .map(|id| GraphTensor::new(*id, &out.shape))
+        .collect();
+    GraphNode::new(op_name::<Op>(), inputs, GraphTensor::of(out))
+}
+
+/// Same as [try_unary_op], but when the tape [Tape::is_higher_order], this also records
+/// `derivative`, which computes the gradient of the input from the gradient of the output,
+/// the input, and the output.
+///
+/// All of these are passed as [Flat] tensors laid out like the data buffer of the input.
+pub(crate) fn try_unary_op_higher_order<
+    Op: 'static + Clone,
+    S: Shape,
+    E: Dtype,
+    D: UnaryKernel<Op, E>,
+    T: Tape<E, D>,
+    F: 'static
+        + FnOnce(
+            &HigherOrderOps<E, D>,
+            Flat<E, D>,
+            Flat<E, D>,
+            Flat<E, D>,
+        ) -> Result<Flat<E, D>, Error>,
+>(
+    op: Op,
+    inp: Tensor<S, E, D, T>,
+    derivative: F,
+) -> Result<Tensor<S, E, D, T>, crate::tensor::Error> {
+    if !inp.tape.is_higher_order() {
+        return try_unary_op(op, inp);
+    }
+    let (inp, mut tape) = inp.split_tape();
+    let out = inp.device.forward(op.clone(), Cow::Borrowed(&inp))?;
+    let inp_ghost = inp.ghost();
+    let out_ghost = out.ghost();
+    let (inp_clone, out_clone) = (inp.clone(), out.clone());
+    let (bwd_inp_ghost, bwd_out_ghost) = (inp_ghost.clone(), out_ghost.clone());
+    tape.add_backward_op(move |grads| {
+        grads.try_alloc_for(&bwd_inp_ghost)?;
+        grads.try_alloc_for(&bwd_out_ghost)?;
+        let (grad_inp, grad_out) = grads.mut_and_ref(&bwd_inp_ghost, &bwd_out_ghost);
+        let dev = inp_clone.device.clone();
+        if D::BACKWARD_WITHOUT_DATA {
+            dev.backward(op, &bwd_inp_ghost, grad_inp, &bwd_out_ghost, grad_out)
+        } else if D::BACKWARD_WITHOUT_INP {
+            dev.backward(op, &bwd_inp_ghost, grad_inp, &out_clone, grad_out)
+        } else {
+            dev.backward(op, &inp_clone, grad_inp, &bwd_out_ghost, grad_out)
+        }
+    });
+    tape.add_graph_node(&out.data, || {
+        elementwise_node::<Op, _, _, _>(&[inp.id], &out)
+    })?;
+    let out_clone = out.clone();
+    tape.add_traced_backward_op(move |grads| {
+        let ops = grads.ops;
+        if inp.strides == out_clone.strides && inp.len() == out_clone.len() {
+            // the output is laid out like the input (even if it is broadcasted),
+            // so the derivative can be computed elementwise on the buffers.
+            if let Some(grad_out) = grads.get_buffer(&out_ghost) {
+                let (inp, out) = (
+                    HigherOrderOps::buffer(&inp),
+                    HigherOrderOps::buffer(&out_clone),
+                );
+                let grad_inp = derivative(&ops, grad_out, inp, out)?;
+                grads.accumulate_buffer(&inp_ghost, grad_inp)?;
+            }
+        } else if let Some(grad_out) = grads.get_checked(&out_ghost) {
+            let grad_out = ops.try_flatten(grad_out)?;
+            let inp = ops.try_flatten(inp.put_tape(Default::default()))?;
+            let out = ops.try_flatten(out_clone.put_tape(Default::default()))?;
+            let grad_inp = derivative(&ops, grad_out, inp, out)?;
+            let grad_inp = ops.try_unflatten(grad_inp, &inp_ghost)?;
+            grads.accumulate_buffer(&inp_ghost, grad_inp)?;
+        }
+        Ok(())
+    });
+    Ok(out.put_tape(tape))
+}
+
+/// Same as [try_binary_op], but when the tape [Tape::is_higher_order], this also records
+/// `derivative`, which computes the gradients of lhs & rhs from the gradient of the output,
+/// lhs, and rhs. These are passed as contiguous [Flat] tensors.
+///
+/// With [Tape::is_forward_mode], `derivative` is used to compute the tangent of the output
+/// instead, since the jacobian of an elementwise op is diagonal.
+///
+/// Returns [Error::ShapeMismatch] if the runtime shapes of lhs & rhs differ.
+pub(crate) fn try_binary_op_higher_order<
+    Op: 'static + Copy,
+    S: Shape,
+    E: Dtype,
+    D: BinaryKernel<Op, E>,
+    RhsTape,
+    LhsTape: Tape<E, D> + Merge<RhsTape>,
+    F: 'static
+        + Clone
+        + FnOnce(
+            &HigherOrderOps<E, D>,
+            Flat<E, D>,
+            Flat<E, D>,
+            Flat<E, D>,
+        ) -> Result<(Flat<E, D>, Flat<E, D>), Error>,
+>(
+    op: Op,
+    lhs: Tensor<S, E, D, LhsTape>,
+    rhs: Tensor<S, E, D, RhsTape>,
+    derivative: F,
+) -> Result<Tensor<S, E, D, LhsTape>, crate::tensor::Error> {
+    if lhs.shape() != rhs.shape() {
+        return Err(Error::ShapeMismatch {
+            expected: lhs.shape().concrete().into_iter().collect(),
+            found: rhs.shape().concrete().into_iter().collect(),
+        });
+    }
+    let (lhs, ltape) = lhs.split_tape();
+    let (rhs, rtape) = rhs.split_tape();
+    let mut tape = ltape.merge(rtape);
+    if tape.is_forward_mode() {
+        let out = lhs
+            .device
+            .forward(op, Cow::Borrowed(&lhs), Cow::Borrowed(&rhs))?;
+        tape.add_forward_op(|tangents, ops| {
+            let flat = |t: Tensor<S, E, D>| ops.try_flatten(t.put_tape(Default::default()));
+            let tangent_lhs = match tangents.get_checked(&lhs) {
+                Some(t) => {
+                    let (l, r) = (flat(lhs.clone())?, flat(rhs.clone())?);
+                    Some(derivative.clone()(ops, flat(t)?, l, r)?.0)
+                }
+                None => None,
+            };
+            let tangent_rhs = match tangents.get_checked(&rhs) {
+                Some(t) => Some(derivative(ops, flat(t)?, flat(lhs)?, flat(rhs)?)?.1),
+                None => None,
+            };
+            let tangent_out = match (tangent_lhs, tangent_rhs) {
+                (Some(l), Some(r)) => ops.try_add(l, r)?,
+                (Some(t), None) | (None, Some(t)) => t,
+                (None, None) => return Ok(()),
+            };
+            // the output of binary kernels is contiguous
+            let tangent_out = Tensor {
+                id: unique_id(),
+                data: tangent_out.data,
+                shape: out.shape,
+                strides: out.strides,
+                device: tangent_out.device,
+                tape: NoneTape,
+            };
+            tangents.insert(&out, tangent_out);
+            Ok(())
+        })?;
+        return Ok(out.put_tape(tape));
+    }
+    if !tape.is_higher_order() {
+        return try_binary_op(op, lhs.put_tape(tape), rhs);
+    }
+    let lhs_ghost = lhs.ghost();
+    let rhs_ghost = rhs.ghost();
+    let out = lhs
+        .device
+        .forward(op, Cow::Borrowed(&lhs), Cow::Borrowed(&rhs))?;
+    let out_ghost = out.ghost();
+    let (lhs_clone, rhs_clone, out_clone) = (lhs.clone(), rhs.clone(), out_ghost.clone());
+    tape.add_backward_op(move |grads| {
+        grads.try_alloc_for(&lhs_clone)?;
+        grads.try_alloc_for(&rhs_clone)?;
+        grads.try_alloc_for(&out_clone)?;
+        let (grad_lhs, grad_rhs, grad_out) = grads.muts_and_ref(&lhs_clone, &rhs_clone, &out_clone);
+        let dev = lhs_clone.device.clone();
+        if D::BACKWARD_WITHOUT_DATA {
+            let (lhs, rhs) = (lhs_clone.ghost(), rhs_clone.ghost());
+            dev.backward(op, &lhs, grad_lhs, &rhs, grad_rhs, grad_out)
+        } else {
+            dev.backward(op, &lhs_clone, grad_lhs, &rhs_clone, grad_rhs, grad_out)
+        }
+    });
+    tape.add_graph_node(&out.data, || {
+        elementwise_node::<Op, _, _, _>(&[lhs.id, rhs.id], &out)
+    })?;
+    tape.add_traced_backward_op(move |grads| {
+        if let Some(grad_out) = grads.get_checked(&out_ghost) {
+            let ops = grads.ops;
+            let grad_out = ops.try_flatten(grad_out)?;
+            let lhs = ops.try_flatten(lhs.put_tape(Default::default()))?;
+            let rhs = ops.try_flatten(rhs.put_tape(Default::default()))?;
+            let (grad_lhs, grad_rhs) = derivative(&ops, grad_out, lhs, rhs)?;
+            let grad_lhs = ops.try_unflatten(grad_lhs, &lhs_ghost)?;
+            grads.accumulate_buffer(&lhs_ghost, grad_lhs)?;
+            let grad_rhs = ops.try_unflatten(grad_rhs, &rhs_ghost)?;
+            grads.accumulate_buffer(&rhs_ghost, grad_rhs)?;
+        }
+        Ok(())
+    });
+    Ok(out.put_tape(tape))
+}
+
+/// Computes the elementwise derivative of `op` at `inp` with its backward kernel.
+/// The result is a constant, so this is only correct for higher order gradients
+/// of piecewise linear operations.
+pub(crate) fn try_unary_derivative<Op: Clone, E: Dtype, D: UnaryKernel<Op, E>>(
+    ops: &HigherOrderOps<E, D>,
+    op: Op,
+    inp: &Flat<E, D>,
+) -> Result<Flat<E, D>, Error> {
+    let inp = inp.retaped::<NoneTape>();
+    let dev = inp.device.clone();
+    let out = dev.forward(op.clone(), Cow::Borrowed(&inp))?;
+    let mut grad_inp = dev.try_alloc_grad(&inp.data)?;
+    let grad_out = ops.try_ones(&dev, inp.len())?;
+    if D::BACKWARD_WITHOUT_DATA {
+        dev.backward(op, &inp.ghost(), &mut grad_inp, &out.ghost(), &grad_out)?;
+    } else if D::BACKWARD_WITHOUT_INP {
+        dev.backward(op, &inp.ghost(), &mut grad_inp, &out, &grad_out)?;
+    } else {
+        dev.backward(op, &inp, &mut grad_inp, &out.ghost(), &grad_out)?;
     }
+    Ok(Tensor {
+        id: unique_id(),
+        data: std::sync::Arc::new(grad_inp),
+        shape: inp.shape,
+        strides: inp.strides,
+        device: dev,
+        tape: Default::default(),
+    })
 }
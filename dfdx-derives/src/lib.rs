@@ -20,6 +20,10 @@ macro_rules! has_attr {
 ///
 /// You can control the name of the built struct with the `#[built(<type name>)]` attribute on the struct.
 ///
+/// Sub modules that are also marked with `#[frozen]` are not trained: optimizers skip their parameters,
+/// and no gradients are allocated for them. Other visitors of the parameters (like gradient clipping)
+/// still see them. See [dfdx::UpdateParams].
+///
 /// # Using CustomModule on unit structs
 ///
 /// Here we have a unit struct that just calls a method on Tensor in the forward:
@@ -77,7 +81,7 @@ macro_rules! has_attr {
 ///     }
 /// }
 /// ```
-#[proc_macro_derive(CustomModule, attributes(module, built))]
+#[proc_macro_derive(CustomModule, attributes(module, built, frozen))]
 pub fn custom_module(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
 
@@ -112,7 +116,12 @@ pub fn custom_module(input: proc_macro::TokenStream) -> proc_macro::TokenStream
                                 } else {
                                     quote!()
                                 };
-                                quote_spanned!(f.span()=> #[module] #safetensors_serialize_attr #vis #name: <#ty as ::dfdx::nn_traits::BuildOnDevice<Elem, Dev>>::Built,)
+                                let frozen_attr = if has_attr!(f, "frozen") {
+                                    quote!(#[frozen])
+                                } else {
+                                    quote!()
+                                };
+                                quote_spanned!(f.span()=> #[module] #frozen_attr #safetensors_serialize_attr #vis #name: <#ty as ::dfdx::nn_traits::BuildOnDevice<Elem, Dev>>::Built,)
                             } else {
                                 quote_spanned!(f.span()=> #vis #name: #ty,)
                             }
@@ -133,7 +142,12 @@ pub fn custom_module(input: proc_macro::TokenStream) -> proc_macro::TokenStream
                                 } else {
                                     quote!()
                                 };
-                                quote_spanned!(f.span()=> #[module] #safetensors_serialize_attr #vis <#ty as ::dfdx::nn_traits::BuildOnDevice<Elem, Dev>>::Built,)
+                                let frozen_attr = if has_attr!(f, "frozen") {
+                                    quote!(#[frozen])
+                                } else {
+                                    quote!()
+                                };
+                                quote_spanned!(f.span()=> #[module] #frozen_attr #safetensors_serialize_attr #vis <#ty as ::dfdx::nn_traits::BuildOnDevice<Elem, Dev>>::Built,)
                             } else {
                                 quote_spanned!(f.span()=> #vis #ty,)
                             }
@@ -664,7 +678,13 @@ pub fn reset_params(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     })
 }
 
-#[proc_macro_derive(UpdateParams, attributes(param, module))]
+/// Implements [dfdx::nn_traits::UpdateParams] by visiting every field marked with `#[param]` (a tensor)
+/// or `#[module]` (something that implements UpdateParams).
+///
+/// The parameters of fields that are also marked with `#[frozen]` are passed to
+/// [dfdx::nn_traits::Optimizer::update_frozen_tensor()] instead, so optimizers won't update them
+/// and won't report them as unused.
+#[proc_macro_derive(UpdateParams, attributes(param, module, frozen))]
 pub fn update_params(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let mut input = parse_macro_input!(input as DeriveInput);
 
@@ -688,41 +708,92 @@ pub fn update_params(input: proc_macro::TokenStream) -> proc_macro::TokenStream
     }
 
     let where_clause = input.generics.make_where_clause();
-    let updates = match &input.data {
+    let (updates, paths, visits) = match &input.data {
         Data::Struct(ref obj) => match obj.fields {
             Fields::Named(ref fields) => {
                 let updates = fields.named.iter().map(|f| {
                     let name = &f.ident;
                     let ty = &f.ty;
+                    let name_str = name.as_ref().map(|n| n.to_string());
+                    let frozen = has_attr!(f, "frozen");
                     if has_attr!(f, "module") {
                         where_clause
                             .predicates
                             .push(parse_quote!(#ty: ::dfdx::nn_traits::UpdateParams<Elem, Dev>));
-                        quote_spanned!(f.span()=>self.#name.try_update_params(optimizer, gradients, missing_tensors)?;)
+                        let update = if frozen {
+                            quote_spanned!(f.span()=>self.#name.try_update_params::<_Model, _>(&mut ::dfdx::nn_traits::Frozen(optimizer), gradients, missing_tensors)?;)
+                        } else {
+                            quote_spanned!(f.span()=>self.#name.try_update_params(optimizer, gradients, missing_tensors)?;)
+                        };
+                        (
+                            update,
+                            quote_spanned!(f.span()=>::dfdx::nn_traits::UpdateParams::<Elem, Dev>::param_paths(&self.#name, &format!("{location}{}", #name_str), paths);),
+                            quote_spanned!(f.span()=>::dfdx::nn_traits::UpdateParams::<Elem, Dev>::try_visit_params(&self.#name, visitor)?;),
+                        )
                     } else if has_attr!(f, "param") {
-                        quote_spanned!(f.span()=>optimizer.update_tensor(&mut self.#name, gradients, missing_tensors)?;)
+                        let update = if frozen {
+                            quote_spanned!(f.span()=>optimizer.update_frozen_tensor(&mut self.#name, gradients, missing_tensors)?;)
+                        } else {
+                            quote_spanned!(f.span()=>optimizer.update_tensor(&mut self.#name, gradients, missing_tensors)?;)
+                        };
+                        (
+                            update,
+                            quote_spanned!(f.span()=>::dfdx::nn_traits::UpdateParams::<Elem, Dev>::param_paths(&self.#name, &format!("{location}{}", #name_str), paths);),
+                            quote_spanned!(f.span()=>::dfdx::nn_traits::ParamVisitor::<Elem, Dev>::visit_tensor(visitor, &self.#name)?;),
+                        )
                     } else {
                         Default::default()
                     }
                 });
-                quote! { #(#updates)* }
+                let (updates, (paths, visits)): (Vec<_>, (Vec<_>, Vec<_>)) =
+                    updates.map(|(u, p, v)| (u, (p, v))).unzip();
+                (
+                    quote! { #(#updates)* },
+                    quote! { #(#paths)* },
+                    quote! { #(#visits)* },
+                )
             }
             Fields::Unnamed(ref fields) => {
                 let updates = fields.unnamed.iter().enumerate().map(|(i, f)| {
                     let index = Index::from(i);
                     let ty = &f.ty;
+                    let frozen = has_attr!(f, "frozen");
                     if has_attr!(f, "module") {
                         where_clause
                             .predicates
                             .push(parse_quote!(#ty: ::dfdx::nn_traits::UpdateParams<Elem, Dev>));
-                        quote_spanned!(f.span()=>self.#index.try_update_params(optimizer, gradients, missing_tensors)?;)
+                        let update = if frozen {
+                            quote_spanned!(f.span()=>self.#index.try_update_params::<_Model, _>(&mut ::dfdx::nn_traits::Frozen(optimizer), gradients, missing_tensors)?;)
+                        } else {
+                            quote_spanned!(f.span()=>self.#index.try_update_params(optimizer, gradients, missing_tensors)?;)
+                        };
+                        (
+                            update,
+                            quote_spanned!(f.span()=>::dfdx::nn_traits::UpdateParams::<Elem, Dev>::param_paths(&self.#index, &format!("{location}{}", #i), paths);),
+                            quote_spanned!(f.span()=>::dfdx::nn_traits::UpdateParams::<Elem, Dev>::try_visit_params(&self.#index, visitor)?;),
+                        )
                     } else if has_attr!(f, "param") {
-                        quote_spanned!(f.span()=>optimizer.update_tensor(&mut self.#index, gradients, missing_tensors)?;)
+                        let update = if frozen {
+                            quote_spanned!(f.span()=>optimizer.update_frozen_tensor(&mut self.#index, gradients, missing_tensors)?;)
+                        } else {
+                            quote_spanned!(f.span()=>optimizer.update_tensor(&mut self.#index, gradients, missing_tensors)?;)
+                        };
+                        (
+                            update,
+                            quote_spanned!(f.span()=>::dfdx::nn_traits::UpdateParams::<Elem, Dev>::param_paths(&self.#index, &format!("{location}{}", #i), paths);),
+                            quote_spanned!(f.span()=>::dfdx::nn_traits::ParamVisitor::<Elem, Dev>::visit_tensor(visitor, &self.#index)?;),
+                        )
                     } else {
                         Default::default()
                     }
                 });
-                quote! { #(#updates)* }
+                let (updates, (paths, visits)): (Vec<_>, (Vec<_>, Vec<_>)) =
+                    updates.map(|(u, p, v)| (u, (p, v))).unzip();
+                (
+                    quote! { #(#updates)* },
+                    quote! { #(#paths)* },
+                    quote! { #(#visits)* },
+                )
             }
             Fields::Unit => Default::default(),
         },
@@ -744,11 +815,29 @@ pub fn update_params(input: proc_macro::TokenStream) -> proc_macro::TokenStream
                 #updates
                 Ok(())
             }
+
+            #[allow(unused_variables)]
+            fn param_paths(&self, location: &str, paths: &mut Vec<(String, ::dfdx::tensor::UniqueId)>) {
+                #paths
+            }
+
+            #[allow(unused_variables)]
+            fn try_visit_params<Visitor: ::dfdx::nn_traits::ParamVisitor<Elem, Dev>>(
+                &self,
+                visitor: &mut Visitor,
+            ) -> Result<(), ::dfdx::tensor::Error> {
+                #visits
+                Ok(())
+            }
         }
     })
 }
 
-#[proc_macro_derive(ZeroGrads, attributes(param, module))]
+/// Implements [dfdx::nn_traits::ZeroGrads] for every field marked with `#[param]` or `#[module]`.
+/// Only the recorded rows of `#[param]`s with sparse rows are zeroed (see `Gradients::try_zero_for()`).
+///
+/// Fields that are also marked with `#[frozen]` are skipped, so no gradients are allocated for them.
+#[proc_macro_derive(ZeroGrads, attributes(param, module, frozen))]
 pub fn zero_grads(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let mut input = parse_macro_input!(input as DeriveInput);
 
@@ -778,15 +867,15 @@ pub fn zero_grads(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
                 let zero_grads = fields.named.iter().map(|f| {
                     let name = &f.ident;
                     let ty = &f.ty;
-                    if has_attr!(f, "module")
-                    {
+                    if has_attr!(f, "frozen") {
+                        Default::default()
+                    } else if has_attr!(f, "module") {
                         where_clause
                             .predicates
                             .push(parse_quote!(#ty: ::dfdx::nn_traits::ZeroGrads<Elem, Dev>));
                         quote_spanned!(f.span()=>self.#name.try_zero_grads(grads)?;)
-                    } else if has_attr!(f, "param")
-                    {
-                        quote_spanned!(f.span()=>self.#name.device().try_fill_with_zeros(grads.get_or_alloc_mut(&self.#name)?)?;)
+                    } else if has_attr!(f, "param") {
+                        quote_spanned!(f.span()=>grads.try_zero_for(&self.#name)?;)
                     } else {
                         Default::default()
                     }
@@ -797,15 +886,15 @@ pub fn zero_grads(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
                 let zero_grads = fields.unnamed.iter().enumerate().map(|(i, f)| {
                     let index = Index::from(i);
                     let ty = &f.ty;
-                    if has_attr!(f, "module")
-                    {
+                    if has_attr!(f, "frozen") {
+                        Default::default()
+                    } else if has_attr!(f, "module") {
                         where_clause
                             .predicates
                             .push(parse_quote!(#ty: ::dfdx::nn_traits::ZeroGrads<Elem, Dev>));
                         quote_spanned!(f.span()=>self.#index.try_zero_grads(grads)?;)
-                    } else if has_attr!(f, "param")
-                    {
-                        quote_spanned!(f.span()=>self.#index.device().try_fill_with_zeros(grads.get_or_alloc_mut(&self.#index)?)?)
+                    } else if has_attr!(f, "param") {
+                        quote_spanned!(f.span()=>grads.try_zero_for(&self.#index)?;)
                     } else {
                         Default::default()
                     }
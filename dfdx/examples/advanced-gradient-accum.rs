@@ -31,4 +31,15 @@ fn main() {
     // finally, we can use ZeroGrads to zero out the accumulated gradients
     model.zero_grads(&mut grads);
     assert_eq!(grads.get(&model.0.weight).array(), [[0.0; 2]; 5]);
+
+    // to train with batches that don't fit in memory, `accumulate_grads` does the
+    // above for you: it splits the batch into micro-batches along the first
+    // dimension, and averages their gradients into a single `Gradients`.
+    let mut model = model;
+    let mut opt = Sgd::new(&model, Default::default());
+    let y: Tensor<Rank2<10, 20>, f32, _> = dev.sample_normal();
+    let grads = accumulate_grads(&mut model, &(x, y), 4, |m, (x, y), grads| {
+        mse_loss(m.forward(x.traced(grads)), y)
+    });
+    opt.update(&mut model, &grads).unwrap();
 }
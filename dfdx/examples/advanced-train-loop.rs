@@ -13,8 +13,10 @@ fn classification_train<
     // Our model just needs to implement these two things! ModuleMut for forward
     // and TensorCollection for optimizer/alloc_grads/zero_grads
     Model: Module<Inp::Traced> + ZeroGrads<E, D> + UpdateParams<E, D>,
-    // optimizer, pretty straight forward
-    Opt: Optimizer<Model, E, D>,
+    // optimizer, pretty straight forward. LearningRate lets the scheduler change its lr
+    Opt: Optimizer<Model, E, D> + LearningRate,
+    // a learning rate schedule, updated every time the optimizer steps
+    Sched: LrScheduler,
     // our data will just be any iterator over these items. easy!
     Data: Iterator<Item = (Inp, Lbl)>,
     // Our loss function that takes the model's output & label and returns
@@ -29,17 +31,21 @@ fn classification_train<
 >(
     model: &mut Model,
     opt: &mut Opt,
+    sched: &Sched,
     mut criterion: Criterion,
     data: Data,
     batch_accum: usize,
 ) -> Result<(), Error> {
     let mut grads = model.try_alloc_grads()?;
+    let mut step = 0;
     for (i, (inp, lbl)) in data.enumerate() {
         let y = model.try_forward_mut(inp.traced(grads))?;
         let loss = criterion(y, lbl);
         let loss_value = loss.array();
         grads = loss.try_backward()?;
         if i % batch_accum == 0 {
+            sched.step(opt, step);
+            step += 1;
             opt.update(model, &grads).unwrap();
             model.try_zero_grads(&mut grads)?;
         }
@@ -55,6 +61,16 @@ fn main() {
     type Dtype = f32;
     let mut model = dev.build_module::<Dtype>(Model::default());
     let mut opt = dfdx::nn::optim::Sgd::new(&model, Default::default());
+    // warm up the learning rate for 10 steps, then decay it by half every 25 steps
+    let sched = LinearWarmup {
+        steps: 10,
+        start_factor: 0.1,
+        then: StepLr {
+            lr: 1e-2,
+            step_size: 25,
+            gamma: 0.5,
+        },
+    };
 
     // just some random data
     let mut data = Vec::new();
@@ -67,6 +83,7 @@ fn main() {
     classification_train(
         &mut model,
         &mut opt,
+        &sched,
         cross_entropy_with_logits_loss,
         data.into_iter(),
         1,
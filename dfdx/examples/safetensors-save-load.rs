@@ -1,4 +1,4 @@
-//! Demonstrates how to save and load arrays with safetensors
+//! Demonstrates how to save and load arrays & optimizer state with safetensors
 
 #[cfg(feature = "safetensors")]
 fn main() {
@@ -8,17 +8,26 @@ fn main() {
 
     type Model = (LinearConstConfig<5, 10>, LinearConstConfig<10, 5>);
 
-    let model = dev.build_module::<f32>(Model::default());
+    let mut model = dev.build_module::<f32>(Model::default());
     model
         .save_safetensors("model.safetensors")
         .expect("Failed to save model");
 
+    // optimizer state is saved using the same keys as the model's parameters
+    let mut opt = Adam::new(&model, Default::default());
+    opt.save_state(&mut model, "optim.safetensors")
+        .expect("Failed to save optimizer state");
+
     let mut model2 = dev.build_module::<f32>(Model::default());
     model2
         .load_safetensors("model.safetensors")
         .expect("Failed to load model");
 
     assert_eq!(model.0.weight.array(), model2.0.weight.array());
+
+    let mut opt2 = Adam::new(&model2, Default::default());
+    opt2.load_state(&mut model2, "optim.safetensors")
+        .expect("Failed to load optimizer state");
 }
 
 #[cfg(not(feature = "safetensors"))]
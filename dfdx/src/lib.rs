@@ -227,6 +227,12 @@
 //! | Adam | [nn::optim::Adam] | `torch.optim.Adam` |
 //! | AdamW | [nn::optim::Adam] with [nn::optim::WeightDecay::Decoupled] | `torch.optim.AdamW` |
 //! | RMSprop | [nn::optim::RMSprop] | `torch.optim.RMSprop` |
+//! | Adagrad | [nn::optim::Adagrad] | `torch.optim.Adagrad` |
+//! | Adadelta | [nn::optim::Adadelta] | `torch.optim.Adadelta` |
+//! | Lion | [nn::optim::Lion] | - |
+//! | LAMB | [nn::optim::Lamb] | - |
+//! | SparseAdam | [nn::optim::SparseAdam] | `torch.optim.SparseAdam` |
+//! | L-BFGS | [nn::optim::Lbfgs] | `torch.optim.LBFGS` |
 //!
 //! You can use optimizers to optimize neural networks (or even tensors!). Here's
 //! a simple example of how to do this:
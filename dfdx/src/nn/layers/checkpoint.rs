@@ -0,0 +1,201 @@
+use crate::prelude::*;
+use crate::tensor::UniqueId;
+
+/// Gradient checkpointing around `T`: the forward pass of `T` is run without
+/// recording any operations, and only the input is kept around. During the backward
+/// pass, the forward of `T` is run again with a fresh tape to compute gradients.
+///
+/// This trades compute for memory, since none of the intermediate tensors of `T` are kept
+/// alive between the forward & backward passes. The copy of `T` that is kept for the re-run
+/// shares the storage of its parameters, and gradients are only allocated for the parameters
+/// that the re-run actually reaches.
+///
+/// **Modules that use randomness in [Module::try_forward_mut] (like [Dropout]) will
+/// draw different random values when their forward is re-run.**
+///
+/// # Generics
+/// - `T`: The underlying module to checkpoint.
+///
+/// # Examples
+/// ```rust
+/// # use dfdx::prelude::*;
+/// # use dfdx::*;
+/// # let dev: Cpu = Default::default();
+/// type Model = Checkpoint<(LinearConstConfig<2, 5>, ReLU, LinearConstConfig<5, 2>)>;
+/// let model = dev.build_module::<f32>(Model::default());
+/// let x: Tensor<Rank1<2>, f32, _> = dev.sample_normal();
+/// let y = model.forward(x.leaky_trace());
+/// let grads = y.sum().backward();
+/// let _ = grads.get(&model.0 .0.weight);
+/// ```
+#[derive(Default, Clone, Debug, ResetParams, ZeroGrads, UpdateParams)]
+#[cfg_attr(feature = "safetensors", derive(SaveSafeTensors, LoadSafeTensors))]
+#[repr(transparent)]
+pub struct Checkpoint<T>(
+    #[module]
+    #[cfg_attr(feature = "safetensors", serialize)]
+    pub T,
+);
+
+impl<E: Dtype, D: Device<E>, T: BuildOnDevice<E, D>> BuildOnDevice<E, D> for Checkpoint<T> {
+    type Built = Checkpoint<T::Built>;
+    fn try_build_on_device(&self, device: &D) -> Result<Self::Built, crate::tensor::Error> {
+        let t = self.0.try_build_on_device(device)?;
+        Ok(Checkpoint(t))
+    }
+}
+
+impl<S: Shape, E: Dtype, D: Device<E>, T: Module<Tensor<S, E, D>>> Module<Tensor<S, E, D>>
+    for Checkpoint<T>
+{
+    type Output = T::Output;
+    fn try_forward(&self, x: Tensor<S, E, D>) -> Result<Self::Output, Error> {
+        self.0.try_forward(x)
+    }
+    fn try_forward_mut(&mut self, x: Tensor<S, E, D>) -> Result<Self::Output, Error> {
+        self.0.try_forward_mut(x)
+    }
+}
+
+impl<S: Shape, Out: Shape, E: Dtype, D: Device<E>, T> Module<Tensor<S, E, D, OwnedTape<E, D>>>
+    for Checkpoint<T>
+where
+    T: 'static
+        + Clone
+        + UpdateParams<E, D>
+        + Module<Tensor<S, E, D>, Output = Tensor<Out, E, D>>
+        + Module<Tensor<S, E, D, OwnedTape<E, D>>, Output = Tensor<Out, E, D, OwnedTape<E, D>>>,
+{
+    type Output = Tensor<Out, E, D, OwnedTape<E, D>>;
+    fn try_forward(&self, x: Tensor<S, E, D, OwnedTape<E, D>>) -> Result<Self::Output, Error> {
+        let (x, tape) = x.split_tape();
+        let y = self.0.try_forward(x.clone())?;
+        Ok(recompute_on_backward(self.0.clone(), x, y, tape, false))
+    }
+    fn try_forward_mut(
+        &mut self,
+        x: Tensor<S, E, D, OwnedTape<E, D>>,
+    ) -> Result<Self::Output, Error> {
+        let (x, tape) = x.split_tape();
+        let y = self.0.try_forward_mut(x.clone())?;
+        // cloned after the forward, so the copy shares storage with the updated
+        // module instead of forcing the forward to copy any state it mutates.
+        Ok(recompute_on_backward(self.0.clone(), x, y, tape, true))
+    }
+}
+
+/// Records a backward op that re-runs `module` on `x` with a fresh tape, and
+/// backprops the gradient of `y` through it.
+fn recompute_on_backward<S: Shape, Out: Shape, E: Dtype, D: Device<E>, T>(
+    mut module: T,
+    x: Tensor<S, E, D>,
+    y: Tensor<Out, E, D>,
+    mut tape: OwnedTape<E, D>,
+    train: bool,
+) -> Tensor<Out, E, D, OwnedTape<E, D>>
+where
+    T: 'static
+        + UpdateParams<E, D>
+        + Module<Tensor<S, E, D, OwnedTape<E, D>>, Output = Tensor<Out, E, D, OwnedTape<E, D>>>,
+{
+    let y_ghost = y.ghost();
+    let mut leafs = Vec::new();
+    module.param_paths("", &mut leafs);
+    let mut leafs: Vec<UniqueId> = leafs.into_iter().map(|(_, id)| id).collect();
+    leafs.push(x.id());
+    tape.add_backward_op(move |grads| {
+        grads.try_alloc_for(&y_ghost)?;
+        let grad_y = grads.get(&y_ghost);
+
+        // only the gradients of the parameters & the input are kept, and they
+        // are allocated lazily by the ops that write to them.
+        let mut sub_grads = Gradients::leaky();
+        sub_grads.retain_leafs(&leafs);
+        let x_traced = x.trace(sub_grads);
+        let y = if train {
+            module.try_forward_mut(x_traced)?
+        } else {
+            module.try_forward(x_traced)?
+        };
+        let sub_grads = y.try_mul(grad_y)?.try_sum()?.try_backward()?;
+        grads.try_accumulate(x.dev(), sub_grads)
+    });
+    y.put_tape(tape)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::*;
+
+    #[test]
+    fn test_checkpoint_matches_unchecked() {
+        let dev: TestDevice = Default::default();
+
+        type Inner = (LinearConstConfig<3, 5>, Tanh, LinearConstConfig<5, 2>);
+        let model = dev.build_module::<TestDtype>(<Checkpoint<Inner>>::default());
+
+        let x: Tensor<Rank2<4, 3>, TestDtype, _> = dev.sample_normal();
+        let y = model.forward(x.leaky_trace());
+        let y_expected = model.0.forward(x.leaky_trace());
+        assert_eq!(y.array(), y_expected.array());
+
+        let g = y.exp().mean().backward();
+        let g_expected = y_expected.exp().mean().backward();
+        assert_eq!(g.get(&x).array(), g_expected.get(&x).array());
+        assert_eq!(
+            g.get(&model.0 .0.weight).array(),
+            g_expected.get(&model.0 .0.weight).array()
+        );
+        assert_eq!(
+            g.get(&model.0 .0.bias).array(),
+            g_expected.get(&model.0 .0.bias).array()
+        );
+        assert_eq!(
+            g.get(&model.0 .2.weight).array(),
+            g_expected.get(&model.0 .2.weight).array()
+        );
+        assert_eq!(
+            g.get(&model.0 .2.bias).array(),
+            g_expected.get(&model.0 .2.bias).array()
+        );
+    }
+
+    #[test]
+    fn test_checkpoint_with_alloc_grads() {
+        let dev: TestDevice = Default::default();
+
+        type Model = (
+            LinearConstConfig<3, 3>,
+            Checkpoint<(LinearConstConfig<3, 3>, ReLU)>,
+            LinearConstConfig<3, 1>,
+        );
+        let mut model = dev.build_module::<TestDtype>(Model::default());
+        let x: Tensor<Rank2<4, 3>, TestDtype, _> = dev.sample_normal();
+
+        let grads = model.alloc_grads();
+        let g = model.forward_mut(x.trace(grads)).square().mean().backward();
+        let grads_expected = model.alloc_grads();
+        let (l0, l1, l2) = &model;
+        let y = l0.forward(x.trace(grads_expected));
+        let y = l1.0 .1.forward(l1.0 .0.forward(y));
+        let g_expected = l2.forward(y).square().mean().backward();
+
+        assert_eq!(
+            g.get(&l0.weight).array(),
+            g_expected.get(&l0.weight).array()
+        );
+        assert_eq!(
+            g.get(&l1.0 .0.weight).array(),
+            g_expected.get(&l1.0 .0.weight).array()
+        );
+        assert_eq!(
+            g.get(&l1.0 .0.bias).array(),
+            g_expected.get(&l1.0 .0.bias).array()
+        );
+        assert_eq!(
+            g.get(&l2.weight).array(),
+            g_expected.get(&l2.weight).array()
+        );
+    }
+}
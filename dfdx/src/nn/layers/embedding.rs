@@ -96,6 +96,107 @@ impl<Batch: Dim, Seq: Dim, V: Dim, M: Dim, E: Dtype, D: Device<E>, T: Tape<E, D>
     }
 }
 
+impl<V: Dim, M: Dim> EmbeddingConfig<V, M> {
+    /// Builds a [SparseEmbedding] instead, which records which rows have gradients.
+    pub fn sparse(self) -> SparseEmbeddingConfig<V, M> {
+        SparseEmbeddingConfig {
+            vocab: self.vocab,
+            model: self.model,
+        }
+    }
+}
+
+/// An [Embedding] that records which rows of `weight` were looked up during backward (see
+/// [crate::tensor::Gradients::sparse_rows()]). [crate::nn::optim::SparseAdam] only updates those
+/// rows instead of the whole table, and [ZeroGrads] only zeroes them. This is only valid if
+/// `weight` isn't also used outside of the embedding (e.g. tied to an output projection).
+///
+/// Build it with [EmbeddingConfig::sparse()]:
+/// ```rust
+/// # use dfdx::prelude::*;
+/// # let dev: Cpu = Default::default();
+/// let model = dev.build_module::<f32>(EmbeddingConstConfig::<100, 2>::default().sparse());
+/// let inputs: Tensor<Rank1<3>, usize, _> = dev.tensor([3, 7, 3]);
+/// let grads = model.forward(inputs.leaky_trace()).sum().backward();
+/// let rows: Vec<usize> = grads.sparse_rows(&model.weight).unwrap().iter().copied().collect();
+/// assert_eq!(rows, [3, 7]);
+/// ```
+#[derive(Default, Clone, Copy, Debug)]
+pub struct SparseEmbeddingConfig<Vocab: Dim, Model: Dim> {
+    pub vocab: Vocab,
+    pub model: Model,
+}
+
+impl<V: Dim, M: Dim, E: Dtype, D: Device<E>> BuildOnDevice<E, D> for SparseEmbeddingConfig<V, M> {
+    type Built = SparseEmbedding<V, M, E, D>;
+    fn try_build_on_device(&self, device: &D) -> Result<Self::Built, crate::tensor::Error> {
+        Ok(SparseEmbedding {
+            weight: device.try_zeros_like(&(self.vocab, self.model))?,
+        })
+    }
+}
+
+/// See [SparseEmbeddingConfig].
+#[derive(Clone, Debug, UpdateParams, ZeroGrads)]
+#[cfg_attr(feature = "safetensors", derive(SaveSafeTensors, LoadSafeTensors))]
+pub struct SparseEmbedding<Vocab: Dim, Model: Dim, Elem: Dtype, Dev: Device<Elem>> {
+    #[param]
+    #[cfg_attr(feature = "safetensors", serialize)]
+    pub weight: Tensor<(Vocab, Model), Elem, Dev>,
+}
+
+impl<V: Dim, M: Dim, E: Dtype, D: Device<E>> ResetParams<E, D> for SparseEmbedding<V, M, E, D>
+where
+    rand_distr::StandardNormal: rand_distr::Distribution<E>,
+{
+    fn try_reset_params(&mut self) -> Result<(), crate::tensor::Error> {
+        self.weight.try_fill_with_distr(rand_distr::StandardNormal)
+    }
+}
+
+impl<V: Dim, M: Dim, E: Dtype, D: Device<E>> SparseEmbedding<V, M, E, D> {
+    /// Records the looked up rows into the gradients of `weight` during backward.
+    fn record_rows<S: Shape, T: Tape<E, D>>(&self, input: &Tensor<S, usize, D>, mut tape: T) -> T {
+        let weight = self.weight.clone();
+        let input = input.clone();
+        tape.add_backward_op(move |grads| {
+            grads.add_sparse_rows(&weight, input.as_vec());
+            Ok(())
+        });
+        tape
+    }
+}
+
+impl<V: Dim, M: Dim, Seq: Dim, E: Dtype, D: Device<E>, T: Tape<E, D>>
+    Module<Tensor<(Seq,), usize, D, T>> for SparseEmbedding<V, M, E, D>
+{
+    type Output = Tensor<(Seq, M), E, D, T>;
+
+    fn try_forward(
+        &self,
+        input: Tensor<(Seq,), usize, D, T>,
+    ) -> Result<Self::Output, crate::tensor::Error> {
+        let (input, tape) = input.split_tape();
+        let tape = self.record_rows(&input, tape);
+        self.weight.clone().put_tape(tape).try_gather(input)
+    }
+}
+
+impl<Batch: Dim, Seq: Dim, V: Dim, M: Dim, E: Dtype, D: Device<E>, T: Tape<E, D>>
+    Module<Tensor<(Batch, Seq), usize, D, T>> for SparseEmbedding<V, M, E, D>
+{
+    type Output = Tensor<(Batch, Seq, M), E, D, T>;
+
+    fn try_forward(
+        &self,
+        input: Tensor<(Batch, Seq), usize, D, T>,
+    ) -> Result<Self::Output, crate::tensor::Error> {
+        let (input, tape) = input.split_tape();
+        let tape = self.record_rows(&input, tape);
+        self.weight.clone().put_tape(tape).try_gather(input)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -146,4 +247,47 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn embedding_sparse_rows() {
+        let dev: TestDevice = Default::default();
+        let mut model =
+            dev.build_module::<TestDtype>(EmbeddingConstConfig::<6, 2>::default().sparse());
+        model.weight = dev.ones();
+        let x = dev.tensor([[4, 1], [4, 2]]);
+        let mut grads = model.forward(x.leaky_trace()).sum().backward();
+        let rows: Vec<usize> = grads
+            .sparse_rows(&model.weight)
+            .unwrap()
+            .iter()
+            .copied()
+            .collect();
+        assert_eq!(rows, [1, 2, 4]);
+        assert_close_to_literal!(
+            grads.get(&model.weight),
+            [[0.0; 2], [1.0; 2], [1.0; 2], [0.0; 2], [2.0; 2], [0.0; 2]]
+        );
+
+        // only the recorded rows are zeroed, and then forgotten
+        model.zero_grads(&mut grads);
+        assert!(grads.sparse_rows(&model.weight).is_none());
+        assert_close_to_literal!(grads.get(&model.weight), [[0.0; 2]; 6]);
+        let grads = model
+            .forward(dev.tensor([0]).traced(grads))
+            .sum()
+            .backward();
+        let rows: Vec<usize> = grads
+            .sparse_rows(&model.weight)
+            .unwrap()
+            .iter()
+            .copied()
+            .collect();
+        assert_eq!(rows, [0]);
+
+        let dense = Embedding {
+            weight: model.weight.clone(),
+        };
+        let grads = dense.forward(x.leaky_trace()).sum().backward();
+        assert!(grads.sparse_rows(&dense.weight).is_none());
+    }
 }
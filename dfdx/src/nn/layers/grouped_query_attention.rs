@@ -0,0 +1,432 @@
+#![allow(clippy::type_complexity)]
+
+use crate::prelude::*;
+
+use super::multi_head_attention::{check_dropout, AttentionRef};
+
+use num_traits::Float;
+
+/// A grouped-query attention layer ([GQA](https://arxiv.org/abs/2305.13245)): a
+/// [MultiHeadAttention] whose keys and values are projected to fewer heads than the queries.
+/// Each key/value head is shared by `NumHeads / num_kv_heads` consecutive query heads, and
+/// a single key/value head is multi-query attention.
+///
+/// Generics:
+/// - `Embed`: The size of query vectors.
+/// - `NumHeads` The number of query heads.
+/// - `KvK`: The size of projected keys, `K / NumHeads` per key/value head.
+/// - *Optional* `KvV`: The size of projected values, `V / NumHeads` per key/value head. Defaults to `KvK`
+/// - *Optional* `K`: The size of query vectors after `w_q`. Defaults to `Embed`
+/// - *Optional* `V` The size of attention outputs before `w_o`. Defaults to `Embed`
+///
+/// Inputs are the same as the batched inputs of [MultiHeadAttention], including [KVCache]s,
+/// which only store the `num_kv_heads` key/value heads.
+///
+/// Examples
+/// - `GroupedQueryAttention<8, 4, 4>` has 4 query heads of size 2, and 2 key/value heads.
+/// - `GroupedQueryAttention<8, 4, 2>` is multi-query attention with a single key/value head.
+///
+/// ```rust
+/// # use dfdx::prelude::*;
+/// # let dev: Cpu = Default::default();
+/// type Model = GroupedQueryAttentionConfig<Const<8>, Const<4>, Const<4>>;
+/// let gqa = dev.build_module::<f32>(Model::default());
+/// let x: Tensor<Rank3<2, 3, 8>, f32, _> = dev.sample_normal();
+/// let y = gqa.forward(x);
+/// ```
+#[derive(Debug, Copy, Clone, CustomModule)]
+#[built(GroupedQueryAttention)]
+pub struct GroupedQueryAttentionConfig<
+    Embed: Dim,
+    NumHeads: Dim,
+    KvK: Dim,
+    KvV: Dim = KvK,
+    K: Dim = Embed,
+    V: Dim = Embed,
+> {
+    #[module]
+    pub w_q: LinearConfig<Embed, K>,
+    #[module]
+    pub w_k: LinearConfig<Embed, KvK>,
+    #[module]
+    pub w_v: LinearConfig<Embed, KvV>,
+    #[module]
+    pub w_o: LinearConfig<V, Embed>,
+    pub num_heads: NumHeads,
+    pub k_dim: K,
+    pub v_dim: V,
+    /// Dropout probability applied to the attention weights in [Module::try_forward_mut()],
+    /// see [GroupedQueryAttentionConfig::with_dropout()]. Defaults to `0.0`.
+    pub dropout: f64,
+    /// Base of the rotary position embedding applied to queries and keys, if any,
+    /// see [GroupedQueryAttentionConfig::with_rope()]. Defaults to `None`.
+    pub rope: Option<f64>,
+}
+
+impl<Embed, NumHeads, KvK, KvV, K, V> Default
+    for GroupedQueryAttentionConfig<Embed, NumHeads, KvK, KvV, K, V>
+where
+    Embed: Dim + Default,
+    NumHeads: Dim + Default,
+    KvK: Dim + Default,
+    KvV: Dim + Default,
+    K: Dim + Default,
+    V: Dim + Default,
+{
+    /// Panics if the heads don't divide evenly, see [GroupedQueryAttentionConfig::new()].
+    fn default() -> Self {
+        Self::new(
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+        )
+    }
+}
+
+/// The number of key/value heads when `k` & `v` are split into `num_heads` query heads, and
+/// projected to `kv_k` & `kv_v` features. `None` if the heads don't divide evenly.
+fn num_kv_heads(num_heads: usize, k: usize, v: usize, kv_k: usize, kv_v: usize) -> Option<usize> {
+    if num_heads == 0 || k % num_heads != 0 || v % num_heads != 0 {
+        return None;
+    }
+    let (k_head, v_head) = (k / num_heads, v / num_heads);
+    if k_head == 0 || v_head == 0 || kv_k % k_head != 0 || kv_v % v_head != 0 {
+        return None;
+    }
+    let kv_heads = kv_k / k_head;
+    let valid = kv_heads > 0 && kv_heads == kv_v / v_head && num_heads % kv_heads == 0;
+    valid.then_some(kv_heads)
+}
+
+impl<Embed: Dim, NumHeads: Dim, KvK: Dim, KvV: Dim, K: Dim, V: Dim>
+    GroupedQueryAttentionConfig<Embed, NumHeads, KvK, KvV, K, V>
+{
+    /// Panics if the heads don't divide evenly, see [GroupedQueryAttentionConfig::try_new()].
+    pub fn new(embed: Embed, num_heads: NumHeads, kv_k: KvK, kv_v: KvV, k: K, v: V) -> Self {
+        Self::try_new(embed, num_heads, kv_k, kv_v, k, v).unwrap()
+    }
+
+    /// Fallible version of [GroupedQueryAttentionConfig::new()]. Returns [Error::InvalidArgument]
+    /// unless `num_heads` divides `k` & `v` evenly, and `kv_k` & `kv_v` hold the same number of
+    /// key/value heads, which divides `num_heads` evenly.
+    pub fn try_new(
+        embed: Embed,
+        num_heads: NumHeads,
+        kv_k: KvK,
+        kv_v: KvV,
+        k: K,
+        v: V,
+    ) -> Result<Self, Error> {
+        let (h, kv_k_size, kv_v_size) = (num_heads.size(), kv_k.size(), kv_v.size());
+        if num_kv_heads(h, k.size(), v.size(), kv_k_size, kv_v_size).is_none() {
+            return Err(Error::InvalidArgument(format!(
+                "{kv_k_size} keys & {kv_v_size} values can't be split into key/value heads for {h} query heads"
+            )));
+        }
+        Ok(Self {
+            w_q: LinearConfig::new(embed, k),
+            w_k: LinearConfig::new(embed, kv_k),
+            w_v: LinearConfig::new(embed, kv_v),
+            w_o: LinearConfig::new(v, embed),
+            num_heads,
+            k_dim: k,
+            v_dim: v,
+            dropout: 0.0,
+            rope: None,
+        })
+    }
+
+    /// Sets the dropout probability applied to the attention weights during training.
+    pub fn with_dropout(self, dropout: f64) -> Self {
+        self.try_with_dropout(dropout).unwrap()
+    }
+
+    /// Fallible version of [GroupedQueryAttentionConfig::with_dropout()]. Returns
+    /// [Error::InvalidArgument] unless `0.0 <= dropout < 1.0`.
+    pub fn try_with_dropout(mut self, dropout: f64) -> Result<Self, Error> {
+        check_dropout(dropout)?;
+        self.dropout = dropout;
+        Ok(self)
+    }
+
+    /// Applies rotary position embedding ([RoFormer](https://arxiv.org/abs/2104.09864))
+    /// with the given `base` (usually `10000.0`) to queries and keys of each head.
+    pub fn with_rope(mut self, base: f64) -> Self {
+        self.rope = Some(base);
+        self
+    }
+}
+
+impl<M: Dim, H: Dim, KvK: Dim, KvV: Dim, K: Dim, V: Dim, E, D>
+    GroupedQueryAttention<M, H, KvK, KvV, K, V, E, D>
+where
+    E: Dtype,
+    D: Device<E>,
+{
+    /// Returns [Error::InvalidArgument] if the key/value heads don't divide the query heads.
+    fn try_attention(&self) -> Result<AttentionRef<'_, M, K, V, KvK, KvV, E, D>, Error> {
+        let num_heads = self.num_heads.size();
+        let kv_k = self.w_k.weight.shape().0.size();
+        let kv_v = self.w_v.weight.shape().0.size();
+        let (k, v) = (self.k_dim.size(), self.v_dim.size());
+        let Some(kv_heads) = num_kv_heads(num_heads, k, v, kv_k, kv_v) else {
+            return Err(Error::InvalidArgument(format!(
+                "{kv_k} keys & {kv_v} values can't be split into key/value heads for {num_heads} query heads"
+            )));
+        };
+        Ok(AttentionRef {
+            w_q: &self.w_q,
+            w_k: &self.w_k,
+            w_v: &self.w_v,
+            w_o: &self.w_o,
+            num_heads,
+            num_kv_heads: kv_heads,
+            k_dim: self.k_dim,
+            v_dim: self.v_dim,
+            dropout: self.dropout,
+            rope: self.rope,
+        })
+    }
+}
+
+impl<M: Dim, H: Dim, KvK: Dim, KvV: Dim, K: Dim, V: Dim, E, D, B, S, T>
+    Module<(Tensor<(B, S, M), E, D, T>, KVCache<B, KvK, KvV, E, D>)>
+    for GroupedQueryAttention<M, H, KvK, KvV, K, V, E, D>
+where
+    E: Dtype + Float,
+    D: Device<E> + TriangleTensor<bool>,
+    B: Dim,
+    S: Dim,
+    T: Tape<E, D>,
+{
+    type Output = (Tensor<(B, S, M), E, D, T>, KVCache<B, KvK, KvV, E, D>);
+
+    /// Incremental causal self attention of new timesteps, using and extending the [KVCache]
+    fn try_forward(
+        &self,
+        (x, cache): (Tensor<(B, S, M), E, D, T>, KVCache<B, KvK, KvV, E, D>),
+    ) -> Result<Self::Output, Error> {
+        self.try_attention()?.try_self_attend_cached(x, cache)
+    }
+}
+
+impl<M: Dim, H: Dim, KvK: Dim, KvV: Dim, K: Dim, V: Dim, E, D, B, S1, S2, T>
+    Module<(
+        Tensor<(B, S1, M), E, D, T>,
+        Tensor<(B, S2, M), E, D>,
+        Tensor<(B, S2, M), E, D>,
+    )> for GroupedQueryAttention<M, H, KvK, KvV, K, V, E, D>
+where
+    E: Dtype + Float,
+    D: Device<E>,
+    B: Dim,
+    S1: Dim,
+    S2: Dim,
+    T: Tape<E, D>,
+{
+    type Output = Tensor<(B, S1, M), E, D, T>;
+
+    /// Batched Encoder-Decoder style attention where one set of tensors is used for values and keys, and another is used for queries
+    fn try_forward(
+        &self,
+        (q, k, v): (
+            Tensor<(B, S1, M), E, D, T>,
+            Tensor<(B, S2, M), E, D>,
+            Tensor<(B, S2, M), E, D>,
+        ),
+    ) -> Result<Self::Output, Error> {
+        self.try_attention()?.try_attend(q, k, v, None, false)
+    }
+
+    fn try_forward_mut(
+        &mut self,
+        (q, k, v): (
+            Tensor<(B, S1, M), E, D, T>,
+            Tensor<(B, S2, M), E, D>,
+            Tensor<(B, S2, M), E, D>,
+        ),
+    ) -> Result<Self::Output, Error> {
+        self.try_attention()?.try_attend(q, k, v, None, true)
+    }
+}
+
+impl<M: Dim, H: Dim, KvK: Dim, KvV: Dim, K: Dim, V: Dim, E, D, B, S1, S2, T>
+    Module<(
+        Tensor<(B, S1, M), E, D, T>,
+        Tensor<(B, S2, M), E, D>,
+        Tensor<(B, S2, M), E, D>,
+        AttentionMask<B, S1, S2, D>,
+    )> for GroupedQueryAttention<M, H, KvK, KvV, K, V, E, D>
+where
+    E: Dtype + Float,
+    D: Device<E>,
+    B: Dim,
+    S1: Dim,
+    S2: Dim,
+    T: Tape<E, D>,
+{
+    type Output = Tensor<(B, S1, M), E, D, T>;
+
+    /// Batched masked Encoder-Decoder style attention
+    fn try_forward(
+        &self,
+        (q, k, v, mask): (
+            Tensor<(B, S1, M), E, D, T>,
+            Tensor<(B, S2, M), E, D>,
+            Tensor<(B, S2, M), E, D>,
+            AttentionMask<B, S1, S2, D>,
+        ),
+    ) -> Result<Self::Output, Error> {
+        self.try_attention()?
+            .try_attend(q, k, v, Some(&mask), false)
+    }
+
+    fn try_forward_mut(
+        &mut self,
+        (q, k, v, mask): (
+            Tensor<(B, S1, M), E, D, T>,
+            Tensor<(B, S2, M), E, D>,
+            Tensor<(B, S2, M), E, D>,
+            AttentionMask<B, S1, S2, D>,
+        ),
+    ) -> Result<Self::Output, Error> {
+        self.try_attention()?.try_attend(q, k, v, Some(&mask), true)
+    }
+}
+
+impl<M: Dim, H: Dim, KvK: Dim, KvV: Dim, K: Dim, V: Dim, E, D, Src> Module<Src>
+    for GroupedQueryAttention<M, H, KvK, KvV, K, V, E, D>
+where
+    E: Dtype,
+    D: Device<E>,
+    Src: SplitTape,
+    Self: Module<(Src, Src::NoTape, Src::NoTape), Output = Src>,
+{
+    type Output = Src;
+
+    fn try_forward(&self, src: Src) -> Result<Self::Output, Error> {
+        let (src, tape) = src.split_tape();
+        self.try_forward((src.clone().put_tape(tape), src.clone(), src))
+    }
+
+    fn try_forward_mut(&mut self, src: Src) -> Result<Self::Output, Error> {
+        let (src, tape) = src.split_tape();
+        self.try_forward_mut((src.clone().put_tape(tape), src.clone(), src))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::*;
+
+    #[test]
+    fn test_gqa_matches_repeated_mha() {
+        let dev: TestDevice = Default::default();
+
+        let gqa = dev.build_module::<TestDtype>(<GroupedQueryAttentionConfig<
+            Const<8>,
+            Const<4>,
+            Const<4>,
+        >>::default());
+        assert_eq!(gqa.w_k.weight.shape(), &(Const::<4>, Const::<8>));
+        assert_eq!(gqa.w_v.weight.shape(), &(Const::<4>, Const::<8>));
+
+        // the same as full attention with each key/value head repeated for its 2 query heads
+        let mut mha = dev
+            .build_module::<TestDtype>(<MultiHeadAttentionConfig<Const<8>, Const<4>>>::default());
+        mha.w_q = gqa.w_q.clone();
+        mha.w_o = gqa.w_o.clone();
+        let repeat = |x: Vec<TestDtype>, row: usize| -> Vec<TestDtype> {
+            x.chunks(2 * row)
+                .flat_map(|head| head.iter().chain(head.iter()).copied())
+                .collect()
+        };
+        mha.w_k
+            .weight
+            .copy_from(&repeat(gqa.w_k.weight.as_vec(), 8));
+        mha.w_k.bias.copy_from(&repeat(gqa.w_k.bias.as_vec(), 1));
+        mha.w_v
+            .weight
+            .copy_from(&repeat(gqa.w_v.weight.as_vec(), 8));
+        mha.w_v.bias.copy_from(&repeat(gqa.w_v.bias.as_vec(), 1));
+
+        let q: Tensor<Rank3<2, 3, 8>, TestDtype, _> = dev.sample_normal();
+        let kv: Tensor<Rank3<2, 5, 8>, TestDtype, _> = dev.sample_normal();
+        let y = gqa.forward((q.clone(), kv.clone(), kv.clone())).array();
+        let e = mha.forward((q, kv.clone(), kv));
+        y.assert_close(&e.array(), y.get_default_tol());
+    }
+
+    #[test]
+    fn test_gqa_multi_query() {
+        let dev: TestDevice = Default::default();
+
+        let mut mqa = dev.build_module::<TestDtype>(
+            <GroupedQueryAttentionConfig<Const<8>, Const<4>, Const<2>>>::default()
+                .with_rope(10000.0),
+        );
+
+        let x: Tensor<Rank3<2, 4, 8>, TestDtype, _> = dev.sample_normal();
+        let expected = mqa.forward((
+            x.clone(),
+            x.clone(),
+            x.clone(),
+            AttentionMask::causal(&dev, Const),
+        ));
+
+        // the cache only stores the single shared key/value head
+        let mut cache = KVCache::default();
+        for i in 0..4 {
+            let (y, new_cache) = mqa.forward((x.clone().slice((.., i..i + 1, ..)), cache));
+            cache = new_cache;
+            let y = y.realize::<Rank3<2, 1, 8>>().array();
+            let e = expected.clone().slice((.., i..i + 1, ..));
+            let e = e.realize::<Rank3<2, 1, 8>>();
+            y.assert_close(&e.array(), y.get_default_tol());
+        }
+        assert_eq!(cache.keys.unwrap().shape(), &(Const::<2>, 4, Const::<2>));
+
+        let g = mqa.forward(x.leaky_trace()).square().mean().backward();
+        let mut opt = crate::nn::optim::Sgd::new(&mqa, Default::default());
+        opt.update(&mut mqa, &g).expect("");
+    }
+
+    #[test]
+    fn test_gqa_uneven_heads() {
+        let dev: TestDevice = Default::default();
+
+        // 3 keys can't be split into heads of size 2
+        assert!(matches!(
+            GroupedQueryAttentionConfig::try_new(
+                Const::<8>, Const::<4>, Const::<3>, Const::<3>, Const::<8>, Const::<8>
+            ),
+            Err(Error::InvalidArgument(_))
+        ));
+
+        // configs that skip the validation are still checked in the forward pass
+        let cfg = GroupedQueryAttentionConfig {
+            w_q: LinearConfig::new(Const::<8>, Const::<8>),
+            w_k: LinearConfig::new(Const::<8>, Const::<3>),
+            w_v: LinearConfig::new(Const::<8>, Const::<3>),
+            w_o: LinearConfig::new(Const::<8>, Const::<8>),
+            num_heads: Const::<4>,
+            k_dim: Const::<8>,
+            v_dim: Const::<8>,
+            dropout: 0.0,
+            rope: None,
+        };
+        let gqa = dev.build_module::<TestDtype>(cfg);
+        let x: Tensor<Rank3<1, 2, 8>, TestDtype, _> = dev.sample_normal();
+        assert!(matches!(gqa.try_forward(x), Err(Error::InvalidArgument(_))));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_gqa_default_uneven_heads() {
+        let _ = <GroupedQueryAttentionConfig<Const<8>, Const<4>, Const<3>>>::default();
+    }
+}
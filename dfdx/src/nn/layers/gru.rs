@@ -0,0 +1,332 @@
+use crate::prelude::*;
+
+use super::rnn::{
+    check_num_layers, first_cell, try_gates, try_run_layers, try_split_gates, try_zero_states,
+    RecurrentCell,
+};
+use rand_distr::Uniform;
+
+/// A single step of a gated recurrent unit, which updates the hidden state `h` with the
+/// reset `r`, update `z` and new `n` gates:
+/// - `n = tanh(weight_in * x + bias_in + r * (weight_hn * h + bias_hn))`
+/// - `h' = (1 - z) * n + z * h`
+///
+/// The weights & biases of the gates are stacked in that order, like pytorch.
+///
+/// Generics:
+/// - `I` The size of the input vectors.
+/// - `H` The size of the hidden state.
+///
+/// **Pytorch equivalent**: `torch.nn.GRUCell(I, H)`
+///
+/// The operations are recorded on the tapes of both `x` and `h`, so use the same tape type for both.
+///
+/// Example:
+/// ```rust
+/// # use dfdx::prelude::*;
+/// # use dfdx::*;
+/// # let dev: Cpu = Default::default();
+/// let cell = dev.build_module::<f32>(GRUCellConstConfig::<3, 5>::default());
+/// let x: Tensor<Rank2<2, 3>, f32, _> = dev.zeros();
+/// let h: Tensor<Rank2<2, 5>, f32, _> = dev.zeros();
+/// let _: Tensor<Rank2<2, 5>, f32, _> = cell.forward((x, h));
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GRUCellConfig<I: Dim, H: Dim> {
+    pub inp: I,
+    pub hidden: H,
+}
+
+impl<I: Dim, H: Dim> GRUCellConfig<I, H> {
+    pub fn new(inp: I, hidden: H) -> Self {
+        Self { inp, hidden }
+    }
+}
+
+/// Compile time sugar alias around [GRUCellConfig].
+pub type GRUCellConstConfig<const I: usize, const H: usize> = GRUCellConfig<Const<I>, Const<H>>;
+
+impl<I: Dim, H: Dim, E: Dtype, D: Device<E>> BuildOnDevice<E, D> for GRUCellConfig<I, H> {
+    type Built = GRUCell<I, H, E, D>;
+    fn try_build_on_device(&self, device: &D) -> Result<Self::Built, crate::tensor::Error> {
+        Ok(GRUCell {
+            weight_ih: device.try_zeros_like(&(Const, self.hidden, self.inp))?,
+            weight_hh: device.try_zeros_like(&(Const, self.hidden, self.hidden))?,
+            bias_ih: device.try_zeros_like(&(Const, self.hidden))?,
+            bias_hh: device.try_zeros_like(&(Const, self.hidden))?,
+        })
+    }
+}
+
+/// See [GRUCellConfig].
+#[derive(Clone, Debug, UpdateParams, ZeroGrads)]
+#[cfg_attr(feature = "safetensors", derive(SaveSafeTensors, LoadSafeTensors))]
+pub struct GRUCell<I: Dim, H: Dim, Elem: Dtype, Dev: Device<Elem>> {
+    #[param]
+    #[cfg_attr(feature = "safetensors", serialize)]
+    pub weight_ih: Tensor<(Const<3>, H, I), Elem, Dev>,
+    #[param]
+    #[cfg_attr(feature = "safetensors", serialize)]
+    pub weight_hh: Tensor<(Const<3>, H, H), Elem, Dev>,
+    #[param]
+    #[cfg_attr(feature = "safetensors", serialize)]
+    pub bias_ih: Tensor<(Const<3>, H), Elem, Dev>,
+    #[param]
+    #[cfg_attr(feature = "safetensors", serialize)]
+    pub bias_hh: Tensor<(Const<3>, H), Elem, Dev>,
+}
+
+impl<I: Dim, H: Dim, E, D: Device<E>> ResetParams<E, D> for GRUCell<I, H, E, D>
+where
+    E: Dtype + num_traits::Float + rand_distr::uniform::SampleUniform,
+{
+    fn try_reset_params(&mut self) -> Result<(), crate::tensor::Error> {
+        let (_, h, _) = self.weight_hh.shape();
+        let b = E::from_f64(1.0 / (h.size() as f64).sqrt()).unwrap();
+        self.weight_ih.try_fill_with_distr(Uniform::new(-b, b))?;
+        self.weight_hh.try_fill_with_distr(Uniform::new(-b, b))?;
+        self.bias_ih.try_fill_with_distr(Uniform::new(-b, b))?;
+        self.bias_hh.try_fill_with_distr(Uniform::new(-b, b))
+    }
+}
+
+impl<I: Dim, H: Dim, E: Dtype, D: Device<E>> RecurrentCell<I, H, E, D> for GRUCell<I, H, E, D> {
+    const NUM_STATES: usize = 1;
+
+    fn try_step<B: Dim, T: Tape<E, D>>(
+        &self,
+        x: Tensor<(B, I), E, D, T>,
+        state: Vec<Tensor<(B, H), E, D, T>>,
+    ) -> Result<Vec<Tensor<(B, H), E, D, T>>, Error> {
+        let (h, tape) = state.into_iter().next().unwrap().split_tape();
+        let [x_r, x_z, x_n] = try_split_gates(try_gates(x, &self.weight_ih, &self.bias_ih)?)?;
+        let h_gates = try_gates(h.clone().put_tape(tape), &self.weight_hh, &self.bias_hh)?;
+        let [h_r, h_z, h_n] = try_split_gates(h_gates)?;
+        let r = h_r.try_add(x_r)?.try_sigmoid()?;
+        let z = x_z.try_add(h_z)?.try_sigmoid()?;
+        let (n, tape) = x_n.try_add(r.try_mul(h_n)?)?.try_tanh()?.split_tape();
+
+        // (1 - z) * n + z * h = z * (h - n) + n
+        let h = z.try_mul(h.retaped::<T>().try_sub(n.clone())?)?;
+        Ok(std::vec![h.try_add(n.put_tape(tape))?])
+    }
+}
+
+impl<B: Dim, I: Dim, H: Dim, E: Dtype, D: Device<E>, T: Tape<E, D>>
+    Module<(Tensor<(B, I), E, D, T>, Tensor<(B, H), E, D, T>)> for GRUCell<I, H, E, D>
+{
+    type Output = Tensor<(B, H), E, D, T>;
+    fn try_forward(
+        &self,
+        (x, h): (Tensor<(B, I), E, D, T>, Tensor<(B, H), E, D, T>),
+    ) -> Result<Self::Output, Error> {
+        Ok(self.try_step(x, std::vec![h])?.remove(0))
+    }
+}
+
+/// A multi-layer gated recurrent unit network over `(Batch, Seq, I)` inputs, made of [GRUCell]s.
+///
+/// Outputs a tuple of:
+/// 1. The hidden state of the last layer at every step, `(Batch, Seq, num_directions * H)`.
+///    With `bidirectional`, the forward direction comes first in the last dimension.
+/// 2. The final hidden state of every layer & direction, `(num_layers * num_directions, Batch, H)`.
+///
+/// The initial hidden state is zeros, unless it's passed in with the input as `(x, h0)`,
+/// where `h0` has the same shape as the final hidden state. The tape of `h0` is merged
+/// with the tape of `x`.
+///
+/// The tape goes to the outputs, and the final hidden state is returned with an empty tape,
+/// see [RNN] for how to backpropagate through it.
+///
+/// **Pytorch equivalent**:
+/// ```python
+/// torch.nn.GRU(I, H, num_layers=cfg.num_layers, bidirectional=cfg.bidirectional, batch_first=True)
+/// ```
+///
+/// Example:
+/// ```rust
+/// # use dfdx::prelude::*;
+/// # use dfdx::*;
+/// # let dev: Cpu = Default::default();
+/// let gru = dev.build_module::<f32>(GRUConfig::new(Const::<3>, Const::<5>, 2, false));
+/// let x: Tensor<Rank3<4, 7, 3>, f32, _> = dev.zeros();
+/// let (y, h_n) = gru.forward(x);
+/// assert_eq!(y.shape(), &(Const::<4>, Const::<7>, 5));
+/// assert_eq!(h_n.shape(), &(2, Const::<4>, Const::<5>));
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct GRUConfig<I: Dim, H: Dim> {
+    pub inp: I,
+    pub hidden: H,
+    /// The number of stacked layers. Defaults to `1`.
+    pub num_layers: usize,
+    /// Whether each layer also runs over the sequence in reverse. Defaults to `false`.
+    pub bidirectional: bool,
+}
+
+impl<I: Dim, H: Dim> GRUConfig<I, H> {
+    pub fn new(inp: I, hidden: H, num_layers: usize, bidirectional: bool) -> Self {
+        Self {
+            inp,
+            hidden,
+            num_layers,
+            bidirectional,
+        }
+    }
+}
+
+impl<I: Dim + Default, H: Dim + Default> Default for GRUConfig<I, H> {
+    fn default() -> Self {
+        Self::new(Default::default(), Default::default(), 1, false)
+    }
+}
+
+/// Compile time sugar alias around [GRUConfig].
+pub type GRUConstConfig<const I: usize, const H: usize> = GRUConfig<Const<I>, Const<H>>;
+
+impl<I: Dim, H: Dim, E: Dtype, D: Device<E>> BuildOnDevice<E, D> for GRUConfig<I, H> {
+    type Built = GRU<I, H, E, D>;
+    fn try_build_on_device(&self, device: &D) -> Result<Self::Built, crate::tensor::Error> {
+        check_num_layers(self.num_layers)?;
+        let dirs = if self.bidirectional { 2 } else { 1 };
+        let first = GRUCellConfig::new(self.inp, self.hidden);
+        let other = GRUCellConfig::new(dirs * self.hidden.size(), self.hidden);
+        Ok(GRU {
+            first_layer: std::vec![first; dirs].try_build_on_device(device)?,
+            other_layers: std::vec![other; (self.num_layers - 1) * dirs]
+                .try_build_on_device(device)?,
+        })
+    }
+}
+
+/// See [GRUConfig].
+#[derive(Clone, Debug, ResetParams, UpdateParams, ZeroGrads)]
+#[cfg_attr(feature = "safetensors", derive(SaveSafeTensors, LoadSafeTensors))]
+pub struct GRU<I: Dim, H: Dim, Elem: Dtype, Dev: Device<Elem>> {
+    /// The cells of the first layer, one per direction.
+    #[module]
+    #[cfg_attr(feature = "safetensors", serialize)]
+    pub first_layer: Vec<GRUCell<I, H, Elem, Dev>>,
+    /// The cells of the remaining layers, one per direction for each layer.
+    #[module]
+    #[cfg_attr(feature = "safetensors", serialize)]
+    pub other_layers: Vec<GRUCell<usize, H, Elem, Dev>>,
+}
+
+impl<B: Dim, S: Dim, I: Dim, H: Dim, E: Dtype, D: Device<E>, T: Tape<E, D>>
+    Module<Tensor<(B, S, I), E, D, T>> for GRU<I, H, E, D>
+{
+    type Output = (
+        Tensor<(B, S, usize), E, D, T>,
+        Tensor<(usize, B, H), E, D, T>,
+    );
+    fn try_forward(&self, x: Tensor<(B, S, I), E, D, T>) -> Result<Self::Output, Error> {
+        let rows = self.first_layer.len() + self.other_layers.len();
+        let hidden = first_cell(&self.first_layer)?.weight_hh.shape().1;
+        let h0 = try_zero_states(&x, rows, hidden)?;
+        self.try_forward((x, h0))
+    }
+}
+
+impl<B: Dim, S: Dim, I: Dim, H: Dim, E: Dtype, D: Device<E>, T: Tape<E, D>>
+    Module<(Tensor<(B, S, I), E, D, T>, Tensor<(usize, B, H), E, D, T>)> for GRU<I, H, E, D>
+{
+    type Output = (
+        Tensor<(B, S, usize), E, D, T>,
+        Tensor<(usize, B, H), E, D, T>,
+    );
+    fn try_forward(
+        &self,
+        (x, h0): (Tensor<(B, S, I), E, D, T>, Tensor<(usize, B, H), E, D, T>),
+    ) -> Result<Self::Output, Error> {
+        let (y, mut states) =
+            try_run_layers(&self.first_layer, &self.other_layers, x, std::vec![h0])?;
+        Ok((y, states.remove(0)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{gradcheck::*, tests::*};
+
+    #[test]
+    fn test_gru_cell() {
+        let dev: TestDevice = Default::default();
+        let cell = GRUCell {
+            weight_ih: dev
+                .tensor([[[0.1, -0.2]], [[0.3, 0.4]], [[-0.5, 0.1]]])
+                .to_dtype::<TestDtype>(),
+            weight_hh: dev
+                .tensor([[[0.5]], [[-0.1]], [[0.3]]])
+                .to_dtype::<TestDtype>(),
+            bias_ih: dev.tensor([[0.1], [0.0], [0.2]]).to_dtype::<TestDtype>(),
+            bias_hh: dev.tensor([[0.0], [0.3], [0.1]]).to_dtype::<TestDtype>(),
+        };
+        let x = dev.tensor([[1.0, 2.0]]).to_dtype::<TestDtype>();
+        let h = dev.tensor([[0.5]]).to_dtype::<TestDtype>();
+        let h_new = cell.forward((x.leaky_trace(), h.leaky_trace()));
+        assert_close_to_literal!(h_new, [[0.40285326]]);
+
+        let g = h_new.sum().backward();
+        assert_close_to_literal!(
+            g.get(&cell.bias_ih),
+            [[0.012848694], [0.077147106], [0.20570762]]
+        );
+        assert_close_to_literal!(g.get(&h), [[0.82446665]]);
+    }
+
+    #[test]
+    fn test_gru_matches_cells() {
+        let dev: TestDevice = Default::default();
+        let gru = dev.build_module::<TestDtype>(GRUConstConfig::<3, 4>::default());
+        let x: Tensor<Rank3<2, 5, 3>, TestDtype, _> = dev.sample_normal();
+        let (y, h_n) = gru.forward(x.clone());
+
+        let cell = &gru.first_layer[0];
+        let mut h: Tensor<Rank2<2, 4>, TestDtype, _> = dev.zeros();
+        for t in 0..5 {
+            let x_t = x
+                .clone()
+                .slice((.., t..t + 1, ..))
+                .reshape_like(&(Const::<2>, Const::<3>));
+            h = cell.forward((x_t, h));
+            let y_t = y
+                .clone()
+                .slice((.., t..t + 1, ..))
+                .reshape_like(&(Const::<2>, Const::<4>));
+            assert_eq!(y_t.array(), h.array());
+        }
+        assert_eq!(
+            h_n.reshape_like(&(Const::<2>, Const::<4>)).array(),
+            h.array()
+        );
+    }
+
+    #[test]
+    fn test_gru_bidirectional_grads() {
+        let dev: TestDevice = Default::default();
+        let mut gru =
+            dev.build_module::<TestDtype>(GRUConfig::new(Const::<3>, Const::<4>, 2, true));
+        let x: Tensor<Rank3<2, 6, 3>, TestDtype, _> = dev.sample_normal();
+        let (y, h_n) = gru.forward(x.clone());
+        assert_eq!(y.shape(), &(Const::<2>, Const::<6>, 8));
+        assert_eq!(h_n.shape(), &(4, Const::<2>, Const::<4>));
+
+        // backpropagate through the outputs & the final states
+        let h0: Tensor<(usize, Const<2>, Const<4>), TestDtype, _> =
+            dev.sample_normal_like(&(4, Const, Const));
+        let report = gradcheck_module(
+            &mut gru,
+            &h0,
+            |gru, h0| {
+                let (y, h_n) = gru.forward((x.retaped(), h0));
+                let (y, tape) = y.split_tape();
+                let h_n = h_n.split_tape().0.put_tape(tape);
+                h_n.square().mean() + y.retaped::<OwnedTape<_, _>>().square().mean()
+            },
+            Default::default(),
+        );
+        assert!(report.passed(), "{report}");
+    }
+}
@@ -0,0 +1,452 @@
+use crate::prelude::*;
+
+use super::rnn::{
+    check_num_layers, first_cell, try_gates, try_run_layers, try_split_gates, try_zero_states,
+    RecurrentCell,
+};
+use rand_distr::Uniform;
+
+/// A single step of a long short-term memory cell, which updates the hidden state `h` and
+/// cell state `c` with the input `i`, forget `f`, cell `g` and output `o` gates:
+/// - `c' = f * c + i * g`
+/// - `h' = o * tanh(c')`
+///
+/// The weights & biases of the gates are stacked in that order, like pytorch.
+///
+/// Generics:
+/// - `I` The size of the input vectors.
+/// - `H` The size of the hidden & cell states.
+///
+/// **Pytorch equivalent**: `torch.nn.LSTMCell(I, H)`
+///
+/// Takes `(x, (h, c))` and outputs `(h', c')`. The tapes of `x`, `h` & `c` are merged, and like
+/// every op with several outputs, the tape goes to the first output `h'`. `c'` is returned with
+/// an empty tape of the same type, so it can be passed back in with `h'`. To backpropagate
+/// through `c'` on its own, move the tape of `h'` onto it with `split_tape()` & `put_tape()`.
+///
+/// Example:
+/// ```rust
+/// # use dfdx::prelude::*;
+/// # use dfdx::*;
+/// # let dev: Cpu = Default::default();
+/// let cell = dev.build_module::<f32>(LSTMCellConstConfig::<3, 5>::default());
+/// let x: Tensor<Rank2<2, 3>, f32, _> = dev.zeros();
+/// let h: Tensor<Rank2<2, 5>, f32, _> = dev.zeros();
+/// let (h, c) = cell.forward((x, (h.clone(), h)));
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LSTMCellConfig<I: Dim, H: Dim> {
+    pub inp: I,
+    pub hidden: H,
+}
+
+impl<I: Dim, H: Dim> LSTMCellConfig<I, H> {
+    pub fn new(inp: I, hidden: H) -> Self {
+        Self { inp, hidden }
+    }
+}
+
+/// Compile time sugar alias around [LSTMCellConfig].
+pub type LSTMCellConstConfig<const I: usize, const H: usize> = LSTMCellConfig<Const<I>, Const<H>>;
+
+impl<I: Dim, H: Dim, E: Dtype, D: Device<E>> BuildOnDevice<E, D> for LSTMCellConfig<I, H> {
+    type Built = LSTMCell<I, H, E, D>;
+    fn try_build_on_device(&self, device: &D) -> Result<Self::Built, crate::tensor::Error> {
+        Ok(LSTMCell {
+            weight_ih: device.try_zeros_like(&(Const, self.hidden, self.inp))?,
+            weight_hh: device.try_zeros_like(&(Const, self.hidden, self.hidden))?,
+            bias_ih: device.try_zeros_like(&(Const, self.hidden))?,
+            bias_hh: device.try_zeros_like(&(Const, self.hidden))?,
+        })
+    }
+}
+
+/// See [LSTMCellConfig].
+#[derive(Clone, Debug, UpdateParams, ZeroGrads)]
+#[cfg_attr(feature = "safetensors", derive(SaveSafeTensors, LoadSafeTensors))]
+pub struct LSTMCell<I: Dim, H: Dim, Elem: Dtype, Dev: Device<Elem>> {
+    #[param]
+    #[cfg_attr(feature = "safetensors", serialize)]
+    pub weight_ih: Tensor<(Const<4>, H, I), Elem, Dev>,
+    #[param]
+    #[cfg_attr(feature = "safetensors", serialize)]
+    pub weight_hh: Tensor<(Const<4>, H, H), Elem, Dev>,
+    #[param]
+    #[cfg_attr(feature = "safetensors", serialize)]
+    pub bias_ih: Tensor<(Const<4>, H), Elem, Dev>,
+    #[param]
+    #[cfg_attr(feature = "safetensors", serialize)]
+    pub bias_hh: Tensor<(Const<4>, H), Elem, Dev>,
+}
+
+impl<I: Dim, H: Dim, E, D: Device<E>> ResetParams<E, D> for LSTMCell<I, H, E, D>
+where
+    E: Dtype + num_traits::Float + rand_distr::uniform::SampleUniform,
+{
+    fn try_reset_params(&mut self) -> Result<(), crate::tensor::Error> {
+        let (_, h, _) = self.weight_hh.shape();
+        let b = E::from_f64(1.0 / (h.size() as f64).sqrt()).unwrap();
+        self.weight_ih.try_fill_with_distr(Uniform::new(-b, b))?;
+        self.weight_hh.try_fill_with_distr(Uniform::new(-b, b))?;
+        self.bias_ih.try_fill_with_distr(Uniform::new(-b, b))?;
+        self.bias_hh.try_fill_with_distr(Uniform::new(-b, b))
+    }
+}
+
+impl<I: Dim, H: Dim, E: Dtype, D: Device<E>> RecurrentCell<I, H, E, D> for LSTMCell<I, H, E, D> {
+    const NUM_STATES: usize = 2;
+
+    fn try_step<B: Dim, T: Tape<E, D>>(
+        &self,
+        x: Tensor<(B, I), E, D, T>,
+        state: Vec<Tensor<(B, H), E, D, T>>,
+    ) -> Result<Vec<Tensor<(B, H), E, D, T>>, Error> {
+        let mut state = state.into_iter();
+        let (h, c) = (state.next().unwrap(), state.next().unwrap());
+        let gates = try_gates(x, &self.weight_ih, &self.bias_ih)?.try_add(try_gates(
+            h,
+            &self.weight_hh,
+            &self.bias_hh,
+        )?)?;
+        let [i, f, g, o] = try_split_gates(gates)?;
+        let i = i.try_sigmoid()?;
+        let f = f.try_sigmoid()?;
+        let g = g.try_tanh()?;
+        let o = o.try_sigmoid()?;
+
+        let (c, tape) = f.try_mul(c)?.try_add(i.try_mul(g)?)?.split_tape();
+        let h = o.try_mul(c.clone().put_tape(tape).try_tanh()?)?;
+        Ok(std::vec![h, c.retaped::<T>()])
+    }
+}
+
+impl<B: Dim, I: Dim, H: Dim, E: Dtype, D: Device<E>, T: Tape<E, D>>
+    Module<(
+        Tensor<(B, I), E, D, T>,
+        (Tensor<(B, H), E, D, T>, Tensor<(B, H), E, D, T>),
+    )> for LSTMCell<I, H, E, D>
+{
+    type Output = (Tensor<(B, H), E, D, T>, Tensor<(B, H), E, D, T>);
+    fn try_forward(
+        &self,
+        (x, (h, c)): (
+            Tensor<(B, I), E, D, T>,
+            (Tensor<(B, H), E, D, T>, Tensor<(B, H), E, D, T>),
+        ),
+    ) -> Result<Self::Output, Error> {
+        let mut state = self.try_step(x, std::vec![h, c])?.into_iter();
+        Ok((state.next().unwrap(), state.next().unwrap()))
+    }
+}
+
+/// A multi-layer long short-term memory network over `(Batch, Seq, I)` inputs, made of [LSTMCell]s.
+///
+/// Outputs a tuple of:
+/// 1. The hidden state of the last layer at every step, `(Batch, Seq, num_directions * H)`.
+///    With `bidirectional`, the forward direction comes first in the last dimension.
+/// 2. The final hidden & cell states of every layer & direction, each `(num_layers * num_directions, Batch, H)`.
+///
+/// The initial states are zeros, unless they're passed in with the input as `(x, (h0, c0))`,
+/// where `h0` & `c0` have the same shapes as the final states. Their tapes are merged with
+/// the tape of `x`.
+///
+/// The tape goes to the outputs, and the final states are returned with empty tapes,
+/// see [RNN] for how to backpropagate through them.
+///
+/// **Pytorch equivalent**:
+/// ```python
+/// torch.nn.LSTM(I, H, num_layers=cfg.num_layers, bidirectional=cfg.bidirectional, batch_first=True)
+/// ```
+///
+/// Example:
+/// ```rust
+/// # use dfdx::prelude::*;
+/// # use dfdx::*;
+/// # let dev: Cpu = Default::default();
+/// let lstm = dev.build_module::<f32>(LSTMConfig::new(Const::<3>, Const::<5>, 2, false));
+/// let x: Tensor<Rank3<4, 7, 3>, f32, _> = dev.zeros();
+/// let (y, (h_n, c_n)) = lstm.forward(x);
+/// assert_eq!(y.shape(), &(Const::<4>, Const::<7>, 5));
+/// assert_eq!(c_n.shape(), &(2, Const::<4>, Const::<5>));
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct LSTMConfig<I: Dim, H: Dim> {
+    pub inp: I,
+    pub hidden: H,
+    /// The number of stacked layers. Defaults to `1`.
+    pub num_layers: usize,
+    /// Whether each layer also runs over the sequence in reverse. Defaults to `false`.
+    pub bidirectional: bool,
+}
+
+impl<I: Dim, H: Dim> LSTMConfig<I, H> {
+    pub fn new(inp: I, hidden: H, num_layers: usize, bidirectional: bool) -> Self {
+        Self {
+            inp,
+            hidden,
+            num_layers,
+            bidirectional,
+        }
+    }
+}
+
+impl<I: Dim + Default, H: Dim + Default> Default for LSTMConfig<I, H> {
+    fn default() -> Self {
+        Self::new(Default::default(), Default::default(), 1, false)
+    }
+}
+
+/// Compile time sugar alias around [LSTMConfig].
+pub type LSTMConstConfig<const I: usize, const H: usize> = LSTMConfig<Const<I>, Const<H>>;
+
+impl<I: Dim, H: Dim, E: Dtype, D: Device<E>> BuildOnDevice<E, D> for LSTMConfig<I, H> {
+    type Built = LSTM<I, H, E, D>;
+    fn try_build_on_device(&self, device: &D) -> Result<Self::Built, crate::tensor::Error> {
+        check_num_layers(self.num_layers)?;
+        let dirs = if self.bidirectional { 2 } else { 1 };
+        let first = LSTMCellConfig::new(self.inp, self.hidden);
+        let other = LSTMCellConfig::new(dirs * self.hidden.size(), self.hidden);
+        Ok(LSTM {
+            first_layer: std::vec![first; dirs].try_build_on_device(device)?,
+            other_layers: std::vec![other; (self.num_layers - 1) * dirs]
+                .try_build_on_device(device)?,
+        })
+    }
+}
+
+/// See [LSTMConfig].
+#[derive(Clone, Debug, ResetParams, UpdateParams, ZeroGrads)]
+#[cfg_attr(feature = "safetensors", derive(SaveSafeTensors, LoadSafeTensors))]
+pub struct LSTM<I: Dim, H: Dim, Elem: Dtype, Dev: Device<Elem>> {
+    /// The cells of the first layer, one per direction.
+    #[module]
+    #[cfg_attr(feature = "safetensors", serialize)]
+    pub first_layer: Vec<LSTMCell<I, H, Elem, Dev>>,
+    /// The cells of the remaining layers, one per direction for each layer.
+    #[module]
+    #[cfg_attr(feature = "safetensors", serialize)]
+    pub other_layers: Vec<LSTMCell<usize, H, Elem, Dev>>,
+}
+
+impl<B: Dim, S: Dim, I: Dim, H: Dim, E: Dtype, D: Device<E>, T: Tape<E, D>>
+    Module<Tensor<(B, S, I), E, D, T>> for LSTM<I, H, E, D>
+{
+    type Output = (
+        Tensor<(B, S, usize), E, D, T>,
+        (
+            Tensor<(usize, B, H), E, D, T>,
+            Tensor<(usize, B, H), E, D, T>,
+        ),
+    );
+    fn try_forward(&self, x: Tensor<(B, S, I), E, D, T>) -> Result<Self::Output, Error> {
+        let rows = self.first_layer.len() + self.other_layers.len();
+        let hidden = first_cell(&self.first_layer)?.weight_hh.shape().1;
+        let h0 = try_zero_states(&x, rows, hidden)?;
+        let c0 = try_zero_states(&x, rows, hidden)?;
+        self.try_forward((x, (h0, c0)))
+    }
+}
+
+impl<B: Dim, S: Dim, I: Dim, H: Dim, E: Dtype, D: Device<E>, T: Tape<E, D>>
+    Module<(
+        Tensor<(B, S, I), E, D, T>,
+        (
+            Tensor<(usize, B, H), E, D, T>,
+            Tensor<(usize, B, H), E, D, T>,
+        ),
+    )> for LSTM<I, H, E, D>
+{
+    type Output = (
+        Tensor<(B, S, usize), E, D, T>,
+        (
+            Tensor<(usize, B, H), E, D, T>,
+            Tensor<(usize, B, H), E, D, T>,
+        ),
+    );
+    fn try_forward(
+        &self,
+        (x, (h0, c0)): (
+            Tensor<(B, S, I), E, D, T>,
+            (
+                Tensor<(usize, B, H), E, D, T>,
+                Tensor<(usize, B, H), E, D, T>,
+            ),
+        ),
+    ) -> Result<Self::Output, Error> {
+        let (y, states) =
+            try_run_layers(&self.first_layer, &self.other_layers, x, std::vec![h0, c0])?;
+        let mut states = states.into_iter();
+        Ok((y, (states.next().unwrap(), states.next().unwrap())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{gradcheck::*, tests::*};
+
+    #[test]
+    fn test_lstm_cell() {
+        let dev: TestDevice = Default::default();
+        let cell = LSTMCell {
+            weight_ih: dev
+                .tensor([[[0.1, -0.2]], [[0.3, 0.4]], [[-0.5, 0.1]], [[0.2, 0.2]]])
+                .to_dtype::<TestDtype>(),
+            weight_hh: dev
+                .tensor([[[0.5]], [[-0.1]], [[0.3]], [[0.0]]])
+                .to_dtype::<TestDtype>(),
+            bias_ih: dev
+                .tensor([[0.1], [0.0], [0.2], [-0.1]])
+                .to_dtype::<TestDtype>(),
+            bias_hh: dev
+                .tensor([[0.0], [0.3], [0.0], [0.1]])
+                .to_dtype::<TestDtype>(),
+        };
+        let x = dev.tensor([[1.0, 2.0]]).to_dtype::<TestDtype>();
+        let h = dev.tensor([[0.5]]).to_dtype::<TestDtype>();
+        let c = dev.tensor([[-1.0]]).to_dtype::<TestDtype>();
+        let (h, c) = cell.forward((x.leaky_trace(), (h.leaky_trace(), c.leaky_trace())));
+        assert_close_to_literal!(h, [[-0.41714019]]);
+        assert_close_to_literal!(c, [[-0.76852609]]);
+
+        let g = h.sum().backward();
+        assert_close_to_literal!(
+            g.get(&cell.bias_ih),
+            [[0.0046950742], [-0.061496557], [0.19229674], [-0.147811]]
+        );
+    }
+
+    #[test]
+    fn test_lstm_cell_state_grads() {
+        let dev: TestDevice = Default::default();
+        let mut cell = dev.build_module::<TestDtype>(LSTMCellConstConfig::<3, 4>::default());
+        let x: Tensor<Rank2<2, 3>, TestDtype, _> = dev.sample_normal();
+        let h: Tensor<Rank2<2, 4>, TestDtype, _> = dev.sample_normal();
+        let c: Tensor<Rank2<2, 4>, TestDtype, _> = dev.sample_normal();
+        let report = gradcheck_module(
+            &mut cell,
+            &h,
+            |cell, h| {
+                let (h, c) = cell.forward((x.retaped(), (h, c.retaped())));
+                // move the tape of `h'` onto `c'` to backpropagate through both
+                let (h, tape) = h.split_tape();
+                let c = c.split_tape().0.put_tape(tape);
+                c.square().mean() + h.retaped::<OwnedTape<_, _>>().square().mean()
+            },
+            Default::default(),
+        );
+        assert!(report.passed(), "{report}");
+    }
+
+    #[test]
+    fn test_lstm_matches_cells() {
+        let dev: TestDevice = Default::default();
+        let lstm = dev.build_module::<TestDtype>(LSTMConstConfig::<3, 4>::default());
+        let x: Tensor<Rank3<2, 5, 3>, TestDtype, _> = dev.sample_normal();
+        let (y, (h_n, c_n)) = lstm.forward(x.clone());
+
+        let cell = &lstm.first_layer[0];
+        let mut h: Tensor<Rank2<2, 4>, TestDtype, _> = dev.zeros();
+        let mut c: Tensor<Rank2<2, 4>, TestDtype, _> = dev.zeros();
+        for t in 0..5 {
+            let x_t = x
+                .clone()
+                .slice((.., t..t + 1, ..))
+                .reshape_like(&(Const::<2>, Const::<3>));
+            (h, c) = cell.forward((x_t, (h, c)));
+            let y_t = y
+                .clone()
+                .slice((.., t..t + 1, ..))
+                .reshape_like(&(Const::<2>, Const::<4>));
+            assert_eq!(y_t.array(), h.array());
+        }
+        assert_eq!(
+            h_n.reshape_like(&(Const::<2>, Const::<4>)).array(),
+            h.array()
+        );
+        assert_eq!(
+            c_n.reshape_like(&(Const::<2>, Const::<4>)).array(),
+            c.array()
+        );
+    }
+
+    type States = Tensor<
+        (usize, Const<2>, Const<4>),
+        TestDtype,
+        TestDevice,
+        OwnedTape<TestDtype, TestDevice>,
+    >;
+
+    /// A loss over the outputs & both final states. The tape of the outputs is moved onto `c_n`.
+    fn bptt_loss(
+        y: Tensor<
+            (Const<2>, Const<6>, usize),
+            TestDtype,
+            TestDevice,
+            OwnedTape<TestDtype, TestDevice>,
+        >,
+        (h_n, c_n): (States, States),
+    ) -> Tensor<Rank0, TestDtype, TestDevice, OwnedTape<TestDtype, TestDevice>> {
+        let (y, tape) = y.split_tape();
+        let c_n = c_n.split_tape().0.put_tape(tape);
+        c_n.square().mean() + h_n.square().mean() + y.retaped::<OwnedTape<_, _>>().square().mean()
+    }
+
+    #[test]
+    fn test_lstm_bidirectional_grads() {
+        let dev: TestDevice = Default::default();
+        let mut lstm =
+            dev.build_module::<TestDtype>(LSTMConfig::new(Const::<3>, Const::<4>, 2, true));
+        let x: Tensor<Rank3<2, 6, 3>, TestDtype, _> = dev.sample_normal();
+        let (y, (h_n, c_n)) = lstm.forward(x.clone());
+        assert_eq!(y.shape(), &(Const::<2>, Const::<6>, 8));
+        assert_eq!(h_n.shape(), &(4, Const::<2>, Const::<4>));
+        assert_eq!(c_n.shape(), &(4, Const::<2>, Const::<4>));
+
+        // backpropagate through the outputs & both final states
+        let h0: Tensor<(usize, Const<2>, Const<4>), TestDtype, _> =
+            dev.sample_normal_like(&(4, Const, Const));
+        let c0: Tensor<(usize, Const<2>, Const<4>), TestDtype, _> =
+            dev.sample_normal_like(&(4, Const, Const));
+        let report = gradcheck_module(
+            &mut lstm,
+            &x,
+            |lstm, x| {
+                let (y, states) = lstm.forward((x, (h0.retaped(), c0.retaped())));
+                bptt_loss(y, states)
+            },
+            Default::default(),
+        );
+        assert!(report.passed(), "{report}");
+
+        let report = gradcheck_module(
+            &mut lstm,
+            &c0,
+            |lstm, c0| {
+                let (y, states) = lstm.forward((x.retaped(), (h0.retaped(), c0)));
+                bptt_loss(y, states)
+            },
+            Default::default(),
+        );
+        assert!(report.passed(), "{report}");
+    }
+
+    #[cfg(feature = "safetensors")]
+    #[test]
+    fn test_lstm_save_load() {
+        use tempfile::NamedTempFile;
+
+        let dev: TestDevice = Default::default();
+        let cfg = LSTMConfig::new(Const::<3>, Const::<4>, 2, true);
+        let lstm = dev.build_module::<TestDtype>(cfg);
+        let file = NamedTempFile::new().expect("failed to create tempfile");
+        lstm.save_safetensors(file.path()).unwrap();
+
+        let mut loaded = dev.build_module::<TestDtype>(cfg);
+        loaded.load_safetensors(file.path()).unwrap();
+        let x: Tensor<Rank3<2, 5, 3>, TestDtype, _> = dev.sample_normal();
+        let (y, _) = lstm.forward(x.clone());
+        let (loaded_y, _) = loaded.forward(x);
+        assert_eq!(y.as_vec(), loaded_y.as_vec());
+    }
+}
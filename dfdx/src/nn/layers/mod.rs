@@ -4,6 +4,7 @@ mod batch_norm1d;
 mod batch_norm2d;
 mod bias1d;
 mod bias2d;
+mod checkpoint;
 #[cfg(feature = "nightly")]
 mod conv1d;
 #[cfg(feature = "nightly")]
@@ -19,11 +20,14 @@ mod flatten2d;
 mod gelu;
 mod generalized_add;
 mod generalized_mul;
+mod grouped_query_attention;
+mod gru;
 mod layer_norm1d;
 mod leaky_relu;
 mod linear;
 mod ln;
 mod log_softmax;
+mod lstm;
 mod matmul;
 mod multi_head_attention;
 #[cfg(feature = "nightly")]
@@ -35,12 +39,14 @@ mod pool_2d_min;
 mod pool_global_avg;
 mod pool_global_max;
 mod pool_global_min;
+mod positional_encoding;
 mod prelu;
 mod prelu1d;
 mod relu;
 mod reshape;
 mod residual_add;
 mod residual_mul;
+mod rnn;
 mod sigmoid;
 mod sin;
 mod softmax;
@@ -57,6 +63,7 @@ pub use batch_norm1d::{BatchNorm1D, BatchNorm1DConfig, BatchNorm1DConstConfig};
 pub use batch_norm2d::{BatchNorm2D, BatchNorm2DConfig, BatchNorm2DConstConfig};
 pub use bias1d::{Bias1D, Bias1DConfig, Bias1DConstConfig};
 pub use bias2d::{Bias2D, Bias2DConfig, Bias2DConstConfig};
+pub use checkpoint::Checkpoint;
 #[cfg(feature = "nightly")]
 pub use conv1d::{Conv1D, Conv1DConfig, Conv1DConstConfig};
 #[cfg(feature = "nightly")]
@@ -65,20 +72,27 @@ pub use conv2d::{Conv2D, Conv2DConfig, Conv2DConstConfig};
 pub use conv_trans2d::{ConvTrans2D, ConvTrans2DConfig, ConvTrans2DConstConfig};
 pub use cos::Cos;
 pub use dropout::{Dropout, DropoutOneIn};
-pub use embedding::{Embedding, EmbeddingConfig, EmbeddingConstConfig};
+pub use embedding::{
+    Embedding, EmbeddingConfig, EmbeddingConstConfig, SparseEmbedding, SparseEmbeddingConfig,
+};
 pub use exp::Exp;
 #[cfg(feature = "nightly")]
 pub use flatten2d::Flatten2D;
 pub use gelu::{AccurateGeLU, FastGeLU};
 pub use generalized_add::GeneralizedAdd;
 pub use generalized_mul::GeneralizedMul;
+pub use grouped_query_attention::{GroupedQueryAttention, GroupedQueryAttentionConfig};
+pub use gru::{GRUCell, GRUCellConfig, GRUCellConstConfig, GRUConfig, GRUConstConfig, GRU};
 pub use layer_norm1d::{LayerNorm1D, LayerNorm1DConfig, LayerNorm1DConstConfig};
 pub use leaky_relu::LeakyReLU;
 pub use linear::{Linear, LinearConfig, LinearConstConfig};
 pub use ln::Ln;
 pub use log_softmax::LogSoftmax;
+pub use lstm::{LSTMCell, LSTMCellConfig, LSTMCellConstConfig, LSTMConfig, LSTMConstConfig, LSTM};
 pub use matmul::{MatMul, MatMulConfig, MatMulConstConfig};
-pub use multi_head_attention::{MultiHeadAttention, MultiHeadAttentionConfig};
+pub use multi_head_attention::{
+    AttentionMask, KVCache, MultiHeadAttention, MultiHeadAttentionConfig,
+};
 #[cfg(feature = "nightly")]
 pub use pool_2d_avg::{AvgPool2D, AvgPool2DConst};
 #[cfg(feature = "nightly")]
@@ -88,12 +102,14 @@ pub use pool_2d_min::{MinPool2D, MinPool2DConst};
 pub use pool_global_avg::AvgPoolGlobal;
 pub use pool_global_max::MaxPoolGlobal;
 pub use pool_global_min::MinPoolGlobal;
+pub use positional_encoding::SinusoidalPositionalEncoding;
 pub use prelu::{PReLU, PReLUConfig};
 pub use prelu1d::{PReLU1D, PReLU1DConfig};
 pub use relu::ReLU;
 pub use reshape::Reshape;
 pub use residual_add::ResidualAdd;
 pub use residual_mul::ResidualMul;
+pub use rnn::{RNNCell, RNNCellConfig, RNNCellConstConfig, RNNConfig, RNNConstConfig, RNN};
 pub use sigmoid::Sigmoid;
 pub use sin::Sin;
 pub use softmax::Softmax;
@@ -102,7 +118,7 @@ pub use sqrt::Sqrt;
 pub use square::Square;
 pub use tanh::Tanh;
 pub use transformer::{
-    DecoderBlock, DecoderBlockConfig, EncoderBlock, EncoderBlockConfig, Transformer,
-    TransformerConfig,
+    DecoderBlock, DecoderBlockCache, DecoderBlockConfig, EncoderBlock, EncoderBlockConfig,
+    Transformer, TransformerConfig,
 };
 pub use upscale2d::{Upscale2D, Upscale2DBy, Upscale2DByConst, Upscale2DConst};
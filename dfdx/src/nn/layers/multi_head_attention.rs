@@ -1,5 +1,9 @@
+#![allow(clippy::type_complexity)]
+
 use crate::prelude::*;
 
+use super::positional_encoding::try_rotary_embedding;
+
 use num_traits::Float;
 
 /// A multi-head attention layer.
@@ -10,6 +14,8 @@ use num_traits::Float;
 /// - *Optional* `K`: The size of key vectors. Defaults to `Embed`
 /// - *Optional* `V` The size of value vectors. Defaults to `Embed`
 ///
+/// See [GroupedQueryAttentionConfig] to project keys and values to fewer heads than queries.
+///
 /// **Pytorch equivalent**: `torch.nn.MultiheadAttention(Embed, NumHeads, batch_first=True)`
 ///
 /// Examples
@@ -30,6 +36,12 @@ pub struct MultiHeadAttentionConfig<Embed: Dim, NumHeads: Dim, K: Dim = Embed, V
     pub num_heads: NumHeads,
     pub k_dim: K,
     pub v_dim: V,
+    /// Dropout probability applied to the attention weights in [Module::try_forward_mut()],
+    /// see [MultiHeadAttentionConfig::with_dropout()]. Defaults to `0.0`.
+    pub dropout: f64,
+    /// Base of the rotary position embedding applied to queries and keys, if any,
+    /// see [MultiHeadAttentionConfig::with_rope()]. Defaults to `None`.
+    pub rope: Option<f64>,
 }
 
 impl<Embed: Dim, NumHeads: Dim, K: Dim, V: Dim> MultiHeadAttentionConfig<Embed, NumHeads, K, V> {
@@ -46,10 +58,497 @@ impl<Embed: Dim, NumHeads: Dim, K: Dim, V: Dim> MultiHeadAttentionConfig<Embed,
             num_heads,
             k_dim: k,
             v_dim: v,
+            dropout: 0.0,
+            rope: None,
+        }
+    }
+
+    /// Sets the dropout probability applied to the attention weights during training.
+    pub fn with_dropout(self, dropout: f64) -> Self {
+        self.try_with_dropout(dropout).unwrap()
+    }
+
+    /// Fallible version of [MultiHeadAttentionConfig::with_dropout()]. Returns
+    /// [Error::InvalidArgument] unless `0.0 <= dropout < 1.0`.
+    pub fn try_with_dropout(mut self, dropout: f64) -> Result<Self, Error> {
+        check_dropout(dropout)?;
+        self.dropout = dropout;
+        Ok(self)
+    }
+
+    /// Applies rotary position embedding ([RoFormer](https://arxiv.org/abs/2104.09864))
+    /// with the given `base` (usually `10000.0`) to queries and keys of each head.
+    pub fn with_rope(mut self, base: f64) -> Self {
+        self.rope = Some(base);
+        self
+    }
+}
+
+/// Returns [Error::InvalidArgument] unless the dropout probability `p` is in `[0.0, 1.0)`.
+pub(super) fn check_dropout(p: f64) -> Result<(), Error> {
+    if !(0.0..1.0).contains(&p) {
+        return Err(Error::InvalidArgument(std::format!(
+            "dropout probability must be in [0.0, 1.0), found {p}"
+        )));
+    }
+    Ok(())
+}
+
+/// Boolean masks applied to the attention weights of [MultiHeadAttention] before the softmax.
+/// `true` entries are masked out, meaning the query does not attend to that key.
+///
+/// - `attn`: a `(S1, S2)` mask shared by every batch item and head, e.g. [AttentionMask::causal()].
+/// - `key_padding`: a `(B, S2)` mask marking the padded keys of each batch item.
+///
+/// Masked weights are filled with the lowest finite value of the dtype rather than negative
+/// infinity, so a query whose keys are *all* masked attends to every key equally instead of
+/// producing `NaN`s.
+///
+/// **Pytorch equivalent**: the boolean `attn_mask` and `key_padding_mask` arguments of
+/// `torch.nn.MultiheadAttention.forward`.
+///
+/// Examples
+/// ```rust
+/// # use dfdx::prelude::*;
+/// # let dev: Cpu = Default::default();
+/// type Model = MultiHeadAttentionConfig<Const<8>, Const<2>>;
+/// let mha = dev.build_module::<f32>(Model::default());
+/// let x: Tensor<Rank3<2, 3, 8>, f32, _> = dev.sample_normal();
+/// let padding = dev.tensor([[false, false, false], [false, false, true]]);
+/// let mask = AttentionMask::causal(&dev, Const::<3>).with_key_padding(padding);
+/// let y = mha.forward((x.clone(), x.clone(), x, mask));
+/// ```
+#[derive(Clone, Debug)]
+pub struct AttentionMask<B: Dim, S1: Dim, S2: Dim, D: Storage<bool>> {
+    pub attn: Option<Tensor<(S1, S2), bool, D>>,
+    pub key_padding: Option<Tensor<(B, S2), bool, D>>,
+}
+
+impl<B: Dim, S1: Dim, S2: Dim, D: Storage<bool>> Default for AttentionMask<B, S1, S2, D> {
+    fn default() -> Self {
+        Self {
+            attn: None,
+            key_padding: None,
         }
     }
 }
 
+impl<B: Dim, S: Dim, D: Storage<bool> + TriangleTensor<bool>> AttentionMask<B, S, S, D> {
+    /// A causal mask, where each position may only attend to itself and earlier positions.
+    pub fn causal(device: &D, seq: S) -> Self {
+        Self::try_causal(device, seq).unwrap()
+    }
+
+    /// Fallible version of [AttentionMask::causal()]
+    pub fn try_causal(device: &D, seq: S) -> Result<Self, crate::tensor::Error> {
+        Ok(Self {
+            attn: Some(device.try_upper_tri_like(&(seq, seq), true, 1)?),
+            key_padding: None,
+        })
+    }
+}
+
+impl<B: Dim, S1: Dim, S2: Dim, D: Storage<bool>> AttentionMask<B, S1, S2, D> {
+    /// A mask of only padded keys, where `true` marks padding.
+    pub fn key_padding(mask: Tensor<(B, S2), bool, D>) -> Self {
+        Self {
+            attn: None,
+            key_padding: Some(mask),
+        }
+    }
+
+    /// Sets the key padding mask, where `true` marks padding.
+    pub fn with_key_padding(mut self, mask: Tensor<(B, S2), bool, D>) -> Self {
+        self.key_padding = Some(mask);
+        self
+    }
+
+    /// Fills the masked entries of `(B, H, S1, S2)` attention `weights` with the lowest finite value.
+    fn try_apply<H: Dim, E: Dtype + Float, T: Tape<E, D>>(
+        &self,
+        mut weights: Tensor<(B, H, S1, S2), E, D, T>,
+    ) -> Result<Tensor<(B, H, S1, S2), E, D, T>, crate::tensor::Error>
+    where
+        D: Device<E>,
+    {
+        let shape = *weights.shape();
+        let fill = weights
+            .device()
+            .try_tensor(E::min_value())?
+            .try_broadcast_like(&shape)?;
+        if let Some(attn) = &self.attn {
+            let masked = attn.clone().try_broadcast_like::<_, Axes2<0, 1>>(&shape)?;
+            let (w, tape) = weights.split_tape();
+            weights = masked.try_choose(fill.clone().put_tape(tape), w)?;
+        }
+        if let Some(key_padding) = &self.key_padding {
+            let masked = key_padding
+                .clone()
+                .try_broadcast_like::<_, Axes2<1, 2>>(&shape)?;
+            let (w, tape) = weights.split_tape();
+            weights = masked.try_choose(fill.put_tape(tape), w)?;
+        }
+        Ok(weights)
+    }
+}
+
+/// Keys and values of previous timesteps cached by [MultiHeadAttention] for incremental
+/// (autoregressive) decoding. Keys and values are stored after the `w_k` and `w_v`
+/// projections, so each new timestep only projects its own tokens.
+///
+/// Start decoding with [KVCache::default()], then pass the returned cache to the next step.
+///
+/// Examples
+/// ```rust
+/// # use dfdx::prelude::*;
+/// # let dev: Cpu = Default::default();
+/// type Model = MultiHeadAttentionConfig<Const<8>, Const<2>>;
+/// let mha = dev.build_module::<f32>(Model::default());
+/// let prompt: Tensor<Rank3<1, 3, 8>, f32, _> = dev.sample_normal();
+/// let (_, cache) = mha.forward((prompt, KVCache::default()));
+/// assert_eq!(cache.len(), 3);
+/// let token: Tensor<Rank3<1, 1, 8>, f32, _> = dev.sample_normal();
+/// let (y, cache) = mha.forward((token, cache));
+/// assert_eq!(cache.len(), 4);
+/// ```
+#[derive(Clone, Debug)]
+pub struct KVCache<B: Dim, K: Dim, V: Dim, E, D: Storage<E>> {
+    pub keys: Option<Tensor<(B, usize, K), E, D>>,
+    pub values: Option<Tensor<(B, usize, V), E, D>>,
+}
+
+impl<B: Dim, K: Dim, V: Dim, E, D: Storage<E>> Default for KVCache<B, K, V, E, D> {
+    fn default() -> Self {
+        Self {
+            keys: None,
+            values: None,
+        }
+    }
+}
+
+impl<B: Dim, K: Dim, V: Dim, E: Dtype, D: Device<E>> KVCache<B, K, V, E, D> {
+    /// The number of cached timesteps.
+    pub fn len(&self) -> usize {
+        self.keys.as_ref().map_or(0, |k| k.shape().1)
+    }
+
+    /// Whether nothing has been cached yet.
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_none()
+    }
+
+    /// Concatenates `keys` and `values` of new timesteps onto the cache.
+    pub fn try_append(
+        self,
+        keys: Tensor<(B, usize, K), E, D>,
+        values: Tensor<(B, usize, V), E, D>,
+    ) -> Result<Self, crate::tensor::Error> {
+        let keys = match self.keys {
+            Some(cached) => (cached, keys).try_concat_tensor_along(Axis::<1>)?,
+            None => keys,
+        };
+        let values = match self.values {
+            Some(cached) => (cached, values).try_concat_tensor_along(Axis::<1>)?,
+            None => values,
+        };
+        Ok(Self {
+            keys: Some(keys),
+            values: Some(values),
+        })
+    }
+}
+
+/// The parameters that [MultiHeadAttention] & [super::GroupedQueryAttention] attend with.
+/// Keys and values are projected to `KvK` & `KvV` features of `num_kv_heads` heads, each
+/// shared by `num_heads / num_kv_heads` consecutive query heads.
+pub(super) struct AttentionRef<'a, M: Dim, K: Dim, V: Dim, KvK: Dim, KvV: Dim, E, D>
+where
+    E: Dtype,
+    D: Device<E>,
+{
+    pub(super) w_q: &'a Linear<M, K, E, D>,
+    pub(super) w_k: &'a Linear<M, KvK, E, D>,
+    pub(super) w_v: &'a Linear<M, KvV, E, D>,
+    pub(super) w_o: &'a Linear<V, M, E, D>,
+    pub(super) num_heads: usize,
+    pub(super) num_kv_heads: usize,
+    pub(super) k_dim: K,
+    pub(super) v_dim: V,
+    pub(super) dropout: f64,
+    pub(super) rope: Option<f64>,
+}
+
+impl<M: Dim, H: Dim, K: Dim, V: Dim, E, D> MultiHeadAttention<M, H, K, V, E, D>
+where
+    E: Dtype,
+    D: Device<E>,
+{
+    pub(super) fn attention(&self) -> AttentionRef<'_, M, K, V, K, V, E, D> {
+        AttentionRef {
+            w_q: &self.w_q,
+            w_k: &self.w_k,
+            w_v: &self.w_v,
+            w_o: &self.w_o,
+            num_heads: self.num_heads.size(),
+            num_kv_heads: self.num_heads.size(),
+            k_dim: self.k_dim,
+            v_dim: self.v_dim,
+            dropout: self.dropout,
+            rope: self.rope,
+        }
+    }
+}
+
+impl<M: Dim, K: Dim, V: Dim, KvK: Dim, KvV: Dim, E, D> AttentionRef<'_, M, K, V, KvK, KvV, E, D>
+where
+    E: Dtype + Float,
+    D: Device<E>,
+{
+    /// Batched attention of `q` to `k` and `v`. Dropout is only applied to the
+    /// attention weights when `train` is true.
+    pub(super) fn try_attend<B: Dim, S1: Dim, S2: Dim, T: Tape<E, D>>(
+        &self,
+        q: Tensor<(B, S1, M), E, D, T>,
+        k: Tensor<(B, S2, M), E, D>,
+        v: Tensor<(B, S2, M), E, D>,
+        mask: Option<&AttentionMask<B, S1, S2, D>>,
+        train: bool,
+    ) -> Result<Tensor<(B, S1, M), E, D, T>, crate::tensor::Error> {
+        let k = self.w_k.try_forward(k.retaped::<T>())?;
+        let k = self.try_rope(k, 0)?;
+        let v = self.w_v.try_forward(v.retaped::<T>())?;
+        self.try_attend_projected(q, k, v, mask, 0, train)
+    }
+
+    /// Applies the rotary position embedding, if any, to projected queries or keys
+    /// whose first timestep is at position `offset`.
+    fn try_rope<B: Dim, S: Dim, X: Dim, T: Tape<E, D>>(
+        &self,
+        x: Tensor<(B, S, X), E, D, T>,
+        offset: usize,
+    ) -> Result<Tensor<(B, S, X), E, D, T>, crate::tensor::Error> {
+        match self.rope {
+            Some(base) => {
+                let (b, s, x_dim) = *x.shape();
+                let head = self.k_dim.size() / self.num_heads;
+                let x = x.try_reshape_like(&(b, s, x_dim.size() / head, head))?;
+                let x = try_rotary_embedding(x, base, offset)?;
+                x.try_reshape_like(&(b, s, x_dim))
+            }
+            None => Ok(x),
+        }
+    }
+
+    /// Same as [AttentionRef::try_attend()], but `k` and `v` have already been
+    /// passed through `w_k` and `w_v`. The first query is at position `q_offset`.
+    fn try_attend_projected<B: Dim, S1: Dim, S2: Dim, T: Tape<E, D>>(
+        &self,
+        q: Tensor<(B, S1, M), E, D, T>,
+        k: Tensor<(B, S2, KvK), E, D, T>,
+        v: Tensor<(B, S2, KvV), E, D, T>,
+        mask: Option<&AttentionMask<B, S1, S2, D>>,
+        q_offset: usize,
+        train: bool,
+    ) -> Result<Tensor<(B, S1, M), E, D, T>, crate::tensor::Error> {
+        assert_eq!(q.shape().0, k.shape().0);
+        assert_eq!(q.shape().0, v.shape().0);
+        assert_eq!(k.shape().1, v.shape().1);
+
+        let (b, s1, _) = *q.shape();
+        let s2 = v.shape().1;
+        let h_dim = self.num_heads;
+        let kv_dim = self.num_kv_heads;
+        let k_dim = self.k_dim.size();
+        let v_dim = self.v_dim.size();
+
+        let v = v.try_reshape_like(&(b, s2, kv_dim, v_dim / h_dim))?;
+        let v = v.try_permute::<_, Axes4<0, 2, 1, 3>>()?;
+        let v = self.try_repeat_kv(v)?;
+
+        let k = k.try_reshape_like(&(b, s2, kv_dim, k_dim / h_dim))?;
+        let k = k.try_permute::<_, Axes4<0, 2, 3, 1>>()?;
+        let k = self.try_repeat_kv(k)?;
+
+        let q = self.w_q.try_forward(q)?;
+        let q = self.try_rope(q, q_offset)?;
+        let q = q.try_reshape_like(&(b, s1, h_dim, k_dim / h_dim))?;
+        let q = q.try_permute::<_, Axes4<0, 2, 1, 3>>()?;
+
+        // Get weights
+        let scalar = 1.0 / ((k_dim / h_dim) as f64).sqrt();
+        let weights = q.try_matmul(k)?.try_mul(scalar)?;
+        let weights = match mask {
+            Some(mask) => mask.try_apply(weights)?,
+            None => weights,
+        };
+        let weights = weights.try_softmax::<Axis<3>>()?;
+        let weights = if train && self.dropout > 0.0 {
+            weights.try_dropout(self.dropout)?
+        } else {
+            weights
+        };
+
+        // Get new tokens
+        let tokens = weights.try_matmul(v)?;
+        let tokens = tokens.try_permute::<_, Axes4<0, 2, 1, 3>>()?;
+        let tokens = tokens.try_reshape_like(&(b, s1, self.v_dim))?;
+
+        self.w_o.try_forward(tokens)
+    }
+
+    /// Shares each of the `num_kv_heads` key/value heads in axis 1 with its group of query heads.
+    fn try_repeat_kv<B: Dim, X: Dim, Y: Dim, T: Tape<E, D>>(
+        &self,
+        x: Tensor<(B, usize, X, Y), E, D, T>,
+    ) -> Result<Tensor<(B, usize, X, Y), E, D, T>, crate::tensor::Error> {
+        let h_dim = self.num_heads;
+        if self.num_kv_heads == h_dim {
+            return Ok(x);
+        }
+        let (b, kv_dim, x_dim, y_dim) = *x.shape();
+        let groups = h_dim / kv_dim;
+        let x = x.try_broadcast_like::<_, Axis<2>>(&(b, kv_dim, groups, x_dim, y_dim))?;
+        x.try_reshape_like(&(b, h_dim, x_dim, y_dim))
+    }
+
+    /// Unbatched version of [AttentionRef::try_attend()], run as a batch of 1.
+    pub(super) fn try_attend_unbatched<S1: Dim, S2: Dim, T: Tape<E, D>>(
+        &self,
+        q: Tensor<(S1, M), E, D, T>,
+        k: Tensor<(S2, M), E, D>,
+        v: Tensor<(S2, M), E, D>,
+        mask: Option<&AttentionMask<Const<1>, S1, S2, D>>,
+        train: bool,
+    ) -> Result<Tensor<(S1, M), E, D, T>, crate::tensor::Error> {
+        assert_eq!(k.shape().0, v.shape().0);
+        let (s1, m) = *q.shape();
+        let s2 = k.shape().0;
+        let q = q.try_broadcast_like(&(Const::<1>, s1, m))?;
+        let k = k.try_broadcast_like(&(Const::<1>, s2, m))?;
+        let v = v.try_broadcast_like(&(Const::<1>, s2, m))?;
+        let out = self.try_attend(q, k, v, mask, train)?;
+        out.try_reshape_like(&(s1, m))
+    }
+
+    /// Projects `k` and `v` with `w_k` and `w_v`, in the layout stored by [KVCache].
+    /// The first key is at position `offset`.
+    pub(super) fn try_project_kv<B: Dim, S2: Dim, T: Tape<E, D>>(
+        &self,
+        k: Tensor<(B, S2, M), E, D, T>,
+        v: Tensor<(B, S2, M), E, D, T>,
+        offset: usize,
+    ) -> Result<
+        (
+            Tensor<(B, usize, KvK), E, D, T>,
+            Tensor<(B, usize, KvV), E, D, T>,
+        ),
+        crate::tensor::Error,
+    > {
+        let (b, s2, _) = *k.shape();
+        let k = self.try_rope(self.w_k.try_forward(k)?, offset)?;
+        let k_dim = k.shape().2;
+        let k = k.try_reshape_like(&(b, s2.size(), k_dim))?;
+        let v = self.w_v.try_forward(v)?;
+        let v_dim = v.shape().2;
+        let v = v.try_reshape_like(&(b, s2.size(), v_dim))?;
+        Ok((k, v))
+    }
+
+    /// Attention of `q` to the `s2` keys and values stored in `cache`.
+    /// The first query is at position `q_offset`.
+    pub(super) fn try_attend_cached<B: Dim, S1: Dim, S2: Dim, T: Tape<E, D>>(
+        &self,
+        q: Tensor<(B, S1, M), E, D, T>,
+        cache: &KVCache<B, KvK, KvV, E, D>,
+        s2: S2,
+        mask: Option<&AttentionMask<B, S1, S2, D>>,
+        q_offset: usize,
+    ) -> Result<Tensor<(B, S1, M), E, D, T>, crate::tensor::Error> {
+        let (Some(k), Some(v)) = (&cache.keys, &cache.values) else {
+            return Err(crate::tensor::Error::InvalidArgument(
+                "KVCache must not be empty".into(),
+            ));
+        };
+        if k.shape().1 != s2.size() || v.shape().1 != s2.size() {
+            return Err(crate::tensor::Error::ShapeMismatch {
+                expected: vec![s2.size()],
+                found: vec![k.shape().1, v.shape().1],
+            });
+        }
+        let (b, _, k_dim) = *k.shape();
+        let k = k.clone().try_reshape_like(&(b, s2, k_dim))?;
+        let v_dim = v.shape().2;
+        let v = v.clone().try_reshape_like(&(b, s2, v_dim))?;
+        self.try_attend_projected(q, k.retaped::<T>(), v.retaped::<T>(), mask, q_offset, false)
+    }
+
+    /// Causal self attention of the new timesteps `x` to themselves and every cached timestep.
+    /// Returns the cache with `x`'s keys and values appended.
+    ///
+    /// Gradients flow through the keys and values of `x`, while the cached ones are constants.
+    pub(super) fn try_self_attend_cached<B: Dim, S: Dim, T: Tape<E, D>>(
+        &self,
+        x: Tensor<(B, S, M), E, D, T>,
+        cache: KVCache<B, KvK, KvV, E, D>,
+    ) -> Result<(Tensor<(B, S, M), E, D, T>, KVCache<B, KvK, KvV, E, D>), crate::tensor::Error>
+    where
+        D: TriangleTensor<bool>,
+    {
+        let s = x.shape().1;
+        let past = cache.len();
+        let (x, tape) = x.split_tape();
+        let (k, v) = self.try_project_kv(x.clone().put_tape(tape), x.retaped::<T>(), past)?;
+        let (k, v) = match (cache.keys, cache.values) {
+            (Some(keys), Some(values)) => (
+                (keys.retaped::<T>(), k).try_concat_tensor_along(Axis::<1>)?,
+                (values.retaped::<T>(), v).try_concat_tensor_along(Axis::<1>)?,
+            ),
+            (None, None) => (k, v),
+            _ => {
+                return Err(crate::tensor::Error::InvalidArgument(
+                    "KVCache must have both keys and values, or neither".into(),
+                ))
+            }
+        };
+        let cache = KVCache {
+            keys: Some(k.retaped()),
+            values: Some(v.retaped()),
+        };
+        // the i-th new timestep may attend to the first `past + i + 1` timesteps
+        let mask = AttentionMask {
+            attn: Some(x.device().try_upper_tri_like(
+                &(s, cache.len()),
+                true,
+                past as isize + 1,
+            )?),
+            key_padding: None,
+        };
+        let y = self.try_attend_projected(x.retaped::<T>(), k, v, Some(&mask), past, false)?;
+        Ok((y, cache))
+    }
+}
+
+impl<M: Dim, H: Dim, K: Dim, V: Dim, E, D, B, S, T>
+    Module<(Tensor<(B, S, M), E, D, T>, KVCache<B, K, V, E, D>)>
+    for MultiHeadAttention<M, H, K, V, E, D>
+where
+    E: Dtype + Float,
+    D: Device<E> + TriangleTensor<bool>,
+    B: Dim,
+    S: Dim,
+    T: Tape<E, D>,
+{
+    type Output = (Tensor<(B, S, M), E, D, T>, KVCache<B, K, V, E, D>);
+
+    /// Incremental causal self attention of new timesteps, using and extending the [KVCache]
+    fn try_forward(
+        &self,
+        (x, cache): (Tensor<(B, S, M), E, D, T>, KVCache<B, K, V, E, D>),
+    ) -> Result<Self::Output, crate::tensor::Error> {
+        self.attention().try_self_attend_cached(x, cache)
+    }
+}
+
 impl<M: Dim, H: Dim, K: Dim, V: Dim, E, D, S1, S2, T>
     Module<(
         Tensor<(S1, M), E, D, T>,
@@ -74,14 +573,62 @@ where
             Tensor<(S2, M), E, D>,
         ),
     ) -> Result<Self::Output, crate::tensor::Error> {
-        assert_eq!(k.shape().0, v.shape().0);
-        let (s1, m) = *q.shape();
-        let s2 = k.shape().0;
-        let q = q.broadcast_like(&(Const::<1>, s1, m));
-        let k = k.broadcast_like(&(Const::<1>, s2, m));
-        let v = v.broadcast_like(&(Const::<1>, s2, m));
-        let out = self.try_forward((q, k, v))?;
-        out.try_reshape_like(&(s1, m))
+        self.attention().try_attend_unbatched(q, k, v, None, false)
+    }
+
+    fn try_forward_mut(
+        &mut self,
+        (q, k, v): (
+            Tensor<(S1, M), E, D, T>,
+            Tensor<(S2, M), E, D>,
+            Tensor<(S2, M), E, D>,
+        ),
+    ) -> Result<Self::Output, crate::tensor::Error> {
+        self.attention().try_attend_unbatched(q, k, v, None, true)
+    }
+}
+
+impl<M: Dim, H: Dim, K: Dim, V: Dim, E, D, S1, S2, T>
+    Module<(
+        Tensor<(S1, M), E, D, T>,
+        Tensor<(S2, M), E, D>,
+        Tensor<(S2, M), E, D>,
+        AttentionMask<Const<1>, S1, S2, D>,
+    )> for MultiHeadAttention<M, H, K, V, E, D>
+where
+    E: Dtype + Float,
+    D: Device<E>,
+    S1: Dim,
+    S2: Dim,
+    T: Tape<E, D>,
+{
+    type Output = Tensor<(S1, M), E, D, T>;
+
+    /// Masked Encoder-Decoder style self attention
+    fn try_forward(
+        &self,
+        (q, k, v, mask): (
+            Tensor<(S1, M), E, D, T>,
+            Tensor<(S2, M), E, D>,
+            Tensor<(S2, M), E, D>,
+            AttentionMask<Const<1>, S1, S2, D>,
+        ),
+    ) -> Result<Self::Output, crate::tensor::Error> {
+        self.attention()
+            .try_attend_unbatched(q, k, v, Some(&mask), false)
+    }
+
+    fn try_forward_mut(
+        &mut self,
+        (q, k, v, mask): (
+            Tensor<(S1, M), E, D, T>,
+            Tensor<(S2, M), E, D>,
+            Tensor<(S2, M), E, D>,
+            AttentionMask<Const<1>, S1, S2, D>,
+        ),
+    ) -> Result<Self::Output, crate::tensor::Error> {
+        self.attention()
+            .try_attend_unbatched(q, k, v, Some(&mask), true)
     }
 }
 
@@ -110,39 +657,61 @@ where
             Tensor<(B, S2, M), E, D>,
         ),
     ) -> Result<Self::Output, crate::tensor::Error> {
-        assert_eq!(q.shape().0, k.shape().0);
-        assert_eq!(q.shape().0, v.shape().0);
-        assert_eq!(k.shape().1, v.shape().1);
-
-        let (b, s1, _) = *q.shape();
-        let s2 = v.shape().1;
-        let h_dim = self.num_heads.size();
-        let k_dim = self.k_dim.size();
-        let v_dim = self.v_dim.size();
-
-        let v = self.w_v.try_forward(v.retaped::<T>())?;
-        let v = v.try_reshape_like(&(b, s2, h_dim, v_dim / h_dim))?;
-        let v = v.try_permute::<_, Axes4<0, 2, 1, 3>>()?;
-
-        let k = self.w_k.try_forward(k.retaped::<T>())?;
-        let k = k.try_reshape_like(&(b, s2, h_dim, k_dim / h_dim))?;
-        let k = k.try_permute::<_, Axes4<0, 2, 3, 1>>()?;
+        self.attention().try_attend(q, k, v, None, false)
+    }
 
-        let q = self.w_q.try_forward(q)?;
-        let q = q.try_reshape_like(&(b, s1, h_dim, k_dim / h_dim))?;
-        let q = q.try_permute::<_, Axes4<0, 2, 1, 3>>()?;
+    fn try_forward_mut(
+        &mut self,
+        (q, k, v): (
+            Tensor<(B, S1, M), E, D, T>,
+            Tensor<(B, S2, M), E, D>,
+            Tensor<(B, S2, M), E, D>,
+        ),
+    ) -> Result<Self::Output, crate::tensor::Error> {
+        self.attention().try_attend(q, k, v, None, true)
+    }
+}
 
-        // Get weights
-        let scalar = 1.0 / ((k_dim / h_dim) as f64).sqrt();
-        let weights = q.try_matmul(k)?.try_mul(scalar)?;
-        let weights = weights.try_softmax::<Axis<3>>()?;
+impl<M: Dim, H: Dim, K: Dim, V: Dim, E, D, B, S1, S2, T>
+    Module<(
+        Tensor<(B, S1, M), E, D, T>,
+        Tensor<(B, S2, M), E, D>,
+        Tensor<(B, S2, M), E, D>,
+        AttentionMask<B, S1, S2, D>,
+    )> for MultiHeadAttention<M, H, K, V, E, D>
+where
+    E: Dtype + Float,
+    D: Device<E>,
+    B: Dim,
+    S1: Dim,
+    S2: Dim,
+    T: Tape<E, D>,
+{
+    type Output = Tensor<(B, S1, M), E, D, T>;
 
-        // Get new tokens
-        let tokens = weights.try_matmul(v)?;
-        let tokens = tokens.try_permute::<_, Axes4<0, 2, 1, 3>>()?;
-        let tokens = tokens.try_reshape_like(&(b, s1, self.v_dim))?;
+    /// Batched masked Encoder-Decoder style self attention
+    fn try_forward(
+        &self,
+        (q, k, v, mask): (
+            Tensor<(B, S1, M), E, D, T>,
+            Tensor<(B, S2, M), E, D>,
+            Tensor<(B, S2, M), E, D>,
+            AttentionMask<B, S1, S2, D>,
+        ),
+    ) -> Result<Self::Output, crate::tensor::Error> {
+        self.attention().try_attend(q, k, v, Some(&mask), false)
+    }
 
-        self.w_o.try_forward(tokens)
+    fn try_forward_mut(
+        &mut self,
+        (q, k, v, mask): (
+            Tensor<(B, S1, M), E, D, T>,
+            Tensor<(B, S2, M), E, D>,
+            Tensor<(B, S2, M), E, D>,
+            AttentionMask<B, S1, S2, D>,
+        ),
+    ) -> Result<Self::Output, crate::tensor::Error> {
+        self.attention().try_attend(q, k, v, Some(&mask), true)
     }
 }
 
@@ -159,13 +728,18 @@ where
         let (src, tape) = src.split_tape();
         self.try_forward((src.clone().put_tape(tape), src.clone(), src))
     }
+
+    fn try_forward_mut(&mut self, src: Src) -> Result<Self::Output, crate::tensor::Error> {
+        let (src, tape) = src.split_tape();
+        self.try_forward_mut((src.clone().put_tape(tape), src.clone(), src))
+    }
 }
 
 #[cfg(test)]
 #[allow(clippy::excessive_precision)]
 mod tests {
     use super::*;
-    use crate::tests::*;
+    use crate::{gradcheck::*, tests::*};
 
     #[test]
     fn test_mha_unbatched() {
@@ -280,4 +854,209 @@ mod tests {
         let mut opt = crate::nn::optim::Sgd::new(&mha, Default::default());
         opt.update(&mut mha, &g).expect("");
     }
+
+    #[test]
+    fn test_mha_causal_mask() {
+        let dev: TestDevice = Default::default();
+
+        let mha = dev
+            .build_module::<TestDtype>(<MultiHeadAttentionConfig<Const<8>, Const<2>>>::default());
+
+        let x: Tensor<Rank3<2, 4, 8>, TestDtype, _> = dev.sample_normal();
+        let y = mha.forward((
+            x.clone(),
+            x.clone(),
+            x.clone(),
+            AttentionMask::causal(&dev, Const),
+        ));
+
+        // each position only attends to itself and the positions before it
+        for i in 0..4 {
+            let q = x.clone().slice((.., i..i + 1, ..));
+            let kv = x.clone().slice((.., ..i + 1, ..));
+            let expected = mha.forward((q, kv.clone(), kv));
+            let expected = expected.realize::<Rank3<2, 1, 8>>().array();
+            let y_i = y.clone().slice((.., i..i + 1, ..));
+            let y_i = y_i.realize::<Rank3<2, 1, 8>>().array();
+            y_i.assert_close(&expected, y_i.get_default_tol());
+        }
+    }
+
+    #[test]
+    fn test_mha_key_padding_mask() {
+        let dev: TestDevice = Default::default();
+
+        let mha = dev
+            .build_module::<TestDtype>(<MultiHeadAttentionConfig<Const<8>, Const<2>>>::default());
+
+        let q: Tensor<Rank3<1, 3, 8>, TestDtype, _> = dev.sample_normal();
+        let k: Tensor<Rank3<1, 4, 8>, TestDtype, _> = dev.sample_normal();
+        let v: Tensor<Rank3<1, 4, 8>, TestDtype, _> = dev.sample_normal();
+        let mask = AttentionMask::key_padding(dev.tensor([[false, false, false, true]]));
+        let y = mha.forward((q.clone(), k.clone(), v.clone(), mask));
+
+        // the padded key is ignored
+        let k = k.slice((.., ..3, ..)).realize::<Rank3<1, 3, 8>>();
+        let v = v.slice((.., ..3, ..)).realize::<Rank3<1, 3, 8>>();
+        let expected = mha.forward((q, k, v));
+        let y = y.array();
+        y.assert_close(&expected.array(), y.get_default_tol());
+    }
+
+    #[test]
+    fn test_mha_fully_masked() {
+        let dev: TestDevice = Default::default();
+
+        let mha = dev
+            .build_module::<TestDtype>(<MultiHeadAttentionConfig<Const<8>, Const<2>>>::default());
+
+        let q: Tensor<Rank3<2, 3, 8>, TestDtype, _> = dev.sample_normal();
+        let k: Tensor<Rank3<2, 4, 8>, TestDtype, _> = dev.sample_normal();
+        let v: Tensor<Rank3<2, 4, 8>, TestDtype, _> = dev.sample_normal();
+        let mask = AttentionMask::key_padding(dev.tensor([[false; 4], [true; 4]]));
+        let y = mha.forward((q.leaky_trace(), k, v, mask));
+        assert!(y.as_vec().iter().all(|y| y.is_finite()));
+
+        let g = y.square().mean().backward();
+        assert!(g.get(&q).as_vec().iter().all(|g| g.is_finite()));
+        assert!(g
+            .get(&mha.w_q.weight)
+            .as_vec()
+            .iter()
+            .all(|g| g.is_finite()));
+    }
+
+    #[test]
+    fn test_mha_dropout() {
+        let dev: TestDevice = Default::default();
+
+        let mut mha = dev.build_module::<TestDtype>(
+            <MultiHeadAttentionConfig<Const<8>, Const<2>>>::default().with_dropout(0.5),
+        );
+
+        let x: Tensor<Rank3<2, 4, 8>, TestDtype, _> = dev.sample_normal();
+        let y1 = mha.forward(x.clone());
+        let y2 = mha.forward(x.clone());
+        assert_eq!(y1.array(), y2.array());
+
+        let y3 = mha.forward_mut(x.leaky_trace());
+        assert_ne!(y1.array(), y3.array());
+        let g = y3.square().mean().backward();
+
+        let mut opt = crate::nn::optim::Sgd::new(&mha, Default::default());
+        opt.update(&mut mha, &g).expect("");
+    }
+
+    #[test]
+    fn test_mha_invalid_dropout() {
+        type Model = MultiHeadAttentionConfig<Const<8>, Const<2>>;
+        for p in [-0.1, 1.0, f64::NAN] {
+            assert!(matches!(
+                Model::default().try_with_dropout(p),
+                Err(Error::InvalidArgument(_))
+            ));
+        }
+        assert_eq!(Model::default().try_with_dropout(0.0).unwrap().dropout, 0.0);
+    }
+
+    #[test]
+    fn test_mha_kv_cache() {
+        let dev: TestDevice = Default::default();
+
+        let mha = dev
+            .build_module::<TestDtype>(<MultiHeadAttentionConfig<Const<8>, Const<2>>>::default());
+
+        let x: Tensor<Rank3<2, 5, 8>, TestDtype, _> = dev.sample_normal();
+        let expected = mha.forward((
+            x.clone(),
+            x.clone(),
+            x.clone(),
+            AttentionMask::causal(&dev, Const),
+        ));
+
+        // a prompt of 3 timesteps, then 1 timestep at a time
+        let (y, cache) = mha.forward((x.clone().slice((.., ..3, ..)), KVCache::default()));
+        assert_eq!(cache.len(), 3);
+        let y = y.realize::<Rank3<2, 3, 8>>().array();
+        let e = expected
+            .clone()
+            .slice((.., ..3, ..))
+            .realize::<Rank3<2, 3, 8>>();
+        y.assert_close(&e.array(), y.get_default_tol());
+
+        let mut cache = cache;
+        for i in 3..5 {
+            let (y, new_cache) = mha.forward((x.clone().slice((.., i..i + 1, ..)), cache));
+            cache = new_cache;
+            assert_eq!(cache.len(), i + 1);
+            let y = y.realize::<Rank3<2, 1, 8>>().array();
+            let e = expected.clone().slice((.., i..i + 1, ..));
+            let e = e.realize::<Rank3<2, 1, 8>>();
+            y.assert_close(&e.array(), y.get_default_tol());
+        }
+    }
+
+    #[test]
+    fn test_mha_kv_cache_grads() {
+        let dev: TestDevice = Default::default();
+        let mut mha = dev.build_module::<TestDtype>(
+            <MultiHeadAttentionConfig<Const<8>, Const<2>>>::default().with_rope(10000.0),
+        );
+
+        let prompt: Tensor<Rank3<2, 3, 8>, TestDtype, _> = dev.sample_normal();
+        let (_, cache) = mha.forward((prompt, KVCache::default()));
+        assert!(mha
+            .try_forward((
+                dev.sample_normal::<Rank3<2, 1, 8>>(),
+                KVCache {
+                    keys: cache.keys.clone(),
+                    values: None,
+                }
+            ))
+            .is_err());
+
+        // gradients flow through the queries, keys and values of the new timesteps
+        let x: Tensor<Rank3<2, 2, 8>, TestDtype, _> = dev.sample_normal();
+        let report = gradcheck_module(
+            &mut mha,
+            &x,
+            |mha, x| mha.forward((x, cache.clone())).0.square().mean(),
+            Default::default(),
+        );
+        assert!(report.passed(), "{report}");
+    }
+
+    #[test]
+    fn test_mha_rope() {
+        let dev: TestDevice = Default::default();
+
+        let mut mha = dev.build_module::<TestDtype>(
+            <MultiHeadAttentionConfig<Const<8>, Const<2>>>::default().with_rope(10000.0),
+        );
+
+        let x: Tensor<Rank3<2, 4, 8>, TestDtype, _> = dev.sample_normal();
+        let causal = AttentionMask::causal(&dev, Const);
+        let expected = mha.forward((x.clone(), x.clone(), x.clone(), causal.clone()));
+
+        // with rope, attention depends on the positions of the timesteps
+        mha.rope = None;
+        let unrotated = mha.forward((x.clone(), x.clone(), x.clone(), causal));
+        assert_ne!(expected.array(), unrotated.array());
+        mha.rope = Some(10000.0);
+
+        // cached decoding rotates the new timesteps by their offset
+        let mut cache = KVCache::default();
+        for i in 0..4 {
+            let (y, new_cache) = mha.forward((x.clone().slice((.., i..i + 1, ..)), cache));
+            cache = new_cache;
+            let y = y.realize::<Rank3<2, 1, 8>>().array();
+            let e = expected.clone().slice((.., i..i + 1, ..));
+            let e = e.realize::<Rank3<2, 1, 8>>();
+            y.assert_close(&e.array(), y.get_default_tol());
+        }
+
+        let g = mha.forward(x.leaky_trace()).square().mean().backward();
+        let mut opt = crate::nn::optim::Sgd::new(&mha, Default::default());
+        opt.update(&mut mha, &g).expect("");
+    }
 }
@@ -0,0 +1,278 @@
+#![allow(clippy::type_complexity)]
+
+use crate::prelude::*;
+
+/// The angle of `pos` for the `i`-th pair of features out of `dim`: `pos / base^(2i / dim)`.
+fn angle(pos: usize, i: usize, dim: usize, base: f64) -> f64 {
+    pos as f64 / base.powf((2 * i) as f64 / dim as f64)
+}
+
+/// Adds the fixed sinusoidal positional encoding from
+/// [Attention is all you need](https://arxiv.org/abs/1706.03762) to `(S, M)` or `(B, S, M)` inputs:
+///
+/// - `PE[pos, 2i] = sin(pos / base^(2i / M))`
+/// - `PE[pos, 2i + 1] = cos(pos / base^(2i / M))`
+///
+/// This layer has no parameters, and works with runtime sequence lengths. Inputs can be paired
+/// with a position offset `(x, offset)`, so that the first timestep of `x` is encoded as
+/// position `offset`, for example when decoding one timestep at a time.
+///
+/// Examples:
+/// ```rust
+/// # use dfdx::prelude::*;
+/// # let dev: Cpu = Default::default();
+/// let pe: SinusoidalPositionalEncoding = Default::default();
+/// let x: Tensor<Rank3<2, 3, 4>, f32, _> = dev.zeros();
+/// let y = pe.forward(x);
+/// assert_eq!(y.array()[0][0], [0.0, 1.0, 0.0, 1.0]);
+/// let x: Tensor<Rank3<2, 1, 4>, f32, _> = dev.zeros();
+/// let y = pe.forward((x, 2));
+/// assert_eq!(y.array()[0][0], [2.0f32.sin(), 2.0f32.cos(), 0.02f32.sin(), 0.02f32.cos()]);
+/// ```
+#[derive(Clone, Copy, Debug, CustomModule)]
+pub struct SinusoidalPositionalEncoding {
+    pub base: f64,
+}
+
+impl Default for SinusoidalPositionalEncoding {
+    /// Sets `self.base` to `10000.0`
+    fn default() -> Self {
+        Self { base: 10000.0 }
+    }
+}
+
+impl SinusoidalPositionalEncoding {
+    /// The `(S, M)` encoding of positions `offset..offset + S`.
+    fn try_encoding<S: Dim, M: Dim, E: Dtype, D: Device<E>>(
+        &self,
+        device: &D,
+        (seq, model): (S, M),
+        offset: usize,
+    ) -> Result<Tensor<(S, M), E, D>, Error> {
+        let dim = model.size();
+        let mut data = Vec::with_capacity(seq.size() * dim);
+        for pos in offset..offset + seq.size() {
+            for j in 0..dim {
+                let a = angle(pos, j / 2, dim, self.base);
+                let x = if j % 2 == 0 { a.sin() } else { a.cos() };
+                data.push(E::from_f64(x).unwrap());
+            }
+        }
+        device.try_tensor_from_vec(data, (seq, model))
+    }
+}
+
+impl<S: Dim, M: Dim, E: Dtype, D: Device<E>, T: Tape<E, D>> Module<Tensor<(S, M), E, D, T>>
+    for SinusoidalPositionalEncoding
+{
+    type Output = Tensor<(S, M), E, D, T>;
+    fn try_forward(&self, x: Tensor<(S, M), E, D, T>) -> Result<Self::Output, Error> {
+        self.try_forward((x, 0))
+    }
+}
+
+impl<S: Dim, M: Dim, E: Dtype, D: Device<E>, T: Tape<E, D>> Module<(Tensor<(S, M), E, D, T>, usize)>
+    for SinusoidalPositionalEncoding
+{
+    type Output = Tensor<(S, M), E, D, T>;
+    fn try_forward(
+        &self,
+        (x, offset): (Tensor<(S, M), E, D, T>, usize),
+    ) -> Result<Self::Output, Error> {
+        let pe = self.try_encoding(x.device(), *x.shape(), offset)?;
+        x.try_add(pe)
+    }
+}
+
+impl<B: Dim, S: Dim, M: Dim, E: Dtype, D: Device<E>, T: Tape<E, D>>
+    Module<Tensor<(B, S, M), E, D, T>> for SinusoidalPositionalEncoding
+{
+    type Output = Tensor<(B, S, M), E, D, T>;
+    fn try_forward(&self, x: Tensor<(B, S, M), E, D, T>) -> Result<Self::Output, Error> {
+        self.try_forward((x, 0))
+    }
+}
+
+impl<B: Dim, S: Dim, M: Dim, E: Dtype, D: Device<E>, T: Tape<E, D>>
+    Module<(Tensor<(B, S, M), E, D, T>, usize)> for SinusoidalPositionalEncoding
+{
+    type Output = Tensor<(B, S, M), E, D, T>;
+    fn try_forward(
+        &self,
+        (x, offset): (Tensor<(B, S, M), E, D, T>, usize),
+    ) -> Result<Self::Output, Error> {
+        let shape = *x.shape();
+        let pe = self.try_encoding(x.device(), (shape.1, shape.2), offset)?;
+        x.try_add(pe.try_broadcast_like(&shape)?)
+    }
+}
+
+/// The `(S, Dh)` cosine and sine tables of the rotary angles of positions `offset..offset + S`.
+/// Both halves of a row share the angles of the `Dh / 2` feature pairs.
+fn try_rotary_tables<S: Dim, Dh: Dim, E: Dtype, D: Device<E>>(
+    device: &D,
+    (seq, head): (S, Dh),
+    base: f64,
+    offset: usize,
+) -> Result<(Tensor<(S, Dh), E, D>, Tensor<(S, Dh), E, D>), Error> {
+    let dim = head.size();
+    let half = dim / 2;
+    let inv_freq: Vec<f64> = (0..half).map(|i| angle(1, i, dim, base)).collect();
+    let mut cos = Vec::with_capacity(seq.size() * dim);
+    let mut sin = Vec::with_capacity(seq.size() * dim);
+    for pos in offset..offset + seq.size() {
+        let start = cos.len();
+        for f in inv_freq.iter() {
+            let a = pos as f64 * f;
+            cos.push(E::from_f64(a.cos()).unwrap());
+            sin.push(E::from_f64(a.sin()).unwrap());
+        }
+        cos.extend_from_within(start..);
+        sin.extend_from_within(start..);
+    }
+    Ok((
+        device.try_tensor_from_vec(cos, (seq, head))?,
+        device.try_tensor_from_vec(sin, (seq, head))?,
+    ))
+}
+
+/// Applies rotary position embedding (RoPE) from
+/// [RoFormer](https://arxiv.org/abs/2104.09864) to `(B, S, H, Dh)` queries or keys, with the
+/// first timestep at position `offset`. Each head is rotated by its two halves:
+/// `x * cos + rotate_half(x) * sin`, where `rotate_half([x1, x2]) = [-x2, x1]`.
+///
+/// Returns [Error::InvalidArgument] if `Dh` is odd.
+pub(super) fn try_rotary_embedding<B: Dim, S: Dim, H: Dim, Dh: Dim, E, D, T>(
+    x: Tensor<(B, S, H, Dh), E, D, T>,
+    base: f64,
+    offset: usize,
+) -> Result<Tensor<(B, S, H, Dh), E, D, T>, Error>
+where
+    E: Dtype,
+    D: Device<E>,
+    T: Tape<E, D>,
+{
+    let shape = *x.shape();
+    let (_, seq, _, head) = shape;
+    let dim = head.size();
+    if dim % 2 != 0 {
+        return Err(Error::InvalidArgument(format!(
+            "rotary embedding requires an even head size, found {dim}"
+        )));
+    }
+    let half = dim / 2;
+
+    let (cos, sin) = try_rotary_tables(x.device(), (seq, head), base, offset)?;
+    let cos = cos.try_broadcast_like::<_, Axes2<0, 2>>(&shape)?;
+    let sin = sin.try_broadcast_like::<_, Axes2<0, 2>>(&shape)?;
+
+    let (x, tape) = x.split_tape();
+    let x1 = x.retaped::<T>().try_slice((.., .., .., ..half))?;
+    let x2 = x.retaped::<T>().try_slice((.., .., .., half..))?;
+    let rotated = (x2.try_negate()?, x1).try_concat_tensor_along(Axis::<3>)?;
+    let rotated = rotated.try_reshape_like(&shape)?;
+    x.put_tape(tape)
+        .try_mul(cos)?
+        .try_add(rotated.try_mul(sin)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{gradcheck::*, tests::*};
+
+    #[test]
+    fn test_sinusoidal_positional_encoding() {
+        let dev: TestDevice = Default::default();
+        let pe: SinusoidalPositionalEncoding = Default::default();
+
+        let x: Tensor<Rank2<3, 4>, TestDtype, _> = dev.zeros();
+        let y = pe.forward(x);
+        #[rustfmt::skip]
+        assert_close_to_literal!(
+            y,
+            [
+                [0.0, 1.0, 0.0, 1.0],
+                [0.84147098, 0.54030231, 0.00999983, 0.99995000],
+                [0.90929743, -0.41614684, 0.01999867, 0.99980001],
+            ]
+        );
+
+        // batched with an offset matches the later positions
+        let x: Tensor<Rank3<2, 2, 4>, TestDtype, _> = dev.zeros();
+        let y = pe.forward((x, 1));
+        #[rustfmt::skip]
+        assert_close_to_literal!(
+            y,
+            [
+                [
+                    [0.84147098, 0.54030231, 0.00999983, 0.99995000],
+                    [0.90929743, -0.41614684, 0.01999867, 0.99980001],
+                ],
+                [
+                    [0.84147098, 0.54030231, 0.00999983, 0.99995000],
+                    [0.90929743, -0.41614684, 0.01999867, 0.99980001],
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sinusoidal_positional_encoding_backward() {
+        let dev: TestDevice = Default::default();
+        let pe: SinusoidalPositionalEncoding = Default::default();
+
+        let x: Tensor<(usize, Const<4>), TestDtype, _> = dev.sample_normal_like(&(5, Const));
+        let y = pe.forward(x.leaky_trace());
+        let g = y.sum().backward();
+        assert_eq!(g.get(&x).as_vec(), vec![TestDtype::ONE; 20]);
+    }
+
+    #[test]
+    fn test_rotary_embedding() {
+        let dev: TestDevice = Default::default();
+
+        let x = dev.tensor([[[[1.0, 2.0, 3.0, 4.0]], [[1.0, 2.0, 3.0, 4.0]]]]);
+        let y =
+            try_rotary_embedding::<_, _, _, _, TestDtype, _, _>(x.to_dtype(), 10000.0, 0).unwrap();
+        // position 0 isn't rotated, position 1 is rotated by angles [1.0, 0.01]
+        #[rustfmt::skip]
+        assert_close_to_literal!(
+            y,
+            [[
+                [[1.0, 2.0, 3.0, 4.0]],
+                [[-1.98411065, 1.95990067, 2.46237790, 4.01979967]],
+            ]]
+        );
+
+        // an offset of 1 rotates the first timestep as position 1
+        let x: Tensor<Rank4<1, 1, 1, 4>, TestDtype, _> =
+            dev.tensor([[[[1.0, 2.0, 3.0, 4.0]]]]).to_dtype();
+        let g = {
+            let y = try_rotary_embedding(x.leaky_trace(), 10000.0, 1).unwrap();
+            assert_close_to_literal!(y, [[[[-1.98411065, 1.95990067, 2.46237790, 4.01979967]]]]);
+            y.sum().backward()
+        };
+        // d/dx of the sum is cos + sin for the first half and cos - sin for the second half
+        assert_close_to_literal!(
+            g.get(&x),
+            [[[[1.38177329, 1.00994983, -0.30116868, 0.98995017]]]]
+        );
+    }
+
+    #[test]
+    fn test_rotary_embedding_grads() {
+        let dev: TestDevice = Default::default();
+
+        let x: Tensor<Rank4<2, 3, 2, 6>, TestDtype, _> = dev.sample_normal();
+        let report = gradcheck(
+            |x| try_rotary_embedding(x, 10000.0, 2).unwrap().square().mean(),
+            &x,
+            Default::default(),
+        );
+        assert!(report.passed(), "{report}");
+
+        let x: Tensor<Rank4<1, 2, 1, 3>, TestDtype, _> = dev.sample_normal();
+        assert!(try_rotary_embedding(x, 10000.0, 0).is_err());
+    }
+}
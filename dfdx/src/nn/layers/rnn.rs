@@ -0,0 +1,647 @@
+#![allow(clippy::type_complexity)]
+
+use crate::prelude::*;
+
+use rand_distr::Uniform;
+
+/// A single step of an Elman RNN: `h' = tanh(weight_ih * x + bias_ih + weight_hh * h + bias_hh)`.
+///
+/// Generics:
+/// - `I` The size of the input vectors.
+/// - `H` The size of the hidden state.
+///
+/// **Pytorch equivalent**: `torch.nn.RNNCell(I, H)`
+///
+/// The operations are recorded on the tapes of both `x` and `h`, so use the same tape type for both.
+///
+/// Example:
+/// ```rust
+/// # use dfdx::prelude::*;
+/// # use dfdx::*;
+/// # let dev: Cpu = Default::default();
+/// let cell = dev.build_module::<f32>(RNNCellConstConfig::<3, 5>::default());
+/// let x: Tensor<Rank2<2, 3>, f32, _> = dev.zeros();
+/// let h: Tensor<Rank2<2, 5>, f32, _> = dev.zeros();
+/// let _: Tensor<Rank2<2, 5>, f32, _> = cell.forward((x, h));
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RNNCellConfig<I: Dim, H: Dim> {
+    pub inp: I,
+    pub hidden: H,
+}
+
+impl<I: Dim, H: Dim> RNNCellConfig<I, H> {
+    pub fn new(inp: I, hidden: H) -> Self {
+        Self { inp, hidden }
+    }
+}
+
+/// Compile time sugar alias around [RNNCellConfig].
+pub type RNNCellConstConfig<const I: usize, const H: usize> = RNNCellConfig<Const<I>, Const<H>>;
+
+impl<I: Dim, H: Dim, E: Dtype, D: Device<E>> BuildOnDevice<E, D> for RNNCellConfig<I, H> {
+    type Built = RNNCell<I, H, E, D>;
+    fn try_build_on_device(&self, device: &D) -> Result<Self::Built, crate::tensor::Error> {
+        Ok(RNNCell {
+            weight_ih: device.try_zeros_like(&(self.hidden, self.inp))?,
+            weight_hh: device.try_zeros_like(&(self.hidden, self.hidden))?,
+            bias_ih: device.try_zeros_like(&(self.hidden,))?,
+            bias_hh: device.try_zeros_like(&(self.hidden,))?,
+        })
+    }
+}
+
+/// See [RNNCellConfig].
+#[derive(Clone, Debug, UpdateParams, ZeroGrads)]
+#[cfg_attr(feature = "safetensors", derive(SaveSafeTensors, LoadSafeTensors))]
+pub struct RNNCell<I: Dim, H: Dim, Elem: Dtype, Dev: Device<Elem>> {
+    #[param]
+    #[cfg_attr(feature = "safetensors", serialize)]
+    pub weight_ih: Tensor<(H, I), Elem, Dev>,
+    #[param]
+    #[cfg_attr(feature = "safetensors", serialize)]
+    pub weight_hh: Tensor<(H, H), Elem, Dev>,
+    #[param]
+    #[cfg_attr(feature = "safetensors", serialize)]
+    pub bias_ih: Tensor<(H,), Elem, Dev>,
+    #[param]
+    #[cfg_attr(feature = "safetensors", serialize)]
+    pub bias_hh: Tensor<(H,), Elem, Dev>,
+}
+
+impl<I: Dim, H: Dim, E, D: Device<E>> ResetParams<E, D> for RNNCell<I, H, E, D>
+where
+    E: Dtype + num_traits::Float + rand_distr::uniform::SampleUniform,
+{
+    fn try_reset_params(&mut self) -> Result<(), crate::tensor::Error> {
+        let (h, _) = self.weight_hh.shape();
+        let b = E::from_f64(1.0 / (h.size() as f64).sqrt()).unwrap();
+        self.weight_ih.try_fill_with_distr(Uniform::new(-b, b))?;
+        self.weight_hh.try_fill_with_distr(Uniform::new(-b, b))?;
+        self.bias_ih.try_fill_with_distr(Uniform::new(-b, b))?;
+        self.bias_hh.try_fill_with_distr(Uniform::new(-b, b))
+    }
+}
+
+impl<I: Dim, H: Dim, E: Dtype, D: Device<E>> RecurrentCell<I, H, E, D> for RNNCell<I, H, E, D> {
+    const NUM_STATES: usize = 1;
+
+    fn try_step<B: Dim, T: Tape<E, D>>(
+        &self,
+        x: Tensor<(B, I), E, D, T>,
+        state: Vec<Tensor<(B, H), E, D, T>>,
+    ) -> Result<Vec<Tensor<(B, H), E, D, T>>, Error> {
+        let h = state.into_iter().next().unwrap();
+        let shape = *h.shape();
+        let x = x
+            .try_matmul(self.weight_ih.retaped::<T>().try_permute()?)?
+            .try_add(self.bias_ih.retaped::<T>().try_broadcast_like(&shape)?)?;
+        let h = h
+            .try_matmul(self.weight_hh.retaped::<T>().try_permute()?)?
+            .try_add(self.bias_hh.retaped::<T>().try_broadcast_like(&shape)?)?;
+        Ok(std::vec![x.try_add(h)?.try_tanh()?])
+    }
+}
+
+impl<B: Dim, I: Dim, H: Dim, E: Dtype, D: Device<E>, T: Tape<E, D>>
+    Module<(Tensor<(B, I), E, D, T>, Tensor<(B, H), E, D, T>)> for RNNCell<I, H, E, D>
+{
+    type Output = Tensor<(B, H), E, D, T>;
+    fn try_forward(
+        &self,
+        (x, h): (Tensor<(B, I), E, D, T>, Tensor<(B, H), E, D, T>),
+    ) -> Result<Self::Output, Error> {
+        Ok(self.try_step(x, std::vec![h])?.remove(0))
+    }
+}
+
+/// A multi-layer Elman RNN over `(Batch, Seq, I)` inputs, made of [RNNCell]s.
+///
+/// Outputs a tuple of:
+/// 1. The hidden state of the last layer at every step, `(Batch, Seq, num_directions * H)`.
+///    With `bidirectional`, the forward direction comes first in the last dimension.
+/// 2. The final hidden state of every layer & direction, `(num_layers * num_directions, Batch, H)`.
+///
+/// The initial hidden state is zeros, unless it's passed in with the input as `(x, h0)`,
+/// where `h0` has the same shape as the final hidden state. The tape of `h0` is merged
+/// with the tape of `x`, so gradients flow back into it.
+///
+/// Like every op with several outputs, the tape goes to the first output, and the final
+/// hidden state is returned with an empty tape of the same type. It can be passed back in
+/// as `h0` of another call, and to backpropagate through it, move the tape of the outputs
+/// onto it (or onto the input of that call) with `split_tape()` & `put_tape()`:
+///
+/// ```rust
+/// # use dfdx::prelude::*;
+/// # use dfdx::*;
+/// # let dev: Cpu = Default::default();
+/// # let encoder = dev.build_module::<f32>(RNNConstConfig::<3, 5>::default());
+/// # let decoder = dev.build_module::<f32>(RNNConstConfig::<3, 5>::default());
+/// # let src: Tensor<Rank3<4, 7, 3>, f32, _> = dev.zeros();
+/// # let tgt: Tensor<Rank3<4, 2, 3>, f32, _> = dev.zeros();
+/// let (y, h_n) = encoder.forward(src.leaky_trace());
+/// let (_, tape) = y.split_tape();
+/// let (y, _) = decoder.forward((tgt.put_tape(tape), h_n));
+/// let grads = y.square().mean().backward();
+/// assert!(grads.get_ref_checked(&encoder.first_layer[0].weight_hh).is_some());
+/// ```
+///
+/// **Pytorch equivalent**:
+/// ```python
+/// torch.nn.RNN(I, H, num_layers=cfg.num_layers, bidirectional=cfg.bidirectional, batch_first=True)
+/// ```
+///
+/// Example:
+/// ```rust
+/// # use dfdx::prelude::*;
+/// # use dfdx::*;
+/// # let dev: Cpu = Default::default();
+/// let rnn = dev.build_module::<f32>(RNNConfig::new(Const::<3>, Const::<5>, 2, true));
+/// let x: Tensor<Rank3<4, 7, 3>, f32, _> = dev.zeros();
+/// let (y, h_n) = rnn.forward(x);
+/// assert_eq!(y.shape(), &(Const::<4>, Const::<7>, 10));
+/// assert_eq!(h_n.shape(), &(4, Const::<4>, Const::<5>));
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct RNNConfig<I: Dim, H: Dim> {
+    pub inp: I,
+    pub hidden: H,
+    /// The number of stacked layers. Defaults to `1`.
+    pub num_layers: usize,
+    /// Whether each layer also runs over the sequence in reverse. Defaults to `false`.
+    pub bidirectional: bool,
+}
+
+impl<I: Dim, H: Dim> RNNConfig<I, H> {
+    pub fn new(inp: I, hidden: H, num_layers: usize, bidirectional: bool) -> Self {
+        Self {
+            inp,
+            hidden,
+            num_layers,
+            bidirectional,
+        }
+    }
+}
+
+impl<I: Dim + Default, H: Dim + Default> Default for RNNConfig<I, H> {
+    fn default() -> Self {
+        Self::new(Default::default(), Default::default(), 1, false)
+    }
+}
+
+/// Compile time sugar alias around [RNNConfig].
+pub type RNNConstConfig<const I: usize, const H: usize> = RNNConfig<Const<I>, Const<H>>;
+
+impl<I: Dim, H: Dim, E: Dtype, D: Device<E>> BuildOnDevice<E, D> for RNNConfig<I, H> {
+    type Built = RNN<I, H, E, D>;
+    fn try_build_on_device(&self, device: &D) -> Result<Self::Built, crate::tensor::Error> {
+        check_num_layers(self.num_layers)?;
+        let dirs = if self.bidirectional { 2 } else { 1 };
+        let first = RNNCellConfig::new(self.inp, self.hidden);
+        let other = RNNCellConfig::new(dirs * self.hidden.size(), self.hidden);
+        Ok(RNN {
+            first_layer: std::vec![first; dirs].try_build_on_device(device)?,
+            other_layers: std::vec![other; (self.num_layers - 1) * dirs]
+                .try_build_on_device(device)?,
+        })
+    }
+}
+
+/// See [RNNConfig].
+#[derive(Clone, Debug, ResetParams, UpdateParams, ZeroGrads)]
+#[cfg_attr(feature = "safetensors", derive(SaveSafeTensors, LoadSafeTensors))]
+pub struct RNN<I: Dim, H: Dim, Elem: Dtype, Dev: Device<Elem>> {
+    /// The cells of the first layer, one per direction.
+    #[module]
+    #[cfg_attr(feature = "safetensors", serialize)]
+    pub first_layer: Vec<RNNCell<I, H, Elem, Dev>>,
+    /// The cells of the remaining layers, one per direction for each layer.
+    #[module]
+    #[cfg_attr(feature = "safetensors", serialize)]
+    pub other_layers: Vec<RNNCell<usize, H, Elem, Dev>>,
+}
+
+impl<B: Dim, S: Dim, I: Dim, H: Dim, E: Dtype, D: Device<E>, T: Tape<E, D>>
+    Module<Tensor<(B, S, I), E, D, T>> for RNN<I, H, E, D>
+{
+    type Output = (
+        Tensor<(B, S, usize), E, D, T>,
+        Tensor<(usize, B, H), E, D, T>,
+    );
+    fn try_forward(&self, x: Tensor<(B, S, I), E, D, T>) -> Result<Self::Output, Error> {
+        let rows = self.first_layer.len() + self.other_layers.len();
+        let hidden = first_cell(&self.first_layer)?.weight_hh.shape().0;
+        let h0 = try_zero_states(&x, rows, hidden)?;
+        self.try_forward((x, h0))
+    }
+}
+
+impl<B: Dim, S: Dim, I: Dim, H: Dim, E: Dtype, D: Device<E>, T: Tape<E, D>>
+    Module<(Tensor<(B, S, I), E, D, T>, Tensor<(usize, B, H), E, D, T>)> for RNN<I, H, E, D>
+{
+    type Output = (
+        Tensor<(B, S, usize), E, D, T>,
+        Tensor<(usize, B, H), E, D, T>,
+    );
+    fn try_forward(
+        &self,
+        (x, h0): (Tensor<(B, S, I), E, D, T>, Tensor<(usize, B, H), E, D, T>),
+    ) -> Result<Self::Output, Error> {
+        let (y, mut states) =
+            try_run_layers(&self.first_layer, &self.other_layers, x, std::vec![h0])?;
+        Ok((y, states.remove(0)))
+    }
+}
+
+/// A recurrent cell that [RNN], [super::LSTM] & [super::GRU] run over sequences.
+pub(super) trait RecurrentCell<I: Dim, H: Dim, E: Dtype, D: Device<E>> {
+    /// The number of state tensors, e.g. `h` for [RNNCell], or `h` & `c` for [super::LSTMCell].
+    const NUM_STATES: usize;
+
+    /// Advances `state` by one step of `x`. The hidden state is `state[0]`, and every
+    /// operation is recorded on its tape. The other states are returned with empty tapes.
+    fn try_step<B: Dim, T: Tape<E, D>>(
+        &self,
+        x: Tensor<(B, I), E, D, T>,
+        state: Vec<Tensor<(B, H), E, D, T>>,
+    ) -> Result<Vec<Tensor<(B, H), E, D, T>>, Error>;
+}
+
+/// Projects `x` with each of `G` gates of stacked weights, i.e. `(B, I) -> (G, B, H)`.
+pub(super) fn try_gates<const G: usize, B: Dim, I: Dim, H: Dim, E, D, T>(
+    x: Tensor<(B, I), E, D, T>,
+    weight: &Tensor<(Const<G>, H, I), E, D>,
+    bias: &Tensor<(Const<G>, H), E, D>,
+) -> Result<Tensor<(Const<G>, B, H), E, D, T>, Error>
+where
+    E: Dtype,
+    D: Device<E>,
+    T: Tape<E, D>,
+{
+    let (b, i) = *x.shape();
+    let (g, h, _) = *weight.shape();
+    let x = x.try_broadcast_like(&(g, b, i))?;
+    let weight = weight.retaped::<T>().try_permute::<_, Axes3<0, 2, 1>>()?;
+    x.try_matmul(weight)?
+        .try_add(bias.retaped::<T>().try_broadcast_like(&(g, b, h))?)
+}
+
+/// Splits `(G, B, H)` gates into `G` tensors of `(B, H)`. The tape goes to the first one.
+pub(super) fn try_split_gates<const G: usize, B: Dim, H: Dim, E, D, T>(
+    gates: Tensor<(Const<G>, B, H), E, D, T>,
+) -> Result<[Tensor<(B, H), E, D, T>; G], Error>
+where
+    E: Dtype,
+    D: Device<E>,
+    T: Tape<E, D>,
+{
+    let (_, b, h) = *gates.shape();
+    let (gates, tape) = gates.split_tape();
+    let mut tape = Some(tape);
+    let mut split = Vec::with_capacity(G);
+    for k in 0..G {
+        let gate = match tape.take() {
+            Some(tape) => gates.clone().put_tape(tape),
+            None => gates.retaped::<T>(),
+        };
+        split.push(
+            gate.try_slice((k..k + 1, .., ..))?
+                .try_reshape_like(&(b, h))?,
+        );
+    }
+    Ok(split.try_into().unwrap_or_else(|_| unreachable!()))
+}
+
+/// Returns [Error::InvalidArgument] unless there is at least one layer.
+pub(super) fn check_num_layers(num_layers: usize) -> Result<(), Error> {
+    if num_layers == 0 {
+        return Err(Error::InvalidArgument(
+            "num_layers must be at least 1".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// The first cell of the first layer, or [Error::InvalidArgument] if the first layer is empty.
+pub(super) fn first_cell<C>(first_layer: &[C]) -> Result<&C, Error> {
+    first_layer.first().ok_or_else(|| {
+        Error::InvalidArgument(
+            "the first layer must have 1 or 2 cells (one per direction), found 0".into(),
+        )
+    })
+}
+
+/// A zero initial state with `rows` layers & directions for the batch of `x`.
+pub(super) fn try_zero_states<B: Dim, S: Dim, I: Dim, H: Dim, E, D, T>(
+    x: &Tensor<(B, S, I), E, D, T>,
+    rows: usize,
+    hidden: H,
+) -> Result<Tensor<(usize, B, H), E, D, T>, Error>
+where
+    E: Dtype,
+    D: Device<E>,
+    T: Tape<E, D>,
+{
+    let (b, _, _) = *x.shape();
+    Ok(x.device()
+        .try_zeros_like(&(rows, b, hidden))?
+        .put_tape(Default::default()))
+}
+
+/// Runs the `first` layer of cells over `x`, and then each of the `other` layers over the
+/// outputs of the previous layer. `init` are the stacked initial states, in the same order as
+/// [RecurrentCell::try_step()], with one row for each direction of each layer. Their tapes
+/// are merged with the tape of `x`.
+///
+/// Returns the outputs of the last layer with the tape, and the final states stacked like `init`
+/// with empty tapes.
+pub(super) fn try_run_layers<C0, C, B: Dim, S: Dim, I: Dim, H: Dim, E, D, T>(
+    first: &[C0],
+    other: &[C],
+    x: Tensor<(B, S, I), E, D, T>,
+    init: Vec<Tensor<(usize, B, H), E, D, T>>,
+) -> Result<
+    (
+        Tensor<(B, S, usize), E, D, T>,
+        Vec<Tensor<(usize, B, H), E, D, T>>,
+    ),
+    Error,
+>
+where
+    C0: RecurrentCell<I, H, E, D>,
+    C: RecurrentCell<usize, H, E, D>,
+    E: Dtype,
+    D: Device<E>,
+    T: Tape<E, D>,
+{
+    let dirs = first.len();
+    if dirs != 1 && dirs != 2 {
+        return Err(Error::InvalidArgument(std::format!(
+            "the first layer must have 1 or 2 cells (one per direction), found {dirs}"
+        )));
+    }
+    if other.len() % dirs != 0 {
+        return Err(Error::InvalidArgument(std::format!(
+            "the other layers must have {dirs} cells each, found {} cells in total",
+            other.len()
+        )));
+    }
+    if init.len() != C0::NUM_STATES {
+        return Err(Error::InvalidArgument(std::format!(
+            "expected {} initial states, found {}",
+            C0::NUM_STATES,
+            init.len()
+        )));
+    }
+    let rows = first.len() + other.len();
+    let (b, _, _) = *x.shape();
+    for state in init.iter() {
+        let (r, sb, h) = *state.shape();
+        if r != rows || sb != b {
+            return Err(Error::ShapeMismatch {
+                expected: std::vec![rows, b.size(), h.size()],
+                found: std::vec![r, sb.size(), h.size()],
+            });
+        }
+    }
+
+    let (x, mut tape) = x.split_tape();
+    let mut states = Vec::with_capacity(init.len());
+    for state in init {
+        let (state, state_tape) = state.split_tape();
+        tape = tape.merge(state_tape);
+        states.push(state);
+    }
+
+    let mut finals = std::vec![Vec::with_capacity(rows); states.len()];
+    let (mut y, mut tape) = try_run_layer(first, &x, &states, 0, tape, &mut finals)?;
+    for (l, cells) in other.chunks(dirs).enumerate() {
+        (y, tape) = try_run_layer(cells, &y, &states, (l + 1) * dirs, tape, &mut finals)?;
+    }
+    // stacked on the tape, so that the final states can be backpropagated through
+    let mut states = Vec::with_capacity(finals.len());
+    for s_n in finals {
+        let s_n: Vec<_> = s_n.into_iter().map(|s| s.put_tape(T::default())).collect();
+        let (s_n, s_tape) = s_n.try_stack()?.split_tape();
+        tape = tape.merge(s_tape);
+        states.push(s_n.put_tape(T::default()));
+    }
+    Ok((y.put_tape(tape), states))
+}
+
+/// Runs one layer (a cell per direction) over `x`, recording on `tape`, and pushes the final
+/// states to `finals`. Returns the outputs and the tape.
+fn try_run_layer<C, B: Dim, S: Dim, F: Dim, H: Dim, E, D, T>(
+    cells: &[C],
+    x: &Tensor<(B, S, F), E, D>,
+    init: &[Tensor<(usize, B, H), E, D>],
+    row: usize,
+    mut tape: T,
+    finals: &mut [Vec<Tensor<(B, H), E, D>>],
+) -> Result<(Tensor<(B, S, usize), E, D>, T), Error>
+where
+    C: RecurrentCell<F, H, E, D>,
+    E: Dtype,
+    D: Device<E>,
+    T: Tape<E, D>,
+{
+    let (b, s, f) = *x.shape();
+    let h = init[0].shape().2;
+    let mut outputs = Vec::with_capacity(cells.len());
+    for (dir, cell) in cells.iter().enumerate() {
+        let k = row + dir;
+        let mut state = Vec::with_capacity(init.len());
+        for s0 in init.iter() {
+            // sliced on the tape, so that gradients flow back into the initial states
+            let s0 = s0.retaped::<T>().try_slice((k..k + 1, .., ..))?;
+            let (s0, s0_tape) = s0.try_reshape_like(&(b, h))?.split_tape();
+            tape = tape.merge(s0_tape);
+            state.push(s0.put_tape(T::default()));
+        }
+        state[0] = state[0].retaped::<NoneTape>().put_tape(tape);
+
+        let mut steps = Vec::with_capacity(s.size());
+        for i in 0..s.size() {
+            let t = if dir == 0 { i } else { s.size() - 1 - i };
+            let x_t = x.retaped::<T>().try_slice((.., t..t + 1, ..))?;
+            state = cell.try_step(x_t.try_reshape_like(&(b, f))?, state)?;
+            steps.push(state[0].retaped::<T>());
+        }
+        if dir == 1 {
+            steps.reverse();
+        }
+        outputs.push(steps.try_stack()?);
+
+        let mut state = state.into_iter();
+        let (h_n, h_tape) = state.next().unwrap().split_tape();
+        tape = h_tape;
+        finals[0].push(h_n);
+        for (final_states, s_n) in finals[1..].iter_mut().zip(state) {
+            final_states.push(s_n.split_tape().0);
+        }
+    }
+
+    // (Dirs, Seq, Batch, H) -> (Batch, Seq, Dirs * H)
+    let y = outputs
+        .try_stack()?
+        .try_permute::<_, Axes4<2, 1, 0, 3>>()?
+        .try_reshape_like(&(b, s, cells.len() * h.size()))?;
+    let (y, y_tape) = y.split_tape();
+    Ok((y, tape.merge(y_tape)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{gradcheck::*, tests::*};
+
+    #[test]
+    fn test_rnn_cell() {
+        let dev: TestDevice = Default::default();
+        let cell = RNNCell {
+            weight_ih: dev
+                .tensor([[0.1, -0.2], [0.3, 0.4]])
+                .to_dtype::<TestDtype>(),
+            weight_hh: dev
+                .tensor([[0.5, 0.0], [-0.1, 0.2]])
+                .to_dtype::<TestDtype>(),
+            bias_ih: dev.tensor([0.1, 0.0]).to_dtype::<TestDtype>(),
+            bias_hh: dev.tensor([0.0, -0.2]).to_dtype::<TestDtype>(),
+        };
+        let x = dev.tensor([[1.0, 2.0]]).to_dtype::<TestDtype>();
+        let h = dev.tensor([[0.5, -1.0]]).to_dtype::<TestDtype>();
+        let h = cell.forward((x.leaky_trace(), h.leaky_trace()));
+        assert_close_to_literal!(h, [[0.049958374, 0.57166997]]);
+
+        let g = h.sum().backward();
+        assert_close_to_literal!(g.get(&cell.bias_ih), [0.99750416, 0.67319345]);
+        assert_close_to_literal!(g.get(&x), [[0.30170845, 0.069776548]]);
+    }
+
+    #[test]
+    fn test_rnn_matches_cells() {
+        let dev: TestDevice = Default::default();
+        let rnn = dev.build_module::<TestDtype>(RNNConstConfig::<3, 4>::default());
+        let x: Tensor<Rank3<2, 5, 3>, TestDtype, _> = dev.sample_normal();
+        let (y, h_n) = rnn.forward(x.clone());
+
+        let cell = &rnn.first_layer[0];
+        let mut h: Tensor<Rank2<2, 4>, TestDtype, _> = dev.zeros();
+        for t in 0..5 {
+            let x_t = x
+                .clone()
+                .slice((.., t..t + 1, ..))
+                .reshape_like(&(Const::<2>, Const::<3>));
+            h = cell.forward((x_t, h));
+            let y_t = y
+                .clone()
+                .slice((.., t..t + 1, ..))
+                .reshape_like(&(Const::<2>, Const::<4>));
+            assert_eq!(y_t.array(), h.array());
+        }
+        assert_eq!(
+            h_n.reshape_like(&(Const::<2>, Const::<4>)).array(),
+            h.array()
+        );
+    }
+
+    #[test]
+    fn test_rnn_bidirectional_multi_layer() {
+        let dev: TestDevice = Default::default();
+        let mut rnn =
+            dev.build_module::<TestDtype>(RNNConfig::new(Const::<3>, Const::<4>, 2, true));
+        assert_eq!(rnn.first_layer.len(), 2);
+        assert_eq!(rnn.other_layers.len(), 2);
+        assert_eq!(rnn.other_layers[0].weight_ih.shape(), &(Const::<4>, 8));
+
+        let x: Tensor<(Const<2>, usize, Const<3>), TestDtype, _> =
+            dev.sample_normal_like(&(Const, 6, Const));
+        let (y, h_n) = rnn.forward(x.clone());
+        assert_eq!(y.shape(), &(Const::<2>, 6, 8));
+        assert_eq!(h_n.shape(), &(4, Const::<2>, Const::<4>));
+
+        // the final states of the last layer are the ends of the output
+        let y_fwd = y.clone().slice((.., 5..6, 0..4));
+        let h_fwd = h_n.clone().slice((2..3, .., ..));
+        assert_eq!(
+            y_fwd.reshape_like(&(Const::<2>, Const::<4>)).array(),
+            h_fwd.reshape_like(&(Const::<2>, Const::<4>)).array()
+        );
+        let y_bwd = y.slice((.., 0..1, 4..8));
+        let h_bwd = h_n.slice((3..4, .., ..));
+        assert_eq!(
+            y_bwd.reshape_like(&(Const::<2>, Const::<4>)).array(),
+            h_bwd.reshape_like(&(Const::<2>, Const::<4>)).array()
+        );
+
+        // backpropagate through the outputs & the final states
+        let h0: Tensor<(usize, Const<2>, Const<4>), TestDtype, _> =
+            dev.sample_normal_like(&(4, Const, Const));
+        let report = gradcheck_module(
+            &mut rnn,
+            &x,
+            |rnn, x| {
+                let (y, h_n) = rnn.forward((x, h0.retaped()));
+                let (y, tape) = y.split_tape();
+                let h_n = h_n.split_tape().0.put_tape(tape);
+                h_n.square().mean() + y.retaped::<OwnedTape<_, _>>().square().mean()
+            },
+            Default::default(),
+        );
+        assert!(report.passed(), "{report}");
+
+        let report = gradcheck_module(
+            &mut rnn,
+            &h0,
+            |rnn, h0| {
+                let (y, h_n) = rnn.forward((x.retaped(), h0));
+                let (y, tape) = y.split_tape();
+                let h_n = h_n.split_tape().0.put_tape(tape);
+                h_n.square().mean() + y.retaped::<OwnedTape<_, _>>().square().mean()
+            },
+            Default::default(),
+        );
+        assert!(report.passed(), "{report}");
+    }
+
+    #[test]
+    fn test_rnn_invalid_arguments() {
+        let dev: TestDevice = Default::default();
+        let cfg = RNNConfig::new(Const::<3>, Const::<4>, 0, false);
+        assert!(matches!(
+            dev.try_build_module::<TestDtype>(cfg),
+            Err(Error::InvalidArgument(_))
+        ));
+
+        let mut rnn =
+            dev.build_module::<TestDtype>(RNNConfig::new(Const::<3>, Const::<4>, 2, false));
+        let x: Tensor<Rank3<2, 5, 3>, TestDtype, _> = dev.sample_normal();
+        let h0: Tensor<(usize, Const<2>, Const<4>), TestDtype, _> =
+            dev.sample_normal_like(&(1, Const, Const));
+        assert!(matches!(
+            rnn.try_forward((x.clone(), h0)),
+            Err(Error::ShapeMismatch { .. })
+        ));
+
+        rnn.first_layer.clear();
+        assert!(matches!(rnn.try_forward(x), Err(Error::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn test_rnn_initial_state() {
+        let dev: TestDevice = Default::default();
+        let rnn = dev.build_module::<TestDtype>(RNNConstConfig::<3, 4>::default());
+        let x: Tensor<Rank3<2, 1, 3>, TestDtype, _> = dev.sample_normal();
+        let h0: Tensor<(usize, Const<2>, Const<4>), TestDtype, _> =
+            dev.sample_normal_like(&(1, Const, Const));
+        let (_, h_n) = rnn.forward((x.clone(), h0.clone()));
+        let h = rnn.first_layer[0].forward((
+            x.reshape_like(&(Const::<2>, Const::<3>)),
+            h0.reshape_like(&(Const::<2>, Const::<4>)),
+        ));
+        assert_eq!(
+            h_n.reshape_like(&(Const::<2>, Const::<4>)).array(),
+            h.array()
+        );
+    }
+}
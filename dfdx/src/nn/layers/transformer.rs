@@ -1,6 +1,13 @@
+#![allow(clippy::type_complexity)]
+
 use crate::prelude::*;
 
-#[derive(Clone, Debug, Sequential)]
+use super::multi_head_attention::check_dropout;
+use crate::tensor::UniqueId;
+
+use num_traits::Float;
+
+#[derive(Default, Clone, Debug, Sequential)]
 #[built(FeedForward)]
 pub struct FeedForwardConfig<Model: Dim, F: Dim> {
     pub l1: LinearConfig<Model, F>,
@@ -15,19 +22,30 @@ pub struct FeedForwardConfig<Model: Dim, F: Dim> {
 /// - `NumHeads`: The number of heads in [MultiHeadAttention].
 /// - `F`: The size of the hidden layer in the feedforward network.
 ///
+/// Inputs can optionally be paired with an [AttentionMask] for the self attention,
+/// e.g. an [AttentionMask::causal()] mask for decoder-only language models.
+/// Dropout is only applied in [Module::try_forward_mut()], see [EncoderBlockConfig::with_dropout()].
+///
 /// **Pytorch equivalent**:
 /// ```python
 /// encoder = torch.nn.TransformerEncoderLayer(
 ///    Model, NumHeads, dim_feedforward=F, batch_first=True, dropout=0.0
 /// )
 /// ```
-#[derive(Clone, Debug, Sequential)]
+#[derive(Default, Clone, Debug, CustomModule)]
 #[built(EncoderBlock)]
 pub struct EncoderBlockConfig<Model: Dim, NumHeads: Dim, F: Dim> {
+    #[module]
     pub self_attn: ResidualAdd<MultiHeadAttentionConfig<Model, NumHeads>>,
+    #[module]
     pub norm1: LayerNorm1DConfig<Model>,
+    #[module]
     pub ff: ResidualAdd<FeedForwardConfig<Model, F>>,
+    #[module]
     pub norm2: LayerNorm1DConfig<Model>,
+    /// Dropout probability applied to the residual branches during training, see
+    /// [EncoderBlockConfig::with_dropout()]. Defaults to `0.0`.
+    pub dropout: f64,
 }
 
 impl<Model: Dim, NumHeads: Dim, F: Dim> EncoderBlockConfig<Model, NumHeads, F> {
@@ -43,8 +61,136 @@ impl<Model: Dim, NumHeads: Dim, F: Dim> EncoderBlockConfig<Model, NumHeads, F> {
                 l2: LinearConfig::new(f, model),
             }),
             norm2: LayerNorm1DConfig(model),
+            dropout: 0.0,
         }
     }
+
+    /// Sets the dropout probabilities of the attention weights (`attn_dropout`)
+    /// and of the residual branches (`dropout`).
+    pub fn with_dropout(self, attn_dropout: f64, dropout: f64) -> Self {
+        self.try_with_dropout(attn_dropout, dropout).unwrap()
+    }
+
+    /// Fallible version of [EncoderBlockConfig::with_dropout()]. Returns
+    /// [Error::InvalidArgument] unless both probabilities are in `[0.0, 1.0)`.
+    pub fn try_with_dropout(mut self, attn_dropout: f64, dropout: f64) -> Result<Self, Error> {
+        check_dropout(dropout)?;
+        self.self_attn.0 = self.self_attn.0.try_with_dropout(attn_dropout)?;
+        self.dropout = dropout;
+        Ok(self)
+    }
+}
+
+/// Applies dropout to `x` if `train` is true and `prob` is non-zero.
+fn try_residual_dropout<S: Shape, E: Dtype, D: Device<E>, T: Tape<E, D>>(
+    x: Tensor<S, E, D, T>,
+    prob: f64,
+    train: bool,
+) -> Result<Tensor<S, E, D, T>, crate::tensor::Error> {
+    if train && prob > 0.0 {
+        x.try_dropout(prob)
+    } else {
+        Ok(x)
+    }
+}
+
+impl<M: Dim, H: Dim, F: Dim, E: Dtype + Float, D: Device<E>> EncoderBlock<M, H, F, E, D> {
+    fn try_encode<B: Dim, S: Dim, T: Tape<E, D>>(
+        &self,
+        x: Tensor<(B, S, M), E, D, T>,
+        mask: Option<&AttentionMask<B, S, S, D>>,
+        train: bool,
+    ) -> Result<Tensor<(B, S, M), E, D, T>, crate::tensor::Error> {
+        let (x, tape) = x.split_tape();
+        let y = self.self_attn.0.attention().try_attend(
+            x.clone().put_tape(tape),
+            x.clone(),
+            x.clone(),
+            mask,
+            train,
+        )?;
+        let y = try_residual_dropout(y, self.dropout, train)?;
+        let x = self.norm1.try_forward(y.try_add(x)?)?;
+
+        let (x, tape) = x.split_tape();
+        let y = self.ff.0.try_forward(x.clone().put_tape(tape))?;
+        let y = try_residual_dropout(y, self.dropout, train)?;
+        self.norm2.try_forward(y.try_add(x)?)
+    }
+
+    fn try_encode_unbatched<S: Dim, T: Tape<E, D>>(
+        &self,
+        x: Tensor<(S, M), E, D, T>,
+        mask: Option<&AttentionMask<Const<1>, S, S, D>>,
+        train: bool,
+    ) -> Result<Tensor<(S, M), E, D, T>, crate::tensor::Error> {
+        let (s, m) = *x.shape();
+        let x = x.try_broadcast_like(&(Const::<1>, s, m))?;
+        let y = self.try_encode(x, mask, train)?;
+        y.try_reshape_like(&(s, m))
+    }
+}
+
+impl<M: Dim, H: Dim, F: Dim, E: Dtype + Float, D: Device<E>, S: Dim, T: Tape<E, D>>
+    Module<Tensor<(S, M), E, D, T>> for EncoderBlock<M, H, F, E, D>
+{
+    type Output = Tensor<(S, M), E, D, T>;
+    fn try_forward(&self, x: Tensor<(S, M), E, D, T>) -> Result<Self::Output, Error> {
+        self.try_encode_unbatched(x, None, false)
+    }
+    fn try_forward_mut(&mut self, x: Tensor<(S, M), E, D, T>) -> Result<Self::Output, Error> {
+        self.try_encode_unbatched(x, None, true)
+    }
+}
+
+impl<M: Dim, H: Dim, F: Dim, E: Dtype + Float, D: Device<E>, S: Dim, T: Tape<E, D>>
+    Module<(Tensor<(S, M), E, D, T>, AttentionMask<Const<1>, S, S, D>)>
+    for EncoderBlock<M, H, F, E, D>
+{
+    type Output = Tensor<(S, M), E, D, T>;
+    fn try_forward(
+        &self,
+        (x, mask): (Tensor<(S, M), E, D, T>, AttentionMask<Const<1>, S, S, D>),
+    ) -> Result<Self::Output, Error> {
+        self.try_encode_unbatched(x, Some(&mask), false)
+    }
+    fn try_forward_mut(
+        &mut self,
+        (x, mask): (Tensor<(S, M), E, D, T>, AttentionMask<Const<1>, S, S, D>),
+    ) -> Result<Self::Output, Error> {
+        self.try_encode_unbatched(x, Some(&mask), true)
+    }
+}
+
+impl<M: Dim, H: Dim, F: Dim, E: Dtype + Float, D: Device<E>, B: Dim, S: Dim, T: Tape<E, D>>
+    Module<Tensor<(B, S, M), E, D, T>> for EncoderBlock<M, H, F, E, D>
+{
+    type Output = Tensor<(B, S, M), E, D, T>;
+    fn try_forward(&self, x: Tensor<(B, S, M), E, D, T>) -> Result<Self::Output, Error> {
+        self.try_encode(x, None, false)
+    }
+    fn try_forward_mut(&mut self, x: Tensor<(B, S, M), E, D, T>) -> Result<Self::Output, Error> {
+        self.try_encode(x, None, true)
+    }
+}
+
+impl<M: Dim, H: Dim, F: Dim, E: Dtype + Float, D: Device<E>, B: Dim, S: Dim, T: Tape<E, D>>
+    Module<(Tensor<(B, S, M), E, D, T>, AttentionMask<B, S, S, D>)>
+    for EncoderBlock<M, H, F, E, D>
+{
+    type Output = Tensor<(B, S, M), E, D, T>;
+    fn try_forward(
+        &self,
+        (x, mask): (Tensor<(B, S, M), E, D, T>, AttentionMask<B, S, S, D>),
+    ) -> Result<Self::Output, Error> {
+        self.try_encode(x, Some(&mask), false)
+    }
+    fn try_forward_mut(
+        &mut self,
+        (x, mask): (Tensor<(B, S, M), E, D, T>, AttentionMask<B, S, S, D>),
+    ) -> Result<Self::Output, Error> {
+        self.try_encode(x, Some(&mask), true)
+    }
 }
 
 /// A transformer decoder block. Different than the normal transformer block
@@ -55,13 +201,17 @@ impl<Model: Dim, NumHeads: Dim, F: Dim> EncoderBlockConfig<Model, NumHeads, F> {
 /// - `NumHeads`: The number of heads in [MultiHeadAttention].
 /// - `F`: The size of the hidden layer in the feedforward network.
 ///
+/// Inputs are `(tgt, mem)`, or `(tgt, mem, tgt_mask, mem_mask)` where `tgt_mask` is
+/// the [AttentionMask] of the self attention and `mem_mask` of the attention to `mem`.
+/// Dropout is only applied in [Module::try_forward_mut()], see [DecoderBlockConfig::with_dropout()].
+///
 /// **Pytorch equivalent**:
 /// ```python
 /// decoder = torch.nn.TransformerDecoderLayer(
 ///    Model, NumHeads, dim_feedforward=F, batch_first=True, dropout=0.0
 /// )
 /// ```
-#[derive(Clone, Debug, CustomModule)]
+#[derive(Default, Clone, Debug, CustomModule)]
 #[built(DecoderBlock)]
 pub struct DecoderBlockConfig<Model: Dim, NumHeads: Dim, F: Dim> {
     #[module]
@@ -76,6 +226,9 @@ pub struct DecoderBlockConfig<Model: Dim, NumHeads: Dim, F: Dim> {
     pub ff: ResidualAdd<FeedForwardConfig<Model, F>>,
     #[module]
     pub norm3: LayerNorm1DConfig<Model>,
+    /// Dropout probability applied to the residual branches during training, see
+    /// [DecoderBlockConfig::with_dropout()]. Defaults to `0.0`.
+    pub dropout: f64,
 }
 
 impl<Model: Dim, NumHeads: Dim, F: Dim> DecoderBlockConfig<Model, NumHeads, F> {
@@ -93,35 +246,352 @@ impl<Model: Dim, NumHeads: Dim, F: Dim> DecoderBlockConfig<Model, NumHeads, F> {
                 l2: LinearConfig::new(f, model),
             }),
             norm3: LayerNorm1DConfig(model),
+            dropout: 0.0,
         }
     }
+
+    /// Sets the dropout probabilities of the attention weights (`attn_dropout`)
+    /// and of the residual branches (`dropout`).
+    pub fn with_dropout(self, attn_dropout: f64, dropout: f64) -> Self {
+        self.try_with_dropout(attn_dropout, dropout).unwrap()
+    }
+
+    /// Fallible version of [DecoderBlockConfig::with_dropout()]. Returns
+    /// [Error::InvalidArgument] unless both probabilities are in `[0.0, 1.0)`.
+    pub fn try_with_dropout(mut self, attn_dropout: f64, dropout: f64) -> Result<Self, Error> {
+        check_dropout(dropout)?;
+        self.self_attn.0 = self.self_attn.0.try_with_dropout(attn_dropout)?;
+        self.mh_attn = self.mh_attn.try_with_dropout(attn_dropout)?;
+        self.dropout = dropout;
+        Ok(self)
+    }
+}
+
+impl<M: Dim, H: Dim, F: Dim, E: Dtype + Float, D: Device<E>> DecoderBlock<M, H, F, E, D> {
+    fn try_decode<B: Dim, S1: Dim, S2: Dim, T: Tape<E, D>>(
+        &self,
+        tgt: Tensor<(B, S1, M), E, D, T>,
+        mem: Tensor<(B, S2, M), E, D>,
+        tgt_mask: Option<&AttentionMask<B, S1, S1, D>>,
+        mem_mask: Option<&AttentionMask<B, S1, S2, D>>,
+        train: bool,
+    ) -> Result<Tensor<(B, S1, M), E, D, T>, crate::tensor::Error> {
+        let (x, tape) = tgt.split_tape();
+        let y = self.self_attn.0.attention().try_attend(
+            x.clone().put_tape(tape),
+            x.clone(),
+            x.clone(),
+            tgt_mask,
+            train,
+        )?;
+        let y = try_residual_dropout(y, self.dropout, train)?;
+        let x = self.norm1.try_forward(y.try_add(x)?)?;
+
+        let (x, tape) = x.split_tape();
+        let y = self.mh_attn.attention().try_attend(
+            x.clone().put_tape(tape),
+            mem.clone(),
+            mem,
+            mem_mask,
+            train,
+        )?;
+        let y = try_residual_dropout(y, self.dropout, train)?;
+        let x = self.norm2.try_forward(y.try_add(x)?)?;
+
+        let (x, tape) = x.split_tape();
+        let y = self.ff.0.try_forward(x.clone().put_tape(tape))?;
+        let y = try_residual_dropout(y, self.dropout, train)?;
+        self.norm3.try_forward(y.try_add(x)?)
+    }
+
+    fn try_decode_unbatched<S1: Dim, S2: Dim, T: Tape<E, D>>(
+        &self,
+        tgt: Tensor<(S1, M), E, D, T>,
+        mem: Tensor<(S2, M), E, D>,
+        tgt_mask: Option<&AttentionMask<Const<1>, S1, S1, D>>,
+        mem_mask: Option<&AttentionMask<Const<1>, S1, S2, D>>,
+        train: bool,
+    ) -> Result<Tensor<(S1, M), E, D, T>, crate::tensor::Error> {
+        let (s1, m) = *tgt.shape();
+        let s2 = mem.shape().0;
+        let tgt = tgt.try_broadcast_like(&(Const::<1>, s1, m))?;
+        let mem = mem.try_broadcast_like(&(Const::<1>, s2, m))?;
+        let y = self.try_decode(tgt, mem, tgt_mask, mem_mask, train)?;
+        y.try_reshape_like(&(s1, m))
+    }
+}
+
+/// `x + f(x)`, with dropout of probability `p` applied to `f(x)` if `p` is non-zero.
+fn try_residual<X, F>(x: X, f: F, p: f64) -> Result<X, Error>
+where
+    X: SplitTape + TryAdd<X::NoTape, Output = X>,
+    F: FnOnce(X) -> Result<X, Error>,
+    Dropout: Module<X, Output = X>,
+{
+    let (x, tape) = x.split_tape();
+    let y = f(x.clone().put_tape(tape))?;
+    let y = if p > 0.0 {
+        Dropout { p }.try_forward_mut(y)?
+    } else {
+        y
+    };
+    y.try_add(x)
 }
 
 impl<M: Dim, H: Dim, F: Dim, E: Dtype, D: Device<E>, Tgt, Mem> Module<(Tgt, Mem)>
     for DecoderBlock<M, H, F, E, D>
 where
-    Tgt: WithEmptyTape + SplitTape + TryAdd<Tgt::NoTape, Output = Tgt>,
+    Tgt: SplitTape + TryAdd<Tgt::NoTape, Output = Tgt>,
     Mem: Clone,
-    ResidualAdd<MultiHeadAttention<M, H, M, M, E, D>>: Module<Tgt, Output = Tgt>,
-    MultiHeadAttention<M, H, M, M, E, D>: Module<(Tgt, Mem, Mem), Output = Tgt>,
+    MultiHeadAttention<M, H, M, M, E, D>:
+        Module<Tgt, Output = Tgt> + Module<(Tgt, Mem, Mem), Output = Tgt>,
     LayerNorm1D<M, E, D>: Module<Tgt, Output = Tgt>,
-    ResidualAdd<FeedForward<M, F, E, D>>: Module<Tgt, Output = Tgt>,
+    FeedForward<M, F, E, D>: Module<Tgt, Output = Tgt>,
+    Dropout: Module<Tgt, Output = Tgt>,
 {
     type Output = Tgt;
-    fn try_forward(&self, (tgt, mem): (Tgt, Mem)) -> Result<Self::Output, crate::tensor::Error> {
-        let x = self.self_attn.try_forward(tgt)?;
+    fn try_forward(&self, (tgt, mem): (Tgt, Mem)) -> Result<Self::Output, Error> {
+        let x = try_residual(tgt, |x| self.self_attn.0.try_forward(x), 0.0)?;
         let x = self.norm1.try_forward(x)?;
-
-        let (x, tape) = x.split_tape();
-        let x_residual = x.clone();
-        let x = self
-            .mh_attn
-            .try_forward((x.put_tape(tape), mem.clone(), mem))?;
-        let x = x.try_add(x_residual)?;
+        let x = try_residual(x, |x| self.mh_attn.try_forward((x, mem.clone(), mem)), 0.0)?;
         let x = self.norm2.try_forward(x)?;
-        let x = self.ff.try_forward(x)?;
+        let x = try_residual(x, |x| self.ff.0.try_forward(x), 0.0)?;
         self.norm3.try_forward(x)
     }
+    fn try_forward_mut(&mut self, (tgt, mem): (Tgt, Mem)) -> Result<Self::Output, Error> {
+        let p = self.dropout;
+        let x = try_residual(tgt, |x| self.self_attn.0.try_forward_mut(x), p)?;
+        let x = self.norm1.try_forward_mut(x)?;
+        let x = try_residual(
+            x,
+            |x| self.mh_attn.try_forward_mut((x, mem.clone(), mem)),
+            p,
+        )?;
+        let x = self.norm2.try_forward_mut(x)?;
+        let x = try_residual(x, |x| self.ff.0.try_forward_mut(x), p)?;
+        self.norm3.try_forward_mut(x)
+    }
+}
+
+impl<M: Dim, H: Dim, F: Dim, E, D, S1: Dim, S2: Dim, T>
+    Module<(
+        Tensor<(S1, M), E, D, T>,
+        Tensor<(S2, M), E, D>,
+        AttentionMask<Const<1>, S1, S1, D>,
+        AttentionMask<Const<1>, S1, S2, D>,
+    )> for DecoderBlock<M, H, F, E, D>
+where
+    E: Dtype + Float,
+    D: Device<E>,
+    T: Tape<E, D>,
+{
+    type Output = Tensor<(S1, M), E, D, T>;
+    fn try_forward(
+        &self,
+        (tgt, mem, tgt_mask, mem_mask): (
+            Tensor<(S1, M), E, D, T>,
+            Tensor<(S2, M), E, D>,
+            AttentionMask<Const<1>, S1, S1, D>,
+            AttentionMask<Const<1>, S1, S2, D>,
+        ),
+    ) -> Result<Self::Output, Error> {
+        self.try_decode_unbatched(tgt, mem, Some(&tgt_mask), Some(&mem_mask), false)
+    }
+    fn try_forward_mut(
+        &mut self,
+        (tgt, mem, tgt_mask, mem_mask): (
+            Tensor<(S1, M), E, D, T>,
+            Tensor<(S2, M), E, D>,
+            AttentionMask<Const<1>, S1, S1, D>,
+            AttentionMask<Const<1>, S1, S2, D>,
+        ),
+    ) -> Result<Self::Output, Error> {
+        self.try_decode_unbatched(tgt, mem, Some(&tgt_mask), Some(&mem_mask), true)
+    }
+}
+
+impl<M: Dim, H: Dim, F: Dim, E, D, B: Dim, S1: Dim, S2: Dim, T>
+    Module<(
+        Tensor<(B, S1, M), E, D, T>,
+        Tensor<(B, S2, M), E, D>,
+        AttentionMask<B, S1, S1, D>,
+        AttentionMask<B, S1, S2, D>,
+    )> for DecoderBlock<M, H, F, E, D>
+where
+    E: Dtype + Float,
+    D: Device<E>,
+    T: Tape<E, D>,
+{
+    type Output = Tensor<(B, S1, M), E, D, T>;
+    fn try_forward(
+        &self,
+        (tgt, mem, tgt_mask, mem_mask): (
+            Tensor<(B, S1, M), E, D, T>,
+            Tensor<(B, S2, M), E, D>,
+            AttentionMask<B, S1, S1, D>,
+            AttentionMask<B, S1, S2, D>,
+        ),
+    ) -> Result<Self::Output, Error> {
+        self.try_decode(tgt, mem, Some(&tgt_mask), Some(&mem_mask), false)
+    }
+    fn try_forward_mut(
+        &mut self,
+        (tgt, mem, tgt_mask, mem_mask): (
+            Tensor<(B, S1, M), E, D, T>,
+            Tensor<(B, S2, M), E, D>,
+            AttentionMask<B, S1, S1, D>,
+            AttentionMask<B, S1, S2, D>,
+        ),
+    ) -> Result<Self::Output, Error> {
+        self.try_decode(tgt, mem, Some(&tgt_mask), Some(&mem_mask), true)
+    }
+}
+
+/// Cached keys and values of a [DecoderBlock] for incremental decoding: the self
+/// attention's previous timesteps, and the projected encoder output.
+///
+/// Start decoding with [DecoderBlockCache::default()], then pass the returned cache to the
+/// next step together with the same encoder output `mem` (or a clone of it). The encoder
+/// output is only projected in the first step, so passing a different `mem` to a non-empty
+/// cache returns [Error::InvalidArgument].
+#[derive(Clone, Debug)]
+pub struct DecoderBlockCache<B: Dim, M: Dim, E, D: Storage<E>> {
+    /// Keys and values of the previous timesteps of the self attention.
+    pub self_attn: KVCache<B, M, M, E, D>,
+    /// Projected keys and values of the encoder output.
+    pub mh_attn: KVCache<B, M, M, E, D>,
+    /// Id of the encoder output that [DecoderBlockCache::mh_attn] was projected from.
+    pub mem_id: Option<UniqueId>,
+}
+
+impl<B: Dim, M: Dim, E, D: Storage<E>> Default for DecoderBlockCache<B, M, E, D> {
+    fn default() -> Self {
+        Self {
+            self_attn: Default::default(),
+            mh_attn: Default::default(),
+            mem_id: None,
+        }
+    }
+}
+
+impl<M: Dim, H: Dim, F: Dim, E, D> DecoderBlock<M, H, F, E, D>
+where
+    E: Dtype + Float,
+    D: Device<E> + TriangleTensor<bool>,
+{
+    /// Incremental decoding of the new timesteps `tgt`, with an optional mask for the
+    /// attention to the encoder output `mem`.
+    fn try_decode_cached<B: Dim, S1: Dim, S2: Dim, T: Tape<E, D>>(
+        &self,
+        tgt: Tensor<(B, S1, M), E, D, T>,
+        mem: Tensor<(B, S2, M), E, D>,
+        mem_mask: Option<&AttentionMask<B, S1, S2, D>>,
+        cache: DecoderBlockCache<B, M, E, D>,
+    ) -> Result<(Tensor<(B, S1, M), E, D, T>, DecoderBlockCache<B, M, E, D>), crate::tensor::Error>
+    {
+        let mem_id = mem.id();
+        if matches!(cache.mem_id, Some(id) if id != mem_id) {
+            return Err(Error::InvalidArgument(
+                "the cache was created with a different encoder output `mem`".into(),
+            ));
+        }
+
+        let past = cache.self_attn.len();
+        let (x, tape) = tgt.split_tape();
+        let (y, self_attn) = self
+            .self_attn
+            .0
+            .attention()
+            .try_self_attend_cached(x.clone().put_tape(tape), cache.self_attn)?;
+        let x = self.norm1.try_forward(y.try_add(x)?)?;
+
+        // the encoder output doesn't change between timesteps, so it is only projected once
+        let s2 = mem.shape().1;
+        let mh_attn = if cache.mh_attn.is_empty() {
+            let (k, v) = self
+                .mh_attn
+                .attention()
+                .try_project_kv(mem.clone(), mem, 0)?;
+            cache.mh_attn.try_append(k, v)?
+        } else {
+            cache.mh_attn
+        };
+        let (x, tape) = x.split_tape();
+        let y = self.mh_attn.attention().try_attend_cached(
+            x.clone().put_tape(tape),
+            &mh_attn,
+            s2,
+            mem_mask,
+            past,
+        )?;
+        let x = self.norm2.try_forward(y.try_add(x)?)?;
+
+        let (x, tape) = x.split_tape();
+        let y = self.ff.0.try_forward(x.clone().put_tape(tape))?;
+        let x = self.norm3.try_forward(y.try_add(x)?)?;
+        Ok((
+            x,
+            DecoderBlockCache {
+                self_attn,
+                mh_attn,
+                mem_id: Some(mem_id),
+            },
+        ))
+    }
+}
+
+impl<M: Dim, H: Dim, F: Dim, E, D, B: Dim, S1: Dim, S2: Dim, T>
+    Module<(
+        Tensor<(B, S1, M), E, D, T>,
+        Tensor<(B, S2, M), E, D>,
+        DecoderBlockCache<B, M, E, D>,
+    )> for DecoderBlock<M, H, F, E, D>
+where
+    E: Dtype + Float,
+    D: Device<E> + TriangleTensor<bool>,
+    T: Tape<E, D>,
+{
+    type Output = (Tensor<(B, S1, M), E, D, T>, DecoderBlockCache<B, M, E, D>);
+
+    /// Incremental decoding of the new timesteps `tgt`, using and extending the [DecoderBlockCache]
+    fn try_forward(
+        &self,
+        (tgt, mem, cache): (
+            Tensor<(B, S1, M), E, D, T>,
+            Tensor<(B, S2, M), E, D>,
+            DecoderBlockCache<B, M, E, D>,
+        ),
+    ) -> Result<Self::Output, Error> {
+        self.try_decode_cached(tgt, mem, None, cache)
+    }
+}
+
+impl<M: Dim, H: Dim, F: Dim, E, D, B: Dim, S1: Dim, S2: Dim, T>
+    Module<(
+        Tensor<(B, S1, M), E, D, T>,
+        Tensor<(B, S2, M), E, D>,
+        AttentionMask<B, S1, S2, D>,
+        DecoderBlockCache<B, M, E, D>,
+    )> for DecoderBlock<M, H, F, E, D>
+where
+    E: Dtype + Float,
+    D: Device<E> + TriangleTensor<bool>,
+    T: Tape<E, D>,
+{
+    type Output = (Tensor<(B, S1, M), E, D, T>, DecoderBlockCache<B, M, E, D>);
+
+    /// Masked incremental decoding, where `mem_mask` masks the attention to the encoder output `mem`
+    fn try_forward(
+        &self,
+        (tgt, mem, mem_mask, cache): (
+            Tensor<(B, S1, M), E, D, T>,
+            Tensor<(B, S2, M), E, D>,
+            AttentionMask<B, S1, S2, D>,
+            DecoderBlockCache<B, M, E, D>,
+        ),
+    ) -> Result<Self::Output, Error> {
+        self.try_decode_cached(tgt, mem, Some(&mem_mask), cache)
+    }
 }
 
 /// Transformer architecture as described in
@@ -134,6 +604,15 @@ where
 /// - `NumHeads`: Number of heads for [MultiHeadAttention].
 /// - `F`: Feedforward hidden dimension for both encoder/decoder
 ///
+/// Inputs are `(src, tgt)`, or `(src, tgt, src_mask, tgt_mask, mem_mask)` with an
+/// [AttentionMask] for the encoder self attention, the decoder self attention,
+/// and the decoder attention to the encoder output respectively.
+///
+/// For incremental decoding, run the encoder once with `transformer.encoder.forward(src)`,
+/// then pass `(tgt, mem, cache)` or `(tgt, mem, mem_mask, cache)` where `tgt` holds only the
+/// new timesteps and `cache` has one [DecoderBlockCache] per decoder block (an empty [Vec] to
+/// start). The decoder self attention is causal in this mode.
+///
 /// **Pytorch equivalent**:
 /// ```python
 /// torch.nn.Transformer(
@@ -172,6 +651,28 @@ impl<Model: Dim, NumHeads: Dim, F: Dim> TransformerConfig<Model, NumHeads, F> {
         }
         Self { encoder, decoder }
     }
+
+    /// Sets the dropout probabilities of the attention weights (`attn_dropout`)
+    /// and of the residual branches (`dropout`) in every encoder and decoder block.
+    pub fn with_dropout(self, attn_dropout: f64, dropout: f64) -> Self {
+        self.try_with_dropout(attn_dropout, dropout).unwrap()
+    }
+
+    /// Fallible version of [TransformerConfig::with_dropout()]. Returns
+    /// [Error::InvalidArgument] unless both probabilities are in `[0.0, 1.0)`.
+    pub fn try_with_dropout(mut self, attn_dropout: f64, dropout: f64) -> Result<Self, Error> {
+        self.encoder = self
+            .encoder
+            .into_iter()
+            .map(|block| block.try_with_dropout(attn_dropout, dropout))
+            .collect::<Result<_, _>>()?;
+        self.decoder = self
+            .decoder
+            .into_iter()
+            .map(|block| block.try_with_dropout(attn_dropout, dropout))
+            .collect::<Result<_, _>>()?;
+        Ok(self)
+    }
 }
 
 impl<M: Dim, H: Dim, F: Dim, E: Dtype, D: Device<E>, Src: SplitTape, Tgt: PutTape<Src::Tape>>
@@ -192,6 +693,167 @@ where
         }
         Ok(tgt)
     }
+    fn try_forward_mut(
+        &mut self,
+        (src, tgt): (Src, Tgt),
+    ) -> Result<Self::Output, crate::tensor::Error> {
+        let (mem, tape) = self.encoder.try_forward_mut(src)?.split_tape();
+        let mut tgt = tgt.put_tape(tape);
+        for block in self.decoder.iter_mut() {
+            tgt = block.try_forward_mut((tgt, mem.clone()))?;
+        }
+        Ok(tgt)
+    }
+}
+
+impl<M: Dim, H: Dim, F: Dim, E, D, Src, Tgt, SrcMask, TgtMask, MemMask>
+    Module<(Src, Tgt, SrcMask, TgtMask, MemMask)> for Transformer<M, H, F, E, D>
+where
+    E: Dtype,
+    D: Device<E>,
+    Src: SplitTape,
+    Tgt: PutTape<Src::Tape>,
+    SrcMask: Clone,
+    TgtMask: Clone,
+    MemMask: Clone,
+    EncoderBlock<M, H, F, E, D>: Module<(Src, SrcMask), Output = Src>,
+    DecoderBlock<M, H, F, E, D>: Module<
+        (
+            <Tgt as PutTape<Src::Tape>>::Output,
+            Src::NoTape,
+            TgtMask,
+            MemMask,
+        ),
+        Output = <Tgt as PutTape<Src::Tape>>::Output,
+    >,
+{
+    type Output = <Tgt as PutTape<Src::Tape>>::Output;
+    fn try_forward(
+        &self,
+        (mut src, tgt, src_mask, tgt_mask, mem_mask): (Src, Tgt, SrcMask, TgtMask, MemMask),
+    ) -> Result<Self::Output, crate::tensor::Error> {
+        for block in self.encoder.iter() {
+            src = block.try_forward((src, src_mask.clone()))?;
+        }
+        let (mem, tape) = src.split_tape();
+        let mut tgt = tgt.put_tape(tape);
+        for block in self.decoder.iter() {
+            tgt = block.try_forward((tgt, mem.clone(), tgt_mask.clone(), mem_mask.clone()))?;
+        }
+        Ok(tgt)
+    }
+    fn try_forward_mut(
+        &mut self,
+        (mut src, tgt, src_mask, tgt_mask, mem_mask): (Src, Tgt, SrcMask, TgtMask, MemMask),
+    ) -> Result<Self::Output, crate::tensor::Error> {
+        for block in self.encoder.iter_mut() {
+            src = block.try_forward_mut((src, src_mask.clone()))?;
+        }
+        let (mem, tape) = src.split_tape();
+        let mut tgt = tgt.put_tape(tape);
+        for block in self.decoder.iter_mut() {
+            tgt = block.try_forward_mut((tgt, mem.clone(), tgt_mask.clone(), mem_mask.clone()))?;
+        }
+        Ok(tgt)
+    }
+}
+
+impl<M: Dim, H: Dim, F: Dim, E, D> Transformer<M, H, F, E, D>
+where
+    E: Dtype + Float,
+    D: Device<E> + TriangleTensor<bool>,
+{
+    fn try_decode_cached<B: Dim, S1: Dim, S2: Dim, T: Tape<E, D>>(
+        &self,
+        mut tgt: Tensor<(B, S1, M), E, D, T>,
+        mem: Tensor<(B, S2, M), E, D>,
+        mem_mask: Option<&AttentionMask<B, S1, S2, D>>,
+        cache: Vec<DecoderBlockCache<B, M, E, D>>,
+    ) -> Result<
+        (
+            Tensor<(B, S1, M), E, D, T>,
+            Vec<DecoderBlockCache<B, M, E, D>>,
+        ),
+        Error,
+    > {
+        if !cache.is_empty() && cache.len() != self.decoder.len() {
+            return Err(Error::InvalidArgument(format!(
+                "expected an empty cache or one DecoderBlockCache per decoder block ({}), found {}",
+                self.decoder.len(),
+                cache.len()
+            )));
+        }
+        let mut cache = cache.into_iter();
+        let mut new_cache = Vec::with_capacity(self.decoder.len());
+        for block in self.decoder.iter() {
+            let block_cache = cache.next().unwrap_or_default();
+            let (y, block_cache) =
+                block.try_decode_cached(tgt, mem.clone(), mem_mask, block_cache)?;
+            tgt = y;
+            new_cache.push(block_cache);
+        }
+        Ok((tgt, new_cache))
+    }
+}
+
+impl<M: Dim, H: Dim, F: Dim, E, D, B: Dim, S1: Dim, S2: Dim, T>
+    Module<(
+        Tensor<(B, S1, M), E, D, T>,
+        Tensor<(B, S2, M), E, D>,
+        Vec<DecoderBlockCache<B, M, E, D>>,
+    )> for Transformer<M, H, F, E, D>
+where
+    E: Dtype + Float,
+    D: Device<E> + TriangleTensor<bool>,
+    T: Tape<E, D>,
+{
+    type Output = (
+        Tensor<(B, S1, M), E, D, T>,
+        Vec<DecoderBlockCache<B, M, E, D>>,
+    );
+
+    /// Incremental decoding of the new timesteps `tgt` given the encoder output `mem`
+    fn try_forward(
+        &self,
+        (tgt, mem, cache): (
+            Tensor<(B, S1, M), E, D, T>,
+            Tensor<(B, S2, M), E, D>,
+            Vec<DecoderBlockCache<B, M, E, D>>,
+        ),
+    ) -> Result<Self::Output, crate::tensor::Error> {
+        self.try_decode_cached(tgt, mem, None, cache)
+    }
+}
+
+impl<M: Dim, H: Dim, F: Dim, E, D, B: Dim, S1: Dim, S2: Dim, T>
+    Module<(
+        Tensor<(B, S1, M), E, D, T>,
+        Tensor<(B, S2, M), E, D>,
+        AttentionMask<B, S1, S2, D>,
+        Vec<DecoderBlockCache<B, M, E, D>>,
+    )> for Transformer<M, H, F, E, D>
+where
+    E: Dtype + Float,
+    D: Device<E> + TriangleTensor<bool>,
+    T: Tape<E, D>,
+{
+    type Output = (
+        Tensor<(B, S1, M), E, D, T>,
+        Vec<DecoderBlockCache<B, M, E, D>>,
+    );
+
+    /// Incremental decoding where `mem_mask` masks the attention to the encoder output `mem`
+    fn try_forward(
+        &self,
+        (tgt, mem, mem_mask, cache): (
+            Tensor<(B, S1, M), E, D, T>,
+            Tensor<(B, S2, M), E, D>,
+            AttentionMask<B, S1, S2, D>,
+            Vec<DecoderBlockCache<B, M, E, D>>,
+        ),
+    ) -> Result<Self::Output, crate::tensor::Error> {
+        self.try_decode_cached(tgt, mem, Some(&mem_mask), cache)
+    }
 }
 
 #[cfg(test)]
@@ -372,4 +1034,211 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_transformer_masked_dropout_backward() {
+        let dev = TestDevice::seed_from_u64(0);
+        let mut t = dev.build_module::<TestDtype>(
+            TransformerConfig::new(Const::<16>, Const::<4>, Const::<8>, 2, 2)
+                .with_dropout(0.1, 0.1),
+        );
+
+        let src = dev.sample_normal::<Rank3<2, 5, 16>>();
+        let tgt = dev.sample_normal::<Rank3<2, 3, 16>>();
+        let src_padding = dev.tensor([[false; 5], [false, false, false, true, true]]);
+        let src_mask = AttentionMask::key_padding(src_padding.clone());
+        let tgt_mask = AttentionMask::causal(&dev, Const::<3>);
+        let mem_mask = AttentionMask::key_padding(src_padding);
+
+        let y1 = t.forward((
+            src.clone(),
+            tgt.clone(),
+            src_mask.clone(),
+            tgt_mask.clone(),
+            mem_mask.clone(),
+        ));
+        let y2 = t.forward((
+            src.clone(),
+            tgt.clone(),
+            src_mask.clone(),
+            tgt_mask.clone(),
+            mem_mask.clone(),
+        ));
+        assert_eq!(y1.array(), y2.array());
+
+        let out: Tensor<Rank3<2, 3, 16>, _, _, _> =
+            t.forward_mut((src.leaky_trace(), tgt, src_mask, tgt_mask, mem_mask));
+        assert_ne!(y1.array(), out.array());
+        let g = out.mean().backward();
+        for v in g.get(&src).as_vec() {
+            assert!(v.is_finite());
+        }
+
+        let mut opt = crate::nn::optim::Sgd::new(&t, Default::default());
+        opt.update(&mut t, &g).expect("");
+    }
+
+    #[test]
+    fn test_decoder_block_dropout() {
+        let dev = TestDevice::seed_from_u64(0);
+        let mut decoder = dev.build_module::<TestDtype>(
+            DecoderBlockConfig::new(Const::<16>, Const::<4>, Const::<8>).with_dropout(0.1, 0.1),
+        );
+
+        let tgt = dev.sample_normal::<Rank3<2, 3, 16>>();
+        let mem = dev.sample_normal::<Rank3<2, 5, 16>>();
+        let y1 = decoder.forward((tgt.clone(), mem.clone()));
+        let y2 = decoder.forward((
+            tgt.clone(),
+            mem.clone(),
+            AttentionMask::default(),
+            AttentionMask::default(),
+        ));
+        assert_eq!(y1.array(), y2.array());
+
+        let y3 = decoder.forward_mut((tgt.leaky_trace(), mem));
+        assert_ne!(y1.array(), y3.array());
+        let g = y3.mean().backward();
+        assert!(g.get(&tgt).as_vec().iter().all(|g| g.is_finite()));
+    }
+
+    #[test]
+    fn test_invalid_dropout() {
+        type Model = TransformerConfig<Const<16>, Const<4>, Const<8>>;
+        let new = || Model::new(Const, Const, Const, 1, 1);
+        assert!(matches!(
+            new().try_with_dropout(1.0, 0.1),
+            Err(Error::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            new().try_with_dropout(0.1, -0.1),
+            Err(Error::InvalidArgument(_))
+        ));
+        let cfg = new().try_with_dropout(0.1, 0.2).unwrap();
+        assert_eq!(cfg.decoder[0].mh_attn.dropout, 0.1);
+        assert_eq!(cfg.encoder[0].dropout, 0.2);
+    }
+
+    #[test]
+    fn test_encoder_block_causal_mask() {
+        let dev: TestDevice = Default::default();
+        let encoder = dev
+            .build_module::<TestDtype>(EncoderBlockConfig::new(Const::<8>, Const::<2>, Const::<4>));
+
+        let x: Tensor<Rank3<2, 4, 8>, TestDtype, _> = dev.sample_normal();
+        let y = encoder.forward((x.clone(), AttentionMask::causal(&dev, Const)));
+
+        // outputs don't depend on later positions
+        let prefix = x.slice((.., ..2, ..)).realize::<Rank3<2, 2, 8>>();
+        let expected = encoder.forward((prefix, AttentionMask::causal(&dev, Const)));
+        let y = y.slice((.., ..2, ..)).realize::<Rank3<2, 2, 8>>().array();
+        y.assert_close(&expected.array(), y.get_default_tol());
+    }
+
+    #[test]
+    fn test_transformer_kv_cache() {
+        let dev = TestDevice::seed_from_u64(0);
+        let t = dev.build_module::<TestDtype>(TransformerConfig::new(
+            Const::<16>,
+            Const::<4>,
+            Const::<8>,
+            2,
+            2,
+        ));
+
+        let src = dev.sample_normal::<Rank3<2, 5, 16>>();
+        let tgt = dev.sample_normal::<Rank3<2, 4, 16>>();
+        let expected = t.forward((
+            src.clone(),
+            tgt.clone(),
+            AttentionMask::default(),
+            AttentionMask::causal(&dev, Const),
+            AttentionMask::default(),
+        ));
+
+        let mem = t.encoder.forward(src);
+        let mut cache = Vec::new();
+        for i in 0..4 {
+            let (y, new_cache) =
+                t.forward((tgt.clone().slice((.., i..i + 1, ..)), mem.clone(), cache));
+            cache = new_cache;
+            assert_eq!(cache.len(), 2);
+            assert_eq!(cache[0].self_attn.len(), i + 1);
+            assert_eq!(cache[0].mh_attn.len(), 5);
+            let y = y.realize::<Rank3<2, 1, 16>>().array();
+            let e = expected.clone().slice((.., i..i + 1, ..));
+            let e = e.realize::<Rank3<2, 1, 16>>();
+            y.assert_close(&e.array(), y.get_default_tol());
+        }
+    }
+
+    #[test]
+    fn test_transformer_kv_cache_mem_mask() {
+        let dev = TestDevice::seed_from_u64(0);
+        let t = dev.build_module::<TestDtype>(TransformerConfig::new(
+            Const::<16>,
+            Const::<4>,
+            Const::<8>,
+            2,
+            2,
+        ));
+
+        let src = dev.sample_normal::<Rank3<2, 5, 16>>();
+        let tgt = dev.sample_normal::<Rank3<2, 3, 16>>();
+        let src_padding = dev.tensor([[false; 5], [false, false, false, true, true]]);
+        let expected = t.forward((
+            src.clone(),
+            tgt.clone(),
+            AttentionMask::key_padding(src_padding.clone()),
+            AttentionMask::causal(&dev, Const),
+            AttentionMask::key_padding(src_padding.clone()),
+        ));
+
+        let mut mem = src;
+        for block in t.encoder.iter() {
+            mem = block.forward((mem, AttentionMask::key_padding(src_padding.clone())));
+        }
+        let mem_mask = AttentionMask::key_padding(src_padding);
+        let mut cache = Vec::new();
+        for i in 0..3 {
+            let (y, new_cache) = t.forward((
+                tgt.clone().slice((.., i..i + 1, ..)),
+                mem.clone(),
+                mem_mask.clone(),
+                cache,
+            ));
+            cache = new_cache;
+            let y = y.realize::<Rank3<2, 1, 16>>().array();
+            let e = expected.clone().slice((.., i..i + 1, ..));
+            let e = e.realize::<Rank3<2, 1, 16>>();
+            y.assert_close(&e.array(), y.get_default_tol());
+        }
+
+        // the cache must have one entry per decoder block
+        cache.pop();
+        let x = tgt.slice((.., ..1, ..));
+        assert!(t.try_forward((x, mem, cache)).is_err());
+    }
+
+    #[test]
+    fn test_decoder_block_cache_mem_mismatch() {
+        let dev: TestDevice = Default::default();
+        let decoder = dev.build_module::<TestDtype>(DecoderBlockConfig::new(
+            Const::<16>,
+            Const::<4>,
+            Const::<8>,
+        ));
+
+        let x = dev.sample_normal::<Rank3<2, 1, 16>>();
+        let mem = dev.sample_normal::<Rank3<2, 5, 16>>();
+        let (_, cache) = decoder.forward((x.clone(), mem.clone(), Default::default()));
+        assert_eq!(cache.mem_id, Some(mem.id()));
+        let (_, cache) = decoder.forward((x.clone(), mem.clone(), cache));
+
+        let other = dev.sample_normal::<Rank3<2, 5, 16>>();
+        assert!(matches!(
+            decoder.try_forward((x, other, cache)),
+            Err(Error::InvalidArgument(_))
+        ));
+    }
 }
@@ -0,0 +1,189 @@
+use std::marker::PhantomData;
+
+use super::param_groups::ParamGroups;
+use crate::{
+    nn::UpdateParams,
+    shapes::{Dtype, Shape},
+    tensor::{Error, Gradients, Storage, Tensor, UniqueId},
+    tensor_ops::{AdadeltaConfig, AdadeltaKernel, Device},
+};
+
+/// An implementation of the Adadelta optimizer from
+/// [ADADELTA: An Adaptive Learning Rate Method](https://arxiv.org/abs/1212.5701).
+///
+/// # Example Usage
+/// ```rust
+/// # use dfdx::prelude::*;
+/// # type Model = Tensor<Rank0, f32, Cpu>;
+/// # let dev: Cpu = Default::default();
+/// # let model: Model = dev.zeros();
+/// let mut opt: Adadelta<Model, f32, Cpu> = optim::Adadelta::new(&model, AdadeltaConfig {
+///     lr: 1.0,
+///     rho: 0.95,
+///     eps: 1e-6,
+///     weight_decay: None,
+/// });
+/// ```
+///
+/// See module level documentation at [crate::nn::optim] for examples of how to actually use an optimizer.
+#[derive(Debug, Clone)]
+pub struct Adadelta<M, E: Dtype, D: Storage<E>> {
+    /// Hyperparameter configuration
+    pub cfg: AdadeltaConfig,
+    groups: ParamGroups<AdadeltaConfig>,
+
+    square_avg: Gradients<E, D>,
+    acc_delta: Gradients<E, D>,
+
+    marker: PhantomData<*const M>,
+}
+
+impl<M, E: Dtype, D: Storage<E>> Adadelta<M, E, D> {
+    /// Constructs using hyperparameters from `cfg`.
+    pub fn new(_model: &M, cfg: AdadeltaConfig) -> Self {
+        Self {
+            cfg,
+            groups: Default::default(),
+            square_avg: Gradients::leaky(),
+            acc_delta: Gradients::leaky(),
+            marker: PhantomData,
+        }
+    }
+
+    /// Uses `cfg` instead of [Adadelta::cfg] for the parameters of `module` whose path (like `"0.bias"`)
+    /// is selected by `select`. See [crate::nn::optim#parameter-groups].
+    pub fn with_param_group<P: UpdateParams<E, D>>(
+        mut self,
+        module: &P,
+        select: impl Fn(&str) -> bool,
+        cfg: AdadeltaConfig,
+    ) -> Self
+    where
+        D: Device<E>,
+    {
+        self.groups.add(module, select, cfg, self.cfg.lr);
+        self
+    }
+}
+
+impl<M, E: Dtype, D: Device<E>> super::FreezeParams<E, D> for Adadelta<M, E, D> {
+    fn set_frozen<P: UpdateParams<E, D>>(&mut self, module: &P, frozen: bool) {
+        self.groups.frozen.set(module, frozen);
+    }
+}
+
+impl<M, E: Dtype, D: Storage<E>> super::LearningRate for Adadelta<M, E, D> {
+    fn lr(&self) -> f64 {
+        self.cfg.lr
+    }
+    fn set_lr(&mut self, lr: f64) {
+        self.cfg.lr = lr;
+        self.groups.set_lr(lr);
+    }
+}
+
+#[cfg(feature = "safetensors")]
+impl<M, E: Dtype, D: Device<E>> crate::nn::OptimizerState<E, D> for Adadelta<M, E, D> {
+    fn state_buffers(&mut self) -> Vec<(&'static str, &mut Gradients<E, D>)> {
+        vec![
+            ("square_avg", &mut self.square_avg),
+            ("acc_delta", &mut self.acc_delta),
+        ]
+    }
+}
+
+impl<M, E: Dtype, D: Device<E> + AdadeltaKernel<E>> crate::nn::Optimizer<M, E, D>
+    for Adadelta<M, E, D>
+{
+    fn update_tensor<S: Shape>(
+        &mut self,
+        t: &mut Tensor<S, E, D>,
+        gradients: &Gradients<E, D>,
+        missing_params: &mut Vec<UniqueId>,
+    ) -> Result<(), Error> {
+        self.groups
+            .try_update(&self.cfg, t, gradients, missing_params, |cfg, t, g| {
+                let sa = self.square_avg.get_or_alloc_mut(t)?;
+                let da = self.acc_delta.get_or_alloc_mut(t)?;
+                cfg.try_update(t, sa, da, g)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{nn::optim::test_utils::test_matches_expected, prelude::*};
+
+    #[test]
+    fn test_adadelta_default() {
+        let cfg = Default::default();
+        #[rustfmt::skip]
+        const EXPECTED: [[f64; 5]; 5] = [
+            [-0.50316155, -0.25316201, 0.096837789, 0.59683773, 0.99683772],
+            [-0.50639547, -0.25639642, 0.093603167, 0.59360305, 0.99360304],
+            [-0.50967224, -0.25967368, 0.090325684, 0.59032551, 0.99032548],
+            [-0.5129772, -0.26297915, 0.087019998, 0.58701976, 0.98701973],
+            [-0.51630164, -0.26630409, 0.08369483, 0.58369453, 0.98369449],
+        ];
+        test_matches_expected(|t| Adadelta::new(t, cfg), EXPECTED);
+    }
+
+    #[test]
+    fn test_adadelta_custom() {
+        let cfg = AdadeltaConfig {
+            lr: 0.5,
+            rho: 0.5,
+            eps: 1e-4,
+            weight_decay: None,
+        };
+        #[rustfmt::skip]
+        const EXPECTED: [[f64; 5]; 5] = [
+            [-0.50703864, -0.25705909, 0.092931893, 0.59292933, 0.99292901],
+            [-0.51512109, -0.26516912, 0.084809684, 0.58480367, 0.98480292],
+            [-0.52396342, -0.27404517, 0.075918734, 0.57590849, 0.97590721],
+            [-0.53342958, -0.28355107, 0.066395286, 0.56638006, 0.96637816],
+            [-0.54343718, -0.29360453, 0.056321541, 0.55630056, 0.95629793],
+        ];
+        test_matches_expected(|t| Adadelta::new(t, cfg), EXPECTED);
+    }
+
+    #[test]
+    fn test_adadelta_l2_weight_decay() {
+        let cfg = AdadeltaConfig {
+            weight_decay: Some(WeightDecay::L2(0.5)),
+            ..Default::default()
+        };
+        #[rustfmt::skip]
+        const EXPECTED: [[f64; 5]; 5] = [
+            [-0.49683922, -0.25316114, 0.096837777, 0.59683773, 0.99683772],
+            [-0.49363466, -0.25636287, 0.093606798, 0.59360275, 0.99360233],
+            [-0.49042754, -0.25956297, 0.09033808, 0.59032451, 0.9903231],
+            [-0.48723892, -0.26274014, 0.08704709, 0.58701762, 0.98701455],
+            [-0.48408147, -0.26588177, 0.083743071, 0.58369079, 0.98368532],
+        ];
+        test_matches_expected(|t| Adadelta::new(t, cfg), EXPECTED);
+    }
+
+    #[test]
+    fn test_adadelta_decoupled_weight_decay() {
+        let cfg = AdadeltaConfig {
+            weight_decay: Some(WeightDecay::Decoupled(0.5)),
+            ..Default::default()
+        };
+        #[rustfmt::skip]
+        const EXPECTED: [[f64; 5]; 5] = [
+            [-0.25316155, -0.12816201, 0.046837789, 0.29683773, 0.49683772],
+            [-0.1304502, -0.067669568, 0.020342591, 0.14619008, 0.24681085],
+            [-0.069648915, -0.037733369, 0.0071482858, 0.07132149, 0.12236458],
+            [-0.039618825, -0.022963433, 0.00056741787, 0.034076955, 0.060342494],
+            [-0.024831662, -0.015700507, -0.0027280711, 0.015529752, 0.029408207],
+        ];
+        test_matches_expected(|t| Adadelta::new(t, cfg), EXPECTED);
+    }
+
+    #[test]
+    fn test_unused_tensors() {
+        crate::nn::optim::test_utils::test_unused_tensors(|t| Adadelta::new(t, Default::default()));
+    }
+}
@@ -0,0 +1,208 @@
+use std::marker::PhantomData;
+
+use super::param_groups::ParamGroups;
+use crate::{
+    nn::UpdateParams,
+    shapes::{Dtype, Shape},
+    tensor::{Error, Gradients, Storage, Tensor, UniqueId},
+    tensor_ops::{AdagradConfig, AdagradKernel, Device},
+};
+
+/// An implementation of the Adagrad optimizer from
+/// [Adaptive Subgradient Methods for Online Learning and Stochastic Optimization](https://jmlr.org/papers/v12/duchi11a.html).
+///
+/// Each parameter's learning rate is divided by the root of the sum of all of its squared gradients so far,
+/// which works well for sparse features.
+///
+/// # Example Usage
+/// ```rust
+/// # use dfdx::prelude::*;
+/// # type Model = Tensor<Rank0, f32, Cpu>;
+/// # let dev: Cpu = Default::default();
+/// # let model: Model = dev.zeros();
+/// let mut opt: Adagrad<Model, f32, Cpu> = optim::Adagrad::new(&model, AdagradConfig {
+///     lr: 1e-1,
+///     lr_decay: 1e-4,
+///     eps: 1e-10,
+///     weight_decay: Some(WeightDecay::L2(1e-4)),
+/// });
+/// ```
+///
+/// See module level documentation at [crate::nn::optim] for examples of how to actually use an optimizer.
+#[derive(Debug, Clone)]
+pub struct Adagrad<M, E: Dtype, D: Storage<E>> {
+    /// Hyperparameter configuration
+    pub cfg: AdagradConfig,
+    groups: ParamGroups<AdagradConfig>,
+
+    t: i32,
+    sum_squares: Gradients<E, D>,
+
+    marker: PhantomData<*const M>,
+}
+
+impl<M, E: Dtype, D: Storage<E>> Adagrad<M, E, D> {
+    /// Constructs using hyperparameters from `cfg`.
+    pub fn new(_model: &M, cfg: AdagradConfig) -> Self {
+        Self {
+            cfg,
+            groups: Default::default(),
+            t: 0,
+            sum_squares: Gradients::leaky(),
+            marker: PhantomData,
+        }
+    }
+
+    /// Uses `cfg` instead of [Adagrad::cfg] for the parameters of `module` whose path (like `"0.bias"`)
+    /// is selected by `select`. See [crate::nn::optim#parameter-groups].
+    pub fn with_param_group<P: UpdateParams<E, D>>(
+        mut self,
+        module: &P,
+        select: impl Fn(&str) -> bool,
+        cfg: AdagradConfig,
+    ) -> Self
+    where
+        D: Device<E>,
+    {
+        self.groups.add(module, select, cfg, self.cfg.lr);
+        self
+    }
+}
+
+impl<M, E: Dtype, D: Device<E>> super::FreezeParams<E, D> for Adagrad<M, E, D> {
+    fn set_frozen<P: UpdateParams<E, D>>(&mut self, module: &P, frozen: bool) {
+        self.groups.frozen.set(module, frozen);
+    }
+}
+
+impl<M, E: Dtype, D: Storage<E>> super::LearningRate for Adagrad<M, E, D> {
+    fn lr(&self) -> f64 {
+        self.cfg.lr
+    }
+    fn set_lr(&mut self, lr: f64) {
+        self.cfg.lr = lr;
+        self.groups.set_lr(lr);
+    }
+}
+
+#[cfg(feature = "safetensors")]
+impl<M, E: Dtype, D: Device<E>> crate::nn::OptimizerState<E, D> for Adagrad<M, E, D> {
+    fn state_buffers(&mut self) -> Vec<(&'static str, &mut Gradients<E, D>)> {
+        vec![("sum_squares", &mut self.sum_squares)]
+    }
+
+    fn write_state_scalars(
+        &self,
+        tensors: &mut Vec<(String, safetensors::Dtype, Vec<usize>, Vec<u8>)>,
+    ) {
+        crate::nn::SaveSafeTensors::write_safetensors(&self.t, "t", tensors);
+    }
+
+    fn read_state_scalars(
+        &mut self,
+        tensors: &safetensors::SafeTensors,
+    ) -> Result<(), safetensors::SafeTensorError> {
+        crate::nn::LoadSafeTensors::read_safetensors(&mut self.t, "t", tensors)
+    }
+}
+
+impl<M, E: Dtype, D: Device<E> + AdagradKernel<E>> crate::nn::Optimizer<M, E, D>
+    for Adagrad<M, E, D>
+{
+    fn update_tensor<S: Shape>(
+        &mut self,
+        t: &mut Tensor<S, E, D>,
+        gradients: &Gradients<E, D>,
+        missing_params: &mut Vec<UniqueId>,
+    ) -> Result<(), Error> {
+        self.groups
+            .try_update(&self.cfg, t, gradients, missing_params, |cfg, t, g| {
+                let s = self.sum_squares.get_or_alloc_mut(t)?;
+                cfg.try_update(self.t, t, s, g)
+            })
+    }
+
+    fn update(&mut self, module: &mut M, gradients: &Gradients<E, D>) -> Result<(), Error>
+    where
+        M: crate::nn::UpdateParams<E, D>,
+    {
+        super::update_and_count_step(self, |opt| &mut opt.t, module, gradients)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{nn::optim::test_utils::test_matches_expected, prelude::*};
+
+    #[test]
+    fn test_adagrad_default() {
+        let cfg = Default::default();
+        #[rustfmt::skip]
+        const EXPECTED: [[f64; 5]; 5] = [
+            [-0.51, -0.26, 0.09, 0.59, 0.99],
+            [-0.51700001, -0.26700001, 0.08299999, 0.58299999, 0.98299999],
+            [-0.52268076, -0.27268076, 0.077319237, 0.57731924, 0.97731924],
+            [-0.5275778, -0.2775778, 0.072422204, 0.5724222, 0.9724222],
+            [-0.53194113, -0.28194113, 0.068058872, 0.56805887, 0.96805887],
+        ];
+        test_matches_expected(|t| Adagrad::new(t, cfg), EXPECTED);
+    }
+
+    #[test]
+    fn test_adagrad_lr_decay() {
+        let cfg = AdagradConfig {
+            lr: 1e-1,
+            lr_decay: 0.5,
+            ..Default::default()
+        };
+        #[rustfmt::skip]
+        const EXPECTED: [[f64; 5]; 5] = [
+            [-0.6, -0.35, 2.0468266e-11, 0.5, 0.9],
+            [-0.64223281, -0.39223281, -0.042232812, 0.45776719, 0.85776719],
+            [-0.66738921, -0.41738921, -0.067389214, 0.43261079, 0.83261079],
+            [-0.68465271, -0.43465271, -0.08465271, 0.41534729, 0.81534729],
+            [-0.6974804, -0.4474804, -0.097480395, 0.4025196, 0.8025196],
+        ];
+        test_matches_expected(|t| Adagrad::new(t, cfg), EXPECTED);
+    }
+
+    #[test]
+    fn test_adagrad_l2_weight_decay() {
+        let cfg = AdagradConfig {
+            weight_decay: Some(WeightDecay::L2(0.5)),
+            ..Default::default()
+        };
+        #[rustfmt::skip]
+        const EXPECTED: [[f64; 5]; 5] = [
+            [-0.49, -0.26, 0.09, 0.59, 0.99],
+            [-0.48321956, -0.26675714, 0.08302726, 0.58299789, 0.98299483],
+            [-0.47783401, -0.27211488, 0.077382691, 0.57731453, 0.97730739],
+            [-0.47327369, -0.27664655, 0.07252642, 0.57241472, 0.97240298],
+            [-0.46927504, -0.28061694, 0.068206732, 0.56804857, 0.96803187],
+        ];
+        test_matches_expected(|t| Adagrad::new(t, cfg), EXPECTED);
+    }
+
+    #[test]
+    fn test_adagrad_decoupled_weight_decay() {
+        let cfg = AdagradConfig {
+            weight_decay: Some(WeightDecay::Decoupled(0.5)),
+            ..Default::default()
+        };
+        #[rustfmt::skip]
+        const EXPECTED: [[f64; 5]; 5] = [
+            [-0.5075, -0.25875, 0.0895, 0.587, 0.985],
+            [-0.51198034, -0.26446518, 0.082056061, 0.57708644, 0.97311077],
+            [-0.51513037, -0.26883839, 0.075970407, 0.56855448, 0.96262179],
+            [-0.51748972, -0.27241066, 0.070700062, 0.56085832, 0.952985],
+            [-0.51931094, -0.27543532, 0.065990584, 0.55374194, 0.94394307],
+        ];
+        test_matches_expected(|t| Adagrad::new(t, cfg), EXPECTED);
+    }
+
+    #[test]
+    fn test_unused_tensors() {
+        crate::nn::optim::test_utils::test_unused_tensors(|t| Adagrad::new(t, Default::default()));
+    }
+}
@@ -1,8 +1,10 @@
 use std::marker::PhantomData;
 
+use super::param_groups::ParamGroups;
 use crate::{
+    nn::UpdateParams,
     shapes::{Dtype, Shape},
-    tensor::{Error, Gradients, Storage, Tensor, Tensorlike, UniqueId},
+    tensor::{Error, Gradients, Storage, Tensor, UniqueId},
     tensor_ops::{AdamConfig, Device},
 };
 
@@ -28,6 +30,7 @@ use crate::{
 pub struct Adam<M, E: Dtype, D: Storage<E>> {
     /// Hyperparameter configuration
     pub cfg: AdamConfig,
+    groups: ParamGroups<AdamConfig>,
 
     t: i32,
     moment1: Gradients<E, D>,
@@ -41,12 +44,68 @@ impl<M, E: Dtype, D: Storage<E>> Adam<M, E, D> {
     pub fn new(_model: &M, cfg: AdamConfig) -> Self {
         Self {
             cfg,
+            groups: Default::default(),
             t: 0,
             moment1: Gradients::leaky(),
             moment2: Gradients::leaky(),
             marker: PhantomData,
         }
     }
+
+    /// Uses `cfg` instead of [Adam::cfg] for the parameters of `module` whose path (like `"0.bias"`)
+    /// is selected by `select`. See [crate::nn::optim#parameter-groups].
+    pub fn with_param_group<P: UpdateParams<E, D>>(
+        mut self,
+        module: &P,
+        select: impl Fn(&str) -> bool,
+        cfg: AdamConfig,
+    ) -> Self
+    where
+        D: Device<E>,
+    {
+        self.groups.add(module, select, cfg, self.cfg.lr);
+        self
+    }
+}
+
+impl<M, E: Dtype, D: Device<E>> super::FreezeParams<E, D> for Adam<M, E, D> {
+    fn set_frozen<P: UpdateParams<E, D>>(&mut self, module: &P, frozen: bool) {
+        self.groups.frozen.set(module, frozen);
+    }
+}
+
+impl<M, E: Dtype, D: Storage<E>> super::LearningRate for Adam<M, E, D> {
+    fn lr(&self) -> f64 {
+        self.cfg.lr
+    }
+    fn set_lr(&mut self, lr: f64) {
+        self.cfg.lr = lr;
+        self.groups.set_lr(lr);
+    }
+}
+
+#[cfg(feature = "safetensors")]
+impl<M, E: Dtype, D: Device<E>> crate::nn::OptimizerState<E, D> for Adam<M, E, D> {
+    fn state_buffers(&mut self) -> Vec<(&'static str, &mut Gradients<E, D>)> {
+        vec![
+            ("moment1", &mut self.moment1),
+            ("moment2", &mut self.moment2),
+        ]
+    }
+
+    fn write_state_scalars(
+        &self,
+        tensors: &mut Vec<(String, safetensors::Dtype, Vec<usize>, Vec<u8>)>,
+    ) {
+        crate::nn::SaveSafeTensors::write_safetensors(&self.t, "t", tensors);
+    }
+
+    fn read_state_scalars(
+        &mut self,
+        tensors: &safetensors::SafeTensors,
+    ) -> Result<(), safetensors::SafeTensorError> {
+        crate::nn::LoadSafeTensors::read_safetensors(&mut self.t, "t", tensors)
+    }
 }
 
 impl<M, E: Dtype, D: Device<E>> crate::nn::Optimizer<M, E, D> for Adam<M, E, D> {
@@ -56,32 +115,19 @@ impl<M, E: Dtype, D: Device<E>> crate::nn::Optimizer<M, E, D> for Adam<M, E, D>
         gradients: &Gradients<E, D>,
         missing_params: &mut Vec<UniqueId>,
     ) -> Result<(), crate::tensor::Error> {
-        let g = gradients.get_ref_checked(t);
-        match g {
-            None => missing_params.push(t.id()),
-            Some(g) => {
+        self.groups
+            .try_update(&self.cfg, t, gradients, missing_params, |cfg, t, g| {
                 let m_t = self.moment1.get_or_alloc_mut(t)?;
                 let v_t = self.moment2.get_or_alloc_mut(t)?;
-                self.cfg.try_update(self.t, t, m_t, v_t, g)?;
-            }
-        }
-        Ok(())
+                cfg.try_update(self.t, t, m_t, v_t, g)
+            })
     }
 
     fn update(&mut self, module: &mut M, gradients: &Gradients<E, D>) -> Result<(), Error>
     where
         M: crate::nn::UpdateParams<E, D>,
     {
-        self.t = self.t.checked_add(1).unwrap();
-
-        // NOTE: the rest of this is identical to default implementation of update.
-        let mut missing_tensors = Vec::new();
-        module.try_update_params(self, gradients, &mut missing_tensors)?;
-        if missing_tensors.is_empty() {
-            Ok(())
-        } else {
-            Err(Error::UnusedTensors(missing_tensors))
-        }
+        super::update_and_count_step(self, |opt| &mut opt.t, module, gradients)
     }
 }
 
@@ -224,6 +270,48 @@ mod tests {
         }
     }
 
+    #[cfg(feature = "safetensors")]
+    #[test]
+    fn test_save_load_state_resumes_training() {
+        use crate::prelude::*;
+        use tempfile::NamedTempFile;
+
+        let dev: TestDevice = Default::default();
+        type Model = (LinearConstConfig<3, 4>, Tanh, LinearConstConfig<4, 2>);
+        let x: Tensor<Rank2<5, 3>, TestDtype, _> = dev.sample_normal();
+        let train = |model: &mut <Model as BuildOnDevice<TestDtype, TestDevice>>::Built,
+                     opt: &mut Adam<_, TestDtype, TestDevice>| {
+            let loss = model.forward(x.leaky_trace()).square().mean();
+            opt.update(model, &loss.backward()).unwrap();
+        };
+
+        let mut model = dev.build_module::<TestDtype>(Model::default());
+        let mut opt = Adam::new(&model, Default::default());
+        for _ in 0..3 {
+            train(&mut model, &mut opt);
+        }
+        let model_file = NamedTempFile::new().expect("failed to create tempfile");
+        let state_file = NamedTempFile::new().expect("failed to create tempfile");
+        model.save_safetensors(model_file.path()).unwrap();
+        opt.save_state(&mut model, state_file.path()).unwrap();
+
+        // a fresh model & optimizer, which have different tensor ids
+        let mut resumed = dev.build_module::<TestDtype>(Model::default());
+        let mut resumed_opt = Adam::new(&resumed, Default::default());
+        resumed.load_safetensors(model_file.path()).unwrap();
+        resumed_opt
+            .load_state(&mut resumed, state_file.path())
+            .unwrap();
+        assert_eq!(resumed_opt.t, 3);
+
+        for _ in 0..3 {
+            train(&mut model, &mut opt);
+            train(&mut resumed, &mut resumed_opt);
+        }
+        assert_eq!(model.0.weight.array(), resumed.0.weight.array());
+        assert_eq!(model.2.bias.array(), resumed.2.bias.array());
+    }
+
     #[test]
     fn test_unused_tensors() {
         let dev: TestDevice = Default::default();
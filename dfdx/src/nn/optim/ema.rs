@@ -0,0 +1,265 @@
+use crate::{
+    nn::{Optimizer, ParamVisitor, UpdateParams},
+    shapes::{Dtype, HasShape, Shape},
+    tensor::{Error, Gradients, Storage, Tensor, UniqueId},
+    tensor_ops::{Device, ReshapeTo},
+};
+
+/// Configuration of [Ema].
+#[derive(Debug, Clone, Copy)]
+pub struct EmaConfig {
+    /// How much of the shadow weights to keep each update. Defaults to `0.999`.
+    pub decay: f64,
+
+    /// Uses `min(decay, (1 + t) / (warmup + t))` as the decay of update `t`, so the shadow
+    /// weights aren't dominated by the initial weights early in training. Defaults to `None`.
+    pub warmup: Option<usize>,
+}
+
+impl Default for EmaConfig {
+    fn default() -> Self {
+        Self {
+            decay: 0.999,
+            warmup: None,
+        }
+    }
+}
+
+/// Exponential moving average of the parameters of a module.
+///
+/// Holds a shadow copy of a built module, and blends it toward the live module with
+/// `shadow = shadow * decay + live * (1 - decay)` each time [Ema::update()] is called
+/// (usually right after [Optimizer::update()]).
+///
+/// Only the parameters visited by [UpdateParams] are averaged, other state
+/// (like the running statistics of batch norm) keeps the values it had when the [Ema] was created.
+///
+/// # Example Usage
+/// ```rust
+/// # use dfdx::prelude::*;
+/// # let dev: Cpu = Default::default();
+/// type Model = LinearConstConfig<5, 2>;
+/// let mut model = dev.build_module::<f32>(Model::default());
+/// let mut ema = Ema::new(&model, EmaConfig { decay: 0.99, warmup: Some(10) });
+/// // -- snip loss computation & opt.update(&mut model, &grads) --
+/// ema.update(&model);
+///
+/// // evaluate with the averaged weights, and then swap back
+/// ema.swap(&mut model);
+/// // -- snip evaluation --
+/// ema.swap(&mut model);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Ema<M> {
+    /// The averaged copy of the module.
+    pub shadow: M,
+    /// Can be changed between updates, e.g. to increase the decay later in training.
+    pub cfg: EmaConfig,
+    num_updates: usize,
+}
+
+impl<M: Clone> Ema<M> {
+    /// Starts the shadow module as a copy of `model`.
+    pub fn new(model: &M, cfg: EmaConfig) -> Self {
+        Self {
+            shadow: model.clone(),
+            cfg,
+            num_updates: 0,
+        }
+    }
+
+    /// The decay that the next call to [Ema::update()] will use.
+    pub fn decay(&self) -> f64 {
+        match self.cfg.warmup {
+            Some(warmup) => {
+                let t = self.num_updates as f64;
+                self.cfg.decay.min((1.0 + t) / (warmup as f64 + t))
+            }
+            None => self.cfg.decay,
+        }
+    }
+
+    /// Blends the shadow module toward the parameters of `model`.
+    pub fn update<E: Dtype, D: Device<E>>(&mut self, model: &M)
+    where
+        M: UpdateParams<E, D>,
+    {
+        self.try_update(model).unwrap()
+    }
+
+    /// Fallible version of [Ema::update()]. Returns an error if `model` doesn't have the
+    /// same parameters as the shadow module.
+    pub fn try_update<E: Dtype, D: Device<E>>(&mut self, model: &M) -> Result<(), Error>
+    where
+        M: UpdateParams<E, D>,
+    {
+        let mut visitor = EmaVisitor {
+            live: Vec::new(),
+            decay: self.decay(),
+        };
+        model.try_visit_params(&mut visitor)?;
+
+        visitor.live.reverse();
+        self.shadow.try_update_params::<M, _>(
+            &mut visitor,
+            &Gradients::leaky(),
+            &mut Vec::new(),
+        )?;
+        if !visitor.live.is_empty() {
+            return Err(Error::InvalidArgument(
+                "Ema: the live module has more parameters than the shadow module".into(),
+            ));
+        }
+        self.num_updates += 1;
+        Ok(())
+    }
+
+    /// Swaps the shadow module with `model`, e.g. to evaluate with the averaged weights.
+    /// Call again to swap them back before continuing training.
+    pub fn swap(&mut self, model: &mut M) {
+        std::mem::swap(&mut self.shadow, model);
+    }
+}
+
+/// Collects the parameters of the live module in the order [UpdateParams] visits them, and
+/// then blends the parameter of the shadow module at the same position toward them. Parameters
+/// are contiguous, so collecting them only clones the `Arc` of their data.
+///
+/// The live module is read with [ParamVisitor], and the shadow module is updated in place
+/// like an [Optimizer] would.
+struct EmaVisitor<E, D: Storage<E>> {
+    /// In reverse order while blending, so the next parameter can be popped.
+    live: Vec<Tensor<(usize,), E, D>>,
+    decay: f64,
+}
+
+impl<E: Dtype, D: Device<E>> ParamVisitor<E, D> for EmaVisitor<E, D> {
+    fn visit_tensor<S: Shape>(&mut self, t: &Tensor<S, E, D>) -> Result<(), Error> {
+        let numel = t.shape().num_elements();
+        self.live.push(t.clone().try_reshape_like(&(numel,))?);
+        Ok(())
+    }
+}
+
+impl<M, E: Dtype, D: Device<E>> Optimizer<M, E, D> for EmaVisitor<E, D> {
+    fn update_tensor<S: Shape>(
+        &mut self,
+        t: &mut Tensor<S, E, D>,
+        _gradients: &Gradients<E, D>,
+        _missing_tensors: &mut Vec<UniqueId>,
+    ) -> Result<(), Error> {
+        let numel = t.shape().num_elements();
+        let decay = self.decay;
+        let Some(live) = self.live.pop() else {
+            return Err(Error::InvalidArgument(
+                "Ema: the shadow module has more parameters than the live module".into(),
+            ));
+        };
+        if live.shape().0 != numel {
+            return Err(Error::ShapeMismatch {
+                expected: t.shape().concrete().into_iter().collect(),
+                found: std::vec![live.shape().0],
+            });
+        }
+        t.try_axpy(decay, &live.try_reshape_like(t.shape())?, 1.0 - decay)
+    }
+
+    fn update_frozen_tensor<S: Shape>(
+        &mut self,
+        t: &mut Tensor<S, E, D>,
+        gradients: &Gradients<E, D>,
+        missing_tensors: &mut Vec<UniqueId>,
+    ) -> Result<(), Error> {
+        Optimizer::<M, E, D>::update_tensor(self, t, gradients, missing_tensors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{prelude::*, tests::*};
+
+    #[test]
+    fn test_ema_blends_toward_live() {
+        let dev: TestDevice = Default::default();
+        let mut model: Tensor<Rank1<2>, TestDtype, _> = dev.zeros();
+        let mut ema = Ema::new(
+            &model,
+            EmaConfig {
+                decay: 0.5,
+                warmup: None,
+            },
+        );
+        model = dev.tensor([2.0, 4.0]).to_dtype();
+        ema.update(&model);
+        assert_close_to_literal!(ema.shadow, [1.0, 2.0]);
+        ema.update(&model);
+        assert_close_to_literal!(ema.shadow, [1.5, 3.0]);
+        // the live module is untouched
+        assert_close_to_literal!(model, [2.0, 4.0]);
+    }
+
+    #[test]
+    fn test_ema_warmup() {
+        let dev: TestDevice = Default::default();
+        let mut model: Tensor<Rank1<2>, TestDtype, _> = dev.zeros();
+        let mut ema = Ema::new(
+            &model,
+            EmaConfig {
+                decay: 0.5,
+                warmup: Some(4),
+            },
+        );
+        model = dev.tensor([1.0, 2.0]).to_dtype();
+        // decays of 1/4, 2/5, then 0.5
+        assert_eq!(ema.decay(), 0.25);
+        ema.update(&model);
+        assert_close_to_literal!(ema.shadow, [0.75, 1.5]);
+        assert_eq!(ema.decay(), 0.4);
+        ema.update(&model);
+        assert_close_to_literal!(ema.shadow, [0.9, 1.8]);
+        assert_eq!(ema.decay(), 0.5);
+        ema.update(&model);
+        assert_close_to_literal!(ema.shadow, [0.95, 1.9]);
+    }
+
+    #[test]
+    fn test_ema_different_module() {
+        let dev: TestDevice = Default::default();
+        let t: Tensor<(usize,), TestDtype, _> = dev.zeros_like(&(2,));
+        let mut ema = Ema::new(&std::vec![t.clone(), t.clone()], Default::default());
+        assert!(matches!(
+            ema.try_update(&std::vec![t.clone()]),
+            Err(Error::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            ema.try_update(&std::vec![t.clone(), dev.zeros_like(&(3,))]),
+            Err(Error::ShapeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_ema_of_module() {
+        let dev: TestDevice = Default::default();
+        let mut model = dev.build_module::<TestDtype>(<LinearConstConfig<2, 2>>::default());
+        model.weight = dev.tensor([[1.0, 2.0], [3.0, 4.0]]).to_dtype();
+        model.bias = dev.tensor([1.0, 1.0]).to_dtype();
+        let mut ema = Ema::new(
+            &model,
+            EmaConfig {
+                decay: 0.75,
+                warmup: None,
+            },
+        );
+
+        model.weight = dev.tensor([[5.0, 6.0], [7.0, 8.0]]).to_dtype();
+        model.bias = dev.tensor([5.0, 5.0]).to_dtype();
+        ema.update(&model);
+        assert_close_to_literal!(ema.shadow.weight, [[2.0, 3.0], [4.0, 5.0]]);
+        assert_close_to_literal!(ema.shadow.bias, [2.0, 2.0]);
+
+        ema.swap(&mut model);
+        assert_close_to_literal!(model.weight, [[2.0, 3.0], [4.0, 5.0]]);
+        assert_close_to_literal!(ema.shadow.weight, [[5.0, 6.0], [7.0, 8.0]]);
+    }
+}
@@ -0,0 +1,313 @@
+use crate::{
+    nn::{Optimizer, ParamVisitor, UpdateParams},
+    shapes::{Dtype, Rank0, Shape},
+    tensor::{Error, Gradients, Storage, Tape, Tensor},
+    tensor_ops::{Device, SumTo, TryAdd, TryDiv, TryMul},
+};
+
+/// Configuration of [GradScaler].
+#[derive(Debug, Clone, Copy)]
+pub struct GradScalerConfig {
+    /// The scale to start with. Defaults to `2^15`, the largest power of 2 that f16 can represent.
+    pub init_scale: f64,
+    /// Multiplies the scale after `growth_interval` steps in a row without Inf/NaN gradients.
+    /// Defaults to `2.0`.
+    pub growth_factor: f64,
+    /// Multiplies the scale whenever the gradients contain Inf/NaN. Defaults to `0.5`.
+    pub backoff_factor: f64,
+    /// Defaults to `2000`.
+    pub growth_interval: usize,
+    /// The scale never backs off below this. Defaults to `1.0`.
+    pub min_scale: f64,
+    /// The scale never grows above this. Defaults to `2^24`.
+    pub max_scale: f64,
+}
+
+impl Default for GradScalerConfig {
+    fn default() -> Self {
+        Self {
+            init_scale: 32768.0,
+            growth_factor: 2.0,
+            backoff_factor: 0.5,
+            growth_interval: 2000,
+            min_scale: 1.0,
+            max_scale: 16777216.0,
+        }
+    }
+}
+
+/// Dynamic loss scaling for mixed precision training with [crate::dtypes::AMP].
+///
+/// Small gradients underflow to zero in f16. Scaling the loss scales every gradient
+/// during backward by the same amount, and the gradients are unscaled again right
+/// before the optimizer step. If the scale is too big, gradients overflow to Inf/NaN:
+/// in that case the step is skipped and the scale is reduced by [GradScalerConfig::backoff_factor].
+/// After [GradScalerConfig::growth_interval] successful steps in a row, the scale grows
+/// by [GradScalerConfig::growth_factor]. The scale is kept between [GradScalerConfig::min_scale]
+/// and [GradScalerConfig::max_scale].
+///
+/// ```rust
+/// # use dfdx::prelude::*;
+/// # let dev: Cpu = Default::default();
+/// let mut model: Tensor<Rank1<2>, f32, _> = dev.tensor([1.0, 2.0]);
+/// let mut opt = Sgd::new(&model, SgdConfig { lr: 0.25, ..Default::default() });
+/// let mut scaler = GradScaler::new(Default::default());
+///
+/// let loss = model.leaky_trace().square().sum();
+/// let mut grads = scaler.scale_loss(loss).backward();
+/// // unscales `grads`, and only updates `model` if they're all finite
+/// assert!(scaler.step(&mut opt, &mut model, &mut grads));
+/// assert_eq!(model.array(), [0.5, 1.0]);
+/// ```
+///
+/// To do something with the unscaled gradients before the update (like [crate::nn::clip_grad_norm()]),
+/// call [GradScaler::unscale()], [Optimizer::update()] and [GradScaler::update_scale()] instead of [GradScaler::step()].
+#[derive(Debug, Clone)]
+pub struct GradScaler {
+    /// The config the scaler was created with. Changing it affects the following steps,
+    /// but not the current scale.
+    pub cfg: GradScalerConfig,
+    scale: f64,
+    clean_steps: usize,
+}
+
+impl GradScaler {
+    /// Starts with a scale of [GradScalerConfig::init_scale].
+    pub fn new(cfg: GradScalerConfig) -> Self {
+        Self {
+            cfg,
+            scale: cfg.init_scale,
+            clean_steps: 0,
+        }
+    }
+
+    /// The current scale.
+    pub fn scale(&self) -> f64 {
+        self.scale
+    }
+
+    /// Multiplies `loss` by the current scale.
+    pub fn scale_loss<S: Shape, E: Dtype, D: Device<E>, T: Tape<E, D>>(
+        &self,
+        loss: Tensor<S, E, D, T>,
+    ) -> Tensor<S, E, D, T> {
+        self.try_scale_loss(loss).unwrap()
+    }
+
+    /// Fallible version of [GradScaler::scale_loss()].
+    pub fn try_scale_loss<S: Shape, E: Dtype, D: Device<E>, T: Tape<E, D>>(
+        &self,
+        loss: Tensor<S, E, D, T>,
+    ) -> Result<Tensor<S, E, D, T>, Error> {
+        loss.try_mul(self.scale)
+    }
+
+    /// Divides the gradients of all parameters of `module` by the current scale. Returns `false`
+    /// without modifying `grads` if any of them contain Inf or NaN.
+    pub fn unscale<M: UpdateParams<E, D>, E: Dtype, D: Device<E>>(
+        &self,
+        module: &M,
+        grads: &mut Gradients<E, D>,
+    ) -> bool {
+        self.try_unscale(module, grads).unwrap()
+    }
+
+    /// Fallible version of [GradScaler::unscale()].
+    pub fn try_unscale<M: UpdateParams<E, D>, E: Dtype, D: Device<E>>(
+        &self,
+        module: &M,
+        grads: &mut Gradients<E, D>,
+    ) -> Result<bool, Error> {
+        let mut visitor = UnscaleVisitor {
+            grads,
+            op: UnscaleOp::CheckFinite(None),
+        };
+        module.try_visit_params(&mut visitor)?;
+        if let UnscaleOp::CheckFinite(Some(check)) = &visitor.op {
+            let check = check.as_vec()[0].to_f64().unwrap();
+            if !check.is_finite() {
+                return Ok(false);
+            }
+        }
+        visitor.op = UnscaleOp::Unscale(self.scale);
+        module.try_visit_params(&mut visitor)?;
+        Ok(true)
+    }
+
+    /// Backs off the scale if the gradients were not `finite`, otherwise grows it
+    /// after enough finite steps in a row. The scale stays within
+    /// [GradScalerConfig::min_scale] and [GradScalerConfig::max_scale].
+    pub fn update_scale(&mut self, finite: bool) {
+        if finite {
+            self.clean_steps += 1;
+            if self.clean_steps >= self.cfg.growth_interval {
+                self.scale *= self.cfg.growth_factor;
+                self.clean_steps = 0;
+            }
+        } else {
+            self.scale *= self.cfg.backoff_factor;
+            self.clean_steps = 0;
+        }
+        self.scale = self.scale.clamp(self.cfg.min_scale, self.cfg.max_scale);
+    }
+
+    /// Unscales `grads`, updates `module` with `opt` if they are all finite, and then updates the scale.
+    /// Returns whether the optimizer step was taken.
+    pub fn step<M: UpdateParams<E, D>, E: Dtype, D: Device<E>, O: Optimizer<M, E, D>>(
+        &mut self,
+        opt: &mut O,
+        module: &mut M,
+        grads: &mut Gradients<E, D>,
+    ) -> bool {
+        self.try_step(opt, module, grads).unwrap()
+    }
+
+    /// Fallible version of [GradScaler::step()].
+    pub fn try_step<M: UpdateParams<E, D>, E: Dtype, D: Device<E>, O: Optimizer<M, E, D>>(
+        &mut self,
+        opt: &mut O,
+        module: &mut M,
+        grads: &mut Gradients<E, D>,
+    ) -> Result<bool, Error> {
+        let finite = self.try_unscale(module, grads)?;
+        if finite {
+            opt.update(module, grads)?;
+        }
+        self.update_scale(finite);
+        Ok(finite)
+    }
+}
+
+enum UnscaleOp<E, D: Storage<E>> {
+    /// The sum of `g * 0` over all gradients `g`. `x * 0` is NaN for Inf & NaN, and the
+    /// sum can't overflow, so this is only finite if all gradients are.
+    CheckFinite(Option<Tensor<Rank0, E, D>>),
+    Unscale(f64),
+}
+
+/// Applies [UnscaleOp] to the gradient of each parameter visited through [UpdateParams].
+struct UnscaleVisitor<'a, E, D: Storage<E>> {
+    grads: &'a mut Gradients<E, D>,
+    op: UnscaleOp<E, D>,
+}
+
+impl<E: Dtype, D: Device<E>> ParamVisitor<E, D> for UnscaleVisitor<'_, E, D> {
+    fn visit_tensor<S: Shape>(&mut self, t: &Tensor<S, E, D>) -> Result<(), Error> {
+        if self.grads.get_ref_checked(t).is_none() {
+            return Ok(());
+        }
+        let g = self.grads.get(t);
+        match &mut self.op {
+            UnscaleOp::CheckFinite(check) => {
+                let g = g.try_mul(0.0)?.try_sum::<Rank0, _>()?;
+                *check = Some(match check.take() {
+                    Some(check) => check.try_add(g)?,
+                    None => g,
+                });
+            }
+            UnscaleOp::Unscale(scale) => {
+                let g = g.try_div(*scale)?;
+                self.grads.insert(t, g);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{nn::optim::*, prelude::*, tests::*};
+
+    #[test]
+    fn test_unscale() {
+        let dev: TestDevice = Default::default();
+        let model: Tensor<Rank1<3>, TestDtype, _> = dev.tensor([1.0, -2.0, 0.5]).to_dtype();
+        let scaler = GradScaler::new(GradScalerConfig {
+            init_scale: 8.0,
+            ..Default::default()
+        });
+        let loss = scaler.scale_loss(model.leaky_trace().square().sum());
+        let mut grads = loss.backward();
+        assert_close_to_literal!(grads.get(&model), [16.0, -32.0, 8.0]);
+        assert!(scaler.unscale(&model, &mut grads));
+        assert_close_to_literal!(grads.get(&model), [2.0, -4.0, 1.0]);
+    }
+
+    #[test]
+    fn test_skips_non_finite_steps() {
+        let dev: TestDevice = Default::default();
+        let mut model: Tensor<Rank1<2>, TestDtype, _> = dev.tensor([1.0, 0.0]).to_dtype();
+        let mut opt = Sgd::new(
+            &model,
+            SgdConfig {
+                lr: 0.5,
+                ..Default::default()
+            },
+        );
+        let mut scaler = GradScaler::new(GradScalerConfig {
+            init_scale: 4.0,
+            growth_interval: 2,
+            ..Default::default()
+        });
+
+        // the gradient of sqrt is Inf at 0
+        let loss = scaler.scale_loss(model.leaky_trace().sqrt().sum());
+        let mut grads = loss.backward();
+        assert!(!scaler.step(&mut opt, &mut model, &mut grads));
+        assert_close_to_literal!(model, [1.0, 0.0]);
+        assert_eq!(scaler.scale(), 2.0);
+
+        // grows after 2 finite steps
+        for _ in 0..2 {
+            let loss = scaler.scale_loss(model.leaky_trace().sum());
+            let mut grads = loss.backward();
+            assert!(scaler.step(&mut opt, &mut model, &mut grads));
+        }
+        assert_close_to_literal!(model, [0.0, -1.0]);
+        assert_eq!(scaler.scale(), 4.0);
+    }
+
+    #[test]
+    fn test_scale_is_clamped() {
+        let mut scaler = GradScaler::new(GradScalerConfig {
+            init_scale: 4.0,
+            growth_interval: 1,
+            min_scale: 2.0,
+            max_scale: 8.0,
+            ..Default::default()
+        });
+        for expected in [2.0, 2.0] {
+            scaler.update_scale(false);
+            assert_eq!(scaler.scale(), expected);
+        }
+        for expected in [4.0, 8.0, 8.0] {
+            scaler.update_scale(true);
+            assert_eq!(scaler.scale(), expected);
+        }
+    }
+
+    #[cfg(feature = "f16")]
+    #[test]
+    fn test_prevents_amp_f16_underflow() {
+        use crate::dtypes::{f16, AMP};
+        let dev: Cpu = Default::default();
+        let model: Tensor<Rank1<2>, AMP<f16>, _> = dev.tensor([AMP(f16::ONE); 2]);
+        let a = dev.tensor([AMP(f16::from_f32(1e4)); 2]);
+        // the gradient of model is `a * 1e-4 * 1e-4 = 1e-4`, but the gradient of the
+        // intermediate `(model * a).sum() * 1e-4` is `1e-8`, which underflows in f16
+        let loss = |model: &Tensor<Rank1<2>, AMP<f16>, Cpu>| {
+            (model.leaky_trace() * a.clone()).sum() * 1e-4
+        };
+
+        let grads = (loss(&model) * 1e-4).backward();
+        assert_eq!(grads.get(&model).array(), [AMP(f16::ZERO); 2]);
+
+        let scaler = GradScaler::new(Default::default());
+        let mut grads = (scaler.scale_loss(loss(&model)) * 1e-4).backward();
+        assert!(scaler.unscale(&model, &mut grads));
+        for g in grads.get(&model).array() {
+            assert!((g.0.to_f32() - 1e-4).abs() < 1e-6, "{g:?}");
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use std::marker::PhantomData;
+
+use super::param_groups::ParamGroups;
+use crate::{
+    nn::UpdateParams,
+    shapes::{Dtype, Shape},
+    tensor::{Error, Gradients, Storage, Tensor, UniqueId},
+    tensor_ops::{Device, LambConfig, LambKernel},
+};
+
+/// An implementation of the LAMB optimizer from
+/// [Large Batch Optimization for Deep Learning: Training BERT in 76 minutes](https://arxiv.org/abs/1904.00962).
+///
+/// The Adam update of each tensor is scaled by the ratio of the norm of the tensor to the norm
+/// of its update, so every layer takes a step that is proportional to its size.
+///
+/// # Example Usage
+/// ```rust
+/// # use dfdx::prelude::*;
+/// # type Model = Tensor<Rank0, f32, Cpu>;
+/// # let dev: Cpu = Default::default();
+/// # let model: Model = dev.zeros();
+/// let mut opt: Lamb<Model, f32, Cpu> = optim::Lamb::new(&model, LambConfig {
+///     lr: 1e-2,
+///     betas: [0.9, 0.999],
+///     eps: 1e-6,
+///     weight_decay: Some(WeightDecay::Decoupled(1e-2)),
+/// });
+/// ```
+///
+/// See module level documentation at [crate::nn::optim] for examples of how to actually use an optimizer.
+#[derive(Debug, Clone)]
+pub struct Lamb<M, E: Dtype, D: Storage<E>> {
+    /// Hyperparameter configuration
+    pub cfg: LambConfig,
+    groups: ParamGroups<LambConfig>,
+
+    t: i32,
+    moment1: Gradients<E, D>,
+    moment2: Gradients<E, D>,
+
+    marker: PhantomData<*const M>,
+}
+
+impl<M, E: Dtype, D: Storage<E>> Lamb<M, E, D> {
+    /// Constructs using hyperparameters from `cfg`.
+    pub fn new(_model: &M, cfg: LambConfig) -> Self {
+        Self {
+            cfg,
+            groups: Default::default(),
+            t: 0,
+            moment1: Gradients::leaky(),
+            moment2: Gradients::leaky(),
+            marker: PhantomData,
+        }
+    }
+
+    /// Uses `cfg` instead of [Lamb::cfg] for the parameters of `module` whose path (like `"0.bias"`)
+    /// is selected by `select`. See [crate::nn::optim#parameter-groups].
+    pub fn with_param_group<P: UpdateParams<E, D>>(
+        mut self,
+        module: &P,
+        select: impl Fn(&str) -> bool,
+        cfg: LambConfig,
+    ) -> Self
+    where
+        D: Device<E>,
+    {
+        self.groups.add(module, select, cfg, self.cfg.lr);
+        self
+    }
+}
+
+impl<M, E: Dtype, D: Device<E>> super::FreezeParams<E, D> for Lamb<M, E, D> {
+    fn set_frozen<P: UpdateParams<E, D>>(&mut self, module: &P, frozen: bool) {
+        self.groups.frozen.set(module, frozen);
+    }
+}
+
+impl<M, E: Dtype, D: Storage<E>> super::LearningRate for Lamb<M, E, D> {
+    fn lr(&self) -> f64 {
+        self.cfg.lr
+    }
+    fn set_lr(&mut self, lr: f64) {
+        self.cfg.lr = lr;
+        self.groups.set_lr(lr);
+    }
+}
+
+#[cfg(feature = "safetensors")]
+impl<M, E: Dtype, D: Device<E>> crate::nn::OptimizerState<E, D> for Lamb<M, E, D> {
+    fn state_buffers(&mut self) -> Vec<(&'static str, &mut Gradients<E, D>)> {
+        vec![
+            ("moment1", &mut self.moment1),
+            ("moment2", &mut self.moment2),
+        ]
+    }
+
+    fn write_state_scalars(
+        &self,
+        tensors: &mut Vec<(String, safetensors::Dtype, Vec<usize>, Vec<u8>)>,
+    ) {
+        crate::nn::SaveSafeTensors::write_safetensors(&self.t, "t", tensors);
+    }
+
+    fn read_state_scalars(
+        &mut self,
+        tensors: &safetensors::SafeTensors,
+    ) -> Result<(), safetensors::SafeTensorError> {
+        crate::nn::LoadSafeTensors::read_safetensors(&mut self.t, "t", tensors)
+    }
+}
+
+impl<M, E: Dtype, D: Device<E> + LambKernel<E>> crate::nn::Optimizer<M, E, D> for Lamb<M, E, D> {
+    fn update_tensor<S: Shape>(
+        &mut self,
+        t: &mut Tensor<S, E, D>,
+        gradients: &Gradients<E, D>,
+        missing_params: &mut Vec<UniqueId>,
+    ) -> Result<(), Error> {
+        self.groups
+            .try_update(&self.cfg, t, gradients, missing_params, |cfg, t, g| {
+                let m_t = self.moment1.get_or_alloc_mut(t)?;
+                let v_t = self.moment2.get_or_alloc_mut(t)?;
+                cfg.try_update(self.t, t, m_t, v_t, g)
+            })
+    }
+
+    fn update(&mut self, module: &mut M, gradients: &Gradients<E, D>) -> Result<(), Error>
+    where
+        M: crate::nn::UpdateParams<E, D>,
+    {
+        super::update_and_count_step(self, |opt| &mut opt.t, module, gradients)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{nn::optim::test_utils::test_matches_expected, prelude::*};
+
+    #[test]
+    fn test_lamb_default() {
+        let cfg = Default::default();
+        #[rustfmt::skip]
+        const EXPECTED: [[f64; 5]; 5] = [
+            [-0.50058008, -0.25058009, 0.099419913, 0.59941991, 0.99941991],
+            [-0.50115998, -0.25115998, 0.098840016, 0.59884001, 0.99884001],
+            [-0.50173968, -0.25173969, 0.098260309, 0.59826031, 0.99826031],
+            [-0.5023192, -0.25231921, 0.09768079, 0.59768079, 0.99768079],
+            [-0.50289853, -0.25289854, 0.097101458, 0.59710145, 0.99710145],
+        ];
+        test_matches_expected(|t| Lamb::new(t, cfg), EXPECTED);
+    }
+
+    #[test]
+    fn test_lamb_custom() {
+        let cfg = LambConfig {
+            lr: 1e-2,
+            betas: [0.5, 0.25],
+            eps: 1e-8,
+            weight_decay: None,
+        };
+        #[rustfmt::skip]
+        const EXPECTED: [[f64; 5]; 5] = [
+            [-0.50580086, -0.25580086, 0.094199138, 0.59419914, 0.99419914],
+            [-0.51158298, -0.26158298, 0.088417016, 0.58841702, 0.98841702],
+            [-0.51734695, -0.26734695, 0.082653054, 0.58265305, 0.98265305],
+            [-0.52309333, -0.27309333, 0.076906671, 0.57690667, 0.97690667],
+            [-0.52882271, -0.27882271, 0.071177292, 0.57117729, 0.97117729],
+        ];
+        test_matches_expected(|t| Lamb::new(t, cfg), EXPECTED);
+    }
+
+    #[test]
+    fn test_lamb_l2_weight_decay() {
+        let cfg = LambConfig {
+            weight_decay: Some(WeightDecay::L2(0.5)),
+            ..Default::default()
+        };
+        #[rustfmt::skip]
+        const EXPECTED: [[f64; 5]; 5] = [
+            [-0.49941992, -0.25058008, 0.099419912, 0.59941991, 0.99941991],
+            [-0.49884025, -0.25115974, 0.098840198, 0.59884019, 0.99884019],
+            [-0.49826103, -0.25173895, 0.098260847, 0.59826082, 0.99826082],
+            [-0.49768226, -0.2523177, 0.097681846, 0.59768178, 0.99768177],
+            [-0.49710396, -0.25289594, 0.097103184, 0.59710306, 0.99710305],
+        ];
+        test_matches_expected(|t| Lamb::new(t, cfg), EXPECTED);
+    }
+
+    #[test]
+    fn test_lamb_decoupled_weight_decay() {
+        let cfg = LambConfig {
+            weight_decay: Some(WeightDecay::Decoupled(0.5)),
+            ..Default::default()
+        };
+        #[rustfmt::skip]
+        const EXPECTED: [[f64; 5]; 5] = [
+            [-0.50038543, -0.25044967, 0.099460395, 0.59933192, 0.99922913],
+            [-0.50077065, -0.25089909, 0.098921085, 0.5986642, 0.99845869],
+            [-0.50115566, -0.25134827, 0.09838207, 0.59799685, 0.99768867],
+            [-0.50154045, -0.2517972, 0.097843349, 0.59732986, 0.99691906],
+            [-0.50192504, -0.25224589, 0.097304921, 0.59666323, 0.99614988],
+        ];
+        test_matches_expected(|t| Lamb::new(t, cfg), EXPECTED);
+    }
+
+    #[test]
+    fn test_unused_tensors() {
+        crate::nn::optim::test_utils::test_unused_tensors(|t| Lamb::new(t, Default::default()));
+    }
+}
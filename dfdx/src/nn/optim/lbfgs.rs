@@ -0,0 +1,700 @@
+use std::{collections::VecDeque, marker::PhantomData};
+
+use super::param_groups::FrozenParams;
+
+use crate::{
+    nn::{Optimizer, UpdateParams},
+    shapes::{Dtype, HasShape, Rank0, Shape},
+    tensor::{Error, Gradients, OwnedTape, Storage, Tensor, Tensorlike, UniqueId},
+    tensor_ops::{Backward, Device, MaxTo, ReshapeTo, SumTo, TryMul},
+};
+
+/// Configuration of [Lbfgs].
+#[derive(Debug, Clone, Copy)]
+pub struct LbfgsConfig {
+    /// Learning rate. Defaults to `1.0`.
+    pub lr: f64,
+    /// Maximum number of iterations per [Lbfgs::step()]. Defaults to `20`.
+    pub max_iter: usize,
+    /// Maximum number of loss evaluations per [Lbfgs::step()]. Defaults to `25`.
+    pub max_eval: usize,
+    /// Stops when the largest gradient element is at most this. Defaults to `1e-7`.
+    pub tolerance_grad: f64,
+    /// Stops when the loss or the parameters change by less than this. Defaults to `1e-9`.
+    pub tolerance_change: f64,
+    /// Number of previous steps used to approximate the inverse hessian. At least one step
+    /// is always kept. Defaults to `100`.
+    pub history_size: usize,
+    /// Line search used by [Lbfgs::step()]. When `None`, steps of size `lr` are taken. Defaults to `None`.
+    pub line_search: Option<StrongWolfe>,
+}
+
+impl Default for LbfgsConfig {
+    fn default() -> Self {
+        Self {
+            lr: 1.0,
+            max_iter: 20,
+            max_eval: 25,
+            tolerance_grad: 1e-7,
+            tolerance_change: 1e-9,
+            history_size: 100,
+            line_search: None,
+        }
+    }
+}
+
+/// Line search that finds a step size satisfying the strong Wolfe conditions,
+/// as described in Algorithms 3.5 & 3.6 of Nocedal & Wright's *Numerical Optimization*.
+#[derive(Debug, Clone, Copy)]
+pub struct StrongWolfe {
+    /// Sufficient decrease constant. Defaults to `1e-4`.
+    pub c1: f64,
+    /// Curvature constant. Defaults to `0.9`.
+    pub c2: f64,
+    /// Maximum number of loss evaluations per line search. Defaults to `25`.
+    pub max_ls: usize,
+}
+
+impl Default for StrongWolfe {
+    fn default() -> Self {
+        Self {
+            c1: 1e-4,
+            c2: 0.9,
+            max_ls: 25,
+        }
+    }
+}
+
+/// The limited memory BFGS quasi-Newton method, based on
+/// [pytorch's implementation](https://pytorch.org/docs/stable/generated/torch.optim.LBFGS.html).
+///
+/// L-BFGS needs to re-evaluate the loss several times per step, so instead of
+/// [Optimizer::update()] it is usually used with [Lbfgs::step()], which takes a closure that
+/// computes the loss of the module (with its gradients traced). [Optimizer::update()] takes a
+/// single step of size [LbfgsConfig::lr] along the quasi-Newton direction.
+///
+/// All parameters are optimized together, so this is best suited to small models trained on the full dataset.
+///
+/// # Example Usage
+/// ```rust
+/// # use dfdx::prelude::*;
+/// # let dev: Cpu = Default::default();
+/// let mut model: Tensor<Rank1<2>, f32, _> = dev.zeros();
+/// let target = dev.tensor([1.0, -2.0]);
+/// let mut opt = Lbfgs::new(&model, LbfgsConfig {
+///     line_search: Some(Default::default()),
+///     ..Default::default()
+/// });
+/// opt.step(&mut model, |m| (m.leaky_trace() - target.clone()).square().sum())
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct Lbfgs<M, E: Dtype, D: Storage<E>> {
+    /// Hyperparameter configuration
+    pub cfg: LbfgsConfig,
+
+    /// The most recent steps, oldest first.
+    history: VecDeque<HistoryEntry<E, D>>,
+    prev: Option<PrevStep<E, D>>,
+    h_diag: f64,
+    gathered: Gathered<E, D>,
+    frozen: FrozenParams,
+
+    marker: PhantomData<*const M>,
+}
+
+/// A previous step `s`, and the change of the gradient `y` it caused.
+#[derive(Debug, Clone)]
+struct HistoryEntry<E, D: Storage<E>> {
+    s: Flat<E, D>,
+    y: Flat<E, D>,
+    /// `1 / y.s`
+    rho: f64,
+}
+
+/// The direction & size of the last step, and the gradient before it.
+#[derive(Debug, Clone)]
+struct PrevStep<E, D: Storage<E>> {
+    d: Flat<E, D>,
+    t: f64,
+    g: Flat<E, D>,
+}
+
+/// Parameters & gradients handed to [Optimizer::update_tensor()].
+#[derive(Debug, Clone)]
+struct Gathered<E, D: Storage<E>> {
+    x: Flat<E, D>,
+    g: Flat<E, D>,
+}
+
+impl<E, D: Storage<E>> Default for Gathered<E, D> {
+    fn default() -> Self {
+        Self {
+            x: Flat(Vec::new()),
+            g: Flat(Vec::new()),
+        }
+    }
+}
+
+impl<M, E: Dtype, D: Storage<E>> Lbfgs<M, E, D> {
+    /// Constructs using hyperparameters from `cfg`.
+    pub fn new(_model: &M, cfg: LbfgsConfig) -> Self {
+        Self {
+            cfg,
+            history: VecDeque::new(),
+            prev: None,
+            h_diag: 1.0,
+            gathered: Default::default(),
+            frozen: Default::default(),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<M, E: Dtype, D: Device<E>> super::FreezeParams<E, D> for Lbfgs<M, E, D> {
+    fn set_frozen<P: UpdateParams<E, D>>(&mut self, module: &P, frozen: bool) {
+        self.frozen.set(module, frozen);
+    }
+}
+
+impl<M, E: Dtype, D: Storage<E>> super::LearningRate for Lbfgs<M, E, D> {
+    fn lr(&self) -> f64 {
+        self.cfg.lr
+    }
+    fn set_lr(&mut self, lr: f64) {
+        self.cfg.lr = lr;
+    }
+}
+
+impl<M: UpdateParams<E, D>, E: Dtype, D: Device<E>> Lbfgs<M, E, D> {
+    /// Runs up to [LbfgsConfig::max_iter] iterations, where `closure` computes the loss
+    /// of the module (e.g. by calling forward with [Tensor::leaky_trace()]). Returns the loss from
+    /// before the step.
+    pub fn step<F>(&mut self, module: &mut M, mut closure: F) -> Result<f64, Error>
+    where
+        F: FnMut(&mut M) -> Tensor<Rank0, E, D, OwnedTape<E, D>>,
+    {
+        let cfg = self.cfg;
+        let (mut loss, Gathered { mut x, mut g }) = self.eval(module, &mut closure)?;
+        let mut evals = 1;
+        let orig_loss = loss;
+        if g.max_abs()? <= cfg.tolerance_grad {
+            return Ok(orig_loss);
+        }
+
+        for n_iter in 1..=cfg.max_iter {
+            let (d, t) = self.direction(&g)?;
+            let gtd = g.dot(&d)?;
+            if gtd > -cfg.tolerance_change {
+                // not a descent direction, so there is nothing left to do
+                self.prev = Some(PrevStep { d, t: 0.0, g });
+                break;
+            }
+
+            let prev_loss = loss;
+            let (t, new_g) = match cfg.line_search {
+                Some(ls) => {
+                    let (f, new_g, t, ls_evals) =
+                        self.strong_wolfe(module, &mut closure, ls, (&x, t, &d), (loss, &g, gtd))?;
+                    apply(module, &self.frozen, &x, &d, t)?;
+                    x = x.axpy(1.0, &d, t)?;
+                    loss = f;
+                    evals += ls_evals;
+                    (t, new_g)
+                }
+                None => {
+                    apply(module, &self.frozen, &x, &d, t)?;
+                    if n_iter == cfg.max_iter {
+                        // no need to evaluate the loss again
+                        self.prev = Some(PrevStep { d, t, g });
+                        break;
+                    }
+                    let (f, Gathered { x: new_x, g: new_g }) = self.eval(module, &mut closure)?;
+                    evals += 1;
+                    loss = f;
+                    x = new_x;
+                    (t, new_g)
+                }
+            };
+            let step_size = d.max_abs()? * t.abs();
+            let prev_g = std::mem::replace(&mut g, new_g);
+            self.prev = Some(PrevStep { d, t, g: prev_g });
+
+            if n_iter == cfg.max_iter
+                || evals >= cfg.max_eval
+                || g.max_abs()? <= cfg.tolerance_grad
+                || step_size <= cfg.tolerance_change
+                || (loss - prev_loss).abs() < cfg.tolerance_change
+            {
+                break;
+            }
+        }
+        Ok(orig_loss)
+    }
+
+    /// Computes the loss & gradients at the current parameters. Returns the loss, parameters & gradients.
+    fn eval<F>(&mut self, module: &mut M, closure: &mut F) -> Result<(f64, Gathered<E, D>), Error>
+    where
+        F: FnMut(&mut M) -> Tensor<Rank0, E, D, OwnedTape<E, D>>,
+    {
+        let loss = closure(module);
+        let value = loss.as_vec()[0].to_f64().unwrap();
+        let grads = loss.try_backward()?;
+        Ok((value, self.gather(module, &grads)?))
+    }
+
+    fn gather(&mut self, module: &mut M, grads: &Gradients<E, D>) -> Result<Gathered<E, D>, Error> {
+        let mut missing = Vec::new();
+        module.try_update_params::<M, _>(self, grads, &mut missing)?;
+        let gathered = std::mem::take(&mut self.gathered);
+        if missing.is_empty() {
+            Ok(gathered)
+        } else {
+            Err(Error::UnusedTensors(missing))
+        }
+    }
+
+    /// Updates the history with the gradient `g` at the current parameters, and returns
+    /// the direction to search along with the initial step size.
+    fn direction(&mut self, g: &Flat<E, D>) -> Result<(Flat<E, D>, f64), Error> {
+        let Some(prev) = self.prev.take() else {
+            self.h_diag = 1.0;
+            let t = (1.0 / g.sum_abs()?).min(1.0) * self.cfg.lr;
+            return Ok((g.scale(-1.0)?, t));
+        };
+
+        let y = g.axpy(1.0, &prev.g, -1.0)?;
+        let s = prev.d.scale(prev.t)?;
+        let ys = y.dot(&s)?;
+        if ys > 1e-10 {
+            while self.history.len() >= self.cfg.history_size.max(1) {
+                self.history.pop_front();
+            }
+            self.h_diag = ys / y.dot(&y)?;
+            self.history.push_back(HistoryEntry {
+                s,
+                y,
+                rho: 1.0 / ys,
+            });
+        }
+
+        // two-loop recursion to compute `-H * g`
+        let mut q = g.scale(-1.0)?;
+        let mut al = vec![0.0; self.history.len()];
+        for (i, h) in self.history.iter().enumerate().rev() {
+            al[i] = h.s.dot(&q)? * h.rho;
+            q = q.axpy(1.0, &h.y, -al[i])?;
+        }
+        let mut r = q.scale(self.h_diag)?;
+        for (i, h) in self.history.iter().enumerate() {
+            let be = h.y.dot(&r)? * h.rho;
+            r = r.axpy(1.0, &h.s, al[i] - be)?;
+        }
+        Ok((r, self.cfg.lr))
+    }
+
+    /// Returns the loss, gradient & step size it found, along with the number of loss evaluations.
+    /// `module` is left at an arbitrary point along `d`.
+    fn strong_wolfe<F>(
+        &mut self,
+        module: &mut M,
+        closure: &mut F,
+        ls: StrongWolfe,
+        (x, mut t, d): (&Flat<E, D>, f64, &Flat<E, D>),
+        (f, g, gtd): (f64, &Flat<E, D>, f64),
+    ) -> Result<(f64, Flat<E, D>, f64, usize), Error>
+    where
+        F: FnMut(&mut M) -> Tensor<Rank0, E, D, OwnedTape<E, D>>,
+    {
+        let d_norm = d.max_abs()?;
+        let mut obj = |this: &mut Self, t: f64| -> Result<(f64, Flat<E, D>, f64), Error> {
+            apply(module, &this.frozen, x, d, t)?;
+            let (f, Gathered { g, .. }) = this.eval(module, closure)?;
+            let gtd = g.dot(d)?;
+            Ok((f, g, gtd))
+        };
+
+        let (mut f_new, mut g_new, mut gtd_new) = obj(self, t)?;
+        let mut evals = 1;
+        let (mut t_prev, mut f_prev, mut g_prev, mut gtd_prev) = (0.0, f, g.clone(), gtd);
+        let mut done = false;
+        let mut ls_iter = 0;
+
+        // find a bracket containing a point that satisfies the conditions
+        let (mut bracket, mut bracket_f, mut bracket_g, mut bracket_gtd) = loop {
+            if ls_iter == ls.max_ls {
+                break ([0.0, t], [f, f_new], [g.clone(), g_new], [gtd, gtd_new]);
+            }
+            if f_new > f + ls.c1 * t * gtd || (ls_iter > 1 && f_new >= f_prev) {
+                break (
+                    [t_prev, t],
+                    [f_prev, f_new],
+                    [g_prev, g_new],
+                    [gtd_prev, gtd_new],
+                );
+            }
+            if gtd_new.abs() <= -ls.c2 * gtd {
+                done = true;
+                break (
+                    [t, t],
+                    [f_new, f_new],
+                    [g_new.clone(), g_new],
+                    [gtd_new, gtd_new],
+                );
+            }
+            if gtd_new >= 0.0 {
+                break (
+                    [t_prev, t],
+                    [f_prev, f_new],
+                    [g_prev, g_new],
+                    [gtd_prev, gtd_new],
+                );
+            }
+
+            // extrapolate
+            let min_step = t + 0.01 * (t - t_prev);
+            let max_step = t * 10.0;
+            let tmp = t;
+            t = cubic_interpolate(
+                (t_prev, f_prev, gtd_prev),
+                (t, f_new, gtd_new),
+                Some((min_step, max_step)),
+            );
+            (t_prev, f_prev, g_prev, gtd_prev) = (tmp, f_new, g_new, gtd_new);
+            (f_new, g_new, gtd_new) = obj(self, t)?;
+            evals += 1;
+            ls_iter += 1;
+        };
+
+        // zoom in on the point that satisfies the conditions
+        let mut insuf_progress = false;
+        let (mut low, mut high) = if bracket_f[0] <= bracket_f[1] {
+            (0, 1)
+        } else {
+            (1, 0)
+        };
+        while !done && ls_iter < ls.max_ls {
+            if (bracket[1] - bracket[0]).abs() * d_norm < self.cfg.tolerance_change {
+                break;
+            }
+            let mut t = cubic_interpolate(
+                (bracket[0], bracket_f[0], bracket_gtd[0]),
+                (bracket[1], bracket_f[1], bracket_gtd[1]),
+                None,
+            );
+
+            // don't get too close to the ends of the bracket
+            let (b_min, b_max) = (bracket[0].min(bracket[1]), bracket[0].max(bracket[1]));
+            let eps = 0.1 * (b_max - b_min);
+            if (b_max - t).min(t - b_min) < eps {
+                if insuf_progress || t >= b_max || t <= b_min {
+                    t = if (t - b_max).abs() < (t - b_min).abs() {
+                        b_max - eps
+                    } else {
+                        b_min + eps
+                    };
+                    insuf_progress = false;
+                } else {
+                    insuf_progress = true;
+                }
+            } else {
+                insuf_progress = false;
+            }
+
+            let (f_new, g_new, gtd_new) = obj(self, t)?;
+            evals += 1;
+            ls_iter += 1;
+
+            if f_new > f + ls.c1 * t * gtd || f_new >= bracket_f[low] {
+                bracket[high] = t;
+                bracket_f[high] = f_new;
+                bracket_g[high] = g_new;
+                bracket_gtd[high] = gtd_new;
+                (low, high) = if bracket_f[0] <= bracket_f[1] {
+                    (0, 1)
+                } else {
+                    (1, 0)
+                };
+            } else {
+                if gtd_new.abs() <= -ls.c2 * gtd {
+                    done = true;
+                } else if gtd_new * (bracket[high] - bracket[low]) >= 0.0 {
+                    bracket[high] = bracket[low];
+                    bracket_f[high] = bracket_f[low];
+                    bracket_g[high] = bracket_g[low].clone();
+                    bracket_gtd[high] = bracket_gtd[low];
+                }
+                bracket[low] = t;
+                bracket_f[low] = f_new;
+                bracket_g[low] = g_new;
+                bracket_gtd[low] = gtd_new;
+            }
+        }
+
+        let [g0, g1] = bracket_g;
+        let g_low = if low == 0 { g0 } else { g1 };
+        Ok((bracket_f[low], g_low, bracket[low], evals))
+    }
+}
+
+/// Minimizes the cubic interpolating two points with their derivatives, within `bounds`.
+fn cubic_interpolate(
+    (x1, f1, g1): (f64, f64, f64),
+    (x2, f2, g2): (f64, f64, f64),
+    bounds: Option<(f64, f64)>,
+) -> f64 {
+    let (min_bound, max_bound) = bounds.unwrap_or((x1.min(x2), x1.max(x2)));
+    let d1 = g1 + g2 - 3.0 * (f1 - f2) / (x1 - x2);
+    let d2_square = d1 * d1 - g1 * g2;
+    if d2_square >= 0.0 {
+        let d2 = d2_square.sqrt();
+        let min_pos = if x1 <= x2 {
+            x2 - (x2 - x1) * ((g2 + d2 - d1) / (g2 - g1 + 2.0 * d2))
+        } else {
+            x1 - (x1 - x2) * ((g1 + d2 - d1) / (g1 - g2 + 2.0 * d2))
+        };
+        min_pos.max(min_bound).min(max_bound)
+    } else {
+        (min_bound + max_bound) / 2.0
+    }
+}
+
+impl<M, E: Dtype, D: Device<E>> Optimizer<M, E, D> for Lbfgs<M, E, D> {
+    /// Gathers the parameter & its gradient. The parameters are updated together by [Optimizer::update()].
+    fn update_tensor<S: Shape>(
+        &mut self,
+        t: &mut Tensor<S, E, D>,
+        gradients: &Gradients<E, D>,
+        missing_params: &mut Vec<UniqueId>,
+    ) -> Result<(), Error> {
+        if self.frozen.contains(&t.id()) {
+            return Ok(());
+        }
+        if gradients.get_ref_checked(t).is_none() {
+            missing_params.push(t.id());
+            return Ok(());
+        }
+        let numel = t.shape().num_elements();
+        self.gathered
+            .x
+            .0
+            .push(t.clone().try_reshape_like(&(numel,))?);
+        self.gathered
+            .g
+            .0
+            .push(gradients.get(t).try_reshape_like(&(numel,))?);
+        Ok(())
+    }
+
+    fn update(&mut self, module: &mut M, gradients: &Gradients<E, D>) -> Result<(), Error>
+    where
+        M: UpdateParams<E, D>,
+    {
+        let Gathered { x, g } = self.gather(module, gradients)?;
+        let (d, t) = self.direction(&g)?;
+        apply(module, &self.frozen, &x, &d, t)?;
+        self.prev = Some(PrevStep { d, t, g });
+        Ok(())
+    }
+}
+
+/// Parameters (or gradients) of a module as flat tensors, in the order [UpdateParams] visits the parameters.
+#[derive(Debug, Clone)]
+struct Flat<E, D: Storage<E>>(Vec<Tensor<(usize,), E, D>>);
+
+impl<E: Dtype, D: Device<E>> Flat<E, D> {
+    fn dot(&self, rhs: &Self) -> Result<f64, Error> {
+        let mut sum = 0.0;
+        for (a, b) in self.0.iter().zip(rhs.0.iter()) {
+            let ab = a.clone().try_mul(b.clone())?.try_sum::<Rank0, _>()?;
+            sum += ab.as_vec()[0].to_f64().unwrap();
+        }
+        Ok(sum)
+    }
+
+    /// `self * alpha + rhs * beta`
+    fn axpy(&self, alpha: f64, rhs: &Self, beta: f64) -> Result<Self, Error> {
+        let mut out = self.clone();
+        for (a, b) in out.0.iter_mut().zip(rhs.0.iter()) {
+            a.try_axpy(alpha, b, beta)?;
+        }
+        Ok(out)
+    }
+
+    fn scale(&self, c: f64) -> Result<Self, Error> {
+        let mut out = Vec::with_capacity(self.0.len());
+        for a in self.0.iter() {
+            out.push(a.clone().try_mul(c)?);
+        }
+        Ok(Self(out))
+    }
+
+    fn max_abs(&self) -> Result<f64, Error> {
+        let mut max: f64 = 0.0;
+        for a in self.0.iter() {
+            let m = a.clone().try_abs()?.try_max::<Rank0, _>()?;
+            max = max.max(m.as_vec()[0].to_f64().unwrap());
+        }
+        Ok(max)
+    }
+
+    fn sum_abs(&self) -> Result<f64, Error> {
+        let mut sum = 0.0;
+        for a in self.0.iter() {
+            let s = a.clone().try_abs()?.try_sum::<Rank0, _>()?;
+            sum += s.as_vec()[0].to_f64().unwrap();
+        }
+        Ok(sum)
+    }
+}
+
+/// Sets each parameter that isn't frozen to `x + step * d`, keeping its [UniqueId].
+struct Apply<'a, E, D: Storage<E>> {
+    frozen: &'a FrozenParams,
+    x: std::slice::Iter<'a, Tensor<(usize,), E, D>>,
+    d: std::slice::Iter<'a, Tensor<(usize,), E, D>>,
+    step: f64,
+}
+
+fn apply<M: UpdateParams<E, D>, E: Dtype, D: Device<E>>(
+    module: &mut M,
+    frozen: &FrozenParams,
+    x: &Flat<E, D>,
+    d: &Flat<E, D>,
+    step: f64,
+) -> Result<(), Error> {
+    let mut visitor = Apply {
+        frozen,
+        x: x.0.iter(),
+        d: d.0.iter(),
+        step,
+    };
+    module.try_update_params::<M, _>(&mut visitor, &Gradients::leaky(), &mut Vec::new())
+}
+
+impl<M, E: Dtype, D: Device<E>> Optimizer<M, E, D> for Apply<'_, E, D> {
+    fn update_tensor<S: Shape>(
+        &mut self,
+        t: &mut Tensor<S, E, D>,
+        _gradients: &Gradients<E, D>,
+        _missing_tensors: &mut Vec<UniqueId>,
+    ) -> Result<(), Error> {
+        if self.frozen.contains(&t.id()) {
+            return Ok(());
+        }
+        let x = self.x.next().unwrap().clone().try_reshape_like(t.shape())?;
+        let d = self.d.next().unwrap().clone().try_reshape_like(t.shape())?;
+        t.try_axpy(0.0, &x, 1.0)?;
+        t.try_axpy(1.0, &d, self.step)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{prelude::*, tests::*};
+
+    type Model = Tensor<Rank1<2>, TestDtype, TestDevice>;
+
+    /// `(x - 1)^2 + 10 * (y + 2)^2`
+    fn quadratic(
+        m: &mut Model,
+    ) -> Tensor<Rank0, TestDtype, TestDevice, OwnedTape<TestDtype, TestDevice>> {
+        let dev = m.device().clone();
+        let target: Model = dev.tensor([1.0, -2.0]).to_dtype();
+        let scale: Model = dev.tensor([1.0, 10.0]).to_dtype();
+        ((m.leaky_trace() - target).square() * scale).sum()
+    }
+
+    #[test]
+    fn test_lbfgs_quadratic() {
+        let dev: TestDevice = Default::default();
+        let mut model: Model = dev.zeros();
+        let mut opt = Lbfgs::new(&model, Default::default());
+        let loss = opt.step(&mut model, quadratic).unwrap();
+        assert_eq!(loss, 41.0);
+        assert_close_to_literal!(model, [1.0, -2.0], 1e-4);
+    }
+
+    #[test]
+    fn test_lbfgs_strong_wolfe_rosenbrock() {
+        let dev: TestDevice = Default::default();
+        let mut model: (Tensor<Rank0, TestDtype, _>, Tensor<Rank0, TestDtype, _>) =
+            (dev.tensor(-1.5).to_dtype(), dev.tensor(2.0).to_dtype());
+        let mut opt = Lbfgs::new(
+            &model,
+            LbfgsConfig {
+                max_iter: 100,
+                max_eval: 200,
+                line_search: Some(Default::default()),
+                ..Default::default()
+            },
+        );
+        // `(1 - x)^2 + 100 * (y - x^2)^2`
+        let rosenbrock = |m: &mut (Tensor<Rank0, _, _>, Tensor<Rank0, _, _>)| {
+            let x = m.0.leaky_trace();
+            (x - 1.0).square() + (m.1.leaky_trace() - m.0.leaky_trace().square()).square() * 100.0
+        };
+        opt.step(&mut model, rosenbrock).unwrap();
+        assert!((model.0.array() as f64 - 1.0).abs() < 1e-3, "{model:?}");
+        assert!((model.1.array() as f64 - 1.0).abs() < 1e-3, "{model:?}");
+    }
+
+    #[test]
+    fn test_lbfgs_update() {
+        let dev: TestDevice = Default::default();
+        let mut model: Model = dev.zeros();
+        let mut opt = Lbfgs::new(&model, Default::default());
+        for _ in 0..20 {
+            let grads = quadratic(&mut model).backward();
+            opt.update(&mut model, &grads).unwrap();
+        }
+        assert_close_to_literal!(model, [1.0, -2.0], 1e-4);
+    }
+
+    #[test]
+    fn test_lbfgs_history_size() {
+        let dev: TestDevice = Default::default();
+        for (history_size, expected) in [(0, 1), (1, 1), (3, 3)] {
+            let mut model: Model = dev.zeros();
+            let mut opt = Lbfgs::new(
+                &model,
+                LbfgsConfig {
+                    lr: 0.01,
+                    history_size,
+                    ..Default::default()
+                },
+            );
+            for _ in 0..10 {
+                let grads = quadratic(&mut model).backward();
+                opt.update(&mut model, &grads).unwrap();
+                assert!(opt.history.len() <= expected);
+            }
+            assert_eq!(opt.history.len(), expected);
+        }
+    }
+
+    #[test]
+    fn test_lbfgs_unused_params() {
+        let dev: TestDevice = Default::default();
+        let mut model: (Model, Model) = (dev.zeros(), dev.zeros());
+        let mut opt = Lbfgs::new(&model, Default::default());
+        let before = model.1.array();
+        let err = opt
+            .step(&mut model, |m| m.0.leaky_trace().square().sum())
+            .unwrap_err();
+        assert!(matches!(err, Error::UnusedTensors(ids) if ids == [model.1.id()]));
+        assert_eq!(model.1.array(), before);
+    }
+
+    #[test]
+    fn test_lbfgs_set_frozen() {
+        let dev: TestDevice = Default::default();
+        let mut model: (Model, Model) = (dev.zeros(), dev.ones());
+        let mut opt = Lbfgs::new(&model, Default::default());
+        opt.set_frozen(&model.1, true);
+        opt.step(&mut model, |m| quadratic(&mut m.0)).unwrap();
+        assert_close_to_literal!(model.0, [1.0, -2.0], 1e-4);
+        assert_close_to_literal!(model.1, [1.0, 1.0]);
+    }
+}
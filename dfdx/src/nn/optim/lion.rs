@@ -0,0 +1,184 @@
+use std::marker::PhantomData;
+
+use super::param_groups::ParamGroups;
+use crate::{
+    nn::UpdateParams,
+    shapes::{Dtype, Shape},
+    tensor::{Error, Gradients, Storage, Tensor, UniqueId},
+    tensor_ops::{Device, LionConfig, LionKernel},
+};
+
+/// An implementation of the Lion optimizer from
+/// [Symbolic Discovery of Optimization Algorithms](https://arxiv.org/abs/2302.06675).
+///
+/// Lion only keeps track of momentum, and updates every parameter by `lr` in the direction
+/// of the sign of its interpolated momentum.
+///
+/// # Example Usage
+/// ```rust
+/// # use dfdx::prelude::*;
+/// # type Model = Tensor<Rank0, f32, Cpu>;
+/// # let dev: Cpu = Default::default();
+/// # let model: Model = dev.zeros();
+/// let mut opt: Lion<Model, f32, Cpu> = optim::Lion::new(&model, LionConfig {
+///     lr: 3e-5,
+///     betas: [0.9, 0.99],
+///     weight_decay: Some(WeightDecay::Decoupled(1.0)),
+/// });
+/// ```
+///
+/// See module level documentation at [crate::nn::optim] for examples of how to actually use an optimizer.
+#[derive(Debug, Clone)]
+pub struct Lion<M, E: Dtype, D: Storage<E>> {
+    /// Hyperparameter configuration
+    pub cfg: LionConfig,
+    groups: ParamGroups<LionConfig>,
+
+    moment: Gradients<E, D>,
+
+    marker: PhantomData<*const M>,
+}
+
+impl<M, E: Dtype, D: Storage<E>> Lion<M, E, D> {
+    /// Constructs using hyperparameters from `cfg`.
+    pub fn new(_model: &M, cfg: LionConfig) -> Self {
+        Self {
+            cfg,
+            groups: Default::default(),
+            moment: Gradients::leaky(),
+            marker: PhantomData,
+        }
+    }
+
+    /// Uses `cfg` instead of [Lion::cfg] for the parameters of `module` whose path (like `"0.bias"`)
+    /// is selected by `select`. See [crate::nn::optim#parameter-groups].
+    pub fn with_param_group<P: UpdateParams<E, D>>(
+        mut self,
+        module: &P,
+        select: impl Fn(&str) -> bool,
+        cfg: LionConfig,
+    ) -> Self
+    where
+        D: Device<E>,
+    {
+        self.groups.add(module, select, cfg, self.cfg.lr);
+        self
+    }
+}
+
+impl<M, E: Dtype, D: Device<E>> super::FreezeParams<E, D> for Lion<M, E, D> {
+    fn set_frozen<P: UpdateParams<E, D>>(&mut self, module: &P, frozen: bool) {
+        self.groups.frozen.set(module, frozen);
+    }
+}
+
+impl<M, E: Dtype, D: Storage<E>> super::LearningRate for Lion<M, E, D> {
+    fn lr(&self) -> f64 {
+        self.cfg.lr
+    }
+    fn set_lr(&mut self, lr: f64) {
+        self.cfg.lr = lr;
+        self.groups.set_lr(lr);
+    }
+}
+
+#[cfg(feature = "safetensors")]
+impl<M, E: Dtype, D: Device<E>> crate::nn::OptimizerState<E, D> for Lion<M, E, D> {
+    fn state_buffers(&mut self) -> Vec<(&'static str, &mut Gradients<E, D>)> {
+        vec![("moment", &mut self.moment)]
+    }
+}
+
+impl<M, E: Dtype, D: Device<E> + LionKernel<E>> crate::nn::Optimizer<M, E, D> for Lion<M, E, D> {
+    fn update_tensor<S: Shape>(
+        &mut self,
+        t: &mut Tensor<S, E, D>,
+        gradients: &Gradients<E, D>,
+        missing_params: &mut Vec<UniqueId>,
+    ) -> Result<(), Error> {
+        self.groups
+            .try_update(&self.cfg, t, gradients, missing_params, |cfg, t, g| {
+                let m = self.moment.get_or_alloc_mut(t)?;
+                cfg.try_update(t, m, g)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{nn::optim::test_utils::test_matches_expected, prelude::*};
+
+    #[test]
+    fn test_lion_default() {
+        let cfg = Default::default();
+        #[rustfmt::skip]
+        const EXPECTED: [[f64; 5]; 5] = [
+            [-0.5001, -0.2501, 0.0999, 0.5999, 0.9999],
+            [-0.5002, -0.2502, 0.0998, 0.5998, 0.9998],
+            [-0.5003, -0.2503, 0.0997, 0.5997, 0.9997],
+            [-0.5004, -0.2504, 0.0996, 0.5996, 0.9996],
+            [-0.5005, -0.2505, 0.0995, 0.5995, 0.9995],
+        ];
+        test_matches_expected(|t| Lion::new(t, cfg), EXPECTED);
+    }
+
+    #[test]
+    fn test_lion_custom() {
+        let cfg = LionConfig {
+            lr: 1e-2,
+            betas: [0.5, 0.25],
+            weight_decay: None,
+        };
+        #[rustfmt::skip]
+        const EXPECTED: [[f64; 5]; 5] = [
+            [-0.51, -0.26, 0.09, 0.59, 0.99],
+            [-0.52, -0.27, 0.08, 0.58, 0.98],
+            [-0.53, -0.28, 0.07, 0.57, 0.97],
+            [-0.54, -0.29, 0.06, 0.56, 0.96],
+            [-0.55, -0.3, 0.05, 0.55, 0.95],
+        ];
+        test_matches_expected(|t| Lion::new(t, cfg), EXPECTED);
+    }
+
+    #[test]
+    fn test_lion_l2_weight_decay() {
+        let cfg = LionConfig {
+            lr: 1e-2,
+            weight_decay: Some(WeightDecay::L2(5.0)),
+            ..Default::default()
+        };
+        #[rustfmt::skip]
+        const EXPECTED: [[f64; 5]; 5] = [
+            [-0.49, -0.24, 0.09, 0.59, 0.99],
+            [-0.48, -0.23, 0.08, 0.58, 0.98],
+            [-0.47, -0.22, 0.07, 0.57, 0.97],
+            [-0.46, -0.21, 0.06, 0.56, 0.96],
+            [-0.45, -0.2, 0.05, 0.55, 0.95],
+        ];
+        test_matches_expected(|t| Lion::new(t, cfg), EXPECTED);
+    }
+
+    #[test]
+    fn test_lion_decoupled_weight_decay() {
+        let cfg = LionConfig {
+            lr: 1e-2,
+            weight_decay: Some(WeightDecay::Decoupled(1.0)),
+            ..Default::default()
+        };
+        #[rustfmt::skip]
+        const EXPECTED: [[f64; 5]; 5] = [
+            [-0.505, -0.2575, 0.089, 0.584, 0.98],
+            [-0.50995, -0.264925, 0.07811, 0.56816, 0.9602],
+            [-0.5148505, -0.27227575, 0.0673289, 0.5524784, 0.940598],
+            [-0.519702, -0.27955299, 0.056655611, 0.53695362, 0.92119202],
+            [-0.52450498, -0.28675746, 0.046089055, 0.52158408, 0.9019801],
+        ];
+        test_matches_expected(|t| Lion::new(t, cfg), EXPECTED);
+    }
+
+    #[test]
+    fn test_unused_tensors() {
+        crate::nn::optim::test_utils::test_unused_tensors(|t| Lion::new(t, Default::default()));
+    }
+}
@@ -0,0 +1,461 @@
+//! Learning rate schedules that update the learning rate of an optimizer
+//! every step (or epoch).
+
+use std::f64::consts::PI;
+
+use crate::tensor::Error;
+
+/// An optimizer that has a single learning rate, like [super::Sgd], [super::Adam] & [super::RMSprop].
+pub trait LearningRate {
+    /// The current learning rate.
+    fn lr(&self) -> f64;
+    /// Sets the learning rate to use for the next update.
+    fn set_lr(&mut self, lr: f64);
+}
+
+/// Computes the learning rate to use at each step (or epoch). Schedules are stateless,
+/// so the same schedule can be reused or queried at any point of training.
+///
+/// Call [LrScheduler::step()] before each optimizer update (or at the start of each epoch)
+/// to set the learning rate on the optimizer. It checks the schedule with
+/// [LrScheduler::validate()] first:
+///
+/// ```rust
+/// # use dfdx::prelude::*;
+/// # let dev: Cpu = Default::default();
+/// # type Model = Tensor<Rank0, f32, Cpu>;
+/// # let model: Model = dev.zeros();
+/// let mut opt: Sgd<Model, f32, Cpu> = Sgd::new(&model, Default::default());
+/// let sched = LinearWarmup {
+///     steps: 2,
+///     start_factor: 0.0,
+///     then: ExponentialLr { lr: 1.0, gamma: 0.5 },
+/// };
+/// let lrs: Vec<f64> = (0..5).map(|t| sched.step(&mut opt, t)).collect();
+/// assert_eq!(lrs, [0.0, 0.5, 1.0, 0.5, 0.25]);
+/// assert_eq!(opt.cfg.lr, 0.25);
+/// ```
+pub trait LrScheduler {
+    /// The learning rate at step `t`, where the first step is `0`. The result is meaningless
+    /// for a schedule that fails [LrScheduler::validate()].
+    fn lr_at(&self, t: usize) -> f64;
+
+    /// Returns [Error::InvalidArgument] if the hyperparameters of the schedule are invalid,
+    /// like a period of `0`. Does nothing by default.
+    fn validate(&self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Sets the learning rate of `opt` to the learning rate at step `t`, and returns it.
+    ///
+    /// **Panics** if the schedule is invalid, see [LrScheduler::try_step()].
+    fn step<O: LearningRate>(&self, opt: &mut O, t: usize) -> f64 {
+        self.try_step(opt, t).unwrap()
+    }
+
+    /// Fallible version of [LrScheduler::step()]. Returns the error of
+    /// [LrScheduler::validate()] without changing the learning rate of `opt`.
+    fn try_step<O: LearningRate>(&self, opt: &mut O, t: usize) -> Result<f64, Error> {
+        self.validate()?;
+        let lr = self.lr_at(t);
+        opt.set_lr(lr);
+        Ok(lr)
+    }
+}
+
+/// Multiplies the learning rate by `gamma` every `step_size` steps.
+///
+/// `lr_at(t) = lr * gamma ^ (t / step_size)`
+#[derive(Debug, Clone, Copy)]
+pub struct StepLr {
+    pub lr: f64,
+    pub step_size: usize,
+    pub gamma: f64,
+}
+
+impl LrScheduler for StepLr {
+    fn lr_at(&self, t: usize) -> f64 {
+        self.lr * self.gamma.powi((t / self.step_size.max(1)) as i32)
+    }
+
+    /// Returns [Error::InvalidArgument] if `step_size` is `0`.
+    fn validate(&self) -> Result<(), Error> {
+        if self.step_size == 0 {
+            return Err(Error::InvalidArgument(
+                "StepLr needs a step_size of at least 1".into(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Multiplies the learning rate by `gamma` every step.
+///
+/// `lr_at(t) = lr * gamma ^ t`
+#[derive(Debug, Clone, Copy)]
+pub struct ExponentialLr {
+    pub lr: f64,
+    pub gamma: f64,
+}
+
+impl LrScheduler for ExponentialLr {
+    fn lr_at(&self, t: usize) -> f64 {
+        self.lr * self.gamma.powi(t as i32)
+    }
+}
+
+/// Cosine annealing from `lr` down to `min_lr` over `period` steps, with warm restarts as
+/// described in [SGDR: Stochastic Gradient Descent with Warm Restarts](https://arxiv.org/abs/1608.03983).
+///
+/// After each restart, the length of the period is multiplied by `period_mult`.
+/// For a single annealing cycle without restarts, set `period` to the total number of steps.
+#[derive(Debug, Clone, Copy)]
+pub struct CosineAnnealingLr {
+    pub lr: f64,
+    pub min_lr: f64,
+    pub period: usize,
+    pub period_mult: usize,
+}
+
+impl LrScheduler for CosineAnnealingLr {
+    fn lr_at(&self, t: usize) -> f64 {
+        let period = self.period.max(1);
+        let (t_cur, t_i) = if self.period_mult <= 1 {
+            (t % period, period)
+        } else {
+            let mut t_cur = t;
+            let mut t_i = period;
+            while t_cur >= t_i {
+                t_cur -= t_i;
+                t_i *= self.period_mult;
+            }
+            (t_cur, t_i)
+        };
+        cosine_anneal(self.lr, self.min_lr, t_cur as f64 / t_i as f64)
+    }
+
+    /// Returns [Error::InvalidArgument] if `period` or `period_mult` is `0`.
+    fn validate(&self) -> Result<(), Error> {
+        if self.period == 0 || self.period_mult == 0 {
+            return Err(Error::InvalidArgument(std::format!(
+                "CosineAnnealingLr needs a period & period_mult of at least 1, found {} & {}",
+                self.period,
+                self.period_mult
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Linearly increases the learning rate from `start_factor * then.lr_at(0)` to `then.lr_at(0)`
+/// over the first `steps` steps, and then follows `then` (starting from its step `0`).
+#[derive(Debug, Clone, Copy)]
+pub struct LinearWarmup<S> {
+    pub steps: usize,
+    pub start_factor: f64,
+    pub then: S,
+}
+
+impl<S: LrScheduler> LrScheduler for LinearWarmup<S> {
+    fn lr_at(&self, t: usize) -> f64 {
+        if t < self.steps {
+            let pct = t as f64 / self.steps as f64;
+            self.then.lr_at(0) * (self.start_factor + (1.0 - self.start_factor) * pct)
+        } else {
+            self.then.lr_at(t - self.steps)
+        }
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        self.then.validate()
+    }
+}
+
+/// Configuration of [OneCycleLr].
+#[derive(Debug, Clone, Copy)]
+pub struct OneCycleLrConfig {
+    pub max_lr: f64,
+    pub total_steps: usize,
+    pub pct_start: f64,
+    pub div_factor: f64,
+    pub final_div_factor: f64,
+}
+
+impl Default for OneCycleLrConfig {
+    fn default() -> Self {
+        Self {
+            max_lr: 1e-2,
+            total_steps: 1000,
+            pct_start: 0.3,
+            div_factor: 25.0,
+            final_div_factor: 1e4,
+        }
+    }
+}
+
+/// The 1cycle policy from [Super-Convergence](https://arxiv.org/abs/1708.07120).
+/// Anneals the learning rate from `max_lr / div_factor` up to `max_lr` over the first
+/// `pct_start * total_steps` steps, and then down to `max_lr / (div_factor * final_div_factor)`
+/// at the last step. Both phases use cosine annealing, like `torch.optim.lr_scheduler.OneCycleLR`.
+///
+/// ```rust
+/// # use dfdx::prelude::*;
+/// let sched = OneCycleLr::new(OneCycleLrConfig {
+///     max_lr: 0.1,
+///     total_steps: 100,
+///     ..Default::default()
+/// });
+/// assert_eq!(sched.lr_at(29), 0.1);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct OneCycleLr {
+    cfg: OneCycleLrConfig,
+}
+
+impl OneCycleLr {
+    /// **Panics** if `cfg` is invalid, see [OneCycleLr::try_new()].
+    pub fn new(cfg: OneCycleLrConfig) -> Self {
+        Self::try_new(cfg).unwrap()
+    }
+
+    /// Returns [Error::InvalidArgument] if `cfg.total_steps` is less than 2, or
+    /// `cfg.pct_start` is not in `(0, 1]`.
+    pub fn try_new(cfg: OneCycleLrConfig) -> Result<Self, Error> {
+        let sched = Self { cfg };
+        sched.validate()?;
+        Ok(sched)
+    }
+
+    /// The configuration this schedule was created with.
+    pub fn cfg(&self) -> &OneCycleLrConfig {
+        &self.cfg
+    }
+}
+
+impl LrScheduler for OneCycleLr {
+    fn lr_at(&self, t: usize) -> f64 {
+        let cfg = &self.cfg;
+        let initial_lr = cfg.max_lr / cfg.div_factor;
+        let min_lr = initial_lr / cfg.final_div_factor;
+        let t = t as f64;
+        // the warmup is empty if it is shorter than a single step
+        let peak = (cfg.pct_start * cfg.total_steps as f64 - 1.0).max(0.0);
+        let last = cfg.total_steps as f64 - 1.0;
+        if t <= peak && peak > 0.0 {
+            cosine_anneal(initial_lr, cfg.max_lr, t / peak)
+        } else if t >= last {
+            min_lr
+        } else {
+            cosine_anneal(cfg.max_lr, min_lr, (t - peak) / (last - peak))
+        }
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        let cfg = &self.cfg;
+        if cfg.total_steps < 2 {
+            return Err(Error::InvalidArgument(std::format!(
+                "OneCycleLr needs at least 2 total_steps, found {}",
+                cfg.total_steps
+            )));
+        }
+        if !(cfg.pct_start > 0.0 && cfg.pct_start <= 1.0) {
+            return Err(Error::InvalidArgument(std::format!(
+                "OneCycleLr needs pct_start in (0, 1], found {}",
+                cfg.pct_start
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Interpolates from `start` (at `pct = 0`) to `end` (at `pct = 1`) along half a cosine.
+fn cosine_anneal(start: f64, end: f64, pct: f64) -> f64 {
+    end + (start - end) * 0.5 * (1.0 + (PI * pct).cos())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{prelude::*, tests::*};
+
+    fn assert_lrs<S: LrScheduler>(sched: &S, expected: &[f64]) {
+        for (t, &e) in expected.iter().enumerate() {
+            let lr = sched.lr_at(t);
+            assert!((lr - e).abs() < 1e-9, "step {t}: {lr} != {e}");
+        }
+    }
+
+    #[test]
+    fn test_step_lr() {
+        let sched = StepLr {
+            lr: 1.0,
+            step_size: 2,
+            gamma: 0.1,
+        };
+        assert_lrs(&sched, &[1.0, 1.0, 0.1, 0.1, 0.01]);
+    }
+
+    #[test]
+    fn test_exponential_lr() {
+        let sched = ExponentialLr {
+            lr: 2.0,
+            gamma: 0.5,
+        };
+        assert_lrs(&sched, &[2.0, 1.0, 0.5, 0.25]);
+    }
+
+    #[test]
+    fn test_cosine_annealing_warm_restarts() {
+        let sched = CosineAnnealingLr {
+            lr: 1.0,
+            min_lr: 0.0,
+            period: 2,
+            period_mult: 2,
+        };
+        // periods of length 2, then 4, then 8
+        let s = 0.5f64.sqrt();
+        let expected = [1.0, 0.5, 1.0, 0.5 + 0.5 * s, 0.5, 0.5 - 0.5 * s, 1.0];
+        assert_lrs(&sched, &expected);
+
+        let no_restarts = CosineAnnealingLr {
+            lr: 1.0,
+            min_lr: 0.5,
+            period: 4,
+            period_mult: 1,
+        };
+        assert_lrs(&no_restarts, &[1.0, 0.75 + 0.25 * s, 0.75, 0.75 - 0.25 * s]);
+    }
+
+    #[test]
+    fn test_linear_warmup() {
+        let sched = LinearWarmup {
+            steps: 4,
+            start_factor: 0.0,
+            then: StepLr {
+                lr: 1.0,
+                step_size: 2,
+                gamma: 0.5,
+            },
+        };
+        assert_lrs(&sched, &[0.0, 0.25, 0.5, 0.75, 1.0, 1.0, 0.5]);
+    }
+
+    #[test]
+    fn test_one_cycle() {
+        let sched = OneCycleLr::new(OneCycleLrConfig {
+            max_lr: 1.0,
+            total_steps: 11,
+            pct_start: 3.0 / 11.0,
+            div_factor: 10.0,
+            final_div_factor: 10.0,
+        });
+        // warms up over steps 0..=2, then anneals over steps 2..=10
+        assert_lrs(&sched, &[0.1, 0.55, 1.0]);
+        assert!((sched.lr_at(6) - 0.505).abs() < 1e-9);
+        assert!((sched.lr_at(10) - 0.01).abs() < 1e-9);
+        assert!((sched.lr_at(20) - 0.01).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_one_cycle_without_warmup() {
+        let sched = OneCycleLr::new(OneCycleLrConfig {
+            max_lr: 1.0,
+            total_steps: 3,
+            pct_start: 0.1,
+            div_factor: 10.0,
+            final_div_factor: 10.0,
+        });
+        assert_lrs(&sched, &[1.0, 0.505, 0.01]);
+    }
+
+    #[test]
+    fn test_one_cycle_invalid_config() {
+        let cfg = OneCycleLrConfig {
+            total_steps: 1,
+            ..Default::default()
+        };
+        assert!(matches!(
+            OneCycleLr::try_new(cfg),
+            Err(Error::InvalidArgument(_))
+        ));
+        let cfg = OneCycleLrConfig {
+            pct_start: 0.0,
+            ..Default::default()
+        };
+        assert!(matches!(
+            OneCycleLr::try_new(cfg),
+            Err(Error::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn test_invalid_schedules() {
+        let dev: TestDevice = Default::default();
+        let x: Tensor<Rank1<2>, TestDtype, _> = dev.zeros();
+        let mut sgd: Sgd<_, TestDtype, TestDevice> = Sgd::new(
+            &x,
+            SgdConfig {
+                lr: 0.5,
+                ..Default::default()
+            },
+        );
+
+        let step = StepLr {
+            lr: 1.0,
+            step_size: 0,
+            gamma: 0.1,
+        };
+        assert!(matches!(
+            step.try_step(&mut sgd, 0),
+            Err(Error::InvalidArgument(_))
+        ));
+        let cosine = CosineAnnealingLr {
+            lr: 1.0,
+            min_lr: 0.0,
+            period: 0,
+            period_mult: 2,
+        };
+        assert!(matches!(
+            cosine.try_step(&mut sgd, 0),
+            Err(Error::InvalidArgument(_))
+        ));
+        let warmup = LinearWarmup {
+            steps: 2,
+            start_factor: 0.0,
+            then: step,
+        };
+        assert!(matches!(
+            warmup.try_step(&mut sgd, 0),
+            Err(Error::InvalidArgument(_))
+        ));
+        // the learning rate is left alone
+        assert_eq!(sgd.cfg.lr, 0.5);
+    }
+
+    #[test]
+    fn test_scheduler_drives_optimizers() {
+        let dev: TestDevice = Default::default();
+        let mut x: Tensor<Rank1<2>, TestDtype, _> = dev.tensor([1.0, 1.0]).to_dtype();
+        let sched = ExponentialLr {
+            lr: 1.0,
+            gamma: 0.5,
+        };
+
+        let mut sgd = Sgd::new(&x, Default::default());
+        let mut adam: Adam<_, TestDtype, TestDevice> = Adam::new(&x, Default::default());
+        let mut rmsprop: RMSprop<_, TestDtype, TestDevice> = RMSprop::new(&x, Default::default());
+        for t in 0..3 {
+            assert_eq!(sched.step(&mut sgd, t), sched.lr_at(t));
+            sched.step(&mut adam, t);
+            sched.step(&mut rmsprop, t);
+        }
+        assert_eq!(sgd.cfg.lr, 0.25);
+        assert_eq!(adam.cfg.lr, 0.25);
+        assert_eq!(rmsprop.lr(), 0.25);
+
+        // the updated learning rate is used by the next update
+        let g = x.leaky_trace().sum().backward();
+        sgd.update(&mut x, &g).unwrap();
+        assert_close_to_literal!(x, [0.75, 0.75]);
+    }
+}
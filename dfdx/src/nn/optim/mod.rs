@@ -1,5 +1,8 @@
 //! Optimizers such as [Sgd], [Adam], and [RMSprop] that can optimize neural networks.
 //!
+//! [Adagrad], [Adadelta], [Lion], [Lamb] and [SparseAdam] are also available, but their kernels are only
+//! implemented for [crate::tensor::Cpu].
+//!
 //! # Initializing
 //!
 //! All the optimizer's provide [Default] implementations, and also provide a way to specify
@@ -7,6 +10,12 @@
 //! - [Sgd::new()] with [SgdConfig]
 //! - [Adam::new()] with [AdamConfig]
 //! - [RMSprop::new()] with [RMSpropConfig]
+//! - [Adagrad::new()] with [AdagradConfig]
+//! - [Adadelta::new()] with [AdadeltaConfig]
+//! - [Lion::new()] with [LionConfig]
+//! - [Lamb::new()] with [LambConfig]
+//! - [SparseAdam::new()] with [SparseAdamConfig]
+//! - [Lbfgs::new()] with [LbfgsConfig]
 //!
 //! # Updating network parameters
 //!
@@ -30,14 +39,179 @@
 //! opt.update(&mut model, &grads);
 //! model.zero_grads(&mut grads);
 //! ```
+//!
+//! # Learning rate schedules
+//!
+//! The learning rate of [Sgd], [Adam] and [RMSprop] can be changed every step (or epoch) with
+//! an [LrScheduler] such as [StepLr], [ExponentialLr], [CosineAnnealingLr], [LinearWarmup] or [OneCycleLr]:
+//!
+//! ```rust
+//! # use dfdx::prelude::*;
+//! # let dev: Cpu = Default::default();
+//! # type Model = Tensor<Rank0, f32, Cpu>;
+//! # let model: Model = dev.zeros();
+//! # let mut opt: Sgd<Model, f32, Cpu> = Sgd::new(&model, Default::default());
+//! let sched = CosineAnnealingLr { lr: 1e-2, min_lr: 1e-4, period: 100, period_mult: 2 };
+//! for step in 0..10 {
+//!     sched.step(&mut opt, step);
+//!     // -- snip forward, backward & opt.update() --
+//! }
+//! ```
+//!
+//! # Parameter groups
+//!
+//! Every optimizer except [Lbfgs] can use a different config for some of the parameters, like excluding
+//! biases & norm gains from weight decay, or using a lower learning rate for a pretrained backbone.
+//! Parameters are selected by their path within a module (see [crate::nn::UpdateParams::param_paths()]);
+//! pass a sub module to select all of its parameters. A parameter belongs to the first group that
+//! selects it, and parameters that aren't in any group use the optimizer's `cfg`:
+//!
+//! ```rust
+//! # use dfdx::prelude::*;
+//! # let dev: Cpu = Default::default();
+//! type Model = (LinearConstConfig<5, 5>, LayerNorm1DConstConfig<5>, LinearConstConfig<5, 2>);
+//! let model = dev.build_module::<f32>(Model::default());
+//! let cfg = AdamConfig {
+//!     weight_decay: Some(WeightDecay::Decoupled(1e-2)),
+//!     ..Default::default()
+//! };
+//! let no_decay = AdamConfig { weight_decay: None, ..cfg };
+//! let mut opt: Adam<_, f32, Cpu> = Adam::new(&model, cfg)
+//!     .with_param_group(&model, |path| path.ends_with("bias"), no_decay)
+//!     .with_param_group(&model.1, |_| true, no_decay)
+//!     .with_param_group(&model.0, |_| true, AdamConfig { lr: 1e-4, ..cfg });
+//! ```
+//!
+//! [LearningRate::set_lr()] (and so an [LrScheduler]) scales the learning rate of each group by the
+//! same factor as the default learning rate.
+//!
+//! # Freezing parameters
+//!
+//! Fields of a module marked with `#[frozen]` (next to `#[param]` or `#[module]`) are never
+//! updated by optimizers, and no gradients are allocated for them. They are still visited by
+//! everything else that walks the parameters, like [crate::nn::clip_grad_norm()], [Ema] and
+//! [crate::nn::UpdateParams::param_paths()]. To freeze parameters at runtime instead, like when
+//! fine-tuning a pretrained encoder and only training a head, use [FreezeParams::set_frozen()].
+//! All optimizers skip frozen parameters without requiring gradients for them.
+//!
+//! # L-BFGS
+//!
+//! [Lbfgs] re-evaluates the loss several times per step, so it is driven by [Lbfgs::step()] with
+//! a closure that computes the loss, optionally with a [StrongWolfe] line search.
+//!
+//! # Exponential moving average
+//!
+//! [Ema] keeps a shadow copy of a module that is blended toward the trained weights after
+//! every update, like the target networks of RL or the averaged weights of diffusion models.
+//! Use [Ema::swap()] to evaluate with the averaged weights.
+//!
+//! # Mixed precision
+//!
+//! [GradScaler] scales the loss up before backward, so small gradients don't underflow in f16
+//! (e.g. with [crate::dtypes::AMP]), and unscales them again before the optimizer step.
+//!
+//! # Saving & loading state
+//!
+//! With the `safetensors` feature, all optimizers except [Lbfgs] implement [crate::nn::OptimizerState], which saves
+//! their per-parameter state (like Adam's moments) using the same keys as
+//! [crate::nn::SaveSafeTensors] uses for the parameters. This lets a training run resume
+//! where it left off instead of cold-starting the optimizer.
 
+mod adadelta;
+mod adagrad;
 mod adam;
+mod ema;
+mod grad_scaler;
+mod lamb;
+mod lbfgs;
+mod lion;
+mod lr_scheduler;
+mod param_groups;
 mod rmsprop;
 mod sgd;
+mod sparse_adam;
 
+pub use adadelta::Adadelta;
+pub use adagrad::Adagrad;
 pub use adam::Adam;
+pub use ema::{Ema, EmaConfig};
+pub use grad_scaler::{GradScaler, GradScalerConfig};
+pub use lamb::Lamb;
+pub use lbfgs::{Lbfgs, LbfgsConfig, StrongWolfe};
+pub use lion::Lion;
+pub use lr_scheduler::{
+    CosineAnnealingLr, ExponentialLr, LearningRate, LinearWarmup, LrScheduler, OneCycleLr,
+    OneCycleLrConfig, StepLr,
+};
+pub use param_groups::FreezeParams;
 pub use rmsprop::RMSprop;
 pub use sgd::Sgd;
+pub use sparse_adam::SparseAdam;
 // re-exports
 pub use super::Optimizer;
-pub use crate::tensor_ops::{AdamConfig, Momentum, RMSpropConfig, SgdConfig, WeightDecay};
+pub use crate::tensor_ops::{
+    AdadeltaConfig, AdagradConfig, AdamConfig, LambConfig, LionConfig, Momentum, RMSpropConfig,
+    SgdConfig, SparseAdamConfig, WeightDecay,
+};
+
+use crate::{
+    nn::UpdateParams,
+    shapes::Dtype,
+    tensor::{Error, Gradients},
+    tensor_ops::Device,
+};
+
+/// Increments the step counter of `opt` (like the `t` of [Adam]), and then updates
+/// `module` the same way the default [Optimizer::update()] does.
+fn update_and_count_step<M: UpdateParams<E, D>, E: Dtype, D: Device<E>, O: Optimizer<M, E, D>>(
+    opt: &mut O,
+    step: fn(&mut O) -> &mut i32,
+    module: &mut M,
+    gradients: &Gradients<E, D>,
+) -> Result<(), Error> {
+    let t = step(opt);
+    *t = t.checked_add(1).unwrap();
+
+    let mut missing_tensors = Vec::new();
+    module.try_update_params(opt, gradients, &mut missing_tensors)?;
+    if missing_tensors.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::UnusedTensors(missing_tensors))
+    }
+}
+
+#[cfg(test)]
+mod test_utils {
+    use crate::{prelude::*, tests::*};
+
+    type Param = Tensor<Rank1<5>, TestDtype, TestDevice>;
+
+    /// Runs an update of the optimizer created by `new` for each row of `expected`,
+    /// and checks that the parameter matches the row afterwards.
+    pub(super) fn test_matches_expected<O: Optimizer<Param, TestDtype, TestDevice>>(
+        new: impl FnOnce(&Param) -> O,
+        expected: [[f64; 5]; 5],
+    ) {
+        let dev: TestDevice = Default::default();
+        let mut t = dev
+            .tensor([-0.5, -0.25, 0.1, 0.6, 1.0])
+            .to_dtype::<TestDtype>();
+        let mut opt = new(&t);
+        for e in expected.iter() {
+            let gradients = t.leaky_trace().exp().square().mean().backward();
+            opt.update(&mut t, &gradients).expect("");
+            assert_close_to_literal!(t, e);
+        }
+    }
+
+    /// Checks that the optimizer created by `new` fails without gradients.
+    pub(super) fn test_unused_tensors<O: Optimizer<Param, TestDtype, TestDevice>>(
+        new: impl FnOnce(&Param) -> O,
+    ) {
+        let dev: TestDevice = Default::default();
+        let mut t: Param = dev.sample_normal();
+        let mut opt = new(&t);
+        opt.update(&mut t, &Gradients::leaky()).expect_err("");
+    }
+}
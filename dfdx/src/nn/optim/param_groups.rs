@@ -0,0 +1,308 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    nn::UpdateParams,
+    shapes::{Dtype, Shape},
+    tensor::{Error, Gradients, Storage, Tensor, Tensorlike, UniqueId},
+    tensor_ops::{
+        AdadeltaConfig, AdagradConfig, AdamConfig, Device, LambConfig, LionConfig, RMSpropConfig,
+        SgdConfig, SparseAdamConfig,
+    },
+};
+
+/// An optimizer config that can be used for a parameter group.
+pub(super) trait GroupConfig {
+    fn lr_mut(&mut self) -> &mut f64;
+}
+
+macro_rules! group_config {
+    ($($Config:ty),+) => {
+        $(
+            impl GroupConfig for $Config {
+                fn lr_mut(&mut self) -> &mut f64 {
+                    &mut self.lr
+                }
+            }
+        )+
+    };
+}
+
+group_config!(
+    SgdConfig,
+    AdamConfig,
+    RMSpropConfig,
+    AdagradConfig,
+    AdadeltaConfig,
+    LionConfig,
+    LambConfig,
+    SparseAdamConfig
+);
+
+/// An optimizer that can stop updating some of the parameters at runtime,
+/// see [crate::nn::optim#freezing-parameters].
+pub trait FreezeParams<E: Dtype, D: Device<E>> {
+    /// Stops (or resumes) updating all parameters of `module`. Frozen parameters are skipped
+    /// without needing gradients, so they don't cause [Error::UnusedTensors].
+    fn set_frozen<P: UpdateParams<E, D>>(&mut self, module: &P, frozen: bool);
+}
+
+/// The parameters that an optimizer skips, see [FreezeParams].
+#[derive(Debug, Clone, Default)]
+pub(super) struct FrozenParams(BTreeSet<UniqueId>);
+
+impl FrozenParams {
+    /// Freezes (or unfreezes) all parameters of `module`.
+    pub(super) fn set<E: Dtype, D: Device<E>, P: UpdateParams<E, D>>(
+        &mut self,
+        module: &P,
+        frozen: bool,
+    ) {
+        let mut paths = Vec::new();
+        module.param_paths("", &mut paths);
+        for (_, id) in paths {
+            if frozen {
+                self.0.insert(id);
+            } else {
+                self.0.remove(&id);
+            }
+        }
+    }
+
+    pub(super) fn contains(&self, id: &UniqueId) -> bool {
+        self.0.contains(id)
+    }
+}
+
+/// The configs of the parameter groups of an optimizer, and which group each parameter belongs to.
+/// Parameters that aren't in any group use the optimizer's own config. Also tracks which
+/// parameters are frozen.
+#[derive(Debug, Clone)]
+pub(super) struct ParamGroups<C> {
+    /// Each group's config, and its learning rate relative to the default learning rate.
+    groups: Vec<(C, Option<f64>)>,
+    ids: BTreeMap<UniqueId, usize>,
+    pub(super) frozen: FrozenParams,
+}
+
+impl<C> Default for ParamGroups<C> {
+    fn default() -> Self {
+        Self {
+            groups: Vec::new(),
+            ids: BTreeMap::new(),
+            frozen: Default::default(),
+        }
+    }
+}
+
+impl<C: GroupConfig> ParamGroups<C> {
+    /// Adds a group with `cfg` containing all parameters of `module` whose path is selected by `select`.
+    /// Parameters that are already in a group are left in that group.
+    pub(super) fn add<E: Dtype, D: Device<E>, P: UpdateParams<E, D>>(
+        &mut self,
+        module: &P,
+        select: impl Fn(&str) -> bool,
+        mut cfg: C,
+        default_lr: f64,
+    ) {
+        let mut paths = Vec::new();
+        module.param_paths("", &mut paths);
+        let group = self.groups.len();
+        for (path, id) in paths {
+            if select(&path) {
+                self.ids.entry(id).or_insert(group);
+            }
+        }
+        let lr_ratio = (default_lr != 0.0).then(|| *cfg.lr_mut() / default_lr);
+        self.groups.push((cfg, lr_ratio));
+    }
+
+    /// Calls `update` with the config (`default` if `t` isn't in any group) & the gradient of `t`.
+    /// Frozen parameters are skipped, and parameters without a gradient are added to `missing_params`.
+    pub(super) fn try_update<S: Shape, E: Dtype, D: Device<E>>(
+        &self,
+        default: &C,
+        t: &mut Tensor<S, E, D>,
+        gradients: &Gradients<E, D>,
+        missing_params: &mut Vec<UniqueId>,
+        update: impl FnOnce(&C, &mut Tensor<S, E, D>, &<D as Storage<E>>::Vec) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        let id = t.id();
+        if self.frozen.contains(&id) {
+            return Ok(());
+        }
+        match gradients.get_ref_checked(t) {
+            None => missing_params.push(id),
+            Some(g) => {
+                let cfg = match self.ids.get(&id) {
+                    Some(&group) => &self.groups[group].0,
+                    None => default,
+                };
+                update(cfg, t, g)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Scales the learning rate of each group along with the default learning rate `lr`.
+    /// Groups that were added when the default learning rate was `0` keep their learning rate.
+    pub(super) fn set_lr(&mut self, lr: f64) {
+        for (cfg, lr_ratio) in self.groups.iter_mut() {
+            if let Some(lr_ratio) = lr_ratio {
+                *cfg.lr_mut() = lr * *lr_ratio;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{prelude::*, tests::*};
+
+    type Model = (LinearConstConfig<3, 2>, LayerNorm1DConstConfig<2>);
+
+    #[test]
+    fn test_param_paths() {
+        let dev: TestDevice = Default::default();
+        let model = dev.build_module::<TestDtype>(Model::default());
+        let mut paths = Vec::new();
+        model.param_paths("", &mut paths);
+        let paths: Vec<&str> = paths.iter().map(|(path, _)| path.as_str()).collect();
+        assert_eq!(paths, ["0.weight", "0.bias", "1.gamma", "1.beta"]);
+    }
+
+    #[test]
+    fn test_per_group_lr_and_weight_decay() {
+        let dev: TestDevice = Default::default();
+        let mut model = dev.build_module::<TestDtype>(Model::default());
+        model.0.weight = dev.tensor([[1.0, 2.0, 3.0], [4.0, 5.0, 6.0]]).to_dtype();
+        model.0.bias = dev.tensor([1.0, 2.0]).to_dtype();
+
+        let cfg = SgdConfig {
+            lr: 1.0,
+            momentum: None,
+            weight_decay: Some(WeightDecay::Decoupled(0.5)),
+        };
+        let bias_cfg = SgdConfig {
+            weight_decay: None,
+            ..cfg
+        };
+        let norm_cfg = SgdConfig {
+            lr: 0.1,
+            weight_decay: None,
+            ..cfg
+        };
+        let mut opt = Sgd::new(&model, cfg)
+            .with_param_group(&model, |path| path.ends_with("bias"), bias_cfg)
+            .with_param_group(&model.1, |_| true, norm_cfg);
+
+        // the gradient of every parameter is all ones
+        let loss = model.0.weight.leaky_trace().sum()
+            + model.0.bias.leaky_trace().sum()
+            + model.1.gamma.leaky_trace().sum()
+            + model.1.beta.leaky_trace().sum();
+        opt.update(&mut model, &loss.backward()).unwrap();
+        assert_close_to_literal!(model.0.weight, [[-0.5, 0.0, 0.5], [1.0, 1.5, 2.0]]);
+        assert_close_to_literal!(model.0.bias, [0.0, 1.0]);
+        assert_close_to_literal!(model.1.gamma, [0.9, 0.9]);
+        assert_close_to_literal!(model.1.beta, [-0.1, -0.1]);
+
+        // the learning rate of each group is scaled along with the default learning rate
+        opt.set_lr(0.5);
+        let loss = model.0.weight.leaky_trace().sum()
+            + model.0.bias.leaky_trace().sum()
+            + model.1.gamma.leaky_trace().sum()
+            + model.1.beta.leaky_trace().sum();
+        opt.update(&mut model, &loss.backward()).unwrap();
+        assert_close_to_literal!(model.0.weight, [[-0.875, -0.5, -0.125], [0.25, 0.625, 1.0]]);
+        assert_close_to_literal!(model.0.bias, [-0.5, 0.5]);
+        assert_close_to_literal!(model.1.gamma, [0.85, 0.85]);
+        assert_close_to_literal!(model.1.beta, [-0.15, -0.15]);
+    }
+
+    #[test]
+    fn test_first_group_wins() {
+        let dev: TestDevice = Default::default();
+        let mut model = dev.build_module::<TestDtype>(Model::default());
+        let beta = model.1.beta.clone();
+        let cfg = AdamConfig {
+            lr: 0.0,
+            ..Default::default()
+        };
+        let mut opt: Adam<_, TestDtype, TestDevice> = Adam::new(&model, Default::default())
+            .with_param_group(&model, |path| path == "1.beta", cfg)
+            .with_param_group(&model, |_| true, Default::default());
+        let loss = model.0.weight.leaky_trace().sum()
+            + model.0.bias.leaky_trace().sum()
+            + model.1.gamma.leaky_trace().sum()
+            + model.1.beta.leaky_trace().sum();
+        opt.update(&mut model, &loss.backward()).unwrap();
+        assert_eq!(model.1.beta.array(), beta.array());
+        assert_close_to_literal!(model.1.gamma, [0.999, 0.999]);
+    }
+
+    #[test]
+    fn test_set_frozen() {
+        let dev: TestDevice = Default::default();
+        let mut model = dev.build_module::<TestDtype>(Model::default());
+        let weight = model.0.weight.clone();
+        let mut opt = Sgd::new(&model, Default::default());
+        opt.set_frozen(&model.0, true);
+
+        // frozen parameters don't need gradients
+        let loss = model.1.gamma.leaky_trace().sum() + model.1.beta.leaky_trace().sum();
+        opt.update(&mut model, &loss.backward()).unwrap();
+        assert_eq!(model.0.weight.array(), weight.array());
+        assert_close_to_literal!(model.1.gamma, [0.99, 0.99]);
+
+        opt.set_frozen(&model.0, false);
+        let loss = model.0.weight.leaky_trace().sum()
+            + model.0.bias.leaky_trace().sum()
+            + model.1.gamma.leaky_trace().sum()
+            + model.1.beta.leaky_trace().sum();
+        opt.update(&mut model, &loss.backward()).unwrap();
+        assert_ne!(model.0.weight.array(), weight.array());
+        let loss = model.1.gamma.leaky_trace().sum();
+        opt.update(&mut model, &loss.backward()).expect_err("");
+    }
+
+    #[derive(Default, Clone, Debug, CustomModule)]
+    #[built(FineTune)]
+    struct FineTuneConfig {
+        #[module]
+        #[frozen]
+        encoder: LinearConstConfig<3, 2>,
+        #[module]
+        head: LayerNorm1DConstConfig<2>,
+    }
+
+    #[test]
+    fn test_frozen_attribute() {
+        let dev: TestDevice = Default::default();
+        let mut model = dev.build_module::<TestDtype>(FineTuneConfig::default());
+        let weight = model.encoder.weight.clone();
+
+        let mut paths = Vec::new();
+        model.param_paths("", &mut paths);
+        let paths: Vec<&str> = paths.iter().map(|(path, _)| path.as_str()).collect();
+        assert_eq!(
+            paths,
+            ["encoderweight", "encoderbias", "headgamma", "headbeta"]
+        );
+
+        let grads = model.alloc_grads();
+        assert!(grads.get_ref_checked(&model.encoder.weight).is_none());
+        assert!(grads.get_ref_checked(&model.head.gamma).is_some());
+
+        let mut opt = Sgd::new(&model, Default::default());
+        let loss = model.head.gamma.leaky_trace().sum() + model.head.beta.leaky_trace().sum();
+        opt.update(&mut model, &loss.backward()).unwrap();
+        assert_eq!(model.encoder.weight.array(), weight.array());
+        assert_close_to_literal!(model.head.beta, [-0.01, -0.01]);
+
+        // frozen parameters are only skipped by optimizers
+        let loss = model.encoder.weight.leaky_trace().sum() * 4.0;
+        let mut grads = loss.backward();
+        clip_grad_norm(&model, &mut grads, 1.0);
+        assert_close_to_literal!(grads.get(&model.encoder.weight), [[0.4082483; 3]; 2]);
+    }
+}
@@ -1,8 +1,10 @@
 use std::marker::PhantomData;
 
+use super::param_groups::ParamGroups;
 use crate::{
+    nn::UpdateParams,
     shapes::{Dtype, Shape},
-    tensor::{Error, Gradients, Storage, Tensor, Tensorlike, UniqueId},
+    tensor::{Error, Gradients, Storage, Tensor, UniqueId},
     tensor_ops::{Device, RMSpropConfig},
 };
 
@@ -37,6 +39,7 @@ use crate::{
 pub struct RMSprop<M, E: Dtype, D: Storage<E>> {
     /// Hyperparameter configuration
     pub cfg: RMSpropConfig,
+    groups: ParamGroups<RMSpropConfig>,
 
     step: usize,
     momentums: Gradients<E, D>,
@@ -51,6 +54,7 @@ impl<M, E: Dtype, D: Storage<E>> RMSprop<M, E, D> {
     pub fn new(_model: &M, cfg: RMSpropConfig) -> Self {
         Self {
             cfg,
+            groups: Default::default(),
             step: 0,
             momentums: Gradients::leaky(),
             square_avg: Gradients::leaky(),
@@ -58,6 +62,62 @@ impl<M, E: Dtype, D: Storage<E>> RMSprop<M, E, D> {
             marker: PhantomData,
         }
     }
+
+    /// Uses `cfg` instead of [RMSprop::cfg] for the parameters of `module` whose path (like `"0.bias"`)
+    /// is selected by `select`. See [crate::nn::optim#parameter-groups].
+    pub fn with_param_group<P: UpdateParams<E, D>>(
+        mut self,
+        module: &P,
+        select: impl Fn(&str) -> bool,
+        cfg: RMSpropConfig,
+    ) -> Self
+    where
+        D: Device<E>,
+    {
+        self.groups.add(module, select, cfg, self.cfg.lr);
+        self
+    }
+}
+
+impl<M, E: Dtype, D: Device<E>> super::FreezeParams<E, D> for RMSprop<M, E, D> {
+    fn set_frozen<P: UpdateParams<E, D>>(&mut self, module: &P, frozen: bool) {
+        self.groups.frozen.set(module, frozen);
+    }
+}
+
+impl<M, E: Dtype, D: Storage<E>> super::LearningRate for RMSprop<M, E, D> {
+    fn lr(&self) -> f64 {
+        self.cfg.lr
+    }
+    fn set_lr(&mut self, lr: f64) {
+        self.cfg.lr = lr;
+        self.groups.set_lr(lr);
+    }
+}
+
+#[cfg(feature = "safetensors")]
+impl<M, E: Dtype, D: Device<E>> crate::nn::OptimizerState<E, D> for RMSprop<M, E, D> {
+    fn state_buffers(&mut self) -> Vec<(&'static str, &mut Gradients<E, D>)> {
+        vec![
+            ("momentums", &mut self.momentums),
+            ("square_avg", &mut self.square_avg),
+            ("grad_avg", &mut self.grad_avg),
+        ]
+    }
+
+    fn write_state_scalars(
+        &self,
+        tensors: &mut Vec<(String, safetensors::Dtype, Vec<usize>, Vec<u8>)>,
+    ) {
+        crate::nn::SaveSafeTensors::write_safetensors(&self.step, "step", tensors);
+    }
+
+    fn read_state_scalars(
+        &mut self,
+        tensors: &safetensors::SafeTensors,
+    ) -> Result<(), safetensors::SafeTensorError> {
+        crate::nn::LoadSafeTensors::read_safetensors(&mut self.step, "step", tensors)
+    }
 }
 
 impl<M, E: Dtype, D: Device<E>> crate::nn::Optimizer<M, E, D> for RMSprop<M, E, D> {
@@ -67,10 +127,8 @@ impl<M, E: Dtype, D: Device<E>> crate::nn::Optimizer<M, E, D> for RMSprop<M, E,
         gradients: &Gradients<E, D>,
         missing_params: &mut Vec<UniqueId>,
     ) -> Result<(), Error> {
-        let g = gradients.get_ref_checked(t);
-        match g {
-            None => missing_params.push(t.id()),
-            Some(g) => {
+        self.groups
+            .try_update(&self.cfg, t, gradients, missing_params, |cfg, t, g| {
                 let m = self.momentums.get_or_alloc_mut(t)?;
                 let sa = self.square_avg.get_or_alloc_mut(t)?;
                 let ga = self.grad_avg.get_or_alloc_mut(t)?;
@@ -79,10 +137,8 @@ impl<M, E: Dtype, D: Device<E>> crate::nn::Optimizer<M, E, D> for RMSprop<M, E,
                     t.device().try_fill_with_ones(sa)?;
                 }
 
-                self.cfg.try_update(t, m, sa, ga, g)?;
-            }
-        }
-        Ok(())
+                cfg.try_update(t, m, sa, ga, g)
+            })
     }
 
     fn update(&mut self, module: &mut M, gradients: &Gradients<E, D>) -> Result<(), Error>
@@ -1,6 +1,8 @@
+use super::param_groups::ParamGroups;
 use crate::{
+    nn::UpdateParams,
     shapes::{Dtype, Shape},
-    tensor::{Gradients, Storage, Tensor, Tensorlike, UniqueId},
+    tensor::{Gradients, Storage, Tensor, UniqueId},
     tensor_ops::{Device, SgdConfig},
 };
 
@@ -31,6 +33,7 @@ use crate::{
 #[derive(Debug, Clone)]
 pub struct Sgd<M, E: Dtype, D: Storage<E>> {
     pub cfg: SgdConfig,
+    groups: ParamGroups<SgdConfig>,
     velocity: Gradients<E, D>,
     module: std::marker::PhantomData<*const M>,
 }
@@ -39,10 +42,49 @@ impl<M, E: Dtype, D: Storage<E>> Sgd<M, E, D> {
     pub fn new(_model: &M, cfg: SgdConfig) -> Self {
         Self {
             cfg,
+            groups: Default::default(),
             velocity: Gradients::leaky(),
             module: std::marker::PhantomData,
         }
     }
+
+    /// Uses `cfg` instead of [Sgd::cfg] for the parameters of `module` whose path (like `"0.bias"`)
+    /// is selected by `select`. See [crate::nn::optim#parameter-groups].
+    pub fn with_param_group<P: UpdateParams<E, D>>(
+        mut self,
+        module: &P,
+        select: impl Fn(&str) -> bool,
+        cfg: SgdConfig,
+    ) -> Self
+    where
+        D: Device<E>,
+    {
+        self.groups.add(module, select, cfg, self.cfg.lr);
+        self
+    }
+}
+
+impl<M, E: Dtype, D: Device<E>> super::FreezeParams<E, D> for Sgd<M, E, D> {
+    fn set_frozen<P: UpdateParams<E, D>>(&mut self, module: &P, frozen: bool) {
+        self.groups.frozen.set(module, frozen);
+    }
+}
+
+impl<M, E: Dtype, D: Storage<E>> super::LearningRate for Sgd<M, E, D> {
+    fn lr(&self) -> f64 {
+        self.cfg.lr
+    }
+    fn set_lr(&mut self, lr: f64) {
+        self.cfg.lr = lr;
+        self.groups.set_lr(lr);
+    }
+}
+
+#[cfg(feature = "safetensors")]
+impl<M, E: Dtype, D: Device<E>> crate::nn::OptimizerState<E, D> for Sgd<M, E, D> {
+    fn state_buffers(&mut self) -> Vec<(&'static str, &mut Gradients<E, D>)> {
+        vec![("velocity", &mut self.velocity)]
+    }
 }
 
 impl<M, E: Dtype, D: Device<E>> crate::nn::Optimizer<M, E, D> for Sgd<M, E, D> {
@@ -52,15 +94,11 @@ impl<M, E: Dtype, D: Device<E>> crate::nn::Optimizer<M, E, D> for Sgd<M, E, D> {
         gradients: &Gradients<E, D>,
         missing_params: &mut Vec<UniqueId>,
     ) -> Result<(), crate::tensor::Error> {
-        let g = gradients.get_ref_checked(t);
-        match g {
-            None => missing_params.push(t.id()),
-            Some(g) => {
+        self.groups
+            .try_update(&self.cfg, t, gradients, missing_params, |cfg, t, g| {
                 let v = self.velocity.get_or_alloc_mut(t)?;
-                self.cfg.try_update(t, v, g)?;
-            }
-        }
-        Ok(())
+                cfg.try_update(t, v, g)
+            })
     }
 }
 
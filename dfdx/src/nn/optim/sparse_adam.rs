@@ -0,0 +1,195 @@
+use std::marker::PhantomData;
+
+use super::param_groups::ParamGroups;
+use crate::{
+    nn::UpdateParams,
+    shapes::{Dtype, Shape},
+    tensor::{Error, Gradients, Storage, Tensor, UniqueId},
+    tensor_ops::{Device, SparseAdamConfig, SparseAdamKernel},
+};
+
+/// A lazy version of [super::Adam] for sparse gradients, like those of an
+/// [crate::nn::SparseEmbedding].
+///
+/// Only the rows recorded in [Gradients::sparse_rows()] are updated, and the moments of
+/// the other rows are left as is, so each step only touches the rows that were looked up
+/// instead of the whole table. Parameters without sparse rows are updated like [super::Adam].
+///
+/// # Example Usage
+/// ```rust
+/// # use dfdx::prelude::*;
+/// # let dev: Cpu = Default::default();
+/// let mut model = dev.build_module::<f32>(EmbeddingConstConfig::<1000, 8>::default().sparse());
+/// let mut opt = SparseAdam::new(&model, Default::default());
+/// let x: Tensor<Rank1<3>, usize, _> = dev.tensor([1, 5, 1]);
+/// let grads = model.forward(x.leaky_trace()).square().mean().backward();
+/// // only updates rows 1 & 5
+/// opt.update(&mut model, &grads).unwrap();
+/// ```
+///
+/// See module level documentation at [crate::nn::optim] for examples of how to actually use an optimizer.
+#[derive(Debug, Clone)]
+pub struct SparseAdam<M, E: Dtype, D: Storage<E>> {
+    /// Hyperparameter configuration
+    pub cfg: SparseAdamConfig,
+    groups: ParamGroups<SparseAdamConfig>,
+
+    t: i32,
+    moment1: Gradients<E, D>,
+    moment2: Gradients<E, D>,
+
+    marker: PhantomData<*const M>,
+}
+
+impl<M, E: Dtype, D: Storage<E>> SparseAdam<M, E, D> {
+    /// Constructs using hyperparameters from `cfg`.
+    pub fn new(_model: &M, cfg: SparseAdamConfig) -> Self {
+        Self {
+            cfg,
+            groups: Default::default(),
+            t: 0,
+            moment1: Gradients::leaky(),
+            moment2: Gradients::leaky(),
+            marker: PhantomData,
+        }
+    }
+
+    /// Uses `cfg` instead of [SparseAdam::cfg] for the parameters of `module` whose path (like `"0.weight"`)
+    /// is selected by `select`. See [crate::nn::optim#parameter-groups].
+    pub fn with_param_group<P: UpdateParams<E, D>>(
+        mut self,
+        module: &P,
+        select: impl Fn(&str) -> bool,
+        cfg: SparseAdamConfig,
+    ) -> Self
+    where
+        D: Device<E>,
+    {
+        self.groups.add(module, select, cfg, self.cfg.lr);
+        self
+    }
+}
+
+impl<M, E: Dtype, D: Device<E>> super::FreezeParams<E, D> for SparseAdam<M, E, D> {
+    fn set_frozen<P: UpdateParams<E, D>>(&mut self, module: &P, frozen: bool) {
+        self.groups.frozen.set(module, frozen);
+    }
+}
+
+impl<M, E: Dtype, D: Storage<E>> super::LearningRate for SparseAdam<M, E, D> {
+    fn lr(&self) -> f64 {
+        self.cfg.lr
+    }
+    fn set_lr(&mut self, lr: f64) {
+        self.cfg.lr = lr;
+        self.groups.set_lr(lr);
+    }
+}
+
+#[cfg(feature = "safetensors")]
+impl<M, E: Dtype, D: Device<E>> crate::nn::OptimizerState<E, D> for SparseAdam<M, E, D> {
+    fn state_buffers(&mut self) -> Vec<(&'static str, &mut Gradients<E, D>)> {
+        vec![
+            ("moment1", &mut self.moment1),
+            ("moment2", &mut self.moment2),
+        ]
+    }
+
+    fn write_state_scalars(
+        &self,
+        tensors: &mut Vec<(String, safetensors::Dtype, Vec<usize>, Vec<u8>)>,
+    ) {
+        crate::nn::SaveSafeTensors::write_safetensors(&self.t, "t", tensors);
+    }
+
+    fn read_state_scalars(
+        &mut self,
+        tensors: &safetensors::SafeTensors,
+    ) -> Result<(), safetensors::SafeTensorError> {
+        crate::nn::LoadSafeTensors::read_safetensors(&mut self.t, "t", tensors)
+    }
+}
+
+impl<M, E: Dtype, D: Device<E> + SparseAdamKernel<E>> crate::nn::Optimizer<M, E, D>
+    for SparseAdam<M, E, D>
+{
+    fn update_tensor<S: Shape>(
+        &mut self,
+        t: &mut Tensor<S, E, D>,
+        gradients: &Gradients<E, D>,
+        missing_params: &mut Vec<UniqueId>,
+    ) -> Result<(), Error> {
+        self.groups
+            .try_update(&self.cfg, t, gradients, missing_params, |cfg, t, g| {
+                let rows: Option<Vec<usize>> = gradients
+                    .sparse_rows(t)
+                    .map(|rows| rows.iter().copied().collect());
+                let m_t = self.moment1.get_or_alloc_mut(t)?;
+                let v_t = self.moment2.get_or_alloc_mut(t)?;
+                cfg.try_update(self.t, t, m_t, v_t, g, rows.as_deref())
+            })
+    }
+
+    fn update(&mut self, module: &mut M, gradients: &Gradients<E, D>) -> Result<(), Error>
+    where
+        M: UpdateParams<E, D>,
+    {
+        super::update_and_count_step(self, |opt| &mut opt.t, module, gradients)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{prelude::*, tests::*};
+
+    #[test]
+    fn test_sparse_adam_dense_grads_match_adam() {
+        let dev: TestDevice = Default::default();
+        let mut t: Tensor<Rank1<5>, TestDtype, _> = dev.ones();
+        let mut opt = SparseAdam::new(&t, Default::default());
+        let rate = dev
+            .tensor([1e-6, 1e-5, 1e-4, 1e-3, 1e-2])
+            .to_dtype::<TestDtype>();
+        let expected = [
+            [0.99999994, 0.999996, 0.9997143, 0.9990244, 0.99900025],
+            [0.9999999, 0.999992, 0.99942863, 0.99804884, 0.9980005],
+            [0.9999998, 0.999988, 0.999143, 0.9970733, 0.9970008],
+        ];
+
+        for e in expected.iter() {
+            let gradients = (t.leaky_trace() * rate.clone()).square().mean().backward();
+            opt.update(&mut t, &gradients).expect("");
+            assert_close_to_literal!(t, e);
+        }
+    }
+
+    #[test]
+    fn test_sparse_adam_only_updates_looked_up_rows() {
+        let dev: TestDevice = Default::default();
+        let mut model =
+            dev.build_module::<TestDtype>(EmbeddingConstConfig::<4, 2>::default().sparse());
+        model.weight = dev.ones();
+        let mut opt = SparseAdam::new(&model, Default::default());
+
+        let mut step = |model: &mut SparseEmbedding<_, _, TestDtype, TestDevice>,
+                        x: Tensor<Rank1<1>, usize, _>| {
+            let grads = model.forward(x.leaky_trace()).sum().backward();
+            opt.update(model, &grads).unwrap();
+        };
+
+        step(&mut model, dev.tensor([1]));
+        // row 3's moments start from zero, instead of decaying during the first step
+        step(&mut model, dev.tensor([3]));
+        assert_close_to_literal!(
+            model.weight,
+            [[1.0; 2], [0.999; 2], [1.0; 2], [0.99925586; 2]]
+        );
+
+        step(&mut model, dev.tensor([3]));
+        assert_close_to_literal!(
+            model.weight,
+            [[1.0; 2], [0.999; 2], [1.0; 2], [0.9983974; 2]]
+        );
+    }
+}